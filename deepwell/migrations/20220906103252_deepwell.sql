@@ -73,6 +73,18 @@ CREATE TABLE user_bot_owner (
     PRIMARY KEY (bot_user_id, human_user_id)
 );
 
+CREATE TABLE user_oidc_identity (
+    provider TEXT NOT NULL,
+    subject TEXT NOT NULL,
+    user_id BIGINT NOT NULL REFERENCES "user"(user_id),
+    email TEXT,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+    last_login_at TIMESTAMP WITH TIME ZONE,
+
+    PRIMARY KEY (provider, subject),
+    UNIQUE (provider, user_id)  -- Only one identity per provider for each user
+);
+
 --
 -- Site
 --
@@ -91,6 +103,9 @@ CREATE TABLE site (
     default_page TEXT NOT NULL DEFAULT 'start',
     custom_domain TEXT,  -- Dependency cycle, add foreign key constraint after
     layout TEXT,  -- Default page layout for the site
+    membership_policy TEXT NOT NULL DEFAULT 'open',  -- check enum value in runtime
+    tag_whitelist TEXT[],  -- If set, only these tags may be used on pages
+    default_license TEXT,  -- SPDX identifier, applied to files uploaded as own work
 
     UNIQUE (slug, deleted_at)
 );
@@ -99,14 +114,41 @@ CREATE TABLE site_domain (
     domain TEXT PRIMARY KEY,
     site_id BIGINT NOT NULL REFERENCES site(site_id),
     created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+    verification_token TEXT,  -- NULL if exempt from verification
+    verified_at TIMESTAMP WITH TIME ZONE,  -- NULL while pending or after lapsing
+    last_checked_at TIMESTAMP WITH TIME ZONE,
 
-    CHECK (length(domain) > 0)
+    CHECK (length(domain) > 0),
+    CHECK (verification_token IS NOT NULL OR verified_at IS NOT NULL)  -- exempt domains are always verified
 );
 
 ALTER TABLE site
     ADD CONSTRAINT site_custom_domain_fk
     FOREIGN KEY (custom_domain) REFERENCES site_domain(domain);
 
+-- Invitations to join a site, either for an existing user or by email.
+-- Emailed invitations are accepted using the token sent to that address.
+CREATE TABLE site_invitation (
+    invitation_id BIGSERIAL PRIMARY KEY,
+    site_id BIGINT NOT NULL REFERENCES site(site_id),
+    created_by BIGINT NOT NULL REFERENCES "user"(user_id),
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL CHECK (expires_at > created_at),
+    user_id BIGINT REFERENCES "user"(user_id),
+    email TEXT,
+    token TEXT UNIQUE,
+    message TEXT NOT NULL,
+    accepted_at TIMESTAMP WITH TIME ZONE,
+    accepted_by BIGINT REFERENCES "user"(user_id),
+    revoked_at TIMESTAMP WITH TIME ZONE,
+    revoked_by BIGINT REFERENCES "user"(user_id),
+
+    CHECK ((user_id IS NULL) != (email IS NULL)),   -- invite exactly one of user or email
+    CHECK ((email IS NULL) = (token IS NULL)),      -- emailed invites have a token
+    CHECK ((accepted_at IS NULL) = (accepted_by IS NULL)),
+    CHECK ((revoked_at IS NULL) = (revoked_by IS NULL))
+);
+
 --
 -- Aliases
 --
@@ -170,14 +212,18 @@ CREATE TABLE relation (
 
 CREATE TABLE session (
     session_token TEXT PRIMARY KEY CHECK (length(session_token) > 48),
+    session_id BIGSERIAL NOT NULL UNIQUE,
     user_id BIGINT NOT NULL REFERENCES "user"(user_id),
     created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
     expires_at TIMESTAMP WITH TIME ZONE NOT NULL CHECK (expires_at > created_at),
-    ip_address TEXT NOT NULL,  -- TODO change to INET
+    last_used_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now() CHECK (last_used_at >= created_at),
+    ip_address INET NOT NULL,
     user_agent TEXT NOT NULL,
     restricted BOOLEAN NOT NULL
 );
 
+CREATE INDEX session_user_idx ON session (user_id);
+
 --
 -- Page
 --
@@ -189,6 +235,10 @@ CREATE TABLE page_category (
     site_id BIGINT NOT NULL REFERENCES site(site_id),
     slug TEXT NOT NULL,
     layout TEXT, -- category-specific override for DOM layout
+    score_type TEXT, -- category-specific override for page scoring, check enum value in runtime
+    template TEXT, -- slug of the page used as the template for pages in this category
+    license TEXT, -- SPDX identifier, category-specific override for the site's license
+    permissions JSON, -- category-specific override for site permissions
 
     UNIQUE (site_id, slug)
 );
@@ -235,17 +285,44 @@ CREATE TYPE page_revision_change AS ENUM (
     'tags'
 );
 
+-- Trained zstd dictionaries for compressing text rows.
+--
+-- Dictionaries are never modified, since rows compressed with one
+-- need it to be decompressed. New ones are added when retrained.
+CREATE TABLE text_dictionary (
+    text_dictionary_id SERIAL PRIMARY KEY,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+    sample_count INTEGER NOT NULL,
+    data BYTEA NOT NULL
+);
+
 -- No unique constraint for 'contents' because that would create
 -- create a separate index, which will impact performance.
 --
 -- If the KangarooTwelve hash algorithm was available in pgcrypto
 -- we'd check directly (hash = digest(contents, 'kangarootwelve')),
 -- but since we can't we'll just verify the hash length.
+--
+-- The hash is always of the original contents, regardless of how it is stored:
+-- * 'plain' rows keep the contents as-is.
+-- * 'zstd' rows keep the contents compressed in 'data', possibly with a dictionary.
+-- * 'delta' rows keep, compressed in 'data', the changes from the text in 'base_hash'.
+--
+-- The 'length' column is the number of characters in the original contents.
 CREATE TABLE text (
     hash BYTEA PRIMARY KEY,
-    contents TEXT NOT NULL,
+    encoding TEXT NOT NULL DEFAULT 'plain',
+    length INTEGER NOT NULL,
+    contents TEXT,
+    data BYTEA,
+    text_dictionary_id INTEGER REFERENCES text_dictionary(text_dictionary_id),
+    base_hash BYTEA REFERENCES text(hash),
+    delta_depth SMALLINT NOT NULL DEFAULT 0,
 
-    CHECK (length(hash) = 16)  -- KangarooTwelve hash size, 128 bits
+    CHECK (length(hash) = 16),  -- KangarooTwelve hash size, 128 bits
+    CHECK (encoding IN ('plain', 'zstd', 'delta')),
+    CHECK ((encoding = 'plain') = (contents IS NOT NULL AND data IS NULL)),
+    CHECK ((encoding = 'delta') = (base_hash IS NOT NULL AND delta_depth > 0))
 );
 
 -- Main revision table
@@ -264,6 +341,8 @@ CREATE TABLE page_revision (
     compiled_hash BYTEA NOT NULL REFERENCES text(hash),
     compiled_at TIMESTAMP WITH TIME ZONE NOT NULL,
     compiled_generator TEXT NOT NULL,
+    compiled_style_hash BYTEA NOT NULL REFERENCES text(hash), -- Page-level CSS, from style blocks
+    compiled_meta JSON NOT NULL DEFAULT '[]', -- HTML meta tags, as produced by ftml
     comments TEXT NOT NULL,
     hidden TEXT[] NOT NULL DEFAULT '{}', -- List of fields to be hidden/suppressed
     title TEXT NOT NULL,
@@ -315,14 +394,19 @@ ALTER TABLE page ADD CONSTRAINT page_revision_revision_id_fk
 -- Page metadata
 --
 
-CREATE TABLE page_parent (
-    parent_page_id BIGINT REFERENCES page(page_id),
-    child_page_id BIGINT REFERENCES page(page_id),
-    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+-- Index of the tags on the latest revision of each page.
+-- Kept in sync by PageRevisionService, the revision's column is the source of truth.
+CREATE TABLE page_tag (
+    page_id BIGINT NOT NULL REFERENCES page(page_id),
+    site_id BIGINT NOT NULL REFERENCES site(site_id),
+    tag TEXT NOT NULL,
 
-    PRIMARY KEY (parent_page_id, child_page_id)
+    PRIMARY KEY (page_id, tag),
+    CHECK (length(tag) > 0)
 );
 
+CREATE INDEX page_tag_site_idx ON page_tag (site_id, tag);
+
 CREATE TABLE page_attribution (
     page_id BIGINT REFERENCES page(page_id),
     user_id BIGINT REFERENCES "user"(user_id),
@@ -430,18 +514,43 @@ CREATE TABLE blob_pending (
     expected_length BIGINT NOT NULL CHECK (expected_length >= 0),
     s3_path TEXT NOT NULL CHECK (length(s3_path) > 1),
     s3_hash BYTEA,  -- NULL means not yet moved, NOT NULL means deleted from s3_path
-    presign_url TEXT NOT NULL CHECK (length(presign_url) > 1),
+    presign_url TEXT CHECK (presign_url IS NULL OR length(presign_url) > 1),
+    multipart_upload_id TEXT CHECK (multipart_upload_id IS NULL OR length(multipart_upload_id) > 0),
+    multipart_part_size BIGINT CHECK (multipart_part_size IS NULL OR multipart_part_size > 0),
 
-    CHECK (expires_at > created_at),                 -- expiration time is not in the relative past
-    CHECK (length(external_id) = 24),                -- default length for a cuid2
-    CHECK (s3_hash IS NULL OR length(s3_hash) = 64)  -- SHA-512 hash size, if present
+    CHECK (expires_at > created_at),                  -- expiration time is not in the relative past
+    CHECK (length(external_id) = 24),                 -- default length for a cuid2
+    CHECK (s3_hash IS NULL OR length(s3_hash) = 64),  -- SHA-512 hash size, if present
+    CHECK ((presign_url IS NULL) <> (multipart_upload_id IS NULL)),          -- either single or multipart upload
+    CHECK ((multipart_upload_id IS NULL) = (multipart_part_size IS NULL))  -- multipart fields are set together
 );
 
 -- Manages blobs which are prohibited from being uploaded
 CREATE TABLE blob_blacklist (
     s3_hash BYTEA PRIMARY KEY CHECK (length(s3_hash) = 64),  -- SHA-512 hash size
     created_at TIMESTAMP WITH TIME ZONE NULL DEFAULT now(),
-    created_by BIGINT NOT NULL REFERENCES "user"(user_id)
+    created_by BIGINT NOT NULL REFERENCES "user"(user_id),
+    reason TEXT NULL
+);
+
+-- Blobs which nothing references, pending deletion by the garbage collector
+CREATE TABLE blob_orphan (
+    s3_hash BYTEA PRIMARY KEY CHECK (length(s3_hash) = 64),  -- SHA-512 hash size
+    size BIGINT NOT NULL,
+    marked_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now()
+);
+
+-- Integrity problems found by the most recent storage scrub
+CREATE TABLE scrub_issue (
+    scrub_issue_id BIGSERIAL PRIMARY KEY,
+    found_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+    target TEXT NOT NULL,
+    issue TEXT NOT NULL,
+    hash BYTEA NOT NULL,
+    details TEXT,
+
+    CHECK (target IN ('text', 'blob')),
+    CHECK (issue IN ('missing', 'corrupted', 'mismatched'))
 );
 
 --
@@ -539,6 +648,27 @@ CREATE TABLE file_revision (
     UNIQUE (file_id, page_id, revision_number)
 );
 
+-- Indexes for storage usage accounting, see StorageService
+CREATE INDEX file_revision_site_blob_idx ON file_revision (site_id, s3_hash);
+CREATE INDEX file_revision_user_blob_idx ON file_revision (user_id, s3_hash);
+
+-- Storage used by a site or user, and their quota if it has been overridden.
+--
+-- Each distinct blob is charged once to every site with a file revision using it,
+-- and once to every user who uploaded it as a file revision.
+CREATE TABLE storage_usage (
+    storage_usage_id BIGSERIAL PRIMARY KEY,
+    site_id BIGINT UNIQUE REFERENCES site(site_id),
+    user_id BIGINT UNIQUE REFERENCES "user"(user_id),
+    used_bytes BIGINT NOT NULL DEFAULT 0 CHECK (used_bytes >= 0),
+    quota_bytes BIGINT CHECK (quota_bytes >= 0),  -- NULL means to use the configured default
+    quota_updated_by BIGINT REFERENCES "user"(user_id),
+    quota_updated_at TIMESTAMP WITH TIME ZONE,
+
+    CHECK ((site_id IS NULL) != (user_id IS NULL)),  -- Exactly one owner
+    CHECK ((quota_updated_by IS NULL) = (quota_updated_at IS NULL))
+);
+
 --
 -- Direct Messages
 --
@@ -625,6 +755,29 @@ CREATE TABLE message_draft (
     CHECK (length(external_id) = 24)  -- default length for a cuid2
 );
 
+-- Files attached to a message.
+--
+-- While the message is a draft, the attachment belongs to the draft.
+-- When it is sent, the attachment is moved over to the resulting record.
+CREATE TABLE message_attachment (
+    message_attachment_id BIGSERIAL PRIMARY KEY,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+    draft_id TEXT REFERENCES message_draft(external_id),
+    record_id TEXT REFERENCES message_record(external_id),
+    name TEXT NOT NULL,
+    s3_hash BYTEA NOT NULL,
+    mime TEXT NOT NULL,
+    size BIGINT NOT NULL,
+
+    CHECK ((draft_id IS NULL) <> (record_id IS NULL)),  -- Belongs to either a draft or a record
+    CHECK (length(name) > 0 AND length(name) < 256),    -- Constrain filename length
+    CHECK (length(s3_hash) = 64),                       -- SHA-512 hash size
+    CHECK (mime != '')                                  -- Should have a MIME hint
+);
+
+CREATE INDEX message_attachment_draft_idx ON message_attachment (draft_id);
+CREATE INDEX message_attachment_record_idx ON message_attachment (record_id);
+
 -- If a message has been reported, then a row for it is created here.
 -- Messages can be reported per-site or globally (at the platform level).
 CREATE TABLE message_report (
@@ -0,0 +1,11 @@
+-- Fills the page_tag index for pages which existed before it was added.
+-- Afterwards it is kept up to date whenever a revision changes a page's tags.
+
+INSERT INTO page_tag (page_id, site_id, tag)
+SELECT DISTINCT page.page_id, page.site_id, tags.tag
+FROM page
+JOIN page_revision
+    ON page_revision.revision_id = page.latest_revision_id
+CROSS JOIN LATERAL unnest(page_revision.tags) AS tags(tag)
+WHERE length(tags.tag) > 0
+ON CONFLICT DO NOTHING;
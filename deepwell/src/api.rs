@@ -30,12 +30,19 @@ use crate::config::{Config, Secrets};
 use crate::endpoints::{
     auth::*, blob::*, category::*, domain::*, email::*, file::*, file_revision::*,
     info::*, link::*, locale::*, message::*, misc::*, page::*, page_revision::*,
-    parent::*, site::*, site_member::*, text::*, user::*, user_bot::*, view::*, vote::*,
+    parent::*, site::*, site_member::*, storage::*, tag::*, text::*, user::*,
+    user_bot::*, view::*, vote::*,
 };
 use crate::locales::Localizations;
 use crate::services::blob::MimeAnalyzer;
-use crate::services::job::JobWorker;
-use crate::services::{into_rpc_error, ServiceContext};
+use crate::services::email::{build_mailer, Mailer};
+use crate::services::job::{JobScheduler, JobService, JobWorker};
+use crate::services::rate_limit::{RateLimitClass, RateLimitKeys};
+use crate::services::render::RenderPool;
+use crate::services::scan::{build_scanner, Scanner};
+use crate::services::{
+    into_rpc_error, RateLimitService, ServiceContext, ViewCacheService,
+};
 use crate::utils::debug_pointer;
 use crate::{database, redis as redis_db};
 use jsonrpsee::server::{RpcModule, Server, ServerHandle};
@@ -43,6 +50,7 @@ use jsonrpsee::types::error::ErrorObjectOwned;
 use rsmq_async::PooledRsmq;
 use s3::bucket::Bucket;
 use sea_orm::{DatabaseConnection, TransactionTrait};
+use std::collections::HashMap;
 use std::fmt::{self, Debug};
 use std::sync::Arc;
 use std::time::Duration;
@@ -56,7 +64,11 @@ pub struct ServerStateInner {
     pub rsmq: PooledRsmq,
     pub localizations: Localizations,
     pub mime_analyzer: MimeAnalyzer,
+    pub render_pool: RenderPool,
     pub s3_bucket: Box<Bucket>,
+    pub scanner: Option<Box<dyn Scanner>>,
+    pub oidc_client_secrets: HashMap<String, String>,
+    pub mailer: Option<Mailer>,
 }
 
 impl Debug for ServerStateInner {
@@ -68,7 +80,11 @@ impl Debug for ServerStateInner {
             .field("rsmq", &debug_pointer(&self.rsmq))
             .field("localizations", &self.localizations)
             .field("mime_analyzer", &self.mime_analyzer)
+            .field("render_pool", &self.render_pool)
             .field("s3_bucket", &self.s3_bucket)
+            .field("scanner", &self.scanner)
+            .field("oidc_client_secrets", &self.oidc_client_secrets.keys())
+            .field("mailer", &self.mailer.is_some())
             .finish()
     }
 }
@@ -91,6 +107,9 @@ pub async fn build_server_state(
     // Load magic data and start MIME thread
     let mime_analyzer = MimeAnalyzer::spawn();
 
+    // Start render threads
+    let render_pool = RenderPool::spawn(&config);
+
     // Create S3 bucket
     info!("Opening S3 bucket");
 
@@ -109,6 +128,12 @@ pub async fn build_server_state(
         bucket
     };
 
+    // Set up malware scanner, if any
+    let scanner = build_scanner(&config);
+
+    // Set up SMTP transport for emails, if any
+    let mailer = build_mailer(&secrets)?;
+
     // Build server state
     let state = Arc::new(ServerStateInner {
         config,
@@ -117,11 +142,19 @@ pub async fn build_server_state(
         rsmq,
         localizations,
         mime_analyzer,
+        render_pool,
         s3_bucket,
+        scanner,
+        oidc_client_secrets: secrets.oidc_client_secrets,
+        mailer,
     });
 
     // Start workers listening to the job queue (requires ServerState)
     JobWorker::spawn_all(&state);
+    JobScheduler::spawn(&state);
+
+    // Begin rerendering pages if ftml has been upgraded
+    JobService::check_ftml_version(&state).await?;
 
     // Return server state
     Ok(state)
@@ -139,11 +172,20 @@ async fn build_module(app_state: ServerState) -> anyhow::Result<RpcModule<Server
     let mut module = RpcModule::new(app_state);
 
     macro_rules! register {
-        ($name:expr, $method:ident $(,)?) => {{
+        ($name:expr, $method:ident $(,)?) => {
+            register!(@inner $name, $method, None)
+        };
+        ($name:expr, $method:ident, $class:ident $(,)?) => {
+            register!(@inner $name, $method, Some(RateLimitClass::$class))
+        };
+        (@inner $name:expr, $method:ident, $class:expr) => {{
             // Register async method.
             //
             // Contains a wrapper around each to set up state, convert error types,
             // and produce a transaction used in ServiceContext, passed in.
+            //
+            // If a rate limit class is specified, the client's buckets for it are
+            // checked before the method is run.
             module.register_async_method($name, |params, state, _extensions| async move {
                 // NOTE: We have our own Arc because we need to share it in some places
                 //       before setting up, but RpcModule insists on adding its own.
@@ -155,19 +197,36 @@ async fn build_module(app_state: ServerState) -> anyhow::Result<RpcModule<Server
                 // automatically based on whether the Result is Ok or Err.
                 //
                 // At this level, we take the database-or-RPC error and make it just an RPC error.
+                //
+                // Any view cache invalidations are only performed after the commit.
                 let db_state = Arc::clone(&state);
-                db_state
+                let (output, invalidations) = db_state
                     .database
                     .transaction(move |txn| {
                         Box::pin(async move {
                             // Run the endpoint's implementation, and convert from
                             // ServiceError to an RPC error.
                             let ctx = ServiceContext::new(&state, &txn);
-                            $method(&ctx, params).await.map_err(ErrorObjectOwned::from)
+                            let class: Option<RateLimitClass> = $class;
+                            if let Some(class) = class {
+                                let keys = RateLimitKeys::from_params(&params);
+                                RateLimitService::check(&ctx, class, &keys)
+                                    .await
+                                    .map_err(ErrorObjectOwned::from)?;
+                            }
+
+                            let output = $method(&ctx, params)
+                                .await
+                                .map_err(ErrorObjectOwned::from)?;
+
+                            Ok((output, ctx.take_view_cache_invalidations()))
                         })
                     })
                     .await
-                    .map_err(into_rpc_error)
+                    .map_err(into_rpc_error)?;
+
+                ViewCacheService::run_invalidations(&db_state, invalidations).await;
+                Ok::<_, ErrorObjectOwned>(output)
             })?;
         }};
     }
@@ -181,6 +240,9 @@ async fn build_module(app_state: ServerState) -> anyhow::Result<RpcModule<Server
 
     // Server Information
     register!("info", server_info);
+    register!("render_metrics", render_metrics);
+    register!("view_cache_metrics", view_cache_metrics);
+    register!("job_schedule", job_schedule);
 
     // Localization
     register!("locale", locale_info);
@@ -192,50 +254,81 @@ async fn build_module(app_state: ServerState) -> anyhow::Result<RpcModule<Server
     register!("admin_view", admin_view);
 
     // Authentication
-    register!("login", auth_login);
+    register!("login", auth_login, Authentication);
     register!("logout", auth_logout);
     register!("session_get", auth_session_get);
     register!("session_get_others", auth_session_get_others);
+    register!("session_list", auth_session_list);
+    register!("session_invalidate", auth_session_invalidate);
     register!("session_invalidate_others", auth_session_invalidate_others);
     register!("session_renew", auth_session_renew);
-    register!("mfa_verify", auth_mfa_verify);
+    register!("mfa_verify", auth_mfa_verify, Authentication);
     register!("mfa_setup", auth_mfa_setup);
     register!("mfa_disable", auth_mfa_disable);
     register!("mfa_reset_recovery", auth_mfa_reset_recovery);
+    register!("oidc_providers", auth_oidc_providers);
+    register!("oidc_authorize", auth_oidc_authorize, Authentication);
+    register!("oidc_callback", auth_oidc_callback, Authentication);
+    register!("oidc_identities", auth_oidc_identities);
+    register!("oidc_unlink", auth_oidc_unlink, Account);
 
     // Site
-    register!("site_create", site_create);
+    register!("site_create", site_create, Account);
     register!("site_get", site_get);
     register!("site_update", site_update);
+    register!("site_delete", site_delete);
+    register!("site_restore", site_restore);
     register!("site_from_domain", site_get_from_domain);
 
     // Site custom domain
     register!("custom_domain_create", site_custom_domain_create);
     register!("custom_domain_get", site_custom_domain_get);
     register!("custom_domain_delete", site_custom_domain_delete);
+    register!("custom_domain_verify", site_custom_domain_verify);
 
     // Site membership
     register!("member_set", membership_set);
     register!("member_get", membership_get);
     register!("member_delete", membership_delete);
+    register!("member_join", membership_join);
+    register!("member_apply", membership_apply, Write);
+    register!("member_application_list", membership_application_list);
+    register!("member_application_accept", membership_application_accept);
+    register!("member_application_reject", membership_application_reject);
+    register!("member_invite_user", membership_invite_user);
+    register!("member_invite_email", membership_invite_email, Message);
+    register!("member_invitation_list", membership_invitation_list);
+    register!("member_invitation_accept", membership_invitation_accept);
+    register!("member_invitation_revoke", membership_invitation_revoke);
 
     // Category
     register!("category_get", category_get);
     register!("category_get_all", category_get_all);
+    register!("category_update", category_update);
+    register!("category_get_pages", category_get_pages);
+    register!("category_prune", category_prune);
+
+    // Tags
+    register!("tag_cloud", tag_cloud);
+    register!("tag_get_pages", tag_get_pages);
+    register!("tag_rename", tag_rename);
+    register!("tag_merge", tag_merge);
 
     // Page
-    register!("page_create", page_create);
+    register!("page_create", page_create, Write);
     register!("page_get", page_get);
     register!("page_get_direct", page_get_direct);
     register!("page_get_deleted", page_get_deleted);
     register!("page_get_score", page_get_score);
     register!("page_get_files", page_get_files);
-    register!("page_edit", page_edit);
-    register!("page_delete", page_delete);
-    register!("page_move", page_move);
-    register!("page_rollback", page_rollback);
+    register!("page_edit", page_edit, Write);
+    register!("page_delete", page_delete, Write);
+    register!("page_move", page_move, Write);
+    register!("page_rollback", page_rollback, Write);
     register!("page_rerender", page_rerender);
-    register!("page_restore", page_restore);
+    register!("page_rerender_all", page_rerender_all);
+    register!("page_rerender_progress", page_rerender_progress);
+    register!("page_restore", page_restore, Write);
     register!("page_set_layout", page_set_layout);
 
     // Page revisions
@@ -258,29 +351,48 @@ async fn build_module(app_state: ServerState) -> anyhow::Result<RpcModule<Server
     register!("parent_relationships_get", parent_relationships_get);
     register!("parent_get_all", parent_get_all);
     register!("parent_update", parent_update);
+    register!("parent_get_history", parent_get_history);
+    register!("parent_breadcrumbs", parent_breadcrumbs);
+    register!("parent_tree", parent_tree);
 
     // Blob data
     register!("blob_get", blob_get);
-    register!("blob_upload", blob_upload);
+    register!("blob_get_range", blob_get_range);
+    register!("blob_upload", blob_upload, Write);
     register!("blob_cancel", blob_cancel);
+    register!("blob_multipart_start", blob_multipart_start, Write);
+    register!("blob_multipart_part", blob_multipart_part);
+    register!("blob_multipart_complete", blob_multipart_complete);
+    register!("blob_multipart_abort", blob_multipart_abort);
 
     // Blob hard deletion
     register!("blob_hard_delete_preview", blob_hard_delete_preview);
     register!("blob_hard_delete_confirm", blob_hard_delete_confirm);
+    register!("blob_collect_orphans_preview", blob_collect_orphans_preview);
+    register!("blob_collect_orphans_confirm", blob_collect_orphans_confirm);
 
     // Blob blacklist
     register!("blob_blacklist_add", blob_blacklist_add);
     register!("blob_blacklist_remove", blob_blacklist_remove);
     register!("blob_blacklist_check", blob_blacklist_check);
 
+    // Storage usage
+    register!("storage_usage_get", storage_usage_get);
+    register!("storage_usage_recalculate", storage_usage_recalculate);
+    register!("storage_quota_set", storage_quota_set);
+    register!("storage_scrub_start", storage_scrub_start);
+    register!("storage_scrub_progress", storage_scrub_progress);
+    register!("storage_scrub_issues", storage_scrub_issues);
+
     // Files
-    register!("file_create", file_create);
-    register!("file_edit", file_edit);
+    register!("file_create", file_create, Write);
+    register!("file_edit", file_edit, Write);
     register!("file_get", file_get);
-    register!("file_delete", file_delete);
-    register!("file_move", file_move);
-    register!("file_restore", file_restore);
-    register!("file_rollback", file_rollback);
+    register!("file_delete", file_delete, Write);
+    register!("file_move", file_move, Write);
+    register!("file_restore", file_restore, Write);
+    register!("file_rollback", file_rollback, Write);
+    register!("file_licensing_issues", file_licensing_issues);
 
     // File revisions
     register!("file_revision_get", file_revision_get);
@@ -291,14 +403,19 @@ async fn build_module(app_state: ServerState) -> anyhow::Result<RpcModule<Server
     // Text
     register!("text_create", text_create);
     register!("text_get", text_get);
+    register!("text_recompress_start", text_recompress_start);
+    register!("text_recompress_progress", text_recompress_progress);
 
     // User
-    register!("user_create", user_create);
+    register!("user_create", user_create, Account);
     register!("user_import", user_import);
     register!("user_get", user_get);
     register!("user_edit", user_edit);
     register!("user_delete", user_delete);
     register!("user_add_name_change", user_add_name_change);
+    register!("user_export_request", user_export_request, Account);
+    register!("user_export_get", user_export_get);
+    register!("user_erase", user_erase, Account);
 
     // Bot user
     register!("bot_user_create", bot_user_create);
@@ -307,18 +424,20 @@ async fn build_module(app_state: ServerState) -> anyhow::Result<RpcModule<Server
     register!("bot_user_owner_remove", bot_user_owner_remove);
 
     // Direct messages
-    register!("message_draft_create", message_draft_create);
-    register!("message_draft_edit", message_draft_edit);
+    register!("message_draft_create", message_draft_create, Write);
+    register!("message_draft_edit", message_draft_edit, Write);
     register!("message_draft_delete", message_draft_delete);
-    register!("message_draft_send", message_draft_send);
+    register!("message_draft_send", message_draft_send, Message);
+    register!("message_attachment_list", message_attachment_list);
+    register!("message_attachment_get", message_attachment_get);
 
     // Email
     register!("email_validate", validate_email);
 
     // Votes
-    register!("vote_set", vote_set);
+    register!("vote_set", vote_set, Write);
     register!("vote_get", vote_get);
-    register!("vote_remove", vote_remove);
+    register!("vote_remove", vote_remove, Write);
     register!("vote_action", vote_action);
     register!("vote_list", vote_list_get);
     register!("vote_list_count", vote_list_count);
@@ -19,7 +19,8 @@
  */
 
 use super::Config;
-use anyhow::Result;
+use crate::types::{OidcProvider, RateLimitBucket, ScannerType, Schedule};
+use anyhow::{bail, Result};
 use femme::LevelFilter;
 use ftml::layout::Layout;
 use std::convert::TryFrom;
@@ -31,6 +32,11 @@ use std::path::PathBuf;
 use std::time::Duration as StdDuration;
 use time::Duration as TimeDuration;
 
+/// The smallest part size, in MiB, that S3 accepts for multipart uploads.
+///
+/// Every part except the last must be at least this large.
+const MINIMUM_MULTIPART_PART_SIZE_MB: u64 = 5;
+
 /// Structure representing a configuration file.
 ///
 /// This differs from the `Config` struct because
@@ -46,6 +52,7 @@ pub struct ConfigFile {
     logger: Logger,
     server: Server,
     database: Database,
+    text: Text,
     security: Security,
     locale: Locale,
     domain: Domain,
@@ -53,8 +60,12 @@ pub struct ConfigFile {
     ftml: Ftml,
     special_pages: SpecialPages,
     user: User,
+    site: Site,
     file: FileSection,
     message: Message,
+    rate_limit: RateLimit,
+    view_cache: ViewCache,
+    oidc: Oidc,
 }
 
 /// Structure containing extra fields not found in `ConfigFile`.
@@ -79,6 +90,7 @@ struct Logger {
 struct Server {
     address: SocketAddr,
     pid_file: Option<PathBuf>,
+    email_sender: String,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -88,6 +100,18 @@ struct Database {
     seeder_path: PathBuf,
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+struct Text {
+    compression: bool,
+    compression_level: i32,
+    compression_min_bytes: usize,
+    dictionary_size: usize,
+    dictionary_samples: u64,
+    delta: bool,
+    max_delta_depth: NonZeroU16,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "kebab-case")]
 struct Security {
@@ -103,6 +127,11 @@ struct Session {
     token_length: usize,
     duration_session_minutes: u64,
     duration_login_minutes: u64,
+    idle_timeout_minutes: u64,
+    bind_user_agent: bool,
+    bind_ip_subnet: bool,
+    ipv4_subnet_prefix: u8,
+    ipv6_subnet_prefix: u8,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -122,10 +151,28 @@ struct Job {
     delay_ms: u64,
     min_delay_poll_secs: u64,
     max_delay_poll_secs: u64,
-    prune_session_secs: u64,
-    prune_text_secs: u64,
-    name_change_refill_secs: u64,
-    lift_expired_punishments_secs: u64,
+    rerender_batch_size: NonZeroU16,
+    rerender_batch_delay_ms: u64,
+    recompress_text_batch_size: NonZeroU16,
+    recompress_text_batch_delay_ms: u64,
+    scrub_batch_size: NonZeroU16,
+    scrub_batch_delay_ms: u64,
+    scheduler_poll_secs: u64,
+    schedule: JobSchedule,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+struct JobSchedule {
+    prune_sessions: Schedule,
+    prune_text: Schedule,
+    prune_uploads: Schedule,
+    prune_exports: Schedule,
+    name_change_refill: Schedule,
+    lift_expired_punishments: Schedule,
+    verify_custom_domains: Schedule,
+    collect_orphaned_blobs: Schedule,
+    scrub_storage: Schedule,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -139,12 +186,17 @@ struct Locale {
 struct Domain {
     main: String,
     files: String,
+    verification_resolver: String,
+    verification_grace_hours: u64,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "kebab-case")]
 struct Ftml {
     render_timeout_ms: u64,
+    render_workers: NonZeroU16,
+    render_background_workers: NonZeroU16,
+    render_queue_size: NonZeroU16,
     rerender_skip: Vec<RerenderSkip>,
     layout: FtmlLayout,
 }
@@ -180,6 +232,14 @@ struct User {
     maximum_name_changes: u8,
     refill_name_change_days: u64,
     minimum_name_bytes: usize,
+    data_export_retention_days: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+struct Site {
+    invitation_token_length: usize,
+    invitation_duration_days: u64,
 }
 
 // NOTE: Name conflict with std::fs::File
@@ -190,6 +250,23 @@ struct FileSection {
     presigned_expiration_minutes: u32,
     maximum_blob_size_kb: i64,
     maximum_avatar_size_kb: i64,
+    maximum_inline_size_kb: i64,
+    download_on_files_domain: bool,
+    multipart_part_size_mb: u64,
+    multipart_expiration_hours: u64,
+    site_storage_quota_mb: i64,
+    user_storage_quota_mb: i64,
+    orphan_grace_hours: u64,
+    scanner: FileScanner,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+struct FileScanner {
+    #[serde(rename = "type")]
+    scanner_type: ScannerType,
+    clamd_address: String,
+    timeout_secs: u64,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -198,6 +275,36 @@ struct Message {
     maximum_subject_bytes: usize,
     maximum_body_bytes: usize,
     maximum_recipients: usize,
+    maximum_attachments: usize,
+    maximum_attachment_bytes: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+struct RateLimit {
+    authentication: RateLimitBucket,
+    write: RateLimitBucket,
+    message: RateLimitBucket,
+    account: RateLimitBucket,
+    lockout_attempts: NonZeroU16,
+    lockout_window_secs: u64,
+    lockout_duration_secs: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+struct ViewCache {
+    enable: bool,
+    ttl_secs: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+struct Oidc {
+    state_expiry_secs: u64,
+
+    #[serde(default)]
+    provider: Vec<OidcProvider>,
 }
 
 impl ConfigFile {
@@ -208,7 +315,9 @@ impl ConfigFile {
         file.read_to_string(&mut contents)?;
 
         // Parse and build objects
-        let config = toml::from_str(&contents)?;
+        let config: ConfigFile = toml::from_str(&contents)?;
+        config.validate()?;
+
         let extra = ExtraConfig {
             raw_toml: contents,
             raw_toml_path: path,
@@ -217,6 +326,18 @@ impl ConfigFile {
         Ok((config, extra))
     }
 
+    /// Checks values which are well-formed but would fail at runtime.
+    fn validate(&self) -> Result<()> {
+        let part_size_mb = self.file.multipart_part_size_mb;
+        if part_size_mb < MINIMUM_MULTIPART_PART_SIZE_MB {
+            bail!(
+                "file.multipart-part-size-mb must be at least {MINIMUM_MULTIPART_PART_SIZE_MB}, got {part_size_mb}",
+            );
+        }
+
+        Ok(())
+    }
+
     /// Deconstruct the `ConfigFile` and flatten it as a `Config` object.
     pub fn into_config(self, extra: ExtraConfig) -> Config {
         macro_rules! time_duration {
@@ -245,12 +366,23 @@ impl ConfigFile {
                 Server {
                     address,
                     mut pid_file,
+                    email_sender,
                 },
             database:
                 Database {
                     run_seeder,
                     seeder_path,
                 },
+            text:
+                Text {
+                    compression: text_compression,
+                    compression_level: text_compression_level,
+                    compression_min_bytes: text_compression_min_bytes,
+                    dictionary_size: text_dictionary_size,
+                    dictionary_samples: text_dictionary_samples,
+                    delta: text_delta,
+                    max_delta_depth: text_max_delta_depth,
+                },
             security:
                 Security {
                     authentication_fail_delay_ms,
@@ -260,6 +392,11 @@ impl ConfigFile {
                             token_length,
                             duration_session_minutes,
                             duration_login_minutes,
+                            idle_timeout_minutes,
+                            bind_user_agent,
+                            bind_ip_subnet,
+                            ipv4_subnet_prefix,
+                            ipv6_subnet_prefix,
                         },
                     mfa:
                         Mfa {
@@ -273,6 +410,8 @@ impl ConfigFile {
                 Domain {
                     main: main_domain,
                     files: files_domain,
+                    verification_resolver: domain_verification_resolver,
+                    verification_grace_hours: domain_verification_grace_hours,
                 },
             job:
                 Job {
@@ -281,10 +420,25 @@ impl ConfigFile {
                     delay_ms: job_work_delay_ms,
                     min_delay_poll_secs: job_min_poll_delay_secs,
                     max_delay_poll_secs: job_max_poll_delay_secs,
-                    prune_session_secs: job_prune_session_secs,
-                    prune_text_secs: job_prune_text_secs,
-                    name_change_refill_secs: job_name_change_refill_secs,
-                    lift_expired_punishments_secs: job_lift_expired_punishments_secs,
+                    rerender_batch_size: job_rerender_batch_size,
+                    rerender_batch_delay_ms: job_rerender_batch_delay_ms,
+                    recompress_text_batch_size: job_recompress_text_batch_size,
+                    recompress_text_batch_delay_ms: job_recompress_text_batch_delay_ms,
+                    scrub_batch_size: job_scrub_batch_size,
+                    scrub_batch_delay_ms: job_scrub_batch_delay_ms,
+                    scheduler_poll_secs: job_scheduler_poll_secs,
+                    schedule:
+                        JobSchedule {
+                            prune_sessions: job_prune_session,
+                            prune_text: job_prune_text,
+                            prune_uploads: job_prune_uploads,
+                            prune_exports: job_prune_exports,
+                            name_change_refill: job_name_change_refill,
+                            lift_expired_punishments: job_lift_expired_punishments,
+                            verify_custom_domains: job_verify_custom_domains,
+                            collect_orphaned_blobs: job_collect_orphaned_blobs,
+                            scrub_storage: job_scrub_storage,
+                        },
                 },
             locale: Locale {
                 path: localization_path,
@@ -292,6 +446,9 @@ impl ConfigFile {
             ftml:
                 Ftml {
                     render_timeout_ms,
+                    render_workers,
+                    render_background_workers,
+                    render_queue_size,
                     rerender_skip,
                     layout:
                         FtmlLayout {
@@ -313,6 +470,12 @@ impl ConfigFile {
                     maximum_name_changes,
                     refill_name_change_days,
                     minimum_name_bytes,
+                    data_export_retention_days,
+                },
+            site:
+                Site {
+                    invitation_token_length,
+                    invitation_duration_days,
                 },
             file:
                 FileSection {
@@ -320,40 +483,66 @@ impl ConfigFile {
                     presigned_expiration_minutes,
                     maximum_blob_size_kb,
                     maximum_avatar_size_kb,
+                    maximum_inline_size_kb,
+                    download_on_files_domain,
+                    multipart_part_size_mb,
+                    multipart_expiration_hours,
+                    site_storage_quota_mb,
+                    user_storage_quota_mb,
+                    orphan_grace_hours,
+                    scanner:
+                        FileScanner {
+                            scanner_type,
+                            clamd_address,
+                            timeout_secs: scanner_timeout_secs,
+                        },
                 },
             message:
                 Message {
                     maximum_subject_bytes: maximum_message_subject_bytes,
                     maximum_body_bytes: maximum_message_body_bytes,
                     maximum_recipients: maximum_message_recipients,
+                    maximum_attachments: maximum_message_attachments,
+                    maximum_attachment_bytes: maximum_message_attachment_bytes,
+                },
+            rate_limit:
+                RateLimit {
+                    authentication: rate_limit_authentication,
+                    write: rate_limit_write,
+                    message: rate_limit_message,
+                    account: rate_limit_account,
+                    lockout_attempts: login_lockout_attempts,
+                    lockout_window_secs: login_lockout_window_secs,
+                    lockout_duration_secs: login_lockout_duration_secs,
+                },
+            view_cache:
+                ViewCache {
+                    enable: view_cache_enable,
+                    ttl_secs: view_cache_ttl_secs,
+                },
+            oidc:
+                Oidc {
+                    state_expiry_secs: oidc_state_expiry_secs,
+                    provider: oidc_providers,
                 },
         } = self;
 
-        // Assertions for bad values
-        const RSMQ_DELAY_LIMIT: u64 = 9999999;
-
-        assert!(
-            job_prune_session_secs < RSMQ_DELAY_LIMIT,
-            "Session prune job period time too long",
-        );
-        assert!(
-            job_prune_text_secs < RSMQ_DELAY_LIMIT,
-            "Text prune job period time too long",
-        );
-        assert!(
-            job_name_change_refill_secs < RSMQ_DELAY_LIMIT,
-            "Name change refill job period time too long",
-        );
-        assert!(
-            job_lift_expired_punishments_secs < RSMQ_DELAY_LIMIT,
-            "Expired punishment cleanup job period time too long",
-        );
-
         // Prefix domains with '.' so we can do easy subdomain checks
         // and concatenations.
         let (main_domain, main_domain_no_dot) = prefix_domain(main_domain);
         let (files_domain, files_domain_no_dot) = prefix_domain(files_domain);
 
+        // Empty means to use the system's resolver configuration.
+        let domain_verification_resolver = if domain_verification_resolver.is_empty() {
+            None
+        } else {
+            Some(
+                domain_verification_resolver
+                    .parse()
+                    .expect("Invalid address for custom domain verification resolver"),
+            )
+        };
+
         // Treats empty strings (which aren't valid paths anyways)
         // as null for the purpose of pid_file.
         if let Some(ref path) = pid_file {
@@ -369,13 +558,26 @@ impl ConfigFile {
             logger_level,
             address,
             pid_file,
+            email_sender,
             main_domain,
             main_domain_no_dot,
             files_domain,
             files_domain_no_dot,
+            domain_verification_resolver,
+            domain_verification_grace: time_duration!(
+                from_secs,
+                domain_verification_grace_hours * 60 * 60,
+            ),
             watch_files: false, // Not set in config file. Always false by default.
             run_seeder,
             seeder_path,
+            text_compression,
+            text_compression_level,
+            text_compression_min_bytes,
+            text_dictionary_size,
+            text_dictionary_samples,
+            text_delta,
+            text_max_delta_depth,
             localization_path,
             authentication_fail_delay: StdDuration::from_millis(
                 authentication_fail_delay_ms,
@@ -390,6 +592,15 @@ impl ConfigFile {
                 from_secs,
                 duration_login_minutes * 60,
             ),
+            session_idle_timeout: if idle_timeout_minutes == 0 {
+                None
+            } else {
+                Some(time_duration!(from_secs, idle_timeout_minutes * 60))
+            },
+            session_bind_user_agent: bind_user_agent,
+            session_bind_ip_subnet: bind_ip_subnet,
+            session_ipv4_subnet_prefix: ipv4_subnet_prefix.min(32),
+            session_ipv6_subnet_prefix: ipv6_subnet_prefix.min(128),
             recovery_code_count,
             recovery_code_length,
             totp_time_step: time_step,
@@ -399,13 +610,30 @@ impl ConfigFile {
             job_work_delay: StdDuration::from_millis(job_work_delay_ms),
             job_min_poll_delay: StdDuration::from_secs(job_min_poll_delay_secs),
             job_max_poll_delay: StdDuration::from_secs(job_max_poll_delay_secs),
-            job_prune_session: StdDuration::from_secs(job_prune_session_secs),
-            job_prune_text: StdDuration::from_secs(job_prune_text_secs),
-            job_name_change_refill: StdDuration::from_secs(job_name_change_refill_secs),
-            job_lift_expired_punishments: StdDuration::from_secs(
-                job_lift_expired_punishments_secs,
+            job_prune_session,
+            job_prune_text,
+            job_prune_uploads,
+            job_prune_exports,
+            job_name_change_refill,
+            job_lift_expired_punishments,
+            job_verify_custom_domains,
+            job_collect_orphaned_blobs,
+            job_scrub_storage,
+            job_rerender_batch_size,
+            job_rerender_batch_delay: StdDuration::from_millis(
+                job_rerender_batch_delay_ms,
             ),
+            job_recompress_text_batch_size,
+            job_recompress_text_batch_delay: StdDuration::from_millis(
+                job_recompress_text_batch_delay_ms,
+            ),
+            job_scrub_batch_size,
+            job_scrub_batch_delay: StdDuration::from_millis(job_scrub_batch_delay_ms),
+            job_scheduler_poll: StdDuration::from_secs(job_scheduler_poll_secs),
             render_timeout: StdDuration::from_millis(render_timeout_ms),
+            render_workers,
+            render_background_workers,
+            render_queue_size,
             rerender_skip: rerender_skip
                 .iter()
                 .map(
@@ -442,17 +670,75 @@ impl ConfigFile {
                 ))
             },
             minimum_name_bytes,
+            user_data_export_retention: StdDuration::from_secs(
+                data_export_retention_days * 24 * 60 * 60,
+            ),
+            invitation_token_length,
+            invitation_duration: time_duration!(
+                from_secs,
+                invitation_duration_days * 24 * 60 * 60,
+            ),
             presigned_path_length,
             presigned_expiry_secs: presigned_expiration_minutes * 60,
             maximum_blob_size: maximum_blob_size_kb * 1024,
             maximum_avatar_size: maximum_avatar_size_kb * 1024,
+            maximum_inline_blob_size: maximum_inline_size_kb * 1024,
+            download_on_files_domain,
+            multipart_part_size: multipart_part_size_mb * 1024 * 1024,
+            multipart_expiry: time_duration!(
+                from_secs,
+                multipart_expiration_hours * 60 * 60,
+            ),
+            site_storage_quota: storage_quota(site_storage_quota_mb),
+            user_storage_quota: storage_quota(user_storage_quota_mb),
+            blob_orphan_grace: time_duration!(from_secs, orphan_grace_hours * 60 * 60),
+            scanner_type,
+            clamd_address,
+            scanner_timeout: StdDuration::from_secs(scanner_timeout_secs),
             maximum_message_subject_bytes,
             maximum_message_body_bytes,
             maximum_message_recipients,
+            maximum_message_attachments,
+            maximum_message_attachment_bytes,
+            rate_limit_authentication,
+            rate_limit_write,
+            rate_limit_message,
+            rate_limit_account,
+            login_lockout_attempts,
+            login_lockout_window: StdDuration::from_secs(login_lockout_window_secs),
+            login_lockout_duration: StdDuration::from_secs(login_lockout_duration_secs),
+            view_cache_enable,
+            view_cache_ttl: StdDuration::from_secs(view_cache_ttl_secs),
+            oidc_state_expiry: StdDuration::from_secs(oidc_state_expiry_secs),
+            oidc_providers,
         }
     }
 }
 
+impl Config {
+    /// Builds the configuration from `config.example.toml`, for tests.
+    #[cfg(test)]
+    pub fn example() -> Self {
+        let raw_toml = include_str!("../../config.example.toml");
+        let config: ConfigFile =
+            toml::from_str(raw_toml).expect("Unable to parse example configuration");
+
+        config.into_config(ExtraConfig {
+            raw_toml: str!(raw_toml),
+            raw_toml_path: PathBuf::from("config.example.toml"),
+        })
+    }
+}
+
+/// Converts a storage quota in MiB to bytes, where zero means unlimited.
+fn storage_quota(quota_mb: i64) -> Option<i64> {
+    if quota_mb == 0 {
+        None
+    } else {
+        Some(quota_mb * 1024 * 1024)
+    }
+}
+
 /// Takes a domain, and returns a value with and without a leading `.`
 ///
 /// # Returns
@@ -484,3 +770,26 @@ fn test_prefix_domain() {
     check!("example.com"; ".example.com", "example.com");
     check!(".example.com"; ".example.com", "example.com");
 }
+
+#[test]
+fn test_validate_multipart_part_size() {
+    let mut config: ConfigFile =
+        toml::from_str(include_str!("../../config.example.toml"))
+            .expect("Unable to parse example configuration");
+
+    assert!(
+        config.validate().is_ok(),
+        "Example configuration is invalid"
+    );
+
+    for part_size_mb in [0, 1, 4] {
+        config.file.multipart_part_size_mb = part_size_mb;
+        assert!(
+            config.validate().is_err(),
+            "Part size of {part_size_mb} MiB was accepted",
+        );
+    }
+
+    config.file.multipart_part_size_mb = 5;
+    assert!(config.validate().is_ok(), "Minimum part size was rejected");
+}
@@ -19,6 +19,7 @@
  */
 
 use super::file::ConfigFile;
+use crate::types::{OidcProvider, RateLimitBucket, ScannerType, Schedule};
 use anyhow::Result;
 use femme::LevelFilter;
 use ftml::layout::Layout;
@@ -54,6 +55,9 @@ pub struct Config {
     /// The PID file (if any) to write to on boot.
     pub pid_file: Option<PathBuf>,
 
+    /// The mailbox which emails are sent from.
+    pub email_sender: String,
+
     /// The main domain to serve sites from.
     ///
     /// Always starts with a `.`
@@ -65,13 +69,19 @@ pub struct Config {
     /// The files domain to serve user-generated content from.
     ///
     /// Always starts with a `.`
-    #[allow(dead_code)] // TEMP
     pub files_domain: String,
 
     /// The files domain, but without a leading `.`
-    #[allow(dead_code)] // TEMP
     pub files_domain_no_dot: String,
 
+    /// The DNS server to query when verifying custom domains.
+    ///
+    /// If `None`, then the system's resolver configuration is used.
+    pub domain_verification_resolver: Option<SocketAddr>,
+
+    /// How long a verified custom domain may fail re-verification before it is disabled.
+    pub domain_verification_grace: TimeDuration,
+
     /// Whether to auto-restart on configuration file change.
     ///
     /// Currently watches:
@@ -86,6 +96,29 @@ pub struct Config {
     /// The location where all the seeder files are kept.
     pub seeder_path: PathBuf,
 
+    /// Whether to compress text rows with zstd.
+    pub text_compression: bool,
+
+    /// What zstd compression level to use for text rows.
+    pub text_compression_level: i32,
+
+    /// Text smaller than this many bytes is stored uncompressed.
+    pub text_compression_min_bytes: usize,
+
+    /// The maximum size, in bytes, of trained zstd dictionaries for text.
+    ///
+    /// If zero, then dictionaries are not trained.
+    pub text_dictionary_size: usize,
+
+    /// How many text rows to sample when training a zstd dictionary.
+    pub text_dictionary_samples: u64,
+
+    /// Whether to store page wikitext as deltas against the previous revision.
+    pub text_delta: bool,
+
+    /// The maximum length of a delta chain before text is stored in full again.
+    pub text_max_delta_depth: NonZeroU16,
+
     /// The location where all Fluent translation files are kept.
     pub localization_path: PathBuf,
 
@@ -104,6 +137,22 @@ pub struct Config {
     /// How long restricted sessions last before expiry.
     pub restricted_session_duration: TimeDuration,
 
+    /// How long a session may go unused before it is no longer valid.
+    /// `None` means that sessions only expire at their absolute expiry.
+    pub session_idle_timeout: Option<TimeDuration>,
+
+    /// Whether sessions are invalidated if used with a different user agent.
+    pub session_bind_user_agent: bool,
+
+    /// Whether sessions are invalidated if used from an IP address in a different subnet.
+    pub session_bind_ip_subnet: bool,
+
+    /// The prefix length of the subnet IPv4 session addresses are bound to.
+    pub session_ipv4_subnet_prefix: u8,
+
+    /// The prefix length of the subnet IPv6 session addresses are bound to.
+    pub session_ipv6_subnet_prefix: u8,
+
     /// The number of recovery codes to have per user.
     pub recovery_code_count: usize,
 
@@ -133,21 +182,66 @@ pub struct Config {
     /// This uses exponential value cappint out at this value.
     pub job_max_poll_delay: StdDuration,
 
-    /// How often to run the "prune expired sessions" recurring job.
-    pub job_prune_session: StdDuration,
+    /// When to run the "prune expired sessions" periodic job.
+    pub job_prune_session: Schedule,
+
+    /// When to run the "prune unused text" periodic job.
+    pub job_prune_text: Schedule,
+
+    /// When to run the "prune expired uploads" periodic job.
+    pub job_prune_uploads: Schedule,
+
+    /// When to run the "prune expired data exports" periodic job.
+    pub job_prune_exports: Schedule,
+
+    /// When to run the "refill name change tokens" periodic job.
+    pub job_name_change_refill: Schedule,
+
+    /// When to run the "lift expired punishments" periodic job.
+    pub job_lift_expired_punishments: Schedule,
 
-    /// How often to run the "prune unused text" recurring job.
-    pub job_prune_text: StdDuration,
+    /// When to run the "re-verify custom domains" periodic job.
+    pub job_verify_custom_domains: Schedule,
 
-    /// How often to run the "refill name change tokens" recurring job.
-    pub job_name_change_refill: StdDuration,
+    /// When to run the "collect orphaned blobs" periodic job.
+    pub job_collect_orphaned_blobs: Schedule,
 
-    /// How often to run the "lift expired punishments" recurring job.
-    pub job_lift_expired_punishments: StdDuration,
+    /// When to run the "scrub storage" periodic job.
+    pub job_scrub_storage: Schedule,
+
+    /// How many pages to queue for rerendering in each batch of a mass rerender.
+    pub job_rerender_batch_size: NonZeroU16,
+
+    /// How long to wait in between batches of a mass rerender.
+    pub job_rerender_batch_delay: StdDuration,
+
+    /// How many text rows to recompress in each batch of a text recompression.
+    pub job_recompress_text_batch_size: NonZeroU16,
+
+    /// How long to wait in between batches of a text recompression.
+    pub job_recompress_text_batch_delay: StdDuration,
+
+    /// How many text rows, blobs, or blob references to check in each batch of a storage scrub.
+    pub job_scrub_batch_size: NonZeroU16,
+
+    /// How long to wait in between batches of a storage scrub.
+    pub job_scrub_batch_delay: StdDuration,
+
+    /// How often the scheduler checks if any periodic jobs are due.
+    pub job_scheduler_poll: StdDuration,
 
     /// Maximum run time for a render request.
     pub render_timeout: StdDuration,
 
+    /// The number of render threads serving interactive requests.
+    pub render_workers: NonZeroU16,
+
+    /// The number of render threads serving background rerender jobs.
+    pub render_background_workers: NonZeroU16,
+
+    /// How many render requests may be waiting in each lane's queue.
+    pub render_queue_size: NonZeroU16,
+
     /// In what circumstances a page rerender should be skipped.
     ///
     /// A list of rerender job depths and durations. If any item in this
@@ -200,6 +294,15 @@ pub struct Config {
     /// Minimum length of bytes in a username.
     pub minimum_name_bytes: usize,
 
+    /// How long completed personal data exports are kept available for download.
+    pub user_data_export_retention: StdDuration,
+
+    /// Length of randomly-generated tokens for emailed site invitations.
+    pub invitation_token_length: usize,
+
+    /// How long site invitations last before expiry.
+    pub invitation_duration: TimeDuration,
+
     /// Length of randomly-generated portion of S3 presigned URLs.
     pub presigned_path_length: usize,
 
@@ -212,6 +315,42 @@ pub struct Config {
     /// Maximum size of a user's avatar image.
     pub maximum_avatar_size: i64,
 
+    /// Maximum size of blob data returned inline in API responses.
+    pub maximum_inline_blob_size: i64,
+
+    /// Whether presigned download URLs are served from the files domain.
+    pub download_on_files_domain: bool,
+
+    /// Size of each part in multipart uploads.
+    pub multipart_part_size: u64,
+
+    /// How long multipart uploads may take before they expire.
+    pub multipart_expiry: TimeDuration,
+
+    /// Default storage quota for each site, in bytes.
+    ///
+    /// If `None`, then sites have unlimited storage unless overridden.
+    pub site_storage_quota: Option<i64>,
+
+    /// Default storage quota for each user, in bytes.
+    ///
+    /// If `None`, then users have unlimited storage unless overridden.
+    pub user_storage_quota: Option<i64>,
+
+    /// How long a blob must be unused before it is deleted.
+    pub blob_orphan_grace: TimeDuration,
+
+    /// Which malware scanner to check uploaded blobs with.
+    pub scanner_type: ScannerType,
+
+    /// Address of the ClamAV daemon, if it is the scanner in use.
+    ///
+    /// Either a TCP `host:port`, or the path to a Unix socket.
+    pub clamd_address: String,
+
+    /// How long to wait on the malware scanner before giving up.
+    pub scanner_timeout: StdDuration,
+
     /// Maximum size of the subject line allowed in a direct message.
     pub maximum_message_subject_bytes: usize,
 
@@ -220,6 +359,45 @@ pub struct Config {
 
     /// Maximum number of total recipients allowed in a direct message.
     pub maximum_message_recipients: usize,
+
+    /// Maximum number of files which can be attached to a direct message.
+    pub maximum_message_attachments: usize,
+
+    /// Maximum size of each file attached to a direct message.
+    pub maximum_message_attachment_bytes: i64,
+
+    /// Rate limit for authentication methods, such as logging in.
+    pub rate_limit_authentication: RateLimitBucket,
+
+    /// Rate limit for methods which modify content, such as editing pages.
+    pub rate_limit_write: RateLimitBucket,
+
+    /// Rate limit for methods which send messages or emails to others.
+    pub rate_limit_message: RateLimitBucket,
+
+    /// Rate limit for methods which create accounts or sites.
+    pub rate_limit_account: RateLimitBucket,
+
+    /// How many failed logins for an account are permitted before it is locked.
+    pub login_lockout_attempts: NonZeroU16,
+
+    /// The period in which failed logins are counted towards a lockout.
+    pub login_lockout_window: StdDuration,
+
+    /// How long an account is locked for after too many failed logins.
+    pub login_lockout_duration: StdDuration,
+
+    /// Whether assembled page views are cached in Redis.
+    pub view_cache_enable: bool,
+
+    /// How long cached page views are kept before expiring.
+    pub view_cache_ttl: StdDuration,
+
+    /// How long a login through an OpenID Connect provider may take to complete.
+    pub oidc_state_expiry: StdDuration,
+
+    /// The OpenID Connect providers users may log in with.
+    pub oidc_providers: Vec<OidcProvider>,
 }
 
 impl Config {
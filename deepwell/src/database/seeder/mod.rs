@@ -27,7 +27,8 @@ use crate::models::sea_orm_active_enums::AliasType;
 use crate::services::alias::{AliasService, CreateAlias};
 use crate::services::domain::{CreateCustomDomain, DomainService};
 use crate::services::file::{
-    CreateFile, CreateFileOutput, DeleteFile, EditFile, EditFileBody, FileService,
+    CreateFile, CreateFileOutput, DeleteFile, EditFile, EditFileBody, FileLicensing,
+    FileService,
 };
 use crate::services::filter::{CreateFilter, FilterService};
 use crate::services::page::{CreatePage, PageService};
@@ -39,7 +40,6 @@ use anyhow::Result;
 use sea_orm::{
     ConnectionTrait, DatabaseBackend, DatabaseTransaction, Statement, TransactionTrait,
 };
-use serde_json::Value as JsonValue;
 use std::borrow::Cow;
 use std::collections::HashMap;
 use std::fs;
@@ -185,8 +185,15 @@ pub async fn seed(state: &ServerState) -> Result<()> {
         for domain in site.domains {
             info!("Creating site domain '{domain}'");
 
-            DomainService::create_custom(&ctx, CreateCustomDomain { site_id, domain })
-                .await?;
+            DomainService::create_custom(
+                &ctx,
+                CreateCustomDomain {
+                    site_id,
+                    domain,
+                    bypass_verification: true,
+                },
+            )
+            .await?;
         }
 
         site_ids.insert(slug, site_id);
@@ -293,7 +300,7 @@ pub async fn seed(state: &ServerState) -> Result<()> {
                             direct_upload: Some(data),
                             revision_comments: str!(),
                             user_id: SYSTEM_USER_ID,
-                            licensing: JsonValue::Null,
+                            licensing: FileLicensing::default(),
                             bypass_filter: true,
                         },
                     )
@@ -20,6 +20,7 @@
 
 use super::prelude::*;
 use crate::models::session::Model as SessionModel;
+use crate::models::user_oidc_identity::Model as UserOidcIdentityModel;
 use crate::services::authentication::{
     AuthenticateUserOutput, AuthenticationService, LoginUser, LoginUserMfa,
     LoginUserOutput, MultiFactorAuthenticateUser,
@@ -27,9 +28,13 @@ use crate::services::authentication::{
 use crate::services::mfa::{
     MultiFactorConfigure, MultiFactorResetOutput, MultiFactorSetupOutput,
 };
+use crate::services::oidc::{
+    OidcAuthorize, OidcAuthorizeOutput, OidcCallback, OidcCallbackOutput,
+    OidcProviderInfo, OidcUnlink,
+};
 use crate::services::session::{
-    CreateSession, GetOtherSessions, GetOtherSessionsOutput, InvalidateOtherSessions,
-    RenewSession,
+    CreateSession, GetOtherSessions, GetOtherSessionsOutput, GetSession,
+    InvalidateOtherSessions, InvalidateSession, RenewSession, SessionClient, SessionInfo,
 };
 use crate::services::user::GetUser;
 use crate::services::Error;
@@ -60,15 +65,16 @@ pub async fn auth_login(
     // to avoid leaking internal state. However since we are an internal
     // API
     //
-    // The only three possible responses to this method should be:
+    // The only four possible responses to this method should be:
     // * success
     // * invalid authentication
+    // * account locked out
     // * server error
     let result = AuthenticationService::auth_password(ctx, authenticate).await;
     let AuthenticateUserOutput { needs_mfa, user_id } = match result {
         Ok(output) => output,
         Err(mut error) => {
-            if !matches!(error, Error::InvalidAuthentication) {
+            if !matches!(error, Error::InvalidAuthentication | Error::LoginLockedOut,) {
                 error!("Unexpected error during user authentication: {error}");
                 error = Error::AuthenticationBackend(Box::new(error));
             }
@@ -115,8 +121,18 @@ pub async fn auth_session_get(
     ctx: &ServiceContext<'_>,
     params: Params<'static>,
 ) -> Result<Option<SessionModel>> {
-    let session_token: String = params.one()?;
-    SessionService::get_optional(ctx, &session_token).await
+    let GetSession {
+        session_token,
+        ip_address,
+        user_agent,
+    } = params.parse()?;
+
+    let client = SessionClient {
+        ip_address,
+        user_agent: &user_agent,
+    };
+
+    SessionService::get_optional_for_client(ctx, &session_token, client).await
 }
 
 pub async fn auth_session_renew(
@@ -134,8 +150,17 @@ pub async fn auth_session_get_others(
     let GetOtherSessions {
         user_id,
         session_token,
+        ip_address,
+        user_agent,
     } = params.parse()?;
 
+    // Ensure the session belongs to this client before exposing any others.
+    let client = SessionClient {
+        ip_address,
+        user_agent: &user_agent,
+    };
+    SessionService::get_for_client(ctx, &session_token, client).await?;
+
     // Produce output struct, which extracts the current session and
     // places it in its own location.
     let mut sessions = SessionService::get_all(ctx, user_id).await?;
@@ -158,6 +183,36 @@ pub async fn auth_session_get_others(
     })
 }
 
+/// Lists all of a user's active sessions, with device information.
+///
+/// Unlike `auth_session_get_others`, this does not expose other sessions' tokens.
+pub async fn auth_session_list(
+    ctx: &ServiceContext<'_>,
+    params: Params<'static>,
+) -> Result<Vec<SessionInfo>> {
+    let GetOtherSessions {
+        user_id,
+        session_token,
+        ip_address,
+        user_agent,
+    } = params.parse()?;
+
+    let client = SessionClient {
+        ip_address,
+        user_agent: &user_agent,
+    };
+
+    SessionService::list(ctx, &session_token, user_id, client).await
+}
+
+pub async fn auth_session_invalidate(
+    ctx: &ServiceContext<'_>,
+    params: Params<'static>,
+) -> Result<()> {
+    let input: InvalidateSession = params.parse()?;
+    SessionService::invalidate_by_id(ctx, input).await
+}
+
 pub async fn auth_session_invalidate_others(
     ctx: &ServiceContext<'_>,
     params: Params<'static>,
@@ -165,9 +220,16 @@ pub async fn auth_session_invalidate_others(
     let InvalidateOtherSessions {
         session_token,
         user_id,
+        ip_address,
+        user_agent,
     } = params.parse()?;
 
-    SessionService::invalidate_others(ctx, &session_token, user_id).await
+    let client = SessionClient {
+        ip_address,
+        user_agent: &user_agent,
+    };
+
+    SessionService::invalidate_others(ctx, &session_token, user_id, client).await
 }
 
 pub async fn auth_mfa_verify(
@@ -188,6 +250,10 @@ pub async fn auth_mfa_verify(
         MultiFactorAuthenticateUser {
             session_token: &session_token,
             totp_or_code: &totp_or_code,
+            client: SessionClient {
+                ip_address,
+                user_agent: &user_agent,
+            },
         },
     )
     .await?;
@@ -220,9 +286,16 @@ pub async fn auth_mfa_disable(
     let MultiFactorConfigure {
         user_id,
         session_token,
+        ip_address,
+        user_agent,
     } = params.parse()?;
 
-    let user = SessionService::get_user(ctx, &session_token, false).await?;
+    let client = SessionClient {
+        ip_address,
+        user_agent: &user_agent,
+    };
+
+    let user = SessionService::get_user(ctx, &session_token, false, client).await?;
     if user.user_id != user_id {
         error!(
             "Passed user ID ({}) does not match session token ({})",
@@ -245,9 +318,16 @@ pub async fn auth_mfa_reset_recovery(
     let MultiFactorConfigure {
         user_id,
         session_token,
+        ip_address,
+        user_agent,
     } = params.parse()?;
 
-    let user = SessionService::get_user(ctx, &session_token, false).await?;
+    let client = SessionClient {
+        ip_address,
+        user_agent: &user_agent,
+    };
+
+    let user = SessionService::get_user(ctx, &session_token, false, client).await?;
     if user.user_id != user_id {
         error!(
             "Passed user ID ({}) does not match session token ({})",
@@ -262,3 +342,57 @@ pub async fn auth_mfa_reset_recovery(
 
     MfaService::reset_recovery_codes(ctx, &user).await
 }
+
+pub async fn auth_oidc_providers(
+    ctx: &ServiceContext<'_>,
+    _params: Params<'static>,
+) -> Result<Vec<OidcProviderInfo>> {
+    Ok(OidcService::providers(ctx.config()))
+}
+
+pub async fn auth_oidc_authorize(
+    ctx: &ServiceContext<'_>,
+    params: Params<'static>,
+) -> Result<OidcAuthorizeOutput> {
+    let input: OidcAuthorize = params.parse()?;
+    OidcService::authorize(ctx, input).await
+}
+
+pub async fn auth_oidc_callback(
+    ctx: &ServiceContext<'_>,
+    params: Params<'static>,
+) -> Result<OidcCallbackOutput> {
+    let input: OidcCallback = params.parse()?;
+
+    // As with password logins, only reveal generic failures.
+    match OidcService::callback(ctx, input).await {
+        Ok(output) => Ok(output),
+        Err(
+            error @ (Error::InvalidAuthentication
+            | Error::OidcStateInvalid
+            | Error::OidcProviderNotFound
+            | Error::OidcIdentityNotLinked
+            | Error::UserExists),
+        ) => Err(error),
+        Err(error) => {
+            error!("Unexpected error during identity provider authentication: {error}");
+            Err(Error::AuthenticationBackend(Box::new(error)))
+        }
+    }
+}
+
+pub async fn auth_oidc_identities(
+    ctx: &ServiceContext<'_>,
+    params: Params<'static>,
+) -> Result<Vec<UserOidcIdentityModel>> {
+    let user_id: i64 = params.one()?;
+    OidcService::get_identities(ctx, user_id).await
+}
+
+pub async fn auth_oidc_unlink(
+    ctx: &ServiceContext<'_>,
+    params: Params<'static>,
+) -> Result<()> {
+    let input: OidcUnlink = params.parse()?;
+    OidcService::unlink(ctx, input).await
+}
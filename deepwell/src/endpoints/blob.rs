@@ -21,37 +21,57 @@
 use super::prelude::*;
 use crate::hash::slice_to_blob_hash;
 use crate::services::blob::{
-    BlobMetadata, CancelBlobUpload, GetBlobOutput, HardDelete, HardDeleteOutput,
-    StartBlobUpload, StartBlobUploadOutput,
+    BlobMetadata, CancelBlobUpload, CollectOrphansOutput, CompleteMultipartUpload,
+    GetBlob, GetBlobOutput, GetBlobRange, GetBlobRangeOutput, GetMultipartUploadPart,
+    GetMultipartUploadPartOutput, HardDelete, HardDeleteOutput, StartBlobUpload,
+    StartBlobUploadOutput, StartMultipartUpload, StartMultipartUploadOutput,
 };
 use crate::services::Result;
 use crate::types::Bytes;
 
-/// Temporary endpoint to get any blob by hash.
-/// Primarily for user avatars, which have no other
-/// way of getting the data at the moment.
+/// Gets any blob by hash.
+///
+/// This produces a presigned URL the blob can be downloaded from,
+/// and only returns the data itself if requested and the blob is small.
+/// Primarily for user avatars, which have no other way of getting
+/// the data at the moment.
 pub async fn blob_get(
     ctx: &ServiceContext<'_>,
     params: Params<'static>,
 ) -> Result<GetBlobOutput> {
     info!("Getting blob for S3 hash");
-    let hash: Bytes = params.parse()?;
-    let data = BlobService::get(ctx, hash.as_ref()).await?;
+    let GetBlob { hash, data } = params.parse()?;
 
     let BlobMetadata {
         mime,
         size,
         created_at,
+        scan,
     } = BlobService::get_metadata(ctx, hash.as_ref()).await?;
 
+    let data = BlobService::get_maybe(ctx, data, hash.as_ref(), size).await?;
+    let download_url =
+        BlobService::get_download_url(ctx, hash.as_ref(), &mime, None).await?;
+
     Ok(GetBlobOutput {
         data,
+        download_url,
         mime,
         size,
         created_at,
+        scan,
     })
 }
 
+/// Gets part of a blob by hash, for instance to seek within media.
+pub async fn blob_get_range(
+    ctx: &ServiceContext<'_>,
+    params: Params<'static>,
+) -> Result<GetBlobRangeOutput> {
+    let GetBlobRange { hash, start, end } = params.parse()?;
+    BlobService::get_range(ctx, hash.as_ref(), start, end).await
+}
+
 /// Cancel a started upload by removing the pending blob.
 pub async fn blob_cancel(
     ctx: &ServiceContext<'_>,
@@ -77,6 +97,68 @@ pub async fn blob_upload(
     BlobService::start_upload(ctx, input).await
 }
 
+/// Starts a new multipart upload by creating a pending blob.
+pub async fn blob_multipart_start(
+    ctx: &ServiceContext<'_>,
+    params: Params<'static>,
+) -> Result<StartMultipartUploadOutput> {
+    info!("Creating new pending multipart blob upload");
+    let input: StartMultipartUpload = params.parse()?;
+    BlobService::start_multipart_upload(ctx, input).await
+}
+
+/// Gets the presign URL to upload one part of a multipart upload.
+pub async fn blob_multipart_part(
+    ctx: &ServiceContext<'_>,
+    params: Params<'static>,
+) -> Result<GetMultipartUploadPartOutput> {
+    let input: GetMultipartUploadPart = params.parse()?;
+    BlobService::get_multipart_part_url(ctx, input).await
+}
+
+/// Completes a multipart upload once all parts have been uploaded.
+pub async fn blob_multipart_complete(
+    ctx: &ServiceContext<'_>,
+    params: Params<'static>,
+) -> Result<()> {
+    info!("Completing a pending multipart blob upload");
+    let input: CompleteMultipartUpload = params.parse()?;
+    BlobService::complete_multipart_upload(ctx, input).await
+}
+
+/// Aborts a multipart upload, removing the pending blob and any uploaded parts.
+pub async fn blob_multipart_abort(
+    ctx: &ServiceContext<'_>,
+    params: Params<'static>,
+) -> Result<()> {
+    info!("Aborting a pending multipart blob upload");
+
+    let CancelBlobUpload {
+        user_id,
+        pending_blob_id,
+    } = params.parse()?;
+
+    BlobService::cancel_upload(ctx, user_id, &pending_blob_id).await
+}
+
+/// Reports which unused blobs would be marked or deleted by garbage collection.
+pub async fn blob_collect_orphans_preview(
+    ctx: &ServiceContext<'_>,
+    _params: Params<'static>,
+) -> Result<CollectOrphansOutput> {
+    info!("Previewing garbage collection of orphaned blobs");
+    BlobService::collect_orphans_preview(ctx).await
+}
+
+/// Runs garbage collection of orphaned blobs now, rather than waiting for the job.
+pub async fn blob_collect_orphans_confirm(
+    ctx: &ServiceContext<'_>,
+    _params: Params<'static>,
+) -> Result<CollectOrphansOutput> {
+    info!("Running garbage collection of orphaned blobs");
+    BlobService::collect_orphans(ctx).await
+}
+
 pub async fn blob_blacklist_add(
     ctx: &ServiceContext<'_>,
     params: Params<'static>,
@@ -85,14 +167,21 @@ pub async fn blob_blacklist_add(
     struct AddBlacklist {
         s3_hash: Bytes<'static>,
         user_id: i64,
+
+        #[serde(default)]
+        reason: Option<String>,
     }
 
-    let AddBlacklist { s3_hash, user_id } = params.parse()?;
+    let AddBlacklist {
+        s3_hash,
+        user_id,
+        reason,
+    } = params.parse()?;
     let s3_hash = slice_to_blob_hash(s3_hash.as_ref());
 
     BlobService::check_hash_not_empty(s3_hash)?;
     BlobService::check_hash_in_use(ctx, s3_hash).await?;
-    BlobService::add_blacklist(ctx, s3_hash, user_id).await
+    BlobService::add_blacklist(ctx, s3_hash, user_id, reason).await
 }
 
 pub async fn blob_blacklist_remove(
@@ -20,7 +20,9 @@
 
 use super::prelude::*;
 use crate::models::page_category::Model as PageCategoryModel;
-use crate::services::category::GetCategory;
+use crate::services::category::{
+    GetCategory, GetCategoryPages, GetCategoryPagesOutput, UpdateCategory,
+};
 use crate::services::site::GetSite;
 
 pub async fn category_get(
@@ -42,3 +44,43 @@ pub async fn category_get_all(
     info!("Getting all page categories in site ID {site_id}");
     CategoryService::get_all(ctx, site_id).await
 }
+
+pub async fn category_update(
+    ctx: &ServiceContext<'_>,
+    params: Params<'static>,
+) -> Result<PageCategoryModel> {
+    let UpdateCategory {
+        site,
+        category,
+        body,
+    } = params.parse()?;
+
+    let site_id = SiteService::get_id(ctx, site).await?;
+    info!("Updating page category {category:?} in site ID {site_id}");
+    CategoryService::update(ctx, site_id, category, body).await
+}
+
+pub async fn category_get_pages(
+    ctx: &ServiceContext<'_>,
+    params: Params<'static>,
+) -> Result<GetCategoryPagesOutput> {
+    let input: GetCategoryPages = params.parse()?;
+    let site_id = SiteService::get_id(ctx, input.site.clone()).await?;
+
+    info!(
+        "Getting pages in page category {:?} in site ID {} (page {}, {} per page)",
+        input.category, site_id, input.page, input.per_page,
+    );
+
+    CategoryService::get_pages(ctx, site_id, input).await
+}
+
+pub async fn category_prune(
+    ctx: &ServiceContext<'_>,
+    params: Params<'static>,
+) -> Result<u64> {
+    let GetSite { site } = params.parse()?;
+    let site_id = SiteService::get_id(ctx, site).await?;
+    info!("Pruning empty page categories in site ID {site_id}");
+    CategoryService::prune(ctx, site_id).await
+}
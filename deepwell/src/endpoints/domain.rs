@@ -20,7 +20,7 @@
 
 use super::prelude::*;
 use crate::models::site::Model as SiteModel;
-use crate::services::domain::CreateCustomDomain;
+use crate::services::domain::{CreateCustomDomain, CreateCustomDomainOutput};
 
 pub async fn site_get_from_domain(
     ctx: &ServiceContext<'_>,
@@ -33,7 +33,7 @@ pub async fn site_get_from_domain(
 pub async fn site_custom_domain_create(
     ctx: &ServiceContext<'_>,
     params: Params<'static>,
-) -> Result<()> {
+) -> Result<CreateCustomDomainOutput> {
     let input: CreateCustomDomain = params.parse()?;
     DomainService::create_custom(ctx, input).await
 }
@@ -54,3 +54,11 @@ pub async fn site_custom_domain_delete(
     let domain: String = params.one()?;
     DomainService::remove_custom(ctx, domain).await
 }
+
+pub async fn site_custom_domain_verify(
+    ctx: &ServiceContext<'_>,
+    params: Params<'static>,
+) -> Result<()> {
+    let domain: String = params.one()?;
+    DomainService::queue_verify(ctx, domain).await
+}
@@ -23,8 +23,8 @@ use crate::models::file::Model as FileModel;
 use crate::models::file_revision::Model as FileRevisionModel;
 use crate::services::file::{
     CreateFile, CreateFileOutput, DeleteFile, DeleteFileOutput, EditFile, EditFileOutput,
-    GetFileDetails, GetFileOutput, MoveFile, MoveFileOutput, RestoreFile,
-    RestoreFileOutput, RollbackFile,
+    FileLicensingIssue, GetFileDetails, GetFileLicensingIssues, GetFileOutput, MoveFile,
+    MoveFileOutput, RestoreFile, RestoreFileOutput, RollbackFile,
 };
 use crate::services::Result;
 use crate::services::{BlobService, FileRevisionService};
@@ -143,13 +143,32 @@ pub async fn file_rollback(
     FileService::rollback(ctx, input).await
 }
 
+/// Lists files in a site whose licensing is missing, invalid, or incompatible.
+pub async fn file_licensing_issues(
+    ctx: &ServiceContext<'_>,
+    params: Params<'static>,
+) -> Result<Vec<FileLicensingIssue>> {
+    let input: GetFileLicensingIssues = params.parse()?;
+    FileService::get_licensing_issues(ctx, input).await
+}
+
 async fn build_file_response(
     ctx: &ServiceContext<'_>,
     file: FileModel,
     revision: FileRevisionModel,
     details: FileDetails,
 ) -> Result<GetFileOutput> {
-    let data = BlobService::get_maybe(ctx, details.data, &revision.s3_hash).await?;
+    let data =
+        BlobService::get_maybe(ctx, details.data, &revision.s3_hash, revision.size)
+            .await?;
+    let download_url = BlobService::get_download_url(
+        ctx,
+        &revision.s3_hash,
+        &revision.mime,
+        Some(&file.name),
+    )
+    .await?;
+
     Ok(GetFileOutput {
         file_id: file.file_id,
         file_created_at: file.created_at,
@@ -163,9 +182,10 @@ async fn build_file_response(
         revision_user_id: revision.user_id,
         name: file.name,
         data: data.map(Bytes::from),
+        download_url,
         mime: revision.mime,
         size: revision.size,
-        licensing: revision.licensing,
+        licensing: serde_json::from_value(revision.licensing)?,
         revision_comments: revision.comments,
         hidden_fields: revision.hidden,
     })
@@ -22,6 +22,10 @@
 
 use super::prelude::*;
 use crate::info;
+use crate::services::job::{JobScheduler, PeriodicJobStatus};
+use crate::services::render::RenderPoolMetrics;
+use crate::services::view::ViewCacheMetrics;
+use crate::services::ViewCacheService;
 use crate::utils::now;
 use std::path::PathBuf;
 use time::OffsetDateTime;
@@ -85,3 +89,27 @@ pub async fn server_info(
         current_time: now(),
     })
 }
+
+pub async fn render_metrics(
+    ctx: &ServiceContext<'_>,
+    _params: Params<'static>,
+) -> Result<RenderPoolMetrics> {
+    info!("Getting render pool metrics");
+    Ok(ctx.render_pool().metrics())
+}
+
+pub async fn view_cache_metrics(
+    _ctx: &ServiceContext<'_>,
+    _params: Params<'static>,
+) -> Result<ViewCacheMetrics> {
+    info!("Getting page view cache metrics");
+    Ok(ViewCacheService::metrics())
+}
+
+pub async fn job_schedule(
+    ctx: &ServiceContext<'_>,
+    _params: Params<'static>,
+) -> Result<Vec<PeriodicJobStatus>> {
+    info!("Getting periodic job schedule");
+    JobScheduler::get_all_status(ctx).await
+}
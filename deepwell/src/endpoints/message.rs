@@ -22,7 +22,9 @@ use super::prelude::*;
 use crate::models::message_draft::Model as MessageDraftModel;
 use crate::models::message_record::Model as MessageRecordModel;
 use crate::services::message::{
-    CreateMessageDraft, DeleteMessageDraft, SendMessageDraft, UpdateMessageDraft,
+    CreateMessageDraft, DeleteMessageDraft, GetMessageAttachment,
+    GetMessageAttachmentOutput, GetMessageAttachments, MessageAttachmentInfo,
+    SendMessageDraft, UpdateMessageDraft,
 };
 
 pub async fn message_draft_create(
@@ -63,3 +65,20 @@ pub async fn message_draft_send(
     info!("Sending message draft with ID {message_draft_id}");
     MessageService::send(ctx, &message_draft_id).await
 }
+
+pub async fn message_attachment_list(
+    ctx: &ServiceContext<'_>,
+    params: Params<'static>,
+) -> Result<Vec<MessageAttachmentInfo>> {
+    let input: GetMessageAttachments = params.parse()?;
+    info!("Listing attachments for message ID {}", input.message_id);
+    MessageService::get_attachments(ctx, input).await
+}
+
+pub async fn message_attachment_get(
+    ctx: &ServiceContext<'_>,
+    params: Params<'static>,
+) -> Result<GetMessageAttachmentOutput> {
+    let input: GetMessageAttachment = params.parse()?;
+    MessageService::get_attachment(ctx, input).await
+}
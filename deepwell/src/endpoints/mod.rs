@@ -32,11 +32,13 @@ mod prelude {
     pub use crate::api::ServerState;
     pub use crate::services::{
         AliasService, BlobService, CategoryService, DomainService, Error as ServiceError,
-        FileRevisionService, FileService, LinkService, MessageReportService,
-        MessageService, MfaService, PageRevisionService, PageService, ParentService,
-        RelationService, RenderService, Result, ScoreService, ServiceContext,
-        SessionService, SettingsService, SiteService, StdResult, TextService,
-        UserService, ViewService, VoteService,
+        FileRevisionService, FileService, LinkService, MembershipService,
+        MessageReportService, MessageService, MfaService, OidcService,
+        PageRevisionService, PageService, ParentService, RateLimitService,
+        RelationService, RenderService, Result, ScoreService, ScrubService,
+        ServiceContext, SessionService, SettingsService, SiteService, StdResult,
+        StorageService, TagService, TextService, UserDataService, UserService,
+        ViewService, VoteService,
     };
     pub use jsonrpsee::types::params::Params;
     pub use std::convert::TryFrom;
@@ -59,6 +61,8 @@ pub mod page_revision;
 pub mod parent;
 pub mod site;
 pub mod site_member;
+pub mod storage;
+pub mod tag;
 pub mod text;
 pub mod user;
 pub mod user_bot;
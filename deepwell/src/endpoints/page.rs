@@ -22,12 +22,14 @@ use super::prelude::*;
 use crate::models::file::Model as FileModel;
 use crate::models::page::Model as PageModel;
 use crate::services::file::{GetFileOutput, GetPageFiles};
+use crate::services::job::{JobService, RerenderProgress, RerenderScope};
 use crate::services::page::{
     CreatePage, CreatePageOutput, DeletePage, DeletePageOutput, EditPage, EditPageOutput,
     GetDeletedPageOutput, GetPageAnyDetails, GetPageDirect, GetPageOutput,
     GetPageReference, GetPageReferenceDetails, GetPageScoreOutput, GetPageSlug, MovePage,
     MovePageOutput, RestorePage, RestorePageOutput, RollbackPage, SetPageLayout,
 };
+use crate::services::render::RenderPriority;
 use crate::services::{Result, TextService};
 use crate::types::{FileOrder, PageDetails, Reference};
 use futures::future::try_join_all;
@@ -182,7 +184,26 @@ pub async fn page_rerender(
 ) -> Result<()> {
     let GetPageDirect { site_id, page_id } = params.parse()?;
     info!("Re-rendering page ID {page_id} in site ID {site_id}");
-    PageRevisionService::rerender(ctx, site_id, page_id, 0).await
+    PageRevisionService::rerender(ctx, site_id, page_id, 0, RenderPriority::Interactive)
+        .await
+}
+
+pub async fn page_rerender_all(
+    ctx: &ServiceContext<'_>,
+    params: Params<'static>,
+) -> Result<RerenderProgress> {
+    let scope: RerenderScope = params.parse()?;
+    info!("Starting mass rerender for {scope:?}");
+    JobService::start_rerender(ctx, scope).await
+}
+
+pub async fn page_rerender_progress(
+    ctx: &ServiceContext<'_>,
+    params: Params<'static>,
+) -> Result<Option<RerenderProgress>> {
+    let scope: RerenderScope = params.parse()?;
+    info!("Getting mass rerender progress for {scope:?}");
+    JobService::get_rerender_progress(ctx, scope).await
 }
 
 pub async fn page_restore(
@@ -249,11 +270,18 @@ async fn build_page_output(
             .await?;
 
     // Get text data, if requested
-    let (wikitext, compiled_html) = try_join!(
+    let (wikitext, compiled_html, compiled_style) = try_join!(
         TextService::get_maybe(ctx, details.wikitext, &revision.wikitext_hash),
         TextService::get_maybe(ctx, details.compiled_html, &revision.compiled_hash),
+        TextService::get_maybe(ctx, details.compiled_html, &revision.compiled_style_hash,),
     )?;
 
+    let compiled_meta = if details.compiled_html {
+        Some(revision.compiled_meta)
+    } else {
+        None
+    };
+
     // Calculate score and determine layout
     let (rating, layout) = try_join!(
         ScoreService::score(ctx, page.page_id),
@@ -278,6 +306,8 @@ async fn build_page_output(
         revision_user_id: revision.user_id,
         wikitext,
         compiled_html,
+        compiled_style,
+        compiled_meta,
         compiled_at: revision.compiled_at,
         compiled_generator: revision.compiled_generator,
         revision_comments: revision.comments,
@@ -329,6 +359,14 @@ async fn build_page_file_output(
         FileRevisionService::get_latest(ctx, file.site_id, file.page_id, file.file_id)
             .await?;
 
+    let download_url = BlobService::get_download_url(
+        ctx,
+        &revision.s3_hash,
+        &revision.mime,
+        Some(&file.name),
+    )
+    .await?;
+
     // Build result struct
     Ok(Some(GetFileOutput {
         file_id: file.file_id,
@@ -343,9 +381,10 @@ async fn build_page_file_output(
         revision_user_id: revision.user_id,
         name: file.name,
         data: None,
+        download_url,
         mime: revision.mime,
         size: revision.size,
-        licensing: revision.licensing,
+        licensing: serde_json::from_value(revision.licensing)?,
         revision_comments: revision.comments,
         hidden_fields: revision.hidden,
     }))
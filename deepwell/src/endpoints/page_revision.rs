@@ -129,6 +129,8 @@ async fn filter_and_populate_revision(
         compiled_hash,
         compiled_at,
         compiled_generator,
+        compiled_style_hash,
+        compiled_meta,
         comments,
         hidden,
         title,
@@ -159,11 +161,18 @@ async fn filter_and_populate_revision(
     }
 
     // Get text data, if requested
-    let (wikitext, compiled_html) = try_join!(
+    let (wikitext, compiled_html, compiled_style) = try_join!(
         TextService::get_maybe(ctx, details.wikitext, &wikitext_hash),
         TextService::get_maybe(ctx, details.compiled_html, &compiled_hash),
+        TextService::get_maybe(ctx, details.compiled_html, &compiled_style_hash),
     )?;
 
+    let compiled_meta = if details.compiled_html {
+        Some(compiled_meta)
+    } else {
+        None
+    };
+
     Ok(PageRevisionModelFiltered {
         revision_id,
         revision_type,
@@ -177,6 +186,8 @@ async fn filter_and_populate_revision(
         changes,
         wikitext,
         compiled_html,
+        compiled_style,
+        compiled_meta,
         compiled_at,
         compiled_generator,
         comments,
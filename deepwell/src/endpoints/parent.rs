@@ -19,11 +19,11 @@
  */
 
 use super::prelude::*;
-use crate::models::page_parent::Model as PageParentModel;
+use crate::models::relation::Model as RelationModel;
 use crate::services::page::GetPageReference;
 use crate::services::parent::{
-    GetParentRelationships, ParentDescription, RemoveParentOutput, UpdateParents,
-    UpdateParentsOutput,
+    GetParentRelationships, GetParentTree, ModifyParent, ParentDescription, ParentPage,
+    ParentTreeNode, RemoveParentOutput, UpdateParents, UpdateParentsOutput,
 };
 use crate::types::Reference;
 use futures::future::try_join_all;
@@ -31,7 +31,7 @@ use futures::future::try_join_all;
 pub async fn parent_relationships_get(
     ctx: &ServiceContext<'_>,
     params: Params<'static>,
-) -> Result<Vec<PageParentModel>> {
+) -> Result<Vec<RelationModel>> {
     let GetParentRelationships {
         site_id,
         page: reference,
@@ -51,7 +51,7 @@ pub async fn parent_relationships_get(
 pub async fn parent_get(
     ctx: &ServiceContext<'_>,
     params: Params<'static>,
-) -> Result<Option<PageParentModel>> {
+) -> Result<Option<RelationModel>> {
     let input: ParentDescription = params.parse()?;
 
     info!(
@@ -62,12 +62,26 @@ pub async fn parent_get(
     ParentService::get_optional(ctx, input).await
 }
 
-pub async fn parent_set(
+pub async fn parent_get_history(
     ctx: &ServiceContext<'_>,
     params: Params<'static>,
-) -> Result<Option<PageParentModel>> {
+) -> Result<Vec<RelationModel>> {
     let input: ParentDescription = params.parse()?;
 
+    info!(
+        "Getting history of parental relationship {:?} -> {:?} in site ID {}",
+        input.parent, input.child, input.site_id,
+    );
+
+    ParentService::get_history(ctx, input).await
+}
+
+pub async fn parent_set(
+    ctx: &ServiceContext<'_>,
+    params: Params<'static>,
+) -> Result<Option<RelationModel>> {
+    let input: ModifyParent = params.parse()?;
+
     info!(
         "Creating parental relationship {:?} -> {:?} in site ID {}",
         input.parent, input.child, input.site_id,
@@ -80,7 +94,7 @@ pub async fn parent_remove(
     ctx: &ServiceContext<'_>,
     params: Params<'static>,
 ) -> Result<RemoveParentOutput> {
-    let input: ParentDescription = params.parse()?;
+    let input: ModifyParent = params.parse()?;
 
     info!(
         "Removing parental relationship {:?} -> {:?} in site ID {}",
@@ -104,7 +118,7 @@ pub async fn parent_get_all(
     let parents: Vec<Reference<'_>> = ParentService::get_parents(ctx, site_id, page)
         .await?
         .iter()
-        .map(|p| Reference::from(p.parent_page_id))
+        .map(|p| Reference::from(p.dest_id))
         .collect();
 
     let pages: Vec<String> = PageService::get_pages(ctx, site_id, parents.as_slice())
@@ -132,10 +146,11 @@ pub async fn parent_update(
             let creation = parents.iter().map(|parent| {
                 ParentService::create(
                     ctx,
-                    ParentDescription {
+                    ModifyParent {
                         site_id: input.site_id,
                         parent: parent.to_owned(),
                         child: input.child.clone(),
+                        user_id: input.user_id,
                     },
                 )
             });
@@ -144,7 +159,7 @@ pub async fn parent_update(
                     .await?
                     .iter()
                     .flatten()
-                    .map(|p| p.parent_page_id)
+                    .map(|p| p.dest_id)
                     .collect(),
             )
         }
@@ -156,10 +171,11 @@ pub async fn parent_update(
             let removal = parents.iter().map(|parent| {
                 ParentService::remove(
                     ctx,
-                    ParentDescription {
+                    ModifyParent {
                         site_id: input.site_id,
                         parent: parent.to_owned(),
                         child: input.child.clone(),
+                        user_id: input.user_id,
                     },
                 )
             });
@@ -179,3 +195,31 @@ pub async fn parent_update(
         removed: removal,
     })
 }
+
+pub async fn parent_breadcrumbs(
+    ctx: &ServiceContext<'_>,
+    params: Params<'static>,
+) -> Result<Vec<ParentPage>> {
+    let GetPageReference { site_id, page } = params.parse()?;
+
+    info!(
+        "Getting parent breadcrumbs for page {:?} in site ID {}",
+        page, site_id,
+    );
+
+    ParentService::get_breadcrumbs(ctx, site_id, page).await
+}
+
+pub async fn parent_tree(
+    ctx: &ServiceContext<'_>,
+    params: Params<'static>,
+) -> Result<ParentTreeNode> {
+    let input: GetParentTree = params.parse()?;
+
+    info!(
+        "Getting descendant tree for page {:?} in site ID {}",
+        input.page, input.site_id,
+    );
+
+    ParentService::get_tree(ctx, input).await
+}
@@ -22,7 +22,8 @@ use super::prelude::*;
 use crate::models::sea_orm_active_enums::AliasType;
 use crate::models::site::Model as SiteModel;
 use crate::services::site::{
-    CreateSite, CreateSiteOutput, GetSite, GetSiteOutput, UpdateSite,
+    CreateSite, CreateSiteOutput, DeleteSite, GetSiteDetails, GetSiteOutput, RestoreSite,
+    UpdateSite,
 };
 
 pub async fn site_create(
@@ -37,9 +38,12 @@ pub async fn site_get(
     ctx: &ServiceContext<'_>,
     params: Params<'static>,
 ) -> Result<Option<GetSiteOutput>> {
-    let GetSite { site } = params.parse()?;
+    let GetSiteDetails {
+        site,
+        allow_deleted,
+    } = params.parse()?;
     info!("Getting site {:?}", site);
-    match SiteService::get_optional(ctx, site).await? {
+    match SiteService::get_optional_allow_deleted(ctx, site, allow_deleted).await? {
         None => Ok(None),
         Some(site) => {
             let (aliases, domains) = try_join!(
@@ -69,3 +73,21 @@ pub async fn site_update(
     info!("Updating site {:?}", site);
     SiteService::update(ctx, site, body, user_id).await
 }
+
+pub async fn site_delete(
+    ctx: &ServiceContext<'_>,
+    params: Params<'static>,
+) -> Result<SiteModel> {
+    let DeleteSite { site, user_id } = params.parse()?;
+    info!("Deleting site {:?}", site);
+    SiteService::delete(ctx, site, user_id).await
+}
+
+pub async fn site_restore(
+    ctx: &ServiceContext<'_>,
+    params: Params<'static>,
+) -> Result<SiteModel> {
+    let input: RestoreSite = params.parse()?;
+    info!("Restoring site ID {}", input.site_id);
+    SiteService::restore(ctx, input).await
+}
@@ -20,7 +20,12 @@
 
 use super::prelude::*;
 use crate::models::relation::Model as RelationModel;
-use crate::services::relation::{CreateSiteMember, GetSiteMember, RemoveSiteMember};
+use crate::models::site_invitation::Model as SiteInvitationModel;
+use crate::services::membership::{
+    AcceptInvitation, AddSiteMember, ApplyToSite, CreateInvitationOutput, InviteEmail,
+    InviteUser, JoinSite, ProcessApplication, RevokeInvitation,
+};
+use crate::services::relation::{GetSiteMember, RemoveSiteMember};
 
 pub async fn membership_get(
     ctx: &ServiceContext<'_>,
@@ -34,8 +39,8 @@ pub async fn membership_set(
     ctx: &ServiceContext<'_>,
     params: Params<'static>,
 ) -> Result<()> {
-    let input: CreateSiteMember = params.parse()?;
-    RelationService::create_site_member(ctx, input).await
+    let input: AddSiteMember = params.parse()?;
+    MembershipService::add_member(ctx, input).await
 }
 
 pub async fn membership_delete(
@@ -45,3 +50,83 @@ pub async fn membership_delete(
     let input: RemoveSiteMember = params.parse()?;
     RelationService::remove_site_member(ctx, input).await
 }
+
+pub async fn membership_join(
+    ctx: &ServiceContext<'_>,
+    params: Params<'static>,
+) -> Result<()> {
+    let input: JoinSite = params.parse()?;
+    MembershipService::join(ctx, input).await
+}
+
+pub async fn membership_apply(
+    ctx: &ServiceContext<'_>,
+    params: Params<'static>,
+) -> Result<()> {
+    let input: ApplyToSite = params.parse()?;
+    MembershipService::apply(ctx, input).await
+}
+
+pub async fn membership_application_list(
+    ctx: &ServiceContext<'_>,
+    params: Params<'static>,
+) -> Result<Vec<RelationModel>> {
+    let site_id: i64 = params.one()?;
+    MembershipService::get_applications(ctx, site_id).await
+}
+
+pub async fn membership_application_accept(
+    ctx: &ServiceContext<'_>,
+    params: Params<'static>,
+) -> Result<()> {
+    let input: ProcessApplication = params.parse()?;
+    MembershipService::accept_application(ctx, input).await
+}
+
+pub async fn membership_application_reject(
+    ctx: &ServiceContext<'_>,
+    params: Params<'static>,
+) -> Result<()> {
+    let input: ProcessApplication = params.parse()?;
+    MembershipService::reject_application(ctx, input).await
+}
+
+pub async fn membership_invite_user(
+    ctx: &ServiceContext<'_>,
+    params: Params<'static>,
+) -> Result<CreateInvitationOutput> {
+    let input: InviteUser = params.parse()?;
+    MembershipService::invite_user(ctx, input).await
+}
+
+pub async fn membership_invite_email(
+    ctx: &ServiceContext<'_>,
+    params: Params<'static>,
+) -> Result<CreateInvitationOutput> {
+    let input: InviteEmail = params.parse()?;
+    MembershipService::invite_email(ctx, input).await
+}
+
+pub async fn membership_invitation_list(
+    ctx: &ServiceContext<'_>,
+    params: Params<'static>,
+) -> Result<Vec<SiteInvitationModel>> {
+    let site_id: i64 = params.one()?;
+    MembershipService::get_invitations(ctx, site_id).await
+}
+
+pub async fn membership_invitation_accept(
+    ctx: &ServiceContext<'_>,
+    params: Params<'static>,
+) -> Result<()> {
+    let input: AcceptInvitation = params.parse()?;
+    MembershipService::accept_invitation(ctx, input).await
+}
+
+pub async fn membership_invitation_revoke(
+    ctx: &ServiceContext<'_>,
+    params: Params<'static>,
+) -> Result<SiteInvitationModel> {
+    let input: RevokeInvitation = params.parse()?;
+    MembershipService::revoke_invitation(ctx, input).await
+}
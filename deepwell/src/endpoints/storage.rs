@@ -0,0 +1,69 @@
+/*
+ * endpoints/storage.rs
+ *
+ * DEEPWELL - Wikijump API provider and database manager
+ * Copyright (C) 2019-2025 Wikijump Team
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use super::prelude::*;
+use crate::services::scrub::{GetScrubIssues, ScrubIssueOutput, ScrubProgress};
+use crate::services::storage::{SetStorageQuota, StorageOwner, StorageUsage};
+
+pub async fn storage_usage_get(
+    ctx: &ServiceContext<'_>,
+    params: Params<'static>,
+) -> Result<StorageUsage> {
+    let owner: StorageOwner = params.parse()?;
+    StorageService::get_usage(ctx, owner).await
+}
+
+pub async fn storage_usage_recalculate(
+    ctx: &ServiceContext<'_>,
+    params: Params<'static>,
+) -> Result<StorageUsage> {
+    let owner: StorageOwner = params.parse()?;
+    StorageService::recalculate(ctx, owner).await
+}
+
+pub async fn storage_quota_set(
+    ctx: &ServiceContext<'_>,
+    params: Params<'static>,
+) -> Result<StorageUsage> {
+    let input: SetStorageQuota = params.parse()?;
+    StorageService::set_quota(ctx, input).await
+}
+
+pub async fn storage_scrub_start(
+    ctx: &ServiceContext<'_>,
+    _params: Params<'static>,
+) -> Result<ScrubProgress> {
+    ScrubService::start(ctx).await
+}
+
+pub async fn storage_scrub_progress(
+    ctx: &ServiceContext<'_>,
+    _params: Params<'static>,
+) -> Result<Option<ScrubProgress>> {
+    ScrubService::get_progress(ctx).await
+}
+
+pub async fn storage_scrub_issues(
+    ctx: &ServiceContext<'_>,
+    params: Params<'static>,
+) -> Result<Vec<ScrubIssueOutput>> {
+    let input: GetScrubIssues = params.parse()?;
+    ScrubService::get_issues(ctx, input).await
+}
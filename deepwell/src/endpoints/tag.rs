@@ -0,0 +1,80 @@
+/*
+ * endpoints/tag.rs
+ *
+ * DEEPWELL - Wikijump API provider and database manager
+ * Copyright (C) 2019-2025 Wikijump Team
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use super::prelude::*;
+use crate::services::site::GetSite;
+use crate::services::tag::{
+    GetTagPages, GetTagPagesOutput, MergeTags, MergeTagsOutput, RenameTag, TagCount,
+};
+
+pub async fn tag_cloud(
+    ctx: &ServiceContext<'_>,
+    params: Params<'static>,
+) -> Result<Vec<TagCount>> {
+    let GetSite { site } = params.parse()?;
+    let site_id = SiteService::get_id(ctx, site).await?;
+    info!("Getting tag cloud for site ID {site_id}");
+    TagService::get_cloud(ctx, site_id).await
+}
+
+pub async fn tag_get_pages(
+    ctx: &ServiceContext<'_>,
+    params: Params<'static>,
+) -> Result<GetTagPagesOutput> {
+    let input: GetTagPages = params.parse()?;
+    let site_id = SiteService::get_id(ctx, input.site.clone()).await?;
+
+    info!(
+        "Getting pages with tag '{}' in site ID {} (page {}, {} per page)",
+        input.tag, site_id, input.page, input.per_page,
+    );
+
+    TagService::get_pages(ctx, site_id, input).await
+}
+
+pub async fn tag_rename(
+    ctx: &ServiceContext<'_>,
+    params: Params<'static>,
+) -> Result<MergeTagsOutput> {
+    let input: RenameTag = params.parse()?;
+    let site_id = SiteService::get_id(ctx, input.site.clone()).await?;
+
+    info!(
+        "Renaming tag '{}' to '{}' in site ID {}",
+        input.tag, input.new_tag, site_id,
+    );
+
+    TagService::rename(ctx, site_id, input).await
+}
+
+pub async fn tag_merge(
+    ctx: &ServiceContext<'_>,
+    params: Params<'static>,
+) -> Result<MergeTagsOutput> {
+    let input: MergeTags = params.parse()?;
+    let site_id = SiteService::get_id(ctx, input.site.clone()).await?;
+
+    info!(
+        "Merging tags {:?} into '{}' in site ID {}",
+        input.tags, input.target, site_id,
+    );
+
+    TagService::merge(ctx, site_id, input).await
+}
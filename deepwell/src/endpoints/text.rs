@@ -19,6 +19,7 @@
  */
 
 use super::prelude::*;
+use crate::services::text::RecompressTextProgress;
 use crate::types::Bytes;
 
 pub async fn text_create(
@@ -39,3 +40,18 @@ pub async fn text_get(
     let hash: Bytes = params.one()?;
     TextService::get(ctx, hash.as_ref()).await
 }
+
+pub async fn text_recompress_start(
+    ctx: &ServiceContext<'_>,
+    _params: Params<'static>,
+) -> Result<RecompressTextProgress> {
+    info!("Starting recompression of stored text");
+    TextService::start_recompress(ctx).await
+}
+
+pub async fn text_recompress_progress(
+    ctx: &ServiceContext<'_>,
+    _params: Params<'static>,
+) -> Result<Option<RecompressTextProgress>> {
+    TextService::get_recompress_progress(ctx).await
+}
@@ -24,6 +24,7 @@ use crate::models::user::Model as UserModel;
 use crate::services::user::{
     CreateUser, CreateUserOutput, GetUser, GetUserOutput, UpdateUser,
 };
+use crate::services::user_data::{UserEraseOutput, UserExport, UserExportOutput};
 
 pub async fn user_create(
     ctx: &ServiceContext<'_>,
@@ -91,3 +92,32 @@ pub async fn user_add_name_change(
     let user = UserService::get(ctx, reference).await?;
     UserService::add_name_change_token(ctx, &user).await
 }
+
+pub async fn user_export_request(
+    ctx: &ServiceContext<'_>,
+    params: Params<'static>,
+) -> Result<UserExport> {
+    let GetUser { user: reference } = params.parse()?;
+    info!("Requesting personal data export for user {:?}", reference);
+    let user = UserService::get(ctx, reference).await?;
+    UserDataService::request_export(ctx, user.user_id).await
+}
+
+pub async fn user_export_get(
+    ctx: &ServiceContext<'_>,
+    params: Params<'static>,
+) -> Result<Option<UserExportOutput>> {
+    let GetUser { user: reference } = params.parse()?;
+    info!("Getting personal data export for user {:?}", reference);
+    let user = UserService::get(ctx, reference).await?;
+    UserDataService::get_export(ctx, user.user_id).await
+}
+
+pub async fn user_erase(
+    ctx: &ServiceContext<'_>,
+    params: Params<'static>,
+) -> Result<UserEraseOutput> {
+    let GetUser { user: reference } = params.parse()?;
+    info!("Erasing personal data for user {:?}", reference);
+    UserDataService::erase(ctx, reference).await
+}
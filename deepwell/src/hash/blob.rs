@@ -45,6 +45,30 @@ pub fn sha512_hash(data: &[u8]) -> BlobHash {
     slice_to_blob_hash(&result)
 }
 
+/// Incrementally computes the SHA-512 hash of data provided in chunks.
+///
+/// This produces the same result as `sha512_hash()`, but without
+/// needing the entire blob in memory at once.
+#[derive(Debug, Default, Clone)]
+pub struct BlobHasher(Sha512);
+
+impl BlobHasher {
+    #[inline]
+    pub fn new() -> Self {
+        BlobHasher::default()
+    }
+
+    #[inline]
+    pub fn update(&mut self, data: &[u8]) {
+        self.0.update(data);
+    }
+
+    pub fn finish(self) -> BlobHash {
+        let result = self.0.finalize();
+        slice_to_blob_hash(&result)
+    }
+}
+
 /// Convert a slice into a hash array.
 ///
 /// # Panics
@@ -32,6 +32,8 @@ pub struct Model {
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+    #[sea_orm(has_many = "super::message_attachment::Entity")]
+    MessageAttachment,
     #[sea_orm(
         belongs_to = "super::message_record::Entity",
         from = "Column::ForwardedFrom",
@@ -74,6 +76,12 @@ pub enum Relation {
     User,
 }
 
+impl Related<super::message_attachment::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::MessageAttachment.def()
+    }
+}
+
 impl Related<super::user::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::User.def()
@@ -35,6 +35,8 @@ pub struct Model {
 pub enum Relation {
     #[sea_orm(has_many = "super::message::Entity")]
     Message,
+    #[sea_orm(has_many = "super::message_attachment::Entity")]
+    MessageAttachment,
     #[sea_orm(has_many = "super::message_recipient::Entity")]
     MessageRecipient,
     #[sea_orm(
@@ -85,6 +87,12 @@ impl Related<super::message::Entity> for Entity {
     }
 }
 
+impl Related<super::message_attachment::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::MessageAttachment.def()
+    }
+}
+
 impl Related<super::message_recipient::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::MessageRecipient.def()
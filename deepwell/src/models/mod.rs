@@ -4,11 +4,13 @@ pub mod prelude;
 
 pub mod alias;
 pub mod blob_blacklist;
+pub mod blob_orphan;
 pub mod blob_pending;
 pub mod file;
 pub mod file_revision;
 pub mod filter;
 pub mod message;
+pub mod message_attachment;
 pub mod message_draft;
 pub mod message_recipient;
 pub mod message_record;
@@ -20,14 +22,19 @@ pub mod page_connection;
 pub mod page_connection_missing;
 pub mod page_link;
 pub mod page_lock;
-pub mod page_parent;
 pub mod page_revision;
+pub mod page_tag;
 pub mod page_vote;
 pub mod relation;
+pub mod scrub_issue;
 pub mod sea_orm_active_enums;
 pub mod session;
 pub mod site;
 pub mod site_domain;
+pub mod site_invitation;
+pub mod storage_usage;
 pub mod text;
+pub mod text_dictionary;
 pub mod user;
 pub mod user_bot_owner;
+pub mod user_oidc_identity;
@@ -17,6 +17,13 @@ pub struct Model {
     pub slug: String,
     #[sea_orm(column_type = "Text", nullable)]
     pub layout: Option<String>,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub score_type: Option<String>,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub template: Option<String>,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub license: Option<String>,
+    pub permissions: Option<Json>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -28,6 +28,9 @@ pub struct Model {
     pub compiled_at: TimeDateTimeWithTimeZone,
     #[sea_orm(column_type = "Text")]
     pub compiled_generator: String,
+    #[sea_orm(column_type = "VarBinary(StringLen::None)")]
+    pub compiled_style_hash: Vec<u8>,
+    pub compiled_meta: Json,
     #[sea_orm(column_type = "Text")]
     pub comments: String,
     pub hidden: Vec<String>,
@@ -1,11 +1,13 @@
 //! `SeaORM` Entity, @generated by sea-orm-codegen 1.1.0
 
 pub use super::alias::Entity as Alias;
+pub use super::blob_orphan::Entity as BlobOrphan;
 pub use super::blob_pending::Entity as BlobPending;
 pub use super::file::Entity as File;
 pub use super::file_revision::Entity as FileRevision;
 pub use super::filter::Entity as Filter;
 pub use super::message::Entity as Message;
+pub use super::message_attachment::Entity as MessageAttachment;
 pub use super::message_draft::Entity as MessageDraft;
 pub use super::message_recipient::Entity as MessageRecipient;
 pub use super::message_record::Entity as MessageRecord;
@@ -17,13 +19,18 @@ pub use super::page_connection::Entity as PageConnection;
 pub use super::page_connection_missing::Entity as PageConnectionMissing;
 pub use super::page_link::Entity as PageLink;
 pub use super::page_lock::Entity as PageLock;
-pub use super::page_parent::Entity as PageParent;
 pub use super::page_revision::Entity as PageRevision;
+pub use super::page_tag::Entity as PageTag;
 pub use super::page_vote::Entity as PageVote;
 pub use super::relation::Entity as Relation;
+pub use super::scrub_issue::Entity as ScrubIssue;
 pub use super::session::Entity as Session;
 pub use super::site::Entity as Site;
 pub use super::site_domain::Entity as SiteDomain;
+pub use super::site_invitation::Entity as SiteInvitation;
+pub use super::storage_usage::Entity as StorageUsage;
 pub use super::text::Entity as Text;
+pub use super::text_dictionary::Entity as TextDictionary;
 pub use super::user::Entity as User;
 pub use super::user_bot_owner::Entity as UserBotOwner;
+pub use super::user_oidc_identity::Entity as UserOidcIdentity;
@@ -8,12 +8,16 @@ use serde::{Deserialize, Serialize};
 pub struct Model {
     #[sea_orm(primary_key, auto_increment = false, column_type = "Text")]
     pub session_token: String,
+    #[sea_orm(unique)]
+    pub session_id: i64,
     pub user_id: i64,
     #[serde(with = "time::serde::rfc3339")]
     pub created_at: TimeDateTimeWithTimeZone,
     #[serde(with = "time::serde::rfc3339")]
     pub expires_at: TimeDateTimeWithTimeZone,
-    #[sea_orm(column_type = "Text")]
+    #[serde(with = "time::serde::rfc3339")]
+    pub last_used_at: TimeDateTimeWithTimeZone,
+    #[sea_orm(column_type = "custom(\"inet\")", select_as = "text", save_as = "inet")]
     pub ip_address: String,
     #[sea_orm(column_type = "Text")]
     pub user_agent: String,
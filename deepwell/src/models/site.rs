@@ -31,6 +31,11 @@ pub struct Model {
     pub custom_domain: Option<String>,
     #[sea_orm(column_type = "Text", nullable)]
     pub layout: Option<String>,
+    #[sea_orm(column_type = "Text")]
+    pub membership_policy: String,
+    pub tag_whitelist: Option<Vec<String>>,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub default_license: Option<String>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -11,6 +11,12 @@ pub struct Model {
     pub site_id: i64,
     #[serde(with = "time::serde::rfc3339")]
     pub created_at: TimeDateTimeWithTimeZone,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub verification_token: Option<String>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub verified_at: Option<TimeDateTimeWithTimeZone>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub last_checked_at: Option<TimeDateTimeWithTimeZone>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
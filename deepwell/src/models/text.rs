@@ -13,10 +13,42 @@ pub struct Model {
     )]
     pub hash: Vec<u8>,
     #[sea_orm(column_type = "Text")]
-    pub contents: String,
+    pub encoding: String,
+    pub length: i32,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub contents: Option<String>,
+    #[sea_orm(column_type = "VarBinary(StringLen::None)", nullable)]
+    pub data: Option<Vec<u8>>,
+    pub text_dictionary_id: Option<i32>,
+    #[sea_orm(column_type = "VarBinary(StringLen::None)", nullable)]
+    pub base_hash: Option<Vec<u8>>,
+    pub delta_depth: i16,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
-pub enum Relation {}
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "Entity",
+        from = "Column::BaseHash",
+        to = "Column::Hash",
+        on_update = "NoAction",
+        on_delete = "NoAction"
+    )]
+    SelfRef,
+    #[sea_orm(
+        belongs_to = "super::text_dictionary::Entity",
+        from = "Column::TextDictionaryId",
+        to = "super::text_dictionary::Column::TextDictionaryId",
+        on_update = "NoAction",
+        on_delete = "NoAction"
+    )]
+    TextDictionary,
+}
+
+impl Related<super::text_dictionary::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::TextDictionary.def()
+    }
+}
 
 impl ActiveModelBehavior for ActiveModel {}
@@ -20,7 +20,8 @@
 
 use super::prelude::*;
 use crate::models::user::{self, Entity as User, Model as UserModel};
-use crate::services::{MfaService, PasswordService, SessionService};
+use crate::services::rate_limit::LoginAccount;
+use crate::services::{MfaService, PasswordService, RateLimitService, SessionService};
 
 #[derive(Debug)]
 pub struct AuthenticationService;
@@ -36,15 +37,49 @@ impl AuthenticationService {
         }: AuthenticateUser,
     ) -> Result<AuthenticateUserOutput> {
         let auth = Self::get_user_auth(ctx, &name_or_email).await?;
-        PasswordService::verify(ctx, &password, &auth.password_hash).await?;
 
-        // User not found, return authentication failure
-        if !auth.valid {
-            return Err(Error::InvalidAuthentication);
+        // Refuse logins to accounts with too many recent failures.
+        //
+        // Failures are counted per user, so that switching between a user's
+        // name and email does not give more attempts. For unknown accounts they
+        // are counted by the submitted name or email, so that lockouts behave
+        // the same and do not reveal whether an account exists.
+        let account = if auth.valid {
+            LoginAccount::User(auth.user_id)
+        } else {
+            LoginAccount::Unknown(&name_or_email)
+        };
+
+        RateLimitService::check_login_lockout(ctx, account).await?;
+
+        let result = PasswordService::verify(ctx, &password, &auth.password_hash)
+            .await
+            .and_then(|()| {
+                // User not found, return authentication failure
+                if auth.valid {
+                    Ok(())
+                } else {
+                    Err(Error::InvalidAuthentication)
+                }
+            });
+
+        if let Err(error) = result {
+            if matches!(error, Error::InvalidAuthentication) {
+                RateLimitService::record_login_failure(ctx, account).await?;
+            }
+
+            return Err(error);
+        }
+
+        // If MFA is still needed, failures are only cleared once that succeeds,
+        // so that logging in again cannot reset failed MFA attempts.
+        let needs_mfa = auth.multi_factor_secret.is_some();
+        if !needs_mfa {
+            RateLimitService::clear_login_failures(ctx, account).await?;
         }
 
         Ok(AuthenticateUserOutput {
-            needs_mfa: auth.multi_factor_secret.is_some(),
+            needs_mfa,
             user_id: auth.user_id,
         })
     }
@@ -58,26 +93,41 @@ impl AuthenticationService {
         MultiFactorAuthenticateUser {
             session_token,
             totp_or_code,
+            client,
         }: MultiFactorAuthenticateUser<'_>,
     ) -> Result<UserModel> {
         // Get associated user model from the session
         //
         // Requires the session is restricted, meaning they are
         // in the middle of logging in still
-        let user = SessionService::get_user(ctx, session_token, true).await?;
+        let user = SessionService::get_user(ctx, session_token, true, client).await?;
+
+        // Failed MFA verifications count towards the same lockout as passwords
+        let account = LoginAccount::User(user.user_id);
+        RateLimitService::check_login_lockout(ctx, account).await?;
 
         // Process input, verifying depending on type
-        match totp_or_code.parse() {
+        let result = match totp_or_code.parse() {
             // If the value is a positive integer, treat it as a TOTP
-            Ok(totp) => MfaService::verify(ctx, &user, totp).await?,
+            Ok(totp) => MfaService::verify(ctx, &user, totp).await,
 
             // Otherwise treat it as a recovery code string
             //
             // We don't need to validate it for length because
             // we want consistent time checks on recovery codes anyways.
-            Err(_) => MfaService::verify_recovery(ctx, &user, totp_or_code).await?,
+            Err(_) => MfaService::verify_recovery(ctx, &user, totp_or_code).await,
+        };
+
+        if let Err(error) = result {
+            if matches!(error, Error::InvalidAuthentication) {
+                RateLimitService::record_login_failure(ctx, account).await?;
+            }
+
+            return Err(error);
         }
 
+        RateLimitService::clear_login_failures(ctx, account).await?;
+
         Ok(user)
     }
 
@@ -19,6 +19,7 @@
  */
 
 use crate::models::user::Model as UserModel;
+use crate::services::session::SessionClient;
 use std::net::IpAddr;
 
 #[derive(Deserialize, Debug, Clone)]
@@ -52,6 +53,7 @@ pub struct LoginUserOutput {
 pub struct MultiFactorAuthenticateUser<'a> {
     pub session_token: &'a str,
     pub totp_or_code: &'a str,
+    pub client: SessionClient<'a>,
 }
 
 #[derive(Deserialize, Debug, Clone)]
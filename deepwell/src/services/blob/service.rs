@@ -20,10 +20,11 @@
 
 use super::prelude::*;
 use crate::constants::SYSTEM_USER_ID;
-use crate::hash::slice_to_blob_hash;
+use crate::hash::{slice_to_blob_hash, BlobHasher};
 use crate::models::blob_blacklist::{
     self, Entity as BlobBlacklist, Model as BlobBlacklistModel,
 };
+use crate::models::blob_orphan::{self, Entity as BlobOrphan};
 use crate::models::blob_pending::{
     self, Entity as BlobPending, Model as BlobPendingModel,
 };
@@ -31,33 +32,55 @@ use crate::models::file::{self, Entity as File, Model as FileModel};
 use crate::models::file_revision::{
     self, Entity as FileRevision, Model as FileRevisionModel,
 };
+use crate::models::message_attachment::{self, Entity as MessageAttachment};
 use crate::models::page::{self, Entity as Page, Model as PageModel};
 use crate::models::site::{self, Entity as Site, Model as SiteModel};
 use crate::models::user::{self, Entity as User, Model as UserModel};
 use crate::services::file::{DeleteFile, FileService};
+use crate::services::scan::ScanResult;
+use crate::services::storage::{StorageOwner, StorageService};
 use crate::utils::assert_is_csprng;
 use bytes::Bytes;
 use cuid2::cuid;
 use futures::TryStreamExt;
 use rand::distributions::{Alphanumeric, DistString};
 use rand::thread_rng;
+use reqwest::Url;
 use s3::request::request_trait::ResponseData;
-use s3::serde_types::HeadObjectResult;
+use s3::serde_types::{HeadObjectResult, Part};
 use sea_orm::{
     prelude::*, DatabaseBackend, FromQueryResult, Statement, StreamTrait,
     TransactionTrait, UpdateResult,
 };
 use sea_query::value::ArrayType;
-use std::collections::{HashMap, HashSet};
+use sea_query::OnConflict;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt::Write;
 use std::hash::Hash;
 use std::str;
 use std::sync::Arc;
-use time::format_description::well_known::Rfc2822;
+use time::format_description::well_known::{Rfc2822, Rfc3339};
 use time::{Duration, OffsetDateTime};
 
 /// How many samples to provide when providing hard deletion stats.
 const SAMPLE_COUNT: u16 = 10;
 
+/// Query for every blob hash referenced in the database.
+///
+/// Any new column which refers to blobs must be added here,
+/// otherwise the garbage collector will delete blobs which are in use,
+/// and the storage scrub will not check that they exist.
+/// See `BlobService::collect_orphans()` and `ScrubService`.
+const REFERENCED_BLOBS_QUERY: &str = r#"
+    SELECT s3_hash FROM file_revision
+    UNION
+    SELECT avatar_s3_hash FROM "user" WHERE avatar_s3_hash IS NOT NULL
+    UNION
+    SELECT s3_hash FROM blob_pending WHERE s3_hash IS NOT NULL
+    UNION
+    SELECT s3_hash FROM message_attachment
+"#;
+
 /// Hash for empty blobs.
 ///
 /// Even though it is not the SHA-512 hash, for simplicity we treat the hash
@@ -81,6 +104,12 @@ pub const EMPTY_BLOB_TIMESTAMP: i64 = 1547769600;
 /// The subdirectory in the S3 bucket where all pending uploads are kept.
 pub const PRESIGN_DIRECTORY: &str = "uploads";
 
+/// The maximum number of parts S3 permits in a multipart upload.
+pub const MAXIMUM_MULTIPART_PARTS: u32 = 10_000;
+
+/// How many bytes from the start of a blob are used to determine its MIME type.
+const MIME_HEADER_LENGTH: usize = 1024 * 1024;
+
 #[derive(Debug)]
 pub struct BlobService;
 
@@ -94,40 +123,21 @@ impl BlobService {
     /// The generated presign URL, which can be uploaded to.
     pub async fn start_upload(
         ctx: &ServiceContext<'_>,
-        StartBlobUpload { user_id, blob_size }: StartBlobUpload,
+        StartBlobUpload {
+            user_id,
+            site_id,
+            blob_size,
+        }: StartBlobUpload,
     ) -> Result<StartBlobUploadOutput> {
         info!("Creating upload by {user_id} with promised length {blob_size}");
         let config = ctx.config();
         let txn = ctx.transaction();
 
-        // Convert expected length integer type, then check it
-        let blob_size = i64::try_from(blob_size).map_err(|_| Error::BlobTooBig)?;
-        if blob_size > config.maximum_blob_size {
-            error!(
-                "Blob proposed to upload is too big ({} > {})",
-                blob_size, config.maximum_blob_size,
-            );
-
-            return Err(Error::BlobTooBig);
-        }
-
-        // Generate primary key and random S3 path
+        // Check expected length, and generate primary key and random S3 path
+        let blob_size = Self::check_upload_size(config, blob_size)?;
+        Self::check_upload_quota(ctx, user_id, site_id, blob_size).await?;
         let pending_blob_id = cuid();
-        let s3_path = {
-            let mut path = format!("{PRESIGN_DIRECTORY}/");
-
-            {
-                let mut rng = thread_rng();
-                assert_is_csprng(&rng);
-                Alphanumeric.append_string(
-                    &mut rng,
-                    &mut path,
-                    config.presigned_path_length,
-                );
-            }
-
-            path
-        };
+        let s3_path = Self::new_upload_path(config);
 
         info!("Creating presign upload URL for blob at path {s3_path} with primary key {pending_blob_id}");
 
@@ -148,7 +158,7 @@ impl BlobService {
             external_id: Set(pending_blob_id),
             expected_length: Set(blob_size),
             s3_path: Set(s3_path),
-            presign_url: Set(presign_url),
+            presign_url: Set(Some(presign_url.clone())),
             created_by: Set(user_id),
             created_at: Set(created_at),
             expires_at: Set(expires_at),
@@ -157,7 +167,6 @@ impl BlobService {
 
         let BlobPendingModel {
             external_id: pending_blob_id,
-            presign_url,
             ..
         } = model.insert(txn).await?;
 
@@ -170,6 +179,257 @@ impl BlobService {
         })
     }
 
+    /// Starts an S3 multipart upload, for blobs too large to upload in one request.
+    ///
+    /// The client then requests a presign URL for each part using `get_multipart_part_url()`,
+    /// uploads them, and calls `complete_multipart_upload()` with the resulting ETags.
+    /// Afterwards, the pending blob can be used like any other upload.
+    pub async fn start_multipart_upload(
+        ctx: &ServiceContext<'_>,
+        StartBlobUpload {
+            user_id,
+            site_id,
+            blob_size,
+        }: StartMultipartUpload,
+    ) -> Result<StartMultipartUploadOutput> {
+        info!("Creating multipart upload by {user_id} with promised length {blob_size}");
+        let config = ctx.config();
+        let txn = ctx.transaction();
+
+        let blob_size = Self::check_upload_size(config, blob_size)?;
+        Self::check_upload_quota(ctx, user_id, site_id, blob_size).await?;
+        let part_size = config.multipart_part_size;
+        let part_count = match u32::try_from(blob_size.unsigned_abs().div_ceil(part_size))
+        {
+            Ok(0) => {
+                error!("Cannot create a multipart upload for an empty blob");
+                return Err(Error::BlobMultipartInvalid);
+            }
+            Ok(count) if count <= MAXIMUM_MULTIPART_PARTS => count,
+            _ => {
+                error!("Blob proposed to upload requires too many parts");
+                return Err(Error::BlobTooBig);
+            }
+        };
+
+        let pending_blob_id = cuid();
+        let s3_path = Self::new_upload_path(config);
+
+        info!("Starting multipart upload for blob at path {s3_path} with primary key {pending_blob_id} ({part_count} parts)");
+
+        // Initiate multipart upload in S3
+        //
+        // The content type is a placeholder, the real one is determined when
+        // the blob is finalized, see move_uploaded_inner().
+        let bucket = ctx.s3_bucket();
+        let response = bucket
+            .initiate_multipart_upload(&s3_path, "application/octet-stream")
+            .await?;
+
+        // Get timestamps
+        let created_at = now();
+        let expires_at = created_at
+            .checked_add(config.multipart_expiry)
+            .expect("getting expiration timestamp overflowed");
+
+        // Add pending blob entry
+        let model = blob_pending::ActiveModel {
+            external_id: Set(pending_blob_id),
+            expected_length: Set(blob_size),
+            s3_path: Set(s3_path),
+            multipart_upload_id: Set(Some(response.upload_id)),
+            multipart_part_size: Set(Some(
+                i64::try_from(part_size).map_err(|_| Error::BlobTooBig)?,
+            )),
+            created_by: Set(user_id),
+            created_at: Set(created_at),
+            expires_at: Set(expires_at),
+            ..Default::default()
+        };
+
+        let BlobPendingModel {
+            external_id: pending_blob_id,
+            ..
+        } = model.insert(txn).await?;
+
+        debug!("New multipart upload will last until {expires_at}");
+
+        Ok(StartMultipartUploadOutput {
+            pending_blob_id,
+            part_size,
+            part_count,
+            expires_at,
+        })
+    }
+
+    /// Creates an S3 presign URL to upload one part of a multipart upload.
+    ///
+    /// Part numbers start at 1. S3 returns an ETag header in response to
+    /// the upload, which the client must keep to complete the upload.
+    pub async fn get_multipart_part_url(
+        ctx: &ServiceContext<'_>,
+        GetMultipartUploadPart {
+            user_id,
+            pending_blob_id,
+            part_number,
+        }: GetMultipartUploadPart,
+    ) -> Result<GetMultipartUploadPartOutput> {
+        info!(
+            "Creating presign URL for part {part_number} of pending ID {pending_blob_id}"
+        );
+        let config = ctx.config();
+
+        let pending = Self::get_pending_blob_path(ctx, user_id, &pending_blob_id).await?;
+        let (upload_id, part_count) = Self::get_multipart_info(&pending)?;
+
+        if part_number == 0 || part_number > part_count {
+            error!("Part number {part_number} is out of range (1-{part_count})");
+            return Err(Error::BlobMultipartInvalid);
+        }
+
+        let mut queries = HashMap::new();
+        queries.insert(str!("partNumber"), part_number.to_string());
+        queries.insert(str!("uploadId"), str!(upload_id));
+
+        let bucket = ctx.s3_bucket();
+        let presign_url = bucket
+            .presign_put(
+                &pending.s3_path,
+                config.presigned_expiry_secs,
+                None,
+                Some(queries),
+            )
+            .await?;
+
+        let expires_at = now()
+            .checked_add(Duration::seconds(i64::from(config.presigned_expiry_secs)))
+            .expect("getting expiration timestamp overflowed");
+
+        Ok(GetMultipartUploadPartOutput {
+            presign_url,
+            expires_at,
+        })
+    }
+
+    /// Completes a multipart upload, assembling all the parts into one object.
+    ///
+    /// Every part must be included exactly once.
+    pub async fn complete_multipart_upload(
+        ctx: &ServiceContext<'_>,
+        CompleteMultipartUpload {
+            user_id,
+            pending_blob_id,
+            mut parts,
+        }: CompleteMultipartUpload,
+    ) -> Result<()> {
+        info!(
+            "Completing multipart upload for pending ID {pending_blob_id} ({} parts)",
+            parts.len(),
+        );
+
+        let pending = Self::get_pending_blob_path(ctx, user_id, &pending_blob_id).await?;
+        let (upload_id, part_count) = Self::get_multipart_info(&pending)?;
+
+        // Ensure parts are in order, and each part is present
+        parts.sort_by_key(|part| part.part_number);
+        let all_present = parts.len() == part_count as usize
+            && parts
+                .iter()
+                .zip(1..)
+                .all(|(part, expected)| part.part_number == expected);
+
+        if !all_present {
+            error!("Multipart upload is missing parts or has duplicates");
+            return Err(Error::BlobMultipartInvalid);
+        }
+
+        let parts = parts
+            .into_iter()
+            .map(|UploadedPart { part_number, etag }| Part { part_number, etag })
+            .collect();
+
+        let bucket = ctx.s3_bucket();
+        let response = bucket
+            .complete_multipart_upload(&pending.s3_path, upload_id, parts)
+            .await?;
+
+        match response.status_code() {
+            200 => Ok(()),
+            _ => s3_error(&response, "completing multipart upload"),
+        }
+    }
+
+    /// Checks that the proposed size of a blob is permitted.
+    fn check_upload_size(config: &Config, blob_size: u64) -> Result<i64> {
+        // Convert expected length integer type, then check it
+        let blob_size = i64::try_from(blob_size).map_err(|_| Error::BlobTooBig)?;
+        if blob_size > config.maximum_blob_size {
+            error!(
+                "Blob proposed to upload is too big ({} > {})",
+                blob_size, config.maximum_blob_size,
+            );
+
+            return Err(Error::BlobTooBig);
+        }
+
+        Ok(blob_size)
+    }
+
+    /// Checks that an upload of this size would fit within the user's storage quota,
+    /// and the site's if it was specified.
+    ///
+    /// This is conservative, as it is not yet known if the blob is a duplicate
+    /// of one which is already charged. The actual charge is made once the upload
+    /// is used in a file, see `StorageService::charge_blob()`.
+    async fn check_upload_quota(
+        ctx: &ServiceContext<'_>,
+        user_id: i64,
+        site_id: Option<i64>,
+        blob_size: i64,
+    ) -> Result<()> {
+        StorageService::check_quota(ctx, StorageOwner::User { user_id }, blob_size)
+            .await?;
+
+        if let Some(site_id) = site_id {
+            StorageService::check_quota(ctx, StorageOwner::Site { site_id }, blob_size)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Generates a new random S3 path to upload a pending blob to.
+    fn new_upload_path(config: &Config) -> String {
+        let mut path = format!("{PRESIGN_DIRECTORY}/");
+
+        {
+            let mut rng = thread_rng();
+            assert_is_csprng(&rng);
+            Alphanumeric.append_string(&mut rng, &mut path, config.presigned_path_length);
+        }
+
+        path
+    }
+
+    /// Gets the S3 upload ID and number of parts for a multipart pending blob.
+    fn get_multipart_info(pending: &PendingBlob) -> Result<(&str, u32)> {
+        match (&pending.multipart_upload_id, pending.multipart_part_size) {
+            (Some(upload_id), Some(part_size)) if pending.moved_hash.is_none() => {
+                let part_count = pending
+                    .expected_length
+                    .unsigned_abs()
+                    .div_ceil(part_size.unsigned_abs());
+                let part_count =
+                    u32::try_from(part_count).map_err(|_| Error::BlobTooBig)?;
+                Ok((upload_id, part_count))
+            }
+            _ => {
+                error!("Pending blob is not an active multipart upload");
+                Err(Error::BlobMultipartInvalid)
+            }
+        }
+    }
+
     async fn get_pending_blob_path(
         ctx: &ServiceContext<'_>,
         user_id: i64,
@@ -182,6 +442,8 @@ impl BlobService {
             s3_hash,
             created_by,
             expected_length,
+            multipart_upload_id,
+            multipart_part_size,
             ..
         } = match row {
             Some(pending) => pending,
@@ -197,9 +459,29 @@ impl BlobService {
             s3_path,
             expected_length,
             moved_hash: s3_hash,
+            multipart_upload_id,
+            multipart_part_size,
         })
     }
 
+    /// Gets the length the uploader declared for a pending blob.
+    ///
+    /// This permits checking size limits before the upload is finished.
+    pub(crate) async fn get_pending_length(
+        ctx: &ServiceContext<'_>,
+        user_id: i64,
+        pending_blob_id: &str,
+    ) -> Result<i64> {
+        let PendingBlob {
+            expected_length, ..
+        } = Self::get_pending_blob_path(ctx, user_id, pending_blob_id).await?;
+
+        Ok(expected_length)
+    }
+
+    /// Cancels a pending upload, deleting anything which has been uploaded so far.
+    ///
+    /// This also aborts the S3 upload if it is a multipart upload.
     pub async fn cancel_upload(
         ctx: &ServiceContext<'_>,
         user_id: i64,
@@ -207,14 +489,68 @@ impl BlobService {
     ) -> Result<()> {
         info!("Cancelling upload for blob for pending ID {pending_blob_id}");
         let txn = ctx.transaction();
-        let PendingBlob { s3_path, .. } =
-            Self::get_pending_blob_path(ctx, user_id, pending_blob_id).await?;
+        let PendingBlob {
+            s3_path,
+            multipart_upload_id,
+            ..
+        } = Self::get_pending_blob_path(ctx, user_id, pending_blob_id).await?;
 
         BlobPending::delete_by_id(pending_blob_id).exec(txn).await?;
+        Self::delete_pending_upload(ctx, &s3_path, multipart_upload_id.as_deref()).await
+    }
 
-        if Self::head(ctx, &s3_path).await?.is_some() {
-            let bucket = ctx.s3_bucket();
-            bucket.delete_object(&s3_path).await?;
+    /// Prunes all expired pending uploads.
+    ///
+    /// Any uploaded data is deleted, and multipart uploads are aborted,
+    /// the same as if the upload had been cancelled.
+    ///
+    /// # Returns
+    /// The number of pruned uploads.
+    pub async fn prune_uploads(ctx: &ServiceContext<'_>) -> Result<u64> {
+        info!("Pruning all expired pending uploads");
+
+        let txn = ctx.transaction();
+        let expired = BlobPending::find()
+            .filter(blob_pending::Column::ExpiresAt.lte(now()))
+            .all(txn)
+            .await?;
+
+        let mut pruned = 0;
+        for BlobPendingModel {
+            external_id,
+            s3_path,
+            multipart_upload_id,
+            ..
+        } in expired
+        {
+            debug!("Pruning expired pending upload ID {external_id}");
+            Self::delete_pending_upload(ctx, &s3_path, multipart_upload_id.as_deref())
+                .await?;
+
+            BlobPending::delete_by_id(external_id).exec(txn).await?;
+            pruned += 1;
+        }
+
+        debug!("Pruned {pruned} expired pending uploads");
+        Ok(pruned)
+    }
+
+    /// Deletes anything uploaded for a pending blob, aborting it if it is a multipart upload.
+    async fn delete_pending_upload(
+        ctx: &ServiceContext<'_>,
+        s3_path: &str,
+        multipart_upload_id: Option<&str>,
+    ) -> Result<()> {
+        let bucket = ctx.s3_bucket();
+        if let Some(upload_id) = multipart_upload_id {
+            // If the upload was already completed, there is nothing to abort
+            if let Err(error) = bucket.abort_upload(s3_path, upload_id).await {
+                warn!("Unable to abort multipart upload (may be complete): {error}");
+            }
+        }
+
+        if Self::head(ctx, s3_path).await?.is_some() {
+            bucket.delete_object(s3_path).await?;
         }
 
         Ok(())
@@ -264,36 +600,67 @@ impl BlobService {
         let bucket = ctx.s3_bucket();
         let txn = ctx.transaction();
 
-        debug!("Download uploaded blob from S3 uploads to get metadata");
-        let response = bucket.get_object(s3_path).await?;
-        let data: Vec<u8> = match response.status_code() {
-            200 => response.into(),
+        // Stream the uploaded blob from S3 to get its metadata
+        //
+        // Rather than holding the entire blob in memory, it is hashed as it
+        // is received, and only the start is kept for MIME type detection.
+        debug!("Streaming uploaded blob from S3 uploads to get metadata");
+        let mut response = bucket.get_object_stream(s3_path).await?;
+        match response.status_code {
+            200 => (),
             404 => {
                 error!("No blob uploaded at presign path {s3_path}");
                 return Err(Error::BlobNotUploaded);
             }
-            _ => {
-                error!("Unable to retrieve uploaded blob at {s3_path} from S3");
-                let error = s3_error(&response, "finalizing uploaded blob")?;
-                return Err(error);
+            status => {
+                error!("Unable to retrieve uploaded blob at {s3_path} from S3 (HTTP {status})");
+                return Err(Error::S3Response);
             }
+        }
+
+        // Check for malware as it is streamed, if a scanner is configured
+        let scanner = ctx.scanner().filter(|_| expected_length > 0);
+        let mut scan = match scanner {
+            Some(scanner) => Some(scanner.begin().await?),
+            None => None,
         };
 
-        if expected_length != data.len() {
+        let mut hasher = BlobHasher::new();
+        let mut header = Vec::new();
+        let mut length = 0;
+
+        while let Some(chunk) = response.bytes().try_next().await? {
+            length += chunk.len();
+            if length > expected_length {
+                // Stop early, no need to download the rest
+                break;
+            }
+
+            hasher.update(&chunk);
+
+            if let Some(ref mut scan) = scan {
+                scan.update(&chunk).await?;
+            }
+
+            if header.len() < MIME_HEADER_LENGTH {
+                let needed = MIME_HEADER_LENGTH - header.len();
+                header.extend_from_slice(&chunk[..needed.min(chunk.len())]);
+            }
+        }
+
+        if expected_length != length {
             error!(
-                "Expected blob length of {} bytes, instead found {} uploaded. Deleting pending.",
-                expected_length,
-                data.len(),
+                "Expected blob length of {expected_length} bytes, instead found {length} uploaded. Deleting pending.",
             );
             bucket.delete_object(&s3_path).await?;
             return Err(Error::BlobSizeMismatch {
                 expected: expected_length,
-                actual: data.len(),
+                actual: length,
             });
         }
 
         // Special handling for empty blobs
-        if data.is_empty() {
+        if length == 0 {
             debug!("File being created is empty, special case");
             return Ok(FinalizeBlobUploadOutput {
                 s3_hash: EMPTY_BLOB_HASH,
@@ -303,16 +670,65 @@ impl BlobService {
             });
         }
 
+        let s3_hash = hasher.finish();
+        let scanned_by = match (scanner, scan) {
+            (Some(scanner), Some(scan)) => {
+                if let ScanResult::Infected { signature } = scan.finish().await? {
+                    let hex_hash = blob_hash_to_hex(&s3_hash);
+                    error!(
+                        "Newly-uploaded blob {pending_blob_id} contains malware '{signature}' (hash {hex_hash})",
+                    );
+
+                    // Blacklist so nobody can upload it again, then remove this pending upload.
+                    //
+                    // This is committed separately, since the error returned
+                    // below causes the request's transaction to be rolled back.
+                    let PendingBlob {
+                        multipart_upload_id,
+                        ..
+                    } = Self::get_pending_blob_path(
+                        ctx,
+                        pending_blob_user_id,
+                        pending_blob_id,
+                    )
+                    .await?;
+
+                    let reason =
+                        format!("Malware found by {}: {signature}", scanner.name());
+                    Self::blacklist_malware_separately(
+                        &ctx.state().database,
+                        s3_hash,
+                        pending_blob_id,
+                        reason,
+                    )
+                    .await?;
+                    Self::delete_pending_upload(
+                        ctx,
+                        s3_path,
+                        multipart_upload_id.as_deref(),
+                    )
+                    .await?;
+                    return Err(Error::BlobMalware(signature));
+                }
+
+                Some(scanner.name())
+            }
+            _ => None,
+        };
+
         debug!("Updating blob metadata in database and S3");
 
         // If the blob exists, then just delete the uploaded one.
         //
         // If it doesn't, then we need to move it. However, within S3
-        // we cannot "move" objects, we have to upload and delete the original.
+        // we cannot "move" objects, we have to copy and delete the original.
+        // The copy is done within S3, so the data is not transferred again.
         //
         // In either case, we delete the blob at the temporary upload location.
 
-        let result = Self::direct_upload(ctx, data).await?;
+        let size = i64::try_from(length).expect("Buffer size exceeds i64");
+        let result =
+            Self::copy_upload(ctx, s3_path, s3_hash, size, header, scanned_by).await?;
         bucket.delete_object(&s3_path).await?;
 
         // Check that new blob is not blacklisted
@@ -341,12 +757,81 @@ impl BlobService {
         Ok(result)
     }
 
+    /// Copies an uploaded blob within S3 to its final destination.
+    ///
+    /// This is the streaming counterpart of `direct_upload()`, used when
+    /// finalizing uploads. The MIME type is determined from the start of
+    /// the blob only, which is all libmagic needs.
+    ///
+    /// If the blob was scanned for malware, the scanner used is recorded
+    /// in the object's metadata, see `get_metadata_optional()`.
+    async fn copy_upload(
+        ctx: &ServiceContext<'_>,
+        s3_path: &str,
+        s3_hash: BlobHash,
+        size: i64,
+        header: Vec<u8>,
+        scanned_by: Option<&str>,
+    ) -> Result<FinalizeBlobUploadOutput> {
+        let hex_hash = blob_hash_to_hex(&s3_hash);
+
+        match Self::head(ctx, &hex_hash).await? {
+            Some(result) => {
+                debug!("Blob with hash {hex_hash} already exists");
+
+                // Content-Type header should be returned
+                let mime = result.content_type.ok_or(Error::S3Response)?;
+
+                Ok(FinalizeBlobUploadOutput {
+                    s3_hash,
+                    mime,
+                    size,
+                    created: false,
+                })
+            }
+            None => {
+                debug!("Blob with hash {hex_hash} to be created");
+
+                // Determine MIME type for the new blob
+                let mime = ctx.mime().get_mime_type(header).await?;
+
+                // Copy S3 object, replacing its metadata with the proper content type
+                let mut bucket = ctx.s3_bucket().clone();
+                bucket.add_header("x-amz-metadata-directive", "REPLACE");
+                bucket.add_header("content-type", &mime);
+
+                if let Some(scanner) = scanned_by {
+                    let scanned_at =
+                        now().format(&Rfc3339).expect("Unable to format timestamp");
+
+                    bucket.add_header("x-amz-meta-scanner", scanner);
+                    bucket.add_header("x-amz-meta-scanned-at", &scanned_at);
+                }
+
+                let status = bucket.copy_object_internal(s3_path, &hex_hash).await?;
+
+                // We assume all unexpected statuses are errors, even if 1XX or 2XX
+                match status {
+                    200 => Ok(FinalizeBlobUploadOutput {
+                        s3_hash,
+                        mime,
+                        size,
+                        created: true,
+                    }),
+                    _ => {
+                        error!("Unable to copy uploaded blob to final location (HTTP {status})");
+                        Err(Error::S3Response)
+                    }
+                }
+            }
+        }
+    }
+
     /// Takes a blob and uploads it to its final destination in S3.
     ///
-    /// This is used in the above `move_uploaded_inner()` method to
-    /// "move" the S3 blob. This is done by uploading to the final
-    /// destination, then afterwards, deleting the blob at the temporary
-    /// upload location.
+    /// This is used for blobs which are already in memory, such as
+    /// from the seeder. Uploads by users are finalized by streaming
+    /// and copying instead, see `move_uploaded_inner()`.
     pub(crate) async fn direct_upload(
         ctx: &ServiceContext<'_>,
         data: Vec<u8>,
@@ -413,6 +898,7 @@ impl BlobService {
             s3_path,
             expected_length,
             moved_hash,
+            ..
         } = Self::get_pending_blob_path(ctx, user_id, pending_blob_id).await?;
 
         let output = match moved_hash {
@@ -506,6 +992,7 @@ impl BlobService {
         let mut pages = SamplerCounter::new();
         let mut sites = SamplerCounter::new();
         let mut total_files_deleted = 0;
+        let mut storage_owners = BTreeSet::new();
 
         match deleter_user_id {
             None => info!(
@@ -604,6 +1091,13 @@ impl BlobService {
                     sites.add(rev.site_id);
 
                     if deleter_user_id.is_some() {
+                        storage_owners.insert(StorageOwner::Site {
+                            site_id: rev.site_id,
+                        });
+                        storage_owners.insert(StorageOwner::User {
+                            user_id: rev.user_id,
+                        });
+
                         // Amend 'hidden' to add 's3_hash'
                         let hidden = {
                             let column = str!("s3_hash"); // avoid double-allocating String
@@ -671,13 +1165,42 @@ impl BlobService {
             }
         };
 
+        // Remove this blob from any message attachments
+        //
+        // Unlike file revisions, there is no history to preserve,
+        // so the attachments are removed entirely.
+        let total_message_attachments = match deleter_user_id {
+            Some(_) => {
+                for user_id in StorageService::attachment_owners(ctx, &s3_hash).await? {
+                    storage_owners.insert(StorageOwner::User { user_id });
+                }
+
+                MessageAttachment::delete_many()
+                    .filter(message_attachment::Column::S3Hash.eq(s3_hash.as_slice()))
+                    .exec(txn)
+                    .await?
+                    .rows_affected
+            }
+            None => {
+                MessageAttachment::find()
+                    .filter(message_attachment::Column::S3Hash.eq(s3_hash.as_slice()))
+                    .count(txn)
+                    .await?
+            }
+        };
+
         if let Some(user_id) = deleter_user_id {
             // Delete and blacklist the hash, nobody should be uploading new versions
             // Only do so if we are actually mutating.
             try_join!(
-                BlobService::add_blacklist(ctx, s3_hash, user_id),
+                BlobService::add_blacklist(ctx, s3_hash, user_id, None),
                 BlobService::hard_delete(ctx, &s3_hash),
             )?;
+
+            // Storage usage no longer includes the deleted blob
+            for owner in storage_owners {
+                StorageService::recalculate(ctx, owner).await?;
+            }
         }
 
         // Finish counting and sampling
@@ -693,6 +1216,7 @@ impl BlobService {
             total_pages,
             total_sites,
             total_users,
+            total_message_attachments,
             sample_revision_ids,
             sample_file_ids,
             sample_page_ids,
@@ -747,6 +1271,7 @@ impl BlobService {
         ctx: &ServiceContext<'_>,
         hash: BlobHash,
         created_by: i64,
+        reason: Option<String>,
     ) -> Result<()> {
         info!("Adding hash {} to blacklist", blob_hash_to_hex(&hash));
 
@@ -766,12 +1291,51 @@ impl BlobService {
         let model = blob_blacklist::ActiveModel {
             s3_hash: Set(hash.to_vec()),
             created_by: Set(created_by),
+            reason: Set(reason),
             ..Default::default()
         };
         model.insert(txn).await?;
         Ok(())
     }
 
+    /// Blacklists a blob found to contain malware and removes its pending upload row.
+    ///
+    /// This runs in its own transaction, so that it persists even though the
+    /// upload itself fails. Deleting the uploaded data from S3 is left to the caller.
+    async fn blacklist_malware_separately(
+        database: &DatabaseConnection,
+        hash: BlobHash,
+        pending_blob_id: &str,
+        reason: String,
+    ) -> Result<()> {
+        info!(
+            "Adding malware hash {} to blacklist and removing pending ID {pending_blob_id}",
+            blob_hash_to_hex(&hash),
+        );
+
+        let txn = database.begin().await?;
+        let model = blob_blacklist::ActiveModel {
+            s3_hash: Set(hash.to_vec()),
+            created_by: Set(SYSTEM_USER_ID),
+            reason: Set(Some(reason)),
+            ..Default::default()
+        };
+        BlobBlacklist::insert(model)
+            .on_conflict(
+                OnConflict::column(blob_blacklist::Column::S3Hash)
+                    .do_nothing()
+                    .to_owned(),
+            )
+            .exec_without_returning(&txn)
+            .await?;
+
+        BlobPending::delete_by_id(pending_blob_id)
+            .exec(&txn)
+            .await?;
+        txn.commit().await?;
+        Ok(())
+    }
+
     pub async fn remove_blacklist(
         ctx: &ServiceContext<'_>,
         hash: BlobHash,
@@ -798,6 +1362,339 @@ impl BlobService {
         Ok(exists)
     }
 
+    // Garbage collection
+
+    /// Reports what garbage collection of orphaned blobs would do, without changing anything.
+    #[inline]
+    pub async fn collect_orphans_preview(
+        ctx: &ServiceContext<'_>,
+    ) -> Result<CollectOrphansOutput> {
+        Self::collect_orphans_inner(ctx, true).await
+    }
+
+    /// Deletes blobs in S3 which are no longer referenced by anything.
+    ///
+    /// This is a mark-and-sweep process. Each run, unreferenced blobs are marked
+    /// as orphans, and those which have stayed orphaned for longer than the grace
+    /// period are deleted. Blobs which have become referenced again are unmarked.
+    ///
+    /// Blacklisted blobs are deleted without waiting, since they could not have
+    /// been used anyway. Their blacklist entries are kept, so the same content
+    /// still cannot be uploaded again.
+    #[inline]
+    pub async fn collect_orphans(
+        ctx: &ServiceContext<'_>,
+    ) -> Result<CollectOrphansOutput> {
+        Self::collect_orphans_inner(ctx, false).await
+    }
+
+    async fn collect_orphans_inner(
+        ctx: &ServiceContext<'_>,
+        dry_run: bool,
+    ) -> Result<CollectOrphansOutput> {
+        info!("Collecting orphaned blobs (dry run: {dry_run})");
+
+        let txn = ctx.transaction();
+        let bucket = ctx.s3_bucket();
+        let grace_cutoff = now() - ctx.config().blob_orphan_grace;
+
+        let stored = Self::list_stored(ctx).await?;
+        let referenced = Self::get_referenced(ctx).await?;
+        let blacklisted = BlobBlacklist::find()
+            .all(txn)
+            .await?
+            .into_iter()
+            .map(|model| slice_to_blob_hash(&model.s3_hash))
+            .collect::<HashSet<_>>();
+        let marked = BlobOrphan::find()
+            .all(txn)
+            .await?
+            .into_iter()
+            .map(|model| (slice_to_blob_hash(&model.s3_hash), model.marked_at))
+            .collect::<HashMap<_, _>>();
+
+        let mut output = CollectOrphansOutput {
+            dry_run,
+            total_blobs: stored.len(),
+            ..Default::default()
+        };
+        let mut deleted = SamplerCounter::new();
+
+        for &(hash, size) in &stored {
+            // Blob is in use, clear any previous mark
+            if referenced.contains(&hash) {
+                output.referenced_blobs += 1;
+
+                if !dry_run && marked.contains_key(&hash) {
+                    BlobOrphan::delete_by_id(hash.to_vec()).exec(txn).await?;
+                }
+
+                continue;
+            }
+
+            let expired = match marked.get(&hash) {
+                Some(marked_at) => *marked_at <= grace_cutoff,
+                None => false,
+            };
+
+            if !expired && !blacklisted.contains(&hash) {
+                if marked.contains_key(&hash) {
+                    output.waiting_orphans += 1;
+                } else {
+                    output.newly_orphaned += 1;
+
+                    if !dry_run {
+                        let model = blob_orphan::ActiveModel {
+                            s3_hash: Set(hash.to_vec()),
+                            size: Set(i64::try_from(size).unwrap_or(i64::MAX)),
+                            ..Default::default()
+                        };
+                        model.insert(txn).await?;
+                    }
+                }
+
+                continue;
+            }
+
+            // Sweep the blob, checking once more in case it was used since listing
+            let hex_hash = blob_hash_to_hex(&hash);
+            if !dry_run {
+                if Self::is_referenced(ctx, &hash).await? {
+                    debug!("Orphaned blob {hex_hash} is referenced again, unmarking");
+                    BlobOrphan::delete_by_id(hash.to_vec()).exec(txn).await?;
+                    output.referenced_blobs += 1;
+                    continue;
+                }
+
+                debug!("Deleting orphaned blob {hex_hash}");
+                bucket.delete_object(hex_hash.as_str()).await?;
+                BlobOrphan::delete_by_id(hash.to_vec()).exec(txn).await?;
+            }
+
+            output.deleted_blobs += 1;
+            output.deleted_bytes += size;
+            deleted.add(hex_hash.to_string());
+        }
+
+        // Clear marks for blobs which no longer exist, e.g. from hard deletion
+        if !dry_run {
+            let stored = stored.iter().map(|(hash, _)| hash).collect::<HashSet<_>>();
+            let missing = marked
+                .keys()
+                .filter(|hash| !stored.contains(hash))
+                .map(|hash| hash.to_vec())
+                .collect::<Vec<_>>();
+
+            if !missing.is_empty() {
+                BlobOrphan::delete_many()
+                    .filter(blob_orphan::Column::S3Hash.is_in(missing))
+                    .exec(txn)
+                    .await?;
+            }
+        }
+
+        let (_, sample_deleted_hashes) = deleted.finish();
+        output.sample_deleted_hashes = sample_deleted_hashes;
+
+        info!(
+            "Found {} orphaned blobs, {} still waiting, {} deleted ({} bytes)",
+            output.newly_orphaned,
+            output.waiting_orphans,
+            output.deleted_blobs,
+            output.deleted_bytes,
+        );
+
+        Ok(output)
+    }
+
+    /// Lists all blobs stored in S3, with their sizes.
+    ///
+    /// Only the top level of the bucket contains blobs, other objects such as
+    /// pending uploads and data exports are in directories, and are skipped.
+    async fn list_stored(ctx: &ServiceContext<'_>) -> Result<Vec<(BlobHash, u64)>> {
+        let bucket = ctx.s3_bucket();
+        let results = bucket.list(str!(), Some(str!("/"))).await?;
+        let mut blobs = Vec::new();
+
+        for result in results {
+            for object in result.contents {
+                let mut hash = [0; 64];
+                match hex::decode_to_slice(&object.key, &mut hash) {
+                    Ok(()) => blobs.push((hash, object.size)),
+                    Err(_) => debug!("Skipping non-blob object '{}'", object.key),
+                }
+            }
+        }
+
+        Ok(blobs)
+    }
+
+    /// Gets the hashes of all blobs referenced in the database.
+    async fn get_referenced(ctx: &ServiceContext<'_>) -> Result<HashSet<BlobHash>> {
+        #[derive(Debug, FromQueryResult)]
+        struct Referenced {
+            s3_hash: Vec<u8>,
+        }
+
+        let txn = ctx.transaction();
+        let hashes = Referenced::find_by_statement(Statement::from_string(
+            DatabaseBackend::Postgres,
+            REFERENCED_BLOBS_QUERY,
+        ))
+        .all(txn)
+        .await?
+        .into_iter()
+        .map(|row| slice_to_blob_hash(&row.s3_hash))
+        .collect();
+
+        Ok(hashes)
+    }
+
+    /// Checks if a particular blob is referenced anywhere in the database.
+    async fn is_referenced(ctx: &ServiceContext<'_>, hash: &[u8]) -> Result<bool> {
+        #[derive(Debug, FromQueryResult)]
+        struct Referenced {
+            referenced: bool,
+        }
+
+        let txn = ctx.transaction();
+        let Referenced { referenced } =
+            Referenced::find_by_statement(Statement::from_sql_and_values(
+                DatabaseBackend::Postgres,
+                format!(
+                    "
+                    SELECT EXISTS (
+                        SELECT 1 FROM ({REFERENCED_BLOBS_QUERY}) AS blobs
+                        WHERE s3_hash = $1
+                    ) AS referenced
+                    ",
+                ),
+                [hash.into()],
+            ))
+            .one(txn)
+            .await?
+            .expect("No results from EXISTS query");
+
+        Ok(referenced)
+    }
+
+    // Integrity checks
+
+    /// Lists one page of stored blobs in S3, in order of hash.
+    ///
+    /// Returns the blobs, and the key to continue listing after, or `None` if
+    /// there are no further objects. Objects which are not blobs are skipped.
+    pub(crate) async fn list_stored_page(
+        ctx: &ServiceContext<'_>,
+        start_after: Option<String>,
+        limit: usize,
+    ) -> Result<(Vec<BlobHash>, Option<String>)> {
+        let bucket = ctx.s3_bucket();
+        let (result, status) = bucket
+            .list_page(str!(), None, None, start_after, Some(limit))
+            .await?;
+
+        if status != 200 {
+            error!("Unable to list blobs in S3 (HTTP {status})");
+            return Err(Error::S3Response);
+        }
+
+        let next = match result.contents.last() {
+            Some(object) if result.is_truncated => Some(object.key.clone()),
+            _ => None,
+        };
+
+        let mut blobs = Vec::new();
+        for object in result.contents {
+            let mut hash = [0; 64];
+            if hex::decode_to_slice(&object.key, &mut hash).is_ok() {
+                blobs.push(hash);
+            }
+        }
+
+        Ok((blobs, next))
+    }
+
+    /// Gets one page of blob hashes referenced in the database, in order.
+    pub(crate) async fn list_referenced_page(
+        ctx: &ServiceContext<'_>,
+        after: &[u8],
+        limit: u64,
+    ) -> Result<Vec<BlobHash>> {
+        #[derive(Debug, FromQueryResult)]
+        struct Referenced {
+            s3_hash: Vec<u8>,
+        }
+
+        let txn = ctx.transaction();
+        let hashes = Referenced::find_by_statement(Statement::from_sql_and_values(
+            DatabaseBackend::Postgres,
+            format!(
+                "
+                SELECT s3_hash FROM ({REFERENCED_BLOBS_QUERY}) AS blobs
+                WHERE s3_hash > $1
+                ORDER BY s3_hash
+                LIMIT $2
+                ",
+            ),
+            [after.into(), limit.into()],
+        ))
+        .all(txn)
+        .await?
+        .into_iter()
+        .map(|row| slice_to_blob_hash(&row.s3_hash))
+        .collect();
+
+        Ok(hashes)
+    }
+
+    /// Reads a stored blob from S3, returning its actual hash and length.
+    ///
+    /// Returns `None` if the blob does not exist.
+    pub(crate) async fn hash_stored(
+        ctx: &ServiceContext<'_>,
+        hash: &[u8],
+    ) -> Result<Option<(BlobHash, u64)>> {
+        let bucket = ctx.s3_bucket();
+        let hex_hash = blob_hash_to_hex(hash);
+        let mut response = bucket.get_object_stream(hex_hash.as_str()).await?;
+        match response.status_code {
+            200 => (),
+            404 => return Ok(None),
+            status => {
+                error!("Unable to retrieve blob {hex_hash} from S3 (HTTP {status})");
+                return Err(Error::S3Response);
+            }
+        }
+
+        let mut hasher = BlobHasher::new();
+        let mut length = 0;
+        while let Some(chunk) = response.bytes().try_next().await? {
+            length += chunk.len() as u64;
+            hasher.update(&chunk);
+        }
+
+        Ok(Some((hasher.finish(), length)))
+    }
+
+    /// Gets the distinct sizes file revisions have recorded for a blob.
+    pub(crate) async fn get_recorded_sizes(
+        ctx: &ServiceContext<'_>,
+        hash: &[u8],
+    ) -> Result<Vec<i64>> {
+        let txn = ctx.transaction();
+        let sizes = FileRevision::find()
+            .select_only()
+            .column(file_revision::Column::Size)
+            .distinct()
+            .filter(file_revision::Column::S3Hash.eq(hash))
+            .into_tuple()
+            .all(txn)
+            .await?;
+
+        Ok(sizes)
+    }
+
     // Getters
 
     pub async fn get_optional(
@@ -837,6 +1734,7 @@ impl BlobService {
                 size: 0,
                 created_at: OffsetDateTime::from_unix_timestamp(EMPTY_BLOB_TIMESTAMP)
                     .unwrap(),
+                scan: None,
             }));
         }
 
@@ -855,10 +1753,22 @@ impl BlobService {
                         .map_err(|_| Error::S3Response)?
                 };
 
+                // Present if the blob was scanned on upload, see copy_upload()
+                let metadata = result.metadata.unwrap_or_default();
+                let scan = match (metadata.get("scanner"), metadata.get("scanned-at")) {
+                    (Some(scanner), Some(timestamp)) => Some(BlobScan {
+                        scanner: scanner.clone(),
+                        scanned_at: OffsetDateTime::parse(timestamp, &Rfc3339)
+                            .map_err(|_| Error::S3Response)?,
+                    }),
+                    _ => None,
+                };
+
                 Ok(Some(BlobMetadata {
                     mime,
                     size,
                     created_at,
+                    scan,
                 }))
             }
         }
@@ -872,6 +1782,126 @@ impl BlobService {
         find_or_error!(Self::get_metadata_optional(ctx, hash), Blob)
     }
 
+    /// Ensures that a blob of the given size can be returned inline.
+    ///
+    /// Large blobs should not be returned directly in API responses, as they
+    /// are very inefficient to encode. Instead, the download URL should be used.
+    pub fn check_inline_size(ctx: &ServiceContext<'_>, size: i64) -> Result<()> {
+        let maximum = ctx.config().maximum_inline_blob_size;
+        if size > maximum {
+            error!("Blob is too large to return inline ({size} > {maximum})");
+            return Err(Error::BlobInlineTooBig);
+        }
+
+        Ok(())
+    }
+
+    /// Creates a short-lived presigned URL to download a blob.
+    ///
+    /// The response headers are overridden so that the blob is served with
+    /// the given MIME type and, if provided, file name. Because it is served
+    /// by S3 directly, it also supports HTTP range requests for streaming media.
+    pub async fn get_download_url(
+        ctx: &ServiceContext<'_>,
+        hash: &[u8],
+        mime: &str,
+        filename: Option<&str>,
+    ) -> Result<String> {
+        // Special handling for empty blobs
+        //
+        // These don't exist in S3, so we cannot presign a URL for them.
+        // Instead, we produce a URL which contains the (lack of) data directly.
+        if hash == EMPTY_BLOB_HASH {
+            debug!("Returning download URL for the empty blob");
+            return Ok(str!("data:,"));
+        }
+
+        let config = ctx.config();
+        let bucket = ctx.s3_bucket();
+        let hex_hash = blob_hash_to_hex(hash);
+
+        let mut queries = HashMap::new();
+        queries.insert(str!("response-content-type"), str!(mime));
+        queries.insert(
+            str!("response-content-disposition"),
+            content_disposition(mime, filename),
+        );
+
+        let url = bucket
+            .presign_get(&hex_hash, config.presigned_expiry_secs, Some(queries))
+            .await?;
+
+        if !config.download_on_files_domain {
+            return Ok(url);
+        }
+
+        // Serve from the files domain instead of the S3 service
+        let mut url = Url::parse(&url).map_err(|error| {
+            error!("Presigned URL from S3 is invalid: {error}");
+            Error::S3Response
+        })?;
+
+        let result = url
+            .set_scheme("https")
+            .and_then(|_| url.set_port(None))
+            .and_then(|_| {
+                url.set_host(Some(&config.files_domain_no_dot))
+                    .map_err(|_| ())
+            });
+
+        if result.is_err() {
+            error!("Unable to change presigned URL host to the files domain");
+            return Err(Error::S3Response);
+        }
+
+        Ok(url.into())
+    }
+
+    /// Retrieves part of a blob, as specified by an inclusive byte range.
+    pub async fn get_range(
+        ctx: &ServiceContext<'_>,
+        hash: &[u8],
+        start: u64,
+        end: u64,
+    ) -> Result<GetBlobRangeOutput> {
+        info!("Getting range {start}-{end} of blob");
+
+        let BlobMetadata { size, .. } = Self::get_metadata(ctx, hash).await?;
+        let length = end.saturating_sub(start).saturating_add(1);
+        let s3_end = match s3_range_end(start, end, u64::try_from(size).unwrap_or(0)) {
+            Some(s3_end) => s3_end,
+            None => {
+                error!(
+                    "Requested range {start}-{end} is invalid for blob of size {size}"
+                );
+                return Err(Error::BlobRangeInvalid);
+            }
+        };
+
+        Self::check_inline_size(ctx, i64::try_from(length).unwrap_or(i64::MAX))?;
+
+        let bucket = ctx.s3_bucket();
+        let hex_hash = blob_hash_to_hex(hash);
+        let response = bucket.get_object_range(&hex_hash, start, s3_end).await?;
+        match response.status_code() {
+            200 | 206 => {
+                // Trim any extra byte requested for single-byte ranges
+                let mut data: Vec<u8> = response.into();
+                data.truncate(usize::try_from(length).unwrap_or(usize::MAX));
+
+                Ok(GetBlobRangeOutput {
+                    data,
+                    start,
+                    end,
+                    size,
+                })
+            }
+            404 => Err(Error::BlobNotFound),
+            416 => Err(Error::BlobRangeInvalid),
+            _ => s3_error(&response, "fetching S3 blob range"),
+        }
+    }
+
     #[allow(dead_code)] // TEMP
     pub async fn exists(ctx: &ServiceContext<'_>, hash: &[u8]) -> Result<bool> {
         // Special handling for the empty blob
@@ -896,8 +1926,10 @@ impl BlobService {
         ctx: &ServiceContext<'_>,
         should_fetch: bool,
         hash: &[u8],
+        size: i64,
     ) -> Result<Option<Vec<u8>>> {
         if should_fetch {
+            Self::check_inline_size(ctx, size)?;
             let data = Self::get(ctx, hash).await?;
             Ok(Some(data))
         } else {
@@ -963,6 +1995,27 @@ fn s3_error<T>(response: &ResponseData, action: &str) -> Result<T> {
     Err(Error::S3Response)
 }
 
+/// Determines the end of the range to request from S3 for an inclusive byte range.
+///
+/// The underlying S3 client requires ranges to have more than one byte,
+/// so single-byte ranges request an extra byte (or the rest of the blob,
+/// if at its end), which must be trimmed from the response.
+///
+/// Returns `None` if the range is not valid for a blob of the given size.
+fn s3_range_end(start: u64, end: u64, size: u64) -> Option<Option<u64>> {
+    if start > end || end >= size {
+        return None;
+    }
+
+    if start < end {
+        Some(Some(end))
+    } else if end + 1 < size {
+        Some(Some(end + 1))
+    } else {
+        Some(None)
+    }
+}
+
 #[derive(Debug)]
 enum HardDeleteInner {
     Commit { s3_hash: BlobHash, user_id: i64 },
@@ -974,6 +2027,8 @@ struct PendingBlob {
     s3_path: String,
     expected_length: i64,
     moved_hash: Option<Vec<u8>>,
+    multipart_upload_id: Option<String>,
+    multipart_part_size: Option<i64>,
 }
 
 /// Helper struct to produce a count of items and a sample list.
@@ -1009,3 +2064,90 @@ impl<T: Hash + Ord + Eq> SamplerCounter<T> {
         (count, samples)
     }
 }
+
+/// Builds the `Content-Disposition` header to serve a blob with.
+///
+/// Media which browsers can display is shown inline, anything else
+/// is downloaded as an attachment. The file name is provided both as
+/// an ASCII fallback and as UTF-8 using the RFC 5987 encoding.
+fn content_disposition(mime: &str, filename: Option<&str>) -> String {
+    let essence = mime.split(';').next().unwrap_or("").trim();
+    let inline = essence.starts_with("image/")
+        || essence.starts_with("audio/")
+        || essence.starts_with("video/")
+        || essence == "text/plain"
+        || essence == "application/pdf";
+
+    let disposition = if inline { "inline" } else { "attachment" };
+    let filename = match filename {
+        Some(filename) => filename,
+        None => return str!(disposition),
+    };
+
+    let fallback: String = filename
+        .chars()
+        .map(|c| match c {
+            '"' | '\\' => '_',
+            ' ' => ' ',
+            _ if c.is_ascii_graphic() => c,
+            _ => '_',
+        })
+        .collect();
+
+    let mut encoded = String::new();
+    for byte in filename.bytes() {
+        if byte.is_ascii_alphanumeric() || b"!#$&+-.^_`|~".contains(&byte) {
+            encoded.push(char::from(byte));
+        } else {
+            write!(&mut encoded, "%{byte:02X}").unwrap();
+        }
+    }
+
+    format!("{disposition}; filename=\"{fallback}\"; filename*=UTF-8''{encoded}")
+}
+
+#[test]
+fn range_end() {
+    assert_eq!(s3_range_end(0, 9, 10), Some(Some(9)));
+    assert_eq!(s3_range_end(4, 4, 10), Some(Some(5)));
+    assert_eq!(s3_range_end(9, 9, 10), Some(None));
+    assert_eq!(s3_range_end(0, 0, 1), Some(None));
+    assert_eq!(s3_range_end(5, 4, 10), None);
+    assert_eq!(s3_range_end(0, 10, 10), None);
+    assert_eq!(s3_range_end(0, 0, 0), None);
+}
+
+#[tokio::test]
+async fn malware_blacklist_committed() {
+    use sea_orm::{MockDatabase, MockExecResult};
+
+    let exec_result = MockExecResult {
+        last_insert_id: 0,
+        rows_affected: 1,
+    };
+    let database = MockDatabase::new(DatabaseBackend::Postgres)
+        .append_exec_results([exec_result.clone(), exec_result])
+        .into_connection();
+
+    BlobService::blacklist_malware_separately(
+        &database,
+        [1; 64],
+        "pending-upload",
+        str!("Malware found by test: Eicar-Signature"),
+    )
+    .await
+    .expect("Unable to blacklist malware");
+
+    // Both the blacklist row and the pending upload deletion must be committed
+    let log = format!("{:?}", database.into_transaction_log());
+    let insert = log
+        .find(r#"INSERT INTO \"blob_blacklist\""#)
+        .expect("No blacklist row inserted");
+    let delete = log
+        .find(r#"DELETE FROM \"blob_pending\""#)
+        .expect("No pending upload deleted");
+    let commit = log.find("COMMIT").expect("Transaction not committed");
+    assert!(insert < commit);
+    assert!(delete < commit);
+    assert!(!log.contains("ROLLBACK"));
+}
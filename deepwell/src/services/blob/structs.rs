@@ -26,6 +26,10 @@ use time::OffsetDateTime;
 #[derive(Deserialize, Debug, Clone)]
 pub struct StartBlobUpload {
     pub user_id: i64,
+
+    /// The site the blob will be used in, if known, so its quota can be checked.
+    #[serde(default)]
+    pub site_id: Option<i64>,
     pub blob_size: u64,
 }
 
@@ -38,6 +42,49 @@ pub struct StartBlobUploadOutput {
     pub expires_at: OffsetDateTime,
 }
 
+pub type StartMultipartUpload = StartBlobUpload;
+
+#[derive(Serialize, Debug, Clone)]
+pub struct StartMultipartUploadOutput {
+    pub pending_blob_id: String,
+    pub part_size: u64,
+    pub part_count: u32,
+
+    #[serde(with = "time::serde::rfc3339")]
+    pub expires_at: OffsetDateTime,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct GetMultipartUploadPart {
+    pub user_id: i64,
+    pub pending_blob_id: String,
+    pub part_number: u32,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct GetMultipartUploadPartOutput {
+    pub presign_url: String,
+
+    #[serde(with = "time::serde::rfc3339")]
+    pub expires_at: OffsetDateTime,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct CompleteMultipartUpload {
+    pub user_id: i64,
+    pub pending_blob_id: String,
+    pub parts: Vec<UploadedPart>,
+}
+
+/// A part which has been uploaded, as reported by the client.
+///
+/// The ETag is returned by S3 in response to each part upload.
+#[derive(Deserialize, Debug, Clone)]
+pub struct UploadedPart {
+    pub part_number: u32,
+    pub etag: String,
+}
+
 #[derive(Deserialize, Debug, Clone)]
 pub struct CancelBlobUpload {
     pub user_id: i64,
@@ -66,6 +113,7 @@ pub struct HardDeleteOutput {
     pub total_pages: usize,
     pub total_sites: usize,
     pub total_users: u64,
+    pub total_message_attachments: u64,
     pub sample_revision_ids: Vec<i64>,
     pub sample_file_ids: Vec<i64>,
     pub sample_page_ids: Vec<i64>,
@@ -73,19 +121,87 @@ pub struct HardDeleteOutput {
     pub sample_user_ids: Vec<i64>,
 }
 
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct CollectOrphansOutput {
+    /// Whether this was a preview, in which case nothing was changed.
+    pub dry_run: bool,
+
+    /// How many blobs are stored in S3.
+    pub total_blobs: usize,
+
+    /// How many stored blobs are still in use.
+    pub referenced_blobs: usize,
+
+    /// How many blobs were found to be unused for the first time.
+    pub newly_orphaned: usize,
+
+    /// How many unused blobs are still within the grace period.
+    pub waiting_orphans: usize,
+
+    /// How many blobs were deleted, or would have been for a preview.
+    pub deleted_blobs: usize,
+    pub deleted_bytes: u64,
+    pub sample_deleted_hashes: Vec<String>,
+}
+
 #[derive(Debug)]
 pub struct BlobMetadata {
     pub mime: String,
     pub size: i64,
     pub created_at: OffsetDateTime,
+    pub scan: Option<BlobScan>,
+}
+
+/// Record of the malware scan done when a blob was uploaded.
+///
+/// Only clean blobs are stored, so its presence means the scan passed.
+/// Blobs created while no scanner was configured do not have one.
+#[derive(Serialize, Debug, Clone)]
+pub struct BlobScan {
+    pub scanner: String,
+
+    #[serde(with = "time::serde::rfc3339")]
+    pub scanned_at: OffsetDateTime,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct GetBlob {
+    pub hash: Bytes<'static>,
+
+    /// Include the contents of the blob.
+    ///
+    /// This is only permitted for small blobs, otherwise
+    /// the download URL should be used instead.
+    #[serde(default)]
+    pub data: bool,
 }
 
 #[derive(Serialize, Debug, Clone)]
 pub struct GetBlobOutput {
-    pub data: Vec<u8>,
+    pub data: Option<Vec<u8>>,
+    pub download_url: String,
     pub mime: String,
     pub size: i64,
 
     #[serde(with = "time::serde::rfc3339")]
     pub created_at: OffsetDateTime,
+
+    pub scan: Option<BlobScan>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct GetBlobRange {
+    pub hash: Bytes<'static>,
+    pub start: u64,
+
+    /// The last byte to be retrieved, inclusive.
+    pub end: u64,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct GetBlobRangeOutput {
+    pub data: Vec<u8>,
+    pub start: u64,
+    pub end: u64,
+    pub size: i64,
 }
@@ -19,9 +19,20 @@
  */
 
 use super::prelude::*;
+use crate::models::page::{self, Entity as Page};
 use crate::models::page_category::{
     self, Entity as PageCategory, Model as PageCategoryModel,
 };
+use crate::services::file::License;
+use crate::services::relation::GetSiteMember;
+use crate::services::{RelationService, ScoreService, ViewCacheService};
+use sea_orm::sea_query::Query;
+use sea_orm::ItemsAndPagesNumber;
+use wikidot_normalize::normalize;
+
+/// The maximum number of pages which can be requested at once
+/// when listing the pages in a category.
+const MAXIMUM_PAGES_PER_REQUEST: u64 = 250;
 
 #[derive(Debug)]
 pub struct CategoryService;
@@ -108,4 +119,232 @@ impl CategoryService {
 
         Ok(categories)
     }
+
+    /// Updates the settings for a category.
+    ///
+    /// If the category is referenced by slug and does not exist yet,
+    /// then it is created. This way categories can be configured before
+    /// any pages are added to them.
+    pub async fn update(
+        ctx: &ServiceContext<'_>,
+        site_id: i64,
+        reference: Reference<'_>,
+        input: UpdateCategoryBody,
+    ) -> Result<PageCategoryModel> {
+        let txn = ctx.transaction();
+        let category = match reference {
+            Reference::Id(_) => Self::get(ctx, site_id, reference).await?,
+            Reference::Slug(ref slug) => Self::get_or_create(ctx, site_id, slug).await?,
+        };
+
+        info!(
+            "Updating settings for page category {} ('{}') in site ID {}",
+            category.category_id, category.slug, site_id,
+        );
+
+        let mut model = page_category::ActiveModel {
+            category_id: Set(category.category_id),
+            ..Default::default()
+        };
+
+        if let Maybe::Set(layout) = input.layout {
+            model.layout = Set(layout.map(|l| str!(l.value())));
+            ViewCacheService::invalidate_site(ctx, site_id);
+        }
+
+        if let Maybe::Set(score_type) = input.score_type {
+            if let Some(score_type) = score_type {
+                if ScoreService::scorer_for(score_type).is_none() {
+                    error!("Score type {score_type:?} is not implemented, cannot use");
+                    return Err(Error::BadRequest);
+                }
+            }
+
+            model.score_type = Set(score_type.map(|s| str!(s.value())));
+        }
+
+        if let Maybe::Set(mut template) = input.template {
+            if let Some(ref mut template) = template {
+                normalize(template);
+
+                if template.is_empty() {
+                    error!("Cannot set category template to an empty slug");
+                    return Err(Error::PageSlugEmpty);
+                }
+            }
+
+            model.template = Set(template);
+        }
+
+        if let Maybe::Set(license) = input.license {
+            let license = match license {
+                Some(id) => Some(License::get_known(&id)?.id.clone()),
+                None => None,
+            };
+
+            model.license = Set(license);
+        }
+
+        if let Maybe::Set(permissions) = input.permissions {
+            let permissions = match permissions {
+                Some(permissions) => Some(serde_json::to_value(permissions)?),
+                None => None,
+            };
+
+            model.permissions = Set(permissions);
+        }
+
+        model.updated_at = Set(Some(now()));
+        let category = model.update(txn).await?;
+        Ok(category)
+    }
+
+    /// Determines if a user may perform an action on pages in this category.
+    ///
+    /// A `user_id` of `None` means a guest. Only category-specific overrides
+    /// are checked here, if there is none for this action it is permitted.
+    pub async fn has_permission(
+        ctx: &ServiceContext<'_>,
+        category: &PageCategoryModel,
+        user_id: Option<i64>,
+        action: CategoryAction,
+    ) -> Result<bool> {
+        let permissions: CategoryPermissions = match category.permissions {
+            Some(ref permissions) => serde_json::from_value(permissions.clone())?,
+            None => return Ok(true),
+        };
+
+        let allowed = match (permissions.level(action), user_id) {
+            (None | Some(PermissionLevel::Anyone), _) => true,
+            (Some(_), None) => false,
+            (Some(PermissionLevel::Registered), Some(_)) => true,
+            (Some(PermissionLevel::Members), Some(user_id)) => {
+                RelationService::site_member_exists(
+                    ctx,
+                    GetSiteMember {
+                        site_id: category.site_id,
+                        user_id,
+                    },
+                )
+                .await?
+            }
+        };
+
+        Ok(allowed)
+    }
+
+    /// Ensures that a user may perform an action on pages in this category.
+    ///
+    /// # Errors
+    /// Returns `Error::CategoryPermissionDenied` if they may not.
+    pub async fn check_permission(
+        ctx: &ServiceContext<'_>,
+        category: &PageCategoryModel,
+        user_id: i64,
+        action: CategoryAction,
+    ) -> Result<()> {
+        if Self::has_permission(ctx, category, Some(user_id), action).await? {
+            Ok(())
+        } else {
+            error!(
+                "User ID {user_id} may not perform {action:?} in page category ID {}",
+                category.category_id,
+            );
+            Err(Error::CategoryPermissionDenied)
+        }
+    }
+
+    /// Gets a paginated list of the extant pages in a category.
+    pub async fn get_pages(
+        ctx: &ServiceContext<'_>,
+        site_id: i64,
+        GetCategoryPages {
+            category: reference,
+            page,
+            per_page,
+            order,
+            descending,
+            ..
+        }: GetCategoryPages<'_>,
+    ) -> Result<GetCategoryPagesOutput> {
+        if per_page == 0 || per_page > MAXIMUM_PAGES_PER_REQUEST {
+            error!(
+                "Invalid number of pages per request: {per_page} (maximum {MAXIMUM_PAGES_PER_REQUEST})",
+            );
+            return Err(Error::BadRequest);
+        }
+
+        let txn = ctx.transaction();
+        let PageCategoryModel { category_id, .. } =
+            Self::get(ctx, site_id, reference).await?;
+
+        let direction = if descending {
+            sea_orm::Order::Desc
+        } else {
+            sea_orm::Order::Asc
+        };
+
+        let paginator = Page::find()
+            .filter(
+                Condition::all()
+                    .add(page::Column::SiteId.eq(site_id))
+                    .add(page::Column::PageCategoryId.eq(category_id))
+                    .add(page::Column::DeletedAt.is_null()),
+            )
+            .order_by(order.into_column(), direction)
+            .order_by_asc(page::Column::PageId)
+            .paginate(txn, per_page);
+
+        let ItemsAndPagesNumber {
+            number_of_items: total_items,
+            number_of_pages: total_pages,
+        } = paginator.num_items_and_pages().await?;
+
+        let pages = paginator.fetch_page(page).await?;
+
+        Ok(GetCategoryPagesOutput {
+            pages,
+            total_pages,
+            total_items,
+        })
+    }
+
+    /// Deletes all unused categories in a site.
+    ///
+    /// A category is only removed if no pages (including deleted ones)
+    /// are in it, and it has no settings configured. The `_default`
+    /// category is never removed.
+    ///
+    /// # Returns
+    /// The number of categories which were deleted.
+    pub async fn prune(ctx: &ServiceContext<'_>, site_id: i64) -> Result<u64> {
+        info!("Pruning empty page categories in site ID {site_id}");
+
+        let txn = ctx.transaction();
+        let DeleteResult { rows_affected } = PageCategory::delete_many()
+            .filter(
+                Condition::all()
+                    .add(page_category::Column::SiteId.eq(site_id))
+                    .add(page_category::Column::Slug.ne("_default"))
+                    .add(page_category::Column::Layout.is_null())
+                    .add(page_category::Column::ScoreType.is_null())
+                    .add(page_category::Column::Template.is_null())
+                    .add(page_category::Column::License.is_null())
+                    .add(page_category::Column::Permissions.is_null())
+                    .add(
+                        page_category::Column::CategoryId.not_in_subquery(
+                            Query::select()
+                                .column(page::Column::PageCategoryId)
+                                .from(Page)
+                                .and_where(page::Column::SiteId.eq(site_id))
+                                .to_owned(),
+                        ),
+                    ),
+            )
+            .exec(txn)
+            .await?;
+
+        debug!("Deleted {rows_affected} empty page categories");
+        Ok(rows_affected)
+    }
 }
@@ -18,10 +18,110 @@
  * along with this program. If not, see <http://www.gnu.org/licenses/>.
  */
 
-use crate::types::Reference;
+use crate::models::page::Model as PageModel;
+use crate::services::score::ScoreType;
+use crate::types::{Maybe, PageOrderColumn, Reference};
+use ftml::layout::Layout;
 
 #[derive(Deserialize, Debug, Clone)]
 pub struct GetCategory<'a> {
     pub site: Reference<'a>,
     pub category: Reference<'a>,
 }
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct UpdateCategory<'a> {
+    pub site: Reference<'a>,
+    pub category: Reference<'a>,
+
+    #[serde(flatten)]
+    pub body: UpdateCategoryBody,
+}
+
+/// The settings which can be configured for a category.
+///
+/// For all of these, `None` means that there is no category-specific
+/// override, and the setting is inherited from the site (or the platform).
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct UpdateCategoryBody {
+    pub layout: Maybe<Option<Layout>>,
+    pub score_type: Maybe<Option<ScoreType>>,
+    pub template: Maybe<Option<String>>,
+    pub license: Maybe<Option<String>>,
+    pub permissions: Maybe<Option<CategoryPermissions>>,
+}
+
+/// Category-specific overrides for site permissions.
+///
+/// Any action which is `None` is inherited from the site.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct CategoryPermissions {
+    pub view: Option<PermissionLevel>,
+    pub create: Option<PermissionLevel>,
+    pub edit: Option<PermissionLevel>,
+    pub rename: Option<PermissionLevel>,
+    pub delete: Option<PermissionLevel>,
+    pub attach_files: Option<PermissionLevel>,
+}
+
+impl CategoryPermissions {
+    /// Gets the permission level override for this action, if any.
+    pub fn level(&self, action: CategoryAction) -> Option<PermissionLevel> {
+        match action {
+            CategoryAction::View => self.view,
+            CategoryAction::Create => self.create,
+            CategoryAction::Edit => self.edit,
+            CategoryAction::Rename => self.rename,
+            CategoryAction::Delete => self.delete,
+            CategoryAction::AttachFiles => self.attach_files,
+        }
+    }
+}
+
+/// An action on pages within a category which can be restricted.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CategoryAction {
+    View,
+    Create,
+    Edit,
+    Rename,
+    Delete,
+    AttachFiles,
+}
+
+/// Which users are allowed to perform an action.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PermissionLevel {
+    /// Any user, including guests.
+    Anyone,
+
+    /// Any logged-in user.
+    Registered,
+
+    /// Only members of the site.
+    Members,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct GetCategoryPages<'a> {
+    pub site: Reference<'a>,
+    pub category: Reference<'a>,
+    pub page: u64,
+    pub per_page: u64,
+
+    #[serde(default)]
+    pub order: PageOrderColumn,
+
+    #[serde(default)]
+    pub descending: bool,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct GetCategoryPagesOutput {
+    pub pages: Vec<PageModel>,
+    pub total_pages: u64,
+    pub total_items: u64,
+}
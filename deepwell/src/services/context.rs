@@ -22,17 +22,23 @@ use crate::api::ServerState;
 use crate::config::Config;
 use crate::locales::Localizations;
 use crate::services::blob::MimeAnalyzer;
+use crate::services::email::Mailer;
 use crate::services::error::Result;
+use crate::services::render::RenderPool;
+use crate::services::scan::Scanner;
+use crate::services::view::ViewCacheInvalidation;
+use crate::types::OidcProvider;
 use redis::aio::MultiplexedConnection as RedisMultiplexedConnection;
 use rsmq_async::PooledRsmq;
 use s3::bucket::Bucket;
 use sea_orm::DatabaseTransaction;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 #[derive(Debug, Clone)]
 pub struct ServiceContext<'txn> {
     state: ServerState,
     transaction: &'txn DatabaseTransaction,
+    view_cache_invalidations: Arc<Mutex<Vec<ViewCacheInvalidation>>>,
 }
 
 impl<'txn> ServiceContext<'txn> {
@@ -44,6 +50,7 @@ impl<'txn> ServiceContext<'txn> {
         ServiceContext {
             state: Arc::clone(state),
             transaction,
+            view_cache_invalidations: Arc::new(Mutex::new(Vec::new())),
         }
     }
 
@@ -87,13 +94,60 @@ impl<'txn> ServiceContext<'txn> {
         &self.state.mime_analyzer
     }
 
+    #[inline]
+    pub fn render_pool(&self) -> &RenderPool {
+        &self.state.render_pool
+    }
+
     #[inline]
     pub fn s3_bucket(&self) -> &Bucket {
         &self.state.s3_bucket
     }
 
+    #[inline]
+    pub fn scanner(&self) -> Option<&dyn Scanner> {
+        self.state.scanner.as_deref()
+    }
+
+    #[inline]
+    pub fn mailer(&self) -> Option<&Mailer> {
+        self.state.mailer.as_ref()
+    }
+
+    #[inline]
+    pub fn oidc_client_secret(&self, provider: &OidcProvider) -> Option<&str> {
+        self.state
+            .oidc_client_secrets
+            .get(&provider.client_secret_env())
+            .map(|s| s.as_str())
+    }
+
     #[inline]
     pub fn transaction(&self) -> &'txn DatabaseTransaction {
         self.transaction
     }
+
+    // View cache
+    //
+    // Invalidations are recorded here and performed once the transaction
+    // has committed, see ViewCacheService::run_invalidations().
+    pub fn add_view_cache_invalidation(&self, invalidation: ViewCacheInvalidation) {
+        let mut invalidations = self
+            .view_cache_invalidations
+            .lock()
+            .expect("View cache invalidation lock poisoned");
+
+        if !invalidations.contains(&invalidation) {
+            invalidations.push(invalidation);
+        }
+    }
+
+    pub fn take_view_cache_invalidations(&self) -> Vec<ViewCacheInvalidation> {
+        let mut invalidations = self
+            .view_cache_invalidations
+            .lock()
+            .expect("View cache invalidation lock poisoned");
+
+        std::mem::take(&mut invalidations)
+    }
 }
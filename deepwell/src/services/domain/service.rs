@@ -22,48 +22,104 @@
 //!
 //! This service has two components, management of canonical domains (e.g. `scp-wiki.wikijump.com`)
 //! and custom domains (e.g. `scpwiki.com`).
-
-// TODO disallow custom domains that are subdomains of the main domain or files domain
+//!
+//! Custom domains begin in a pending state, and only resolve once the site has proven
+//! ownership by adding a DNS TXT record containing its challenge token. This record is
+//! checked periodically afterwards, and domains which no longer have it are disabled.
 
 use super::prelude::*;
 use crate::models::site::{self, Entity as Site, Model as SiteModel};
 use crate::models::site_domain::{self, Entity as SiteDomain, Model as SiteDomainModel};
+use crate::services::job::{Job, JobService};
 use crate::services::SiteService;
+use crate::utils::assert_is_csprng;
+use hickory_resolver::config::{NameServerConfigGroup, ResolverConfig, ResolverOpts};
+use hickory_resolver::error::ResolveErrorKind;
+use hickory_resolver::TokioAsyncResolver;
+use rand::distributions::{Alphanumeric, DistString};
+use rand::thread_rng;
+use sea_orm::sea_query::Expr;
+use sea_orm::UpdateResult;
 use std::borrow::Cow;
 
+/// The label under a custom domain where its challenge TXT record is placed.
+pub const CHALLENGE_RECORD_PREFIX: &str = "_wikijump-challenge";
+
+/// How long the randomly-generated verification token is.
+const VERIFICATION_TOKEN_LENGTH: usize = 32;
+
 #[derive(Debug)]
 pub struct DomainService;
 
 impl DomainService {
     /// Creates a custom domain for a site.
+    ///
+    /// The domain is pending until its challenge record is found, see `verify_custom()`.
+    /// If another site has a pending claim on the domain which was never verified within
+    /// the grace period, then it is released and replaced by this one.
     pub async fn create_custom(
         ctx: &ServiceContext<'_>,
-        CreateCustomDomain { domain, site_id }: CreateCustomDomain,
-    ) -> Result<()> {
+        CreateCustomDomain {
+            domain,
+            site_id,
+            bypass_verification,
+        }: CreateCustomDomain,
+    ) -> Result<CreateCustomDomainOutput> {
         info!("Creating custom domain '{domain}' (site ID {site_id})");
 
         let txn = ctx.transaction();
-        if Self::custom_domain_exists(ctx, &domain).await? {
-            error!("Custom domain already exists, cannot create");
-            return Err(Error::CustomDomainExists);
+        let config = ctx.config();
+        let domain = Self::normalize_custom(config, &domain)?;
+
+        if let Some(model) = SiteDomain::find_by_id(&domain).one(txn).await? {
+            if !Self::is_stale_claim(config, &model) {
+                error!("Custom domain already exists, cannot create");
+                return Err(Error::CustomDomainExists);
+            }
+
+            warn!(
+                "Releasing stale claim on custom domain '{domain}' (site ID {})",
+                model.site_id,
+            );
+            Self::remove_custom(ctx, domain.clone()).await?;
         }
 
+        let (verification_token, verified_at) = if bypass_verification {
+            (None, Some(now()))
+        } else {
+            let mut rng = thread_rng();
+            assert_is_csprng(&rng);
+            let token = Alphanumeric.sample_string(&mut rng, VERIFICATION_TOKEN_LENGTH);
+            (Some(token), None)
+        };
+
         let model = site_domain::ActiveModel {
             domain: Set(domain),
             site_id: Set(site_id),
             created_at: Set(now()),
+            verification_token: Set(verification_token),
+            verified_at: Set(verified_at),
+            last_checked_at: Set(None),
         };
-        model.insert(txn).await?;
-        Ok(())
+        let model = model.insert(txn).await?;
+        let challenge = Self::challenge(&model);
+
+        Ok(CreateCustomDomainOutput {
+            domain: model.domain,
+            challenge,
+        })
     }
 
     /// Delete the given custom domain.
     ///
+    /// If it is the preferred domain of its site, that is unset first.
     /// Yields `Error::CustomDomainNotFound` if it's missing.
     pub async fn remove_custom(ctx: &ServiceContext<'_>, domain: String) -> Result<()> {
         info!("Deleting custom domain '{domain}'");
 
         let txn = ctx.transaction();
+        Self::unset_preferred(ctx, &domain).await?;
+
         let DeleteResult { rows_affected, .. } =
             SiteDomain::delete_by_id(domain).exec(txn).await?;
 
@@ -81,10 +137,17 @@ impl DomainService {
         info!("Getting site for custom domain {domain:?}");
 
         // Join with the site table so we can get that data, rather than just the ID.
+        // Deleted sites keep their custom domains, but they do not resolve.
+        // Neither do domains which are pending or have been disabled.
         let txn = ctx.transaction();
         let model = Site::find()
             .join(JoinType::Join, site::Relation::SiteDomain.def())
-            .filter(site_domain::Column::Domain.eq(domain))
+            .filter(
+                Condition::all()
+                    .add(site_domain::Column::Domain.eq(domain))
+                    .add(site_domain::Column::VerifiedAt.is_not_null())
+                    .add(site::Column::DeletedAt.is_null()),
+            )
             .one(txn)
             .await?;
 
@@ -104,14 +167,16 @@ impl DomainService {
     }
 
     /// Determines if the given custom domain is registered.
-    #[inline]
+    ///
+    /// This includes domains belonging to deleted sites, as well as
+    /// ones which are pending or disabled.
     pub async fn custom_domain_exists(
         ctx: &ServiceContext<'_>,
         domain: &str,
     ) -> Result<bool> {
-        Self::site_from_custom_domain_optional(ctx, domain)
-            .await
-            .map(|site| site.is_some())
+        let txn = ctx.transaction();
+        let model = SiteDomain::find_by_id(domain).one(txn).await?;
+        Ok(model.is_some())
     }
 
     /// Gets the site corresponding with the given domain.
@@ -138,6 +203,10 @@ impl DomainService {
 
     /// Gets the site corresponding with the given domain.
     ///
+    /// Canonical domains using a former slug of a site still resolve, via its site alias.
+    /// The caller should then redirect to the site's preferred domain, see
+    /// `DomainService::domain_for_site()`.
+    ///
     /// Returns one of three variants:
     /// * `Found` &mdash; Site retrieved from the domain.
     /// * `Slug` &mdash; Site does not exist. If it did, domain would be a canonical domain.
@@ -257,4 +326,316 @@ impl DomainService {
 
         Ok(models)
     }
+
+    /// Validates a custom domain, returning its normalized ASCII form.
+    ///
+    /// Internationalized domains are converted to punycode. Domains which are
+    /// not syntactically valid, are a bare label or IP address, or are under
+    /// either the main or files domain are rejected.
+    pub fn normalize_custom(config: &Config, domain: &str) -> Result<String> {
+        // Permit the fully-qualified form, with the root label
+        let domain = domain.strip_suffix('.').unwrap_or(domain);
+
+        let domain = match idna::domain_to_ascii_strict(domain) {
+            Ok(domain) => domain,
+            Err(error) => {
+                error!("Custom domain is not a valid domain name: {error}");
+                return Err(Error::CustomDomainInvalid);
+            }
+        };
+
+        // Must have a top-level domain, which cannot be numeric (i.e. an IPv4 address)
+        match domain.rsplit_once('.') {
+            Some((_, tld)) if !tld.bytes().all(|b| b.is_ascii_digit()) => (),
+            _ => {
+                error!("Custom domain '{domain}' is not under a top-level domain");
+                return Err(Error::CustomDomainInvalid);
+            }
+        }
+
+        for (parent_domain, parent_domain_no_dot) in [
+            (&config.main_domain, &config.main_domain_no_dot),
+            (&config.files_domain, &config.files_domain_no_dot),
+        ] {
+            if &domain == parent_domain_no_dot || domain.ends_with(parent_domain) {
+                error!(
+                    "Custom domain '{domain}' is reserved by '{parent_domain_no_dot}'"
+                );
+                return Err(Error::CustomDomainReserved);
+            }
+        }
+
+        Ok(domain)
+    }
+
+    /// Gets the DNS challenge a site must fulfill to verify this custom domain.
+    ///
+    /// Returns `None` if the domain is exempt from verification.
+    pub fn challenge(model: &SiteDomainModel) -> Option<CustomDomainChallenge> {
+        model
+            .verification_token
+            .as_ref()
+            .map(|token| CustomDomainChallenge {
+                record_name: format!("{CHALLENGE_RECORD_PREFIX}.{}", model.domain),
+                record_value: token.clone(),
+            })
+    }
+
+    /// Queues a job to check the challenge record for this custom domain.
+    pub async fn queue_verify(ctx: &ServiceContext<'_>, domain: String) -> Result<()> {
+        info!("Queuing verification for custom domain '{domain}'");
+
+        if !Self::custom_domain_exists(ctx, &domain).await? {
+            error!("Custom domain does not exist, cannot verify");
+            return Err(Error::CustomDomainNotFound);
+        }
+
+        JobService::queue_job(ctx, &Job::VerifyCustomDomain { domain }, None).await
+    }
+
+    /// Checks the challenge record for this custom domain, updating its state.
+    ///
+    /// Returns whether the domain is currently verified.
+    pub async fn verify_custom(ctx: &ServiceContext<'_>, domain: &str) -> Result<bool> {
+        let txn = ctx.transaction();
+        let model = match SiteDomain::find_by_id(domain).one(txn).await? {
+            Some(model) => model,
+            None => {
+                warn!("Custom domain '{domain}' was removed before it could be verified");
+                return Ok(false);
+            }
+        };
+
+        let resolver = Self::build_resolver(ctx.config())?;
+        Self::check_custom(ctx, &resolver, model).await
+    }
+
+    /// Checks the challenge records for all custom domains.
+    ///
+    /// Pending domains which now have their record are verified, and verified
+    /// domains whose record has been missing for longer than the grace period
+    /// are disabled. A lookup failure for one domain does not stop the others
+    /// from being checked, nor does it count against that domain.
+    pub async fn verify_all_custom(ctx: &ServiceContext<'_>) -> Result<()> {
+        info!("Verifying all custom domains");
+
+        let txn = ctx.transaction();
+        let models = SiteDomain::find()
+            .filter(site_domain::Column::VerificationToken.is_not_null())
+            .order_by_asc(site_domain::Column::Domain)
+            .all(txn)
+            .await?;
+
+        let resolver = Self::build_resolver(ctx.config())?;
+        for model in models {
+            let domain = model.domain.clone();
+            if let Err(error) = Self::check_custom(ctx, &resolver, model).await {
+                error!("Unable to check custom domain '{domain}': {error}");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks the challenge record for one custom domain, and updates its state.
+    ///
+    /// Returns whether the domain is verified after the check.
+    async fn check_custom(
+        ctx: &ServiceContext<'_>,
+        resolver: &TokioAsyncResolver,
+        model: SiteDomainModel,
+    ) -> Result<bool> {
+        let token = match model.verification_token {
+            Some(ref token) => token,
+            None => {
+                debug!(
+                    "Custom domain '{}' is exempt from verification",
+                    model.domain
+                );
+                return Ok(true);
+            }
+        };
+
+        let txn = ctx.transaction();
+        let config = ctx.config();
+        let found = Self::lookup_challenge(resolver, &model.domain, token).await?;
+        let now = now();
+
+        let verified_at = match model.verified_at {
+            // Record present, refresh verification
+            _ if found => {
+                if model.verified_at.is_none() {
+                    info!("Custom domain '{}' has been verified", model.domain);
+                }
+
+                Some(now)
+            }
+
+            // Record missing, but still within the grace period
+            Some(verified_at) if now < verified_at + config.domain_verification_grace => {
+                warn!(
+                    "Challenge record for custom domain '{}' is missing, last verified at {verified_at}",
+                    model.domain,
+                );
+
+                Some(verified_at)
+            }
+
+            // Record missing for too long, disable
+            Some(_) => {
+                warn!(
+                    "Challenge record for custom domain '{}' has lapsed, disabling",
+                    model.domain,
+                );
+
+                Self::unset_preferred(ctx, &model.domain).await?;
+                None
+            }
+
+            // Still pending
+            None => {
+                debug!("Custom domain '{}' is still pending", model.domain);
+                None
+            }
+        };
+
+        let model = site_domain::ActiveModel {
+            domain: Set(model.domain),
+            verified_at: Set(verified_at),
+            last_checked_at: Set(Some(now)),
+            ..Default::default()
+        };
+        model.update(txn).await?;
+        Ok(verified_at.is_some())
+    }
+
+    /// Looks up the challenge TXT record for a domain, and checks if it has the token.
+    async fn lookup_challenge(
+        resolver: &TokioAsyncResolver,
+        domain: &str,
+        token: &str,
+    ) -> Result<bool> {
+        // Fully-qualified, so resolver search domains are not applied
+        let record_name = format!("{CHALLENGE_RECORD_PREFIX}.{domain}.");
+        debug!("Looking up challenge record '{record_name}'");
+
+        let lookup = match resolver.txt_lookup(record_name).await {
+            Ok(lookup) => lookup,
+            Err(error) => match error.kind() {
+                ResolveErrorKind::NoRecordsFound { .. } => return Ok(false),
+                _ => return Err(Error::DnsResolver(error)),
+            },
+        };
+
+        // Long TXT records are split into several strings, which are joined
+        let found = lookup.iter().any(|txt| {
+            let value = txt.txt_data().concat();
+            value == token.as_bytes()
+        });
+
+        Ok(found)
+    }
+
+    fn build_resolver(config: &Config) -> Result<TokioAsyncResolver> {
+        match config.domain_verification_resolver {
+            Some(address) => {
+                debug!("Using DNS server {address} for custom domain verification");
+
+                let name_servers = NameServerConfigGroup::from_ips_clear(
+                    &[address.ip()],
+                    address.port(),
+                    true,
+                );
+                let resolver_config =
+                    ResolverConfig::from_parts(None, vec![], name_servers);
+                let resolver =
+                    TokioAsyncResolver::tokio(resolver_config, ResolverOpts::default());
+
+                Ok(resolver)
+            }
+            None => Ok(TokioAsyncResolver::tokio_from_system_conf()?),
+        }
+    }
+
+    /// Determines if this is a pending claim which was never verified within the grace period.
+    fn is_stale_claim(config: &Config, model: &SiteDomainModel) -> bool {
+        model.verified_at.is_none()
+            && now() > model.created_at + config.domain_verification_grace
+    }
+
+    /// If this custom domain is the preferred domain of its site, unset it.
+    async fn unset_preferred(ctx: &ServiceContext<'_>, domain: &str) -> Result<()> {
+        let txn = ctx.transaction();
+        let UpdateResult { rows_affected } = Site::update_many()
+            .col_expr(
+                site::Column::CustomDomain,
+                Expr::value(Option::<String>::None),
+            )
+            .filter(site::Column::CustomDomain.eq(domain))
+            .exec(txn)
+            .await?;
+
+        if rows_affected > 0 {
+            info!("Unset custom domain '{domain}' as the preferred domain for its site");
+        }
+
+        Ok(())
+    }
+}
+
+#[test]
+fn test_normalize_custom() {
+    let config = Config::example();
+
+    macro_rules! check {
+        ($input:expr, $expected:expr $(,)?) => {{
+            let actual = DomainService::normalize_custom(&config, $input);
+            assert_eq!(
+                actual.ok().as_deref(),
+                Some($expected),
+                "Custom domain {:?} was not normalized as expected",
+                $input,
+            );
+        }};
+    }
+
+    macro_rules! check_err {
+        ($input:expr, $error:pat $(,)?) => {{
+            let actual = DomainService::normalize_custom(&config, $input);
+            assert!(
+                matches!(actual, Err($error)),
+                "Custom domain {:?} was not rejected as expected: {:?}",
+                $input,
+                actual,
+            );
+        }};
+    }
+
+    // Case folding and trailing dot
+    check!("example.com", "example.com");
+    check!("Example.COM", "example.com");
+    check!("www.example.com.", "www.example.com");
+    check!("WWW.Example.Com.", "www.example.com");
+    check!("bücher.example", "xn--bcher-kva.example");
+
+    // Not domain names
+    check_err!("", Error::CustomDomainInvalid);
+    check_err!("example.com:8080", Error::CustomDomainInvalid);
+    check_err!("example.com/path", Error::CustomDomainInvalid);
+    check_err!("https://example.com", Error::CustomDomainInvalid);
+    check_err!("exa mple.com", Error::CustomDomainInvalid);
+    check_err!("example..com", Error::CustomDomainInvalid);
+
+    // Bare labels and IP addresses
+    check_err!("localhost", Error::CustomDomainInvalid);
+    check_err!("192.168.0.1", Error::CustomDomainInvalid);
+
+    // Main and files domains
+    check_err!("wikijump.com", Error::CustomDomainReserved);
+    check_err!("Wikijump.com.", Error::CustomDomainReserved);
+    check_err!("scp-wiki.wikijump.com", Error::CustomDomainReserved);
+    check_err!("wjfiles.com", Error::CustomDomainReserved);
+    check_err!("scp-wiki.wjfiles.com", Error::CustomDomainReserved);
+    check!("notwikijump.com", "notwikijump.com");
+    check!("wikijump.com.example", "wikijump.com.example");
 }
@@ -31,4 +31,20 @@ pub enum SiteDomainResult<'a> {
 pub struct CreateCustomDomain {
     pub domain: String,
     pub site_id: i64,
+
+    #[serde(default)]
+    pub bypass_verification: bool,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct CreateCustomDomainOutput {
+    pub domain: String,
+    pub challenge: Option<CustomDomainChallenge>,
+}
+
+/// The DNS TXT record a site must add to prove ownership of a custom domain.
+#[derive(Serialize, Debug, Clone)]
+pub struct CustomDomainChallenge {
+    pub record_name: String,
+    pub record_value: String,
 }
@@ -27,5 +27,5 @@ mod prelude {
 mod service;
 mod structs;
 
-pub use service::EmailService;
+pub use service::{build_mailer, EmailService, Mailer};
 pub use structs::*;
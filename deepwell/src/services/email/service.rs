@@ -19,11 +19,66 @@
  */
 
 use super::prelude::*;
+use crate::config::Secrets;
+use lettre::message::{header::ContentType, Mailbox};
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+
+/// The transport used to deliver emails.
+pub type Mailer = AsyncSmtpTransport<Tokio1Executor>;
+
+/// Builds the SMTP transport for emails, if one is configured.
+pub fn build_mailer(secrets: &Secrets) -> anyhow::Result<Option<Mailer>> {
+    match secrets.smtp_url {
+        None => {
+            warn!("No SMTP server is configured, emails will not be sent");
+            Ok(None)
+        }
+        Some(ref url) => {
+            info!("Using SMTP server to send emails");
+            let mailer = Mailer::from_url(url)?.build();
+            Ok(Some(mailer))
+        }
+    }
+}
 
 #[derive(Debug)]
 pub struct EmailService;
 
 impl EmailService {
+    /// Sends a plain text email to the given address.
+    ///
+    /// If no SMTP server is configured, the email is only logged.
+    pub async fn send(
+        ctx: &ServiceContext<'_>,
+        recipient: &str,
+        subject: &str,
+        body: String,
+    ) -> Result<()> {
+        let mailer = match ctx.mailer() {
+            Some(mailer) => mailer,
+            None => {
+                warn!("No SMTP server is configured, not sending email '{subject}'");
+                return Ok(());
+            }
+        };
+
+        let message = Message::builder()
+            .from(parse_mailbox(&ctx.config().email_sender)?)
+            .to(parse_mailbox(recipient)?)
+            .subject(subject)
+            .header(ContentType::TEXT_PLAIN)
+            .body(body)
+            .map_err(|error| Error::EmailDelivery(error.to_string()))?;
+
+        info!("Sending email '{subject}'");
+        mailer
+            .send(message)
+            .await
+            .map_err(|error| Error::EmailDelivery(error.to_string()))?;
+
+        Ok(())
+    }
+
     /// Validates an email through the MailCheck API.
     pub async fn validate(email: &str) -> Result<EmailValidationOutput> {
         // Sends a GET request to the MailCheck API and deserializes the response.
@@ -87,3 +142,10 @@ impl EmailService {
         Ok(output)
     }
 }
+
+fn parse_mailbox(address: &str) -> Result<Mailbox> {
+    address.parse().map_err(|error| {
+        error!("Invalid email address: {error}");
+        Error::InvalidEmail
+    })
+}
@@ -19,7 +19,9 @@
  */
 
 use crate::hash::{blob_hash_to_hex, BlobHash};
+use crate::services::storage::StorageOwner;
 use filemagic::FileMagicError;
+use hickory_resolver::error::ResolveError;
 use jsonrpsee::types::error::ErrorObjectOwned;
 use reqwest::Error as ReqwestError;
 use s3::error::S3Error;
@@ -87,6 +89,9 @@ pub enum Error {
     #[error("Email verification error: {}", .0.as_ref().unwrap_or(&str!("<unspecified>")))]
     EmailVerification(Option<String>),
 
+    #[error("Email delivery error: {0}")]
+    EmailDelivery(String),
+
     #[error("Web request error: {0}")]
     WebRequest(#[from] ReqwestError),
 
@@ -96,6 +101,30 @@ pub enum Error {
     #[error("Attempting to perform a wikitext parse and render has timed out")]
     RenderTimeout,
 
+    #[error("Page includer returned results inconsistent with the request")]
+    RenderIncluder,
+
+    #[error("Too many render requests are queued, try again later")]
+    RenderQueueFull,
+
+    #[error("Render worker failed while processing the request")]
+    RenderWorker,
+
+    #[error("Archive error: {0}")]
+    Archive(#[from] zip::result::ZipError),
+
+    #[error("Unable to encode or decode stored text: {0}")]
+    TextCodec(String),
+
+    #[error("Identity provider returned an invalid response: {0}")]
+    OidcProvider(String),
+
+    #[error("DNS resolution error: {0}")]
+    DnsResolver(#[from] ResolveError),
+
+    #[error("Malware scanner failed while scanning the blob: {0}")]
+    BlobScanner(String),
+
     #[error("The user cannot rename as they do not have enough name change tokens")]
     InsufficientNameChanges,
 
@@ -108,6 +137,18 @@ pub enum Error {
     #[error("Invalid session token, cannot be used for authentication")]
     InvalidSessionToken,
 
+    #[error("Invalid or expired identity provider login state")]
+    OidcStateInvalid,
+
+    #[error("Identity is not linked to any user, and new users cannot be created")]
+    OidcIdentityNotLinked,
+
+    #[error("Session was used by a different client, and has been invalidated")]
+    SessionBindingMismatch,
+
+    #[error("This category does not permit this action by this user")]
+    CategoryPermissionDenied,
+
     #[error("User ID {session_user_id} associated with session does not match active user ID {active_user_id}")]
     SessionUserId {
         active_user_id: i64,
@@ -135,6 +176,24 @@ pub enum Error {
     #[error("Revision ID passed for this operation is not the latest")]
     NotLatestRevisionId,
 
+    #[error("Adding this page parent would create a cycle")]
+    PageParentCycle,
+
+    #[error("Cannot restore a site which is not deleted")]
+    SiteNotDeleted,
+
+    #[error("The site's membership policy does not permit this action")]
+    SiteMembershipPolicy,
+
+    #[error("Site invitation has expired or is no longer valid")]
+    SiteInvitationExpired,
+
+    #[error("Site invitation is for a different user")]
+    SiteInvitationWrongUser,
+
+    #[error("Tag is not in the site's tag whitelist")]
+    TagNotAllowed,
+
     #[error("The regular expression found in the database is invalid")]
     FilterRegexInvalid(regex::Error),
 
@@ -192,6 +251,15 @@ pub enum Error {
     #[error("Message has too many recipients")]
     MessageTooManyRecipients,
 
+    #[error("Message has too many attachments")]
+    MessageTooManyAttachments,
+
+    #[error("Message attachment is too large")]
+    MessageAttachmentTooBig { size: i64, maximum: i64 },
+
+    #[error("Page parent graph is too deep")]
+    PageParentDepthExceeded,
+
     #[error("Unspecified entity not found")]
     GeneralNotFound,
 
@@ -240,6 +308,9 @@ pub enum Error {
     #[error("Message draft does not exist")]
     MessageDraftNotFound,
 
+    #[error("Message attachment does not exist")]
+    MessageAttachmentNotFound,
+
     #[error("Blob item does not exist")]
     BlobNotFound,
 
@@ -252,6 +323,38 @@ pub enum Error {
     #[error("Uploaded blob is too big for this operation")]
     BlobTooBig,
 
+    #[error("Blob is too big to be returned inline, use the download URL instead")]
+    BlobInlineTooBig,
+
+    #[error("Requested byte range is not valid for this blob")]
+    BlobRangeInvalid,
+
+    #[error("Invalid request for this multipart upload")]
+    BlobMultipartInvalid,
+
+    #[error("Custom domain is not a valid domain name")]
+    CustomDomainInvalid,
+
+    #[error("Custom domain cannot be under the main or files domain")]
+    CustomDomainReserved,
+
+    #[error("Storage quota would be exceeded by this upload")]
+    StorageQuotaExceeded {
+        owner: StorageOwner,
+        used: i64,
+        quota: i64,
+        size: i64,
+    },
+
+    #[error("Uploaded blob was found to contain malware")]
+    BlobMalware(String),
+
+    #[error("Licence identifier is not recognized")]
+    LicenseUnknown(String),
+
+    #[error("File licensing information is invalid")]
+    FileLicensingInvalid,
+
     #[error("Uploaded blob does not match expected length")]
     BlobSizeMismatch { expected: usize, actual: usize },
 
@@ -266,6 +369,21 @@ pub enum Error {
     #[error("Text item does not exist")]
     TextNotFound,
 
+    #[error("Site membership application does not exist")]
+    SiteApplicationNotFound,
+
+    #[error("Site invitation does not exist")]
+    SiteInvitationNotFound,
+
+    #[error("Identity provider does not exist")]
+    OidcProviderNotFound,
+
+    #[error("Linked identity does not exist")]
+    OidcIdentityNotFound,
+
+    #[error("Session does not exist")]
+    SessionNotFound,
+
     #[error("Cannot perform, user already exists")]
     UserExists,
 
@@ -293,6 +411,15 @@ pub enum Error {
     #[error("Cannot perform, custom domain already exists")]
     CustomDomainExists,
 
+    #[error("Cannot perform, user is already a site member")]
+    SiteMemberExists,
+
+    #[error("Cannot perform, site membership application already exists")]
+    SiteApplicationExists,
+
+    #[error("Cannot perform, a mass rerender for this scope is already running")]
+    RerenderExists,
+
     #[error("Cannot perform this action because you are blocked by the user")]
     UserBlockedUser,
 
@@ -301,6 +428,12 @@ pub enum Error {
 
     #[error("The rate limit for an external API has been reached")]
     RateLimited,
+
+    #[error("Too many requests have been made, try again later")]
+    TooManyRequests,
+
+    #[error("Too many failed login attempts for this account, try again later")]
+    LoginLockedOut,
 }
 
 impl Error {
@@ -342,6 +475,12 @@ impl Error {
             Error::MessageDraftNotFound => 2015,
             Error::BlobNotFound => 2016,
             Error::TextNotFound => 2017,
+            Error::SiteApplicationNotFound => 2018,
+            Error::SiteInvitationNotFound => 2019,
+            Error::OidcProviderNotFound => 2020,
+            Error::OidcIdentityNotFound => 2021,
+            Error::SessionNotFound => 2022,
+            Error::MessageAttachmentNotFound => 2023,
 
             // 2100 -- Existing data
             Error::UserExists => 2100,
@@ -353,6 +492,9 @@ impl Error {
             Error::FileExists => 2106,
             Error::FilterExists => 2107,
             Error::CustomDomainExists => 2108,
+            Error::SiteMemberExists => 2109,
+            Error::SiteApplicationExists => 2110,
+            Error::RerenderExists => 2111,
 
             // 3000 - Server errors, unexpected
             Error::RateLimited => 3000,
@@ -364,6 +506,11 @@ impl Error {
             Error::EmailVerification(_) => 3101,
             Error::S3Service(_) => 3102,
             Error::S3Response => 3103,
+            Error::RenderQueueFull => 3104,
+            Error::OidcProvider(_) => 3105,
+            Error::DnsResolver(_) => 3106,
+            Error::BlobScanner(_) => 3107,
+            Error::EmailDelivery(_) => 3108,
 
             // 3200 -- Backend issues
             Error::Serde(_) => 3200,
@@ -373,6 +520,10 @@ impl Error {
             Error::Otp(_) => 3205,
             Error::Redis(_) => 3206,
             Error::Rsmq(_) => 3207,
+            Error::RenderIncluder => 3208,
+            Error::RenderWorker => 3209,
+            Error::Archive(_) => 3210,
+            Error::TextCodec(_) => 3211,
 
             // 4000 - Client, request errors
             //        BadRequest is pretty general, avoid it except for rare weird cases
@@ -407,6 +558,26 @@ impl Error {
             Error::BlobBlacklisted(_) => 4028,
             Error::BlobCannotBlacklistExisting => 4029,
             Error::NotLatestRevisionId => 4030,
+            Error::PageParentCycle => 4031,
+            Error::SiteNotDeleted => 4032,
+            Error::SiteMembershipPolicy => 4033,
+            Error::SiteInvitationExpired => 4034,
+            Error::SiteInvitationWrongUser => 4035,
+            Error::TagNotAllowed => 4036,
+            Error::TooManyRequests => 4037,
+            Error::LoginLockedOut => 4038,
+            Error::BlobInlineTooBig => 4039,
+            Error::BlobRangeInvalid => 4040,
+            Error::BlobMultipartInvalid => 4041,
+            Error::CustomDomainInvalid => 4042,
+            Error::CustomDomainReserved => 4043,
+            Error::StorageQuotaExceeded { .. } => 4044,
+            Error::BlobMalware(_) => 4045,
+            Error::LicenseUnknown(_) => 4046,
+            Error::FileLicensingInvalid => 4047,
+            Error::MessageTooManyAttachments => 4048,
+            Error::MessageAttachmentTooBig { .. } => 4049,
+            Error::PageParentDepthExceeded => 4050,
 
             // 4100 -- Localization
             Error::LocaleInvalid(_) => 4100,
@@ -429,6 +600,10 @@ impl Error {
             Error::InvalidAuthentication => 5000,
             Error::InvalidSessionToken => 5001,
             Error::SessionUserId { .. } => 5002,
+            Error::OidcStateInvalid => 5003,
+            Error::OidcIdentityNotLinked => 5004,
+            Error::SessionBindingMismatch => 5005,
+            Error::CategoryPermissionDenied => 5006,
             // TODO: permission errors (e.g. locked page, cannot apply bans)
         }
     }
@@ -464,9 +639,30 @@ impl Error {
                 "length": length,
                 "maximum": maximum,
             }),
+            Error::MessageAttachmentTooBig { size, maximum } => json!({
+                "size": size,
+                "maximum": maximum,
+            }),
+            Error::StorageQuotaExceeded {
+                owner,
+                used,
+                quota,
+                size,
+            } => json!({
+                "owner": owner,
+                "used": used,
+                "quota": quota,
+                "size": size,
+            }),
 
             // Emit as-is
             Error::EmailVerification(value) => json!(value),
+            Error::EmailDelivery(value) => json!(value),
+            Error::OidcProvider(value) => json!(value),
+            Error::BlobScanner(value) => json!(value),
+            Error::BlobMalware(value) => json!(value),
+            Error::LicenseUnknown(value) => json!(value),
+            Error::TextCodec(value) => json!(value),
 
             // Emit as a Debug string
             Error::Cryptography(value) => json!(format!("{value:?}")),
@@ -477,6 +673,8 @@ impl Error {
             Error::Serde(value) => json!(format!("{value:?}")),
             Error::S3Service(value) => json!(format!("{value:?}")),
             Error::WebRequest(value) => json!(format!("{value:?}")),
+            Error::Archive(value) => json!(format!("{value:?}")),
+            Error::DnsResolver(value) => json!(format!("{value:?}")),
             Error::FilterRegexInvalid(value) => json!(format!("{value:?}")),
 
             // Emit as hexadecimal bytes
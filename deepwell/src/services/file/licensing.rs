@@ -0,0 +1,412 @@
+/*
+ * services/file/licensing.rs
+ *
+ * DEEPWELL - Wikijump API provider and database manager
+ * Copyright (C) 2019-2025 Wikijump Team
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Structured licensing information for files.
+//!
+//! Licences are identified by their [SPDX identifier](https://spdx.org/licenses/),
+//! which includes the Creative Commons licences (e.g. `CC-BY-SA-4.0`). Only licences
+//! listed here are accepted, so that compatibility with the site's licence can be checked.
+
+use super::prelude::*;
+use once_cell::sync::Lazy;
+use reqwest::Url;
+use serde_json::Value as JsonValue;
+use std::collections::HashMap;
+
+/// The maximum length of the author and source URL fields, in bytes.
+pub const MAXIMUM_LICENSING_FIELD_LENGTH: usize = 1024;
+
+/// Creative Commons licence versions with SPDX identifiers.
+const CREATIVE_COMMONS_VERSIONS: [&str; 5] = ["1.0", "2.0", "2.5", "3.0", "4.0"];
+
+/// Creative Commons licence variants, with whether they are non-commercial
+/// and whether they forbid derivatives.
+const CREATIVE_COMMONS_VARIANTS: [(&str, bool, bool); 6] = [
+    ("BY", false, false),
+    ("BY-SA", false, false),
+    ("BY-ND", false, true),
+    ("BY-NC", true, false),
+    ("BY-NC-SA", true, false),
+    ("BY-NC-ND", true, true),
+];
+
+/// Other accepted licences, with whether they require attribution.
+const OTHER_LICENSES: [(&str, bool); 18] = [
+    ("CC0-1.0", false),
+    ("CC-PDDC", false),
+    ("Unlicense", false),
+    ("MIT", true),
+    ("Apache-2.0", true),
+    ("BSD-2-Clause", true),
+    ("BSD-3-Clause", true),
+    ("GPL-2.0-only", true),
+    ("GPL-2.0-or-later", true),
+    ("GPL-3.0-only", true),
+    ("GPL-3.0-or-later", true),
+    ("LGPL-3.0-or-later", true),
+    ("AGPL-3.0-or-later", true),
+    ("GFDL-1.2-or-later", true),
+    ("GFDL-1.3-only", true),
+    ("GFDL-1.3-or-later", true),
+    ("FAL-1.3", true),
+    ("OFL-1.1", true),
+];
+
+/// All accepted licences, keyed by lowercase identifier.
+static LICENSES: Lazy<HashMap<String, License>> = Lazy::new(|| {
+    let mut licenses = HashMap::new();
+    let mut add = |license: License| {
+        licenses.insert(license.id.to_ascii_lowercase(), license);
+    };
+
+    for version in CREATIVE_COMMONS_VERSIONS {
+        for (variant, non_commercial, no_derivatives) in CREATIVE_COMMONS_VARIANTS {
+            add(License {
+                id: format!("CC-{variant}-{version}"),
+                attribution: true,
+                non_commercial,
+                no_derivatives,
+            });
+        }
+    }
+
+    for (id, attribution) in OTHER_LICENSES {
+        add(License {
+            id: str!(id),
+            attribution,
+            non_commercial: false,
+            no_derivatives: false,
+        });
+    }
+
+    licenses
+});
+
+/// A licence which files or sites may be released under.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct License {
+    /// The canonical SPDX identifier for this licence.
+    pub id: String,
+
+    /// Whether the author must be credited when the work is used.
+    pub attribution: bool,
+
+    /// Whether commercial use of the work is forbidden.
+    pub non_commercial: bool,
+
+    /// Whether derivatives of the work are forbidden.
+    pub no_derivatives: bool,
+}
+
+impl License {
+    /// Looks up a licence by its SPDX identifier, ignoring case.
+    pub fn get(id: &str) -> Option<&'static License> {
+        LICENSES.get(&id.to_ascii_lowercase())
+    }
+
+    /// Looks up a licence, failing if it is not recognized.
+    pub fn get_known(id: &str) -> Result<&'static License> {
+        match Self::get(id) {
+            Some(license) => Ok(license),
+            None => {
+                error!("Unknown licence identifier '{id}'");
+                Err(Error::LicenseUnknown(str!(id)))
+            }
+        }
+    }
+
+    /// Whether a file under this licence can be hosted on a site under the given licence.
+    ///
+    /// This is the case if the file's licence has no restrictions beyond those the site
+    /// already places on its own content.
+    pub fn compatible_with(&self, site_license: &License) -> bool {
+        (!self.non_commercial || site_license.non_commercial)
+            && (!self.no_derivatives || site_license.no_derivatives)
+    }
+}
+
+/// Licensing information for a file revision.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct FileLicensing {
+    /// The SPDX identifier of the licence the file is released under.
+    ///
+    /// If `None`, the licence is unknown. Files uploaded as the user's own work
+    /// without a licence take the site's default licence instead.
+    pub license: Option<String>,
+
+    /// Who the file should be credited to.
+    pub author: Option<String>,
+
+    /// Where the file was originally obtained from.
+    pub source_url: Option<String>,
+
+    /// Whether the file was created by the user who uploaded it.
+    pub own_work: bool,
+
+    /// Whether the file is used under fair use, rather than under a licence.
+    pub fair_use: bool,
+}
+
+impl FileLicensing {
+    /// Validates and normalizes licensing information from the user.
+    ///
+    /// Licence identifiers are replaced with their canonical form,
+    /// and blank text fields are cleared.
+    pub fn validate(&mut self) -> Result<()> {
+        normalize_field(&mut self.author)?;
+        normalize_field(&mut self.source_url)?;
+
+        if let Some(ref mut id) = self.license {
+            *id = License::get_known(id.trim())?.id.clone();
+        }
+
+        if let Some(ref source_url) = self.source_url {
+            match Url::parse(source_url) {
+                Ok(url) if matches!(url.scheme(), "http" | "https") => (),
+                _ => {
+                    error!("Licensing source URL is not a valid web URL");
+                    return Err(Error::FileLicensingInvalid);
+                }
+            }
+        }
+
+        if self.own_work && self.fair_use {
+            error!("Licensing cannot be both own work and fair use");
+            return Err(Error::FileLicensingInvalid);
+        }
+
+        if self.fair_use {
+            // There is no licence for fair use, but the original must be identified
+            if self.license.is_some() || self.source_url.is_none() {
+                error!("Fair use licensing must have a source URL and no licence");
+                return Err(Error::FileLicensingInvalid);
+            }
+        }
+
+        if !self.own_work && self.author.is_none() {
+            if let Some(license) = self.license() {
+                if license.attribution {
+                    error!(
+                        "Licence {} requires attribution, but no author was given",
+                        license.id,
+                    );
+                    return Err(Error::FileLicensingInvalid);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reads stored licensing information, falling back for legacy values.
+    ///
+    /// Revisions from before licensing was structured may hold arbitrary JSON.
+    /// These keep their licence if it names a recognized one, and are otherwise
+    /// treated as having unknown licensing.
+    pub fn from_stored(value: JsonValue) -> Self {
+        if let Ok(licensing) = serde_json::from_value(value.clone()) {
+            return licensing;
+        }
+
+        warn!("Stored licensing is not in the current format, treating as legacy");
+        let license = value
+            .get("license")
+            .and_then(JsonValue::as_str)
+            .and_then(License::get)
+            .map(|license| license.id.clone());
+
+        FileLicensing {
+            license,
+            ..Default::default()
+        }
+    }
+
+    /// Fills in the site's default licence for the user's own work, if no licence was given.
+    pub fn apply_default(&mut self, default_license: Option<&str>) {
+        if self.own_work && self.license.is_none() {
+            self.license = default_license.map(String::from);
+        }
+    }
+
+    /// Gets the licence for this file, if one is set and recognized.
+    #[inline]
+    pub fn license(&self) -> Option<&'static License> {
+        self.license.as_deref().and_then(License::get)
+    }
+
+    /// Determines if there is any problem with this licensing, given the site's licence.
+    pub fn issue(&self, site_license: Option<&License>) -> Option<LicensingIssue> {
+        if self.fair_use {
+            return None;
+        }
+
+        let license = match self.license {
+            None => return Some(LicensingIssue::Missing),
+            Some(ref id) => match License::get(id) {
+                Some(license) => license,
+                None => return Some(LicensingIssue::Invalid),
+            },
+        };
+
+        match site_license {
+            Some(site_license) if !license.compatible_with(site_license) => {
+                Some(LicensingIssue::Incompatible {
+                    license: license.id.clone(),
+                    site_license: site_license.id.clone(),
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// A problem with the licensing of a file.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case", tag = "issue")]
+pub enum LicensingIssue {
+    /// No licence is recorded, and the file is not used under fair use.
+    Missing,
+
+    /// The recorded licensing is malformed or names an unknown licence.
+    Invalid,
+
+    /// The file's licence has restrictions which the site's licence does not.
+    Incompatible {
+        license: String,
+        site_license: String,
+    },
+}
+
+fn normalize_field(field: &mut Option<String>) -> Result<()> {
+    if let Some(value) = field {
+        let trimmed = value.trim();
+        if trimmed.is_empty() {
+            *field = None;
+            return Ok(());
+        }
+
+        if trimmed.len() > MAXIMUM_LICENSING_FIELD_LENGTH {
+            error!("Licensing field is too long ({} bytes)", trimmed.len());
+            return Err(Error::FileLicensingInvalid);
+        }
+
+        *value = str!(trimmed);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn licensing() {
+    use serde_json::json;
+
+    macro_rules! check {
+        ($licensing:expr, $valid:expr $(,)?) => {{
+            let mut licensing = $licensing;
+            assert_eq!(
+                licensing.validate().is_ok(),
+                $valid,
+                "Licensing validation did not match expected: {licensing:?}",
+            );
+        }};
+    }
+
+    let mut licensing = FileLicensing {
+        license: Some(str!("cc-by-sa-3.0")),
+        own_work: true,
+        ..Default::default()
+    };
+    licensing.validate().expect("Valid licensing failed");
+    assert_eq!(licensing.license.as_deref(), Some("CC-BY-SA-3.0"));
+
+    check!(FileLicensing::default(), true);
+    check!(
+        FileLicensing {
+            license: Some(str!("CC-BY-4.0")),
+            author: Some(str!("Anonymous")),
+            source_url: Some(str!("https://example.com/image.png")),
+            ..Default::default()
+        },
+        true,
+    );
+    check!(
+        FileLicensing {
+            license: Some(str!("CC-BY-4.0")),
+            ..Default::default()
+        },
+        false,
+    );
+    check!(
+        FileLicensing {
+            license: Some(str!("CC-BY-5.0")),
+            own_work: true,
+            ..Default::default()
+        },
+        false,
+    );
+    check!(
+        FileLicensing {
+            fair_use: true,
+            source_url: Some(str!("https://example.com/")),
+            ..Default::default()
+        },
+        true,
+    );
+    check!(
+        FileLicensing {
+            fair_use: true,
+            source_url: Some(str!("javascript:alert(1)")),
+            ..Default::default()
+        },
+        false,
+    );
+
+    let site_license = License::get("CC-BY-SA-3.0");
+    let non_commercial = FileLicensing {
+        license: Some(str!("CC-BY-NC-4.0")),
+        ..Default::default()
+    };
+    assert_eq!(
+        non_commercial.issue(site_license),
+        Some(LicensingIssue::Incompatible {
+            license: str!("CC-BY-NC-4.0"),
+            site_license: str!("CC-BY-SA-3.0"),
+        }),
+    );
+    assert_eq!(
+        FileLicensing::default().issue(site_license),
+        Some(LicensingIssue::Missing),
+    );
+
+    assert_eq!(
+        FileLicensing::from_stored(
+            json!({ "license": "cc-by-sa-3.0", "notes": "legacy" })
+        ),
+        FileLicensing {
+            license: Some(str!("CC-BY-SA-3.0")),
+            ..Default::default()
+        },
+    );
+    assert_eq!(
+        FileLicensing::from_stored(json!("Free-form licence text")),
+        FileLicensing::default(),
+    );
+}
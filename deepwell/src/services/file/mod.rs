@@ -24,8 +24,11 @@ mod prelude {
     pub use super::structs::*;
 }
 
+mod licensing;
 mod service;
 mod structs;
 
+pub use self::licensing::*;
+pub(crate) use self::service::check_file_name;
 pub use self::service::FileService;
 pub use self::structs::*;
@@ -18,6 +18,7 @@
  * along with this program. If not, see <http://www.gnu.org/licenses/>.
  */
 
+use super::licensing::{FileLicensing, License, LicensingIssue};
 use super::prelude::*;
 use crate::hash::slice_to_blob_hash;
 use crate::models::file::{self, Entity as File, Model as FileModel};
@@ -26,18 +27,22 @@ use crate::models::file_revision::{
 };
 use crate::models::sea_orm_active_enums::FileRevisionType;
 use crate::services::blob::{FinalizeBlobUploadOutput, EMPTY_BLOB_HASH, EMPTY_BLOB_MIME};
+use crate::services::category::CategoryAction;
 use crate::services::file_revision::{
     CreateFileRevision, CreateFileRevisionBody, CreateFirstFileRevision,
     CreateResurrectionFileRevision, CreateTombstoneFileRevision, FileBlob,
     GetFileRevision,
 };
 use crate::services::filter::{FilterClass, FilterType};
-use crate::services::{BlobService, FileRevisionService, FilterService, PageService};
+use crate::services::{
+    BlobService, FileRevisionService, FilterService, PageService, SiteService,
+};
 use crate::types::FileOrder;
 use crate::utils::regex_replace_in_place;
 use once_cell::sync::Lazy;
 use regex::Regex;
-use sea_orm::ActiveValue;
+use sea_orm::{ActiveValue, DatabaseBackend, FromQueryResult, Statement};
+use serde_json::Value as JsonValue;
 
 pub const MAXIMUM_FILE_NAME_LENGTH: usize = 256;
 
@@ -64,7 +69,7 @@ impl FileService {
             direct_upload,
             revision_comments,
             user_id,
-            licensing,
+            mut licensing,
             bypass_filter,
         }: CreateFile,
     ) -> Result<CreateFileOutput> {
@@ -74,8 +79,12 @@ impl FileService {
         // Verify filename is valid
         check_file_name(&mut name)?;
 
+        // Verify licensing is valid
+        Self::prepare_licensing(ctx, site_id, &mut licensing).await?;
+
         // Ensure row consistency
         Self::check_conflicts(ctx, page_id, &name, "create").await?;
+        Self::check_attach_permission(ctx, site_id, page_id, user_id).await?;
 
         // Perform filter validation
         if !bypass_filter {
@@ -147,6 +156,7 @@ impl FileService {
         info!("Editing file with ID {}", file_id);
 
         let txn = ctx.transaction();
+        Self::check_attach_permission(ctx, site_id, page_id, user_id).await?;
         let last_revision =
             FileRevisionService::get_latest(ctx, site_id, page_id, file_id).await?;
 
@@ -154,11 +164,16 @@ impl FileService {
 
         let EditFileBody {
             mut name,
-            licensing,
+            mut licensing,
             uploaded_blob_id,
             direct_upload,
         } = body;
 
+        // Verify licensing change
+        if let Maybe::Set(ref mut licensing) = licensing {
+            Self::prepare_licensing(ctx, site_id, licensing).await?;
+        }
+
         let mut new_name = ActiveValue::NotSet;
 
         // Verify name change
@@ -279,6 +294,8 @@ impl FileService {
 
         // Ensure there isn't a file with this name on the destination page
         Self::check_conflicts(ctx, destination_page_id, &name, "move").await?;
+        Self::check_attach_permission(ctx, site_id, current_page_id, user_id).await?;
+        Self::check_attach_permission(ctx, site_id, destination_page_id, user_id).await?;
 
         // Update file metadata
         let model = file::ActiveModel {
@@ -432,6 +449,8 @@ impl FileService {
             return Err(Error::FileNotFound);
         }
 
+        Self::check_attach_permission(ctx, site_id, new_page_id, user_id).await?;
+
         if file.deleted_at.is_none() {
             warn!("File requested to be restored is not currently deleted");
             return Err(Error::FileNotDeleted);
@@ -568,7 +587,7 @@ impl FileService {
             body: CreateFileRevisionBody {
                 name: Maybe::Set(name),
                 blob: Maybe::Set(blob),
-                licensing: Maybe::Set(licensing),
+                licensing: Maybe::Set(FileLicensing::from_stored(licensing)),
                 page_id: Maybe::Unset, // rollbacks should never move files
             },
         };
@@ -724,6 +743,111 @@ impl FileService {
         find_or_error!(Self::get_direct_optional(ctx, file_id, allow_deleted), File)
     }
 
+    /// Finds all files on a site whose licensing is missing, invalid, or incompatible.
+    ///
+    /// Only the latest revision of each extant file is checked. Incompatibility is
+    /// determined against the site's default licence, so if the site does not have one,
+    /// only missing or invalid licensing is reported.
+    pub async fn get_licensing_issues(
+        ctx: &ServiceContext<'_>,
+        GetFileLicensingIssues { site_id }: GetFileLicensingIssues,
+    ) -> Result<Vec<FileLicensingIssue>> {
+        info!("Getting files with licensing issues in site ID {site_id}");
+
+        #[derive(Debug, FromQueryResult)]
+        struct LatestRevision {
+            file_id: i64,
+            page_id: i64,
+            revision_id: i64,
+            name: String,
+            licensing: JsonValue,
+        }
+
+        let site = SiteService::get_direct(ctx, site_id, false).await?;
+        let site_license = match site.default_license {
+            Some(ref id) => License::get(id),
+            None => None,
+        };
+
+        let txn = ctx.transaction();
+        let revisions =
+            LatestRevision::find_by_statement(Statement::from_sql_and_values(
+                DatabaseBackend::Postgres,
+                r#"
+                SELECT DISTINCT ON (file_revision.file_id)
+                    file_revision.file_id,
+                    file_revision.page_id,
+                    file_revision.revision_id,
+                    file_revision.name,
+                    file_revision.licensing
+                FROM file_revision
+                JOIN file ON file.file_id = file_revision.file_id
+                WHERE file_revision.site_id = $1
+                AND file.deleted_at IS NULL
+                ORDER BY file_revision.file_id, file_revision.revision_number DESC
+                "#,
+                [site_id.into()],
+            ))
+            .all(txn)
+            .await?;
+
+        let issues = revisions
+            .into_iter()
+            .filter_map(|revision| {
+                // Malformed licensing data is itself an issue
+                let issue =
+                    match serde_json::from_value::<FileLicensing>(revision.licensing) {
+                        Ok(licensing) => licensing.issue(site_license)?,
+                        Err(_) => LicensingIssue::Invalid,
+                    };
+
+                Some(FileLicensingIssue {
+                    file_id: revision.file_id,
+                    page_id: revision.page_id,
+                    revision_id: revision.revision_id,
+                    name: revision.name,
+                    issue,
+                })
+            })
+            .collect();
+
+        Ok(issues)
+    }
+
+    /// Validates licensing for a new file revision, applying the site's default licence.
+    async fn prepare_licensing(
+        ctx: &ServiceContext<'_>,
+        site_id: i64,
+        licensing: &mut FileLicensing,
+    ) -> Result<()> {
+        licensing.validate()?;
+
+        if licensing.own_work && licensing.license.is_none() {
+            let site = SiteService::get_direct(ctx, site_id, false).await?;
+            licensing.apply_default(site.default_license.as_deref());
+        }
+
+        Ok(())
+    }
+
+    /// Ensures that the user may attach files to this page, per its category.
+    async fn check_attach_permission(
+        ctx: &ServiceContext<'_>,
+        site_id: i64,
+        page_id: i64,
+        user_id: i64,
+    ) -> Result<()> {
+        let page = PageService::get_direct(ctx, page_id, false).await?;
+        PageService::check_category_permission(
+            ctx,
+            site_id,
+            page.page_category_id,
+            user_id,
+            CategoryAction::AttachFiles,
+        )
+        .await
+    }
+
     /// Checks to see if a file already exists at the name specified.
     ///
     /// If so, this method fails with `Error::FileExists`. Otherwise it returns nothing.
@@ -789,7 +913,7 @@ impl FileService {
 /// This helper function is generally read-only, but if
 /// it finds a name which has leading or trailing whitespace,
 /// then it trims that off in-place.
-fn check_file_name(name: &mut String) -> Result<()> {
+pub(crate) fn check_file_name(name: &mut String) -> Result<()> {
     // Removes leading or trailing whitespace
     regex_replace_in_place(name, &LEADING_TRAILING_SPACES, "");
     debug!("Trimmed file name: '{name}'");
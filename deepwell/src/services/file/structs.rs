@@ -18,12 +18,12 @@
  * along with this program. If not, see <http://www.gnu.org/licenses/>.
  */
 
+use super::licensing::{FileLicensing, LicensingIssue};
 use crate::models::sea_orm_active_enums::FileRevisionType;
 use crate::services::file_revision::{
     CreateFileRevisionOutput, CreateFirstFileRevisionOutput,
 };
 use crate::types::{Bytes, FileDetails, Maybe, Reference};
-use serde_json::Value as JsonValue;
 use time::OffsetDateTime;
 
 #[derive(Deserialize, Debug, Clone)]
@@ -34,7 +34,9 @@ pub struct CreateFile {
     pub uploaded_blob_id: String,
     pub revision_comments: String,
     pub user_id: i64,
-    pub licensing: JsonValue, // TODO
+
+    #[serde(default)]
+    pub licensing: FileLicensing,
 
     #[serde(default)]
     pub bypass_filter: bool,
@@ -87,9 +89,10 @@ pub struct GetFileOutput {
     pub revision_user_id: i64,
     pub name: String,
     pub data: Option<Bytes<'static>>,
+    pub download_url: String,
     pub mime: String,
     pub size: i64,
-    pub licensing: JsonValue,
+    pub licensing: FileLicensing,
     pub revision_comments: String,
     pub hidden_fields: Vec<String>,
 }
@@ -121,7 +124,7 @@ pub struct EditFile {
 #[serde(default)]
 pub struct EditFileBody {
     pub name: Maybe<String>,
-    pub licensing: Maybe<serde_json::Value>,
+    pub licensing: Maybe<FileLicensing>,
     pub uploaded_blob_id: Maybe<String>,
 
     /// Allows internal users to upload directly.
@@ -199,3 +202,19 @@ pub struct RollbackFile<'a> {
     #[serde(default)]
     pub bypass_filter: bool,
 }
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct GetFileLicensingIssues {
+    pub site_id: i64,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct FileLicensingIssue {
+    pub file_id: i64,
+    pub page_id: i64,
+    pub revision_id: i64,
+    pub name: String,
+
+    #[serde(flatten)]
+    pub issue: LicensingIssue,
+}
@@ -25,7 +25,7 @@ use crate::models::file_revision::{
 };
 use crate::models::{file, page, site};
 use crate::services::blob::{FinalizeBlobUploadOutput, EMPTY_BLOB_HASH, EMPTY_BLOB_MIME};
-use crate::services::{BlobService, OutdateService, PageService};
+use crate::services::{BlobService, OutdateService, PageService, StorageService};
 use crate::types::{Bytes, FetchDirection};
 use once_cell::sync::Lazy;
 use sea_orm::{prelude::*, FromQueryResult};
@@ -128,6 +128,7 @@ impl FileRevisionService {
         }
 
         if let Maybe::Set(new_licensing) = body.licensing {
+            let new_licensing = serde_json::to_value(new_licensing)?;
             if licensing != new_licensing {
                 changes.push(str!("licensing"));
                 licensing = new_licensing;
@@ -149,7 +150,12 @@ impl FileRevisionService {
             return Err(Error::FileMimeEmpty);
         }
 
-        // TODO validate licensing field
+        // Licensing is validated in FileService, before finishing the upload
+
+        // Charge new blob to storage usage, refusing if over quota
+        if changes.iter().any(|change| change == "blob") {
+            StorageService::charge_blob(ctx, site_id, user_id, &s3_hash, size).await?;
+        }
 
         // Run outdater
         let page_slug = Self::get_page_slug(ctx, site_id, page_id).await?;
@@ -203,6 +209,9 @@ impl FileRevisionService {
     ) -> Result<CreateFirstFileRevisionOutput> {
         let txn = ctx.transaction();
 
+        // Charge blob to storage usage, refusing if over quota
+        StorageService::charge_blob(ctx, site_id, user_id, &s3_hash, size).await?;
+
         // Run outdater
         let page_slug = Self::get_page_slug(ctx, site_id, page_id).await?;
         OutdateService::process_page_displace(ctx, site_id, page_id, &page_slug, 0)
@@ -220,7 +229,7 @@ impl FileRevisionService {
             s3_hash: Set(s3_hash.to_vec()),
             mime: Set(mime),
             size: Set(size),
-            licensing: Set(licensing),
+            licensing: Set(serde_json::to_value(licensing)?),
             changes: Set(ALL_CHANGES.clone()),
             comments: Set(revision_comments),
             hidden: Set(vec![]),
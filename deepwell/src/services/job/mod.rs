@@ -22,6 +22,8 @@
 //!
 //! At present we do not use a separate service which stores jobs durably. This
 //! can mean that if this DEEPWELL node fails, the queued jobs will not be run.
+//!
+//! Periodic jobs are not queued, but run by the scheduler (see `scheduler.rs`).
 
 #[allow(unused_imports)]
 mod prelude {
@@ -33,10 +35,12 @@ mod prelude {
     pub use super::structs::*;
 }
 
+mod scheduler;
 mod service;
 mod structs;
 mod worker;
 
+pub use self::scheduler::JobScheduler;
 pub use self::service::*;
 pub use self::structs::*;
 pub use self::worker::JobWorker;
@@ -0,0 +1,324 @@
+/*
+ * services/job/scheduler.rs
+ *
+ * DEEPWELL - Wikijump API provider and database manager
+ * Copyright (C) 2019-2025 Wikijump Team
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Runs periodic jobs according to their configured schedules.
+//!
+//! Each DEEPWELL instance runs a scheduler, and they coordinate through Redis.
+//! The state for each periodic job (when it last ran, its outcome, and when it
+//! is next due) is stored there. When a job is due, each scheduler attempts to
+//! claim it by atomically advancing its next run time, and only the one which
+//! succeeds runs it. Since the tick is claimed and advanced in one step, an
+//! instance which dies while running a job does not hold up later runs.
+//!
+//! Because the next run time is derived from the schedule rather than from the
+//! job itself, a failed run does not stop future runs, and starting more
+//! instances does not cause more runs.
+
+use super::prelude::*;
+use super::worker::{JobWorker, NextJob};
+use crate::api::ServerState;
+use crate::services::ViewCacheService;
+use crate::types::Schedule;
+use once_cell::sync::Lazy;
+use redis::aio::MultiplexedConnection as RedisMultiplexedConnection;
+use redis::{AsyncCommands, Script};
+use sea_orm::TransactionTrait;
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::time;
+
+/// Atomically replaces a periodic job's status, if it has not changed.
+///
+/// Arguments are the status as it was read, and the status to replace it with.
+/// Returns 1 if the status was replaced, and 0 if another scheduler changed it first.
+static CLAIM_TICK_SCRIPT: Lazy<Script> = Lazy::new(|| {
+    Script::new(
+        r#"
+if redis.call('GET', KEYS[1]) ~= ARGV[1] then
+    return 0
+end
+
+redis.call('SET', KEYS[1], ARGV[2])
+return 1
+"#,
+    )
+});
+
+/// A job which is run periodically, rather than being queued.
+#[derive(Debug)]
+struct PeriodicJob<'a> {
+    name: &'static str,
+    job: Job,
+    schedule: &'a Schedule,
+}
+
+/// All the periodic jobs, and their schedules.
+fn periodic_jobs(config: &Config) -> [PeriodicJob<'_>; 9] {
+    [
+        PeriodicJob {
+            name: "prune-sessions",
+            job: Job::PruneSessions,
+            schedule: &config.job_prune_session,
+        },
+        PeriodicJob {
+            name: "prune-text",
+            job: Job::PruneText,
+            schedule: &config.job_prune_text,
+        },
+        PeriodicJob {
+            name: "prune-uploads",
+            job: Job::PruneUploads,
+            schedule: &config.job_prune_uploads,
+        },
+        PeriodicJob {
+            name: "prune-exports",
+            job: Job::PruneUserExports,
+            schedule: &config.job_prune_exports,
+        },
+        PeriodicJob {
+            name: "name-change-refill",
+            job: Job::NameChangeRefill,
+            schedule: &config.job_name_change_refill,
+        },
+        PeriodicJob {
+            name: "lift-expired-punishments",
+            job: Job::LiftExpiredPunishments,
+            schedule: &config.job_lift_expired_punishments,
+        },
+        PeriodicJob {
+            name: "verify-custom-domains",
+            job: Job::VerifyCustomDomains,
+            schedule: &config.job_verify_custom_domains,
+        },
+        PeriodicJob {
+            name: "collect-orphaned-blobs",
+            job: Job::CollectOrphanedBlobs,
+            schedule: &config.job_collect_orphaned_blobs,
+        },
+        PeriodicJob {
+            name: "scrub-storage",
+            job: Job::StartStorageScrub,
+            schedule: &config.job_scrub_storage,
+        },
+    ]
+}
+
+#[derive(Debug)]
+pub struct JobScheduler {
+    state: ServerState,
+}
+
+impl JobScheduler {
+    /// Spawns the periodic job scheduler for this instance.
+    pub fn spawn(state: &ServerState) {
+        info!("Spawning periodic job scheduler");
+        let state = Arc::clone(state);
+        let scheduler = JobScheduler { state };
+        tokio::spawn(scheduler.main_loop());
+    }
+
+    async fn main_loop(self) -> Infallible {
+        loop {
+            for periodic_job in periodic_jobs(&self.state.config) {
+                if let Err(error) = self.check_job(&periodic_job).await {
+                    error!(
+                        "Error while checking periodic job '{}': {error}",
+                        periodic_job.name,
+                    );
+                }
+            }
+
+            time::sleep(self.state.config.job_scheduler_poll).await;
+        }
+    }
+
+    /// Runs the given periodic job, if it is due and no other instance has.
+    async fn check_job(&self, periodic_job: &PeriodicJob<'_>) -> Result<()> {
+        let PeriodicJob {
+            name,
+            ref job,
+            schedule,
+        } = *periodic_job;
+
+        let mut redis = self.state.redis.get_multiplexed_tokio_connection().await?;
+        let schedule_text = schedule.to_string();
+        let now = now();
+
+        // Get current state, setting it up if this is new or the schedule changed
+        let (status, status_data) = match get_status(&mut redis, name).await? {
+            Some((status, data)) if status.schedule == schedule_text => (status, data),
+            previous => {
+                let previous = previous.map(|(status, _)| status);
+                let status = PeriodicJobStatus {
+                    name: str!(name),
+                    schedule: schedule_text,
+                    next_run: schedule.next_after(now),
+                    last_run: previous.as_ref().and_then(|status| status.last_run),
+                    last_duration_ms: previous
+                        .as_ref()
+                        .and_then(|status| status.last_duration_ms),
+                    last_outcome: previous.and_then(|status| status.last_outcome),
+                };
+
+                debug!("Scheduling periodic job '{name}' for {:?}", status.next_run);
+                set_status(&mut redis, &status).await?;
+                return Ok(());
+            }
+        };
+
+        let due = match status.next_run {
+            Some(next_run) if now >= next_run => next_run,
+            Some(_) => {
+                trace!("Periodic job '{name}' is not due yet");
+                return Ok(());
+            }
+            None => {
+                trace!("Periodic job '{name}' has no future runs");
+                return Ok(());
+            }
+        };
+
+        // Claim this tick, advancing to the next one
+        let claimed_status = PeriodicJobStatus {
+            next_run: schedule.next_after(now),
+            ..status
+        };
+        let claimed: i32 = CLAIM_TICK_SCRIPT
+            .key(status_key(name))
+            .arg(status_data)
+            .arg(serde_json::to_string(&claimed_status)?)
+            .invoke_async(&mut redis)
+            .await?;
+
+        if claimed == 0 {
+            debug!("Periodic job '{name}' is being run by another instance");
+            return Ok(());
+        }
+
+        debug!("Claimed periodic job '{name}' for {due:?}");
+
+        // Run the job
+        info!("Running periodic job '{name}'");
+        let start = Instant::now();
+        let result = self.run_job(job.clone()).await;
+        let duration = start.elapsed();
+
+        let last_outcome = match result {
+            Ok(()) => {
+                info!(
+                    "Periodic job '{name}' finished in {} ms",
+                    duration.as_millis(),
+                );
+                PeriodicJobOutcome::Success
+            }
+            Err(error) => {
+                error!("Periodic job '{name}' failed: {error}");
+                PeriodicJobOutcome::Failure {
+                    message: error.to_string(),
+                }
+            }
+        };
+
+        // Record outcome, keeping the next run time as it is now.
+        //
+        // If the job took longer than its interval, another instance may have
+        // claimed the next tick already, so the status is read again.
+        let status = match get_status(&mut redis, name).await? {
+            Some((status, _)) => status,
+            None => claimed_status,
+        };
+        let status = PeriodicJobStatus {
+            last_run: Some(now),
+            last_duration_ms: Some(
+                u64::try_from(duration.as_millis()).unwrap_or(u64::MAX),
+            ),
+            last_outcome: Some(last_outcome),
+            ..status
+        };
+
+        set_status(&mut redis, &status).await?;
+        Ok(())
+    }
+
+    async fn run_job(&self, job: Job) -> Result<()> {
+        let txn = self.state.database.begin().await?;
+        let ctx = &ServiceContext::new(&self.state, &txn);
+
+        match JobWorker::execute(ctx, job).await? {
+            NextJob::Done => (),
+            NextJob::Next { job, delay } => {
+                JobService::queue_job(ctx, &job, delay).await?
+            }
+        }
+
+        let invalidations = ctx.take_view_cache_invalidations();
+        txn.commit().await?;
+        ViewCacheService::run_invalidations(&self.state, invalidations).await;
+        Ok(())
+    }
+
+    /// Gets the current state of all periodic jobs.
+    ///
+    /// If a periodic job has not yet been scheduled, it is omitted.
+    pub async fn get_all_status(
+        ctx: &ServiceContext<'_>,
+    ) -> Result<Vec<PeriodicJobStatus>> {
+        let mut redis = ctx.redis_connect().await?;
+        let mut statuses = Vec::new();
+
+        for PeriodicJob { name, .. } in periodic_jobs(ctx.config()) {
+            if let Some((status, _)) = get_status(&mut redis, name).await? {
+                statuses.push(status);
+            }
+        }
+
+        Ok(statuses)
+    }
+}
+
+#[inline]
+fn status_key(name: &str) -> String {
+    format!("schedule:{name}")
+}
+
+/// Gets the status of a periodic job, along with its serialized form in Redis.
+async fn get_status(
+    redis: &mut RedisMultiplexedConnection,
+    name: &str,
+) -> Result<Option<(PeriodicJobStatus, String)>> {
+    let data: Option<String> = redis.get(status_key(name)).await?;
+    match data {
+        Some(data) => Ok(Some((serde_json::from_str(&data)?, data))),
+        None => Ok(None),
+    }
+}
+
+async fn set_status(
+    redis: &mut RedisMultiplexedConnection,
+    status: &PeriodicJobStatus,
+) -> Result<()> {
+    let data = serde_json::to_string(status)?;
+    redis
+        .set::<_, _, ()>(status_key(&status.name), data)
+        .await?;
+
+    Ok(())
+}
@@ -19,7 +19,13 @@
  */
 
 use super::prelude::*;
+use crate::api::ServerState;
+use crate::models::page::{self, Entity as Page};
+use crate::models::page_revision;
+use ftml::info::VERSION as FTML_VERSION;
+use redis::{AsyncCommands, ExistenceCheck, SetExpiry, SetOptions};
 use rsmq_async::RsmqConnection;
+use sea_orm::{Select, TransactionTrait};
 use std::time::Duration;
 
 pub const JOB_QUEUE_NAME: &str = "job";
@@ -41,14 +47,38 @@ pub const JOB_QUEUE_DELAY: Option<Duration> = None;
 
 /// The maximum size, in bytes, that a job payload is allowed to be
 ///
-/// Presently, our jobs are mostly unit types, and the biggest variant
-/// is composed of three integers, so this is more than large enough.
+/// Presently, our jobs are mostly unit types, and the biggest variants
+/// are composed of a few integers, so this is more than large enough.
 /// If larger jobs become a thing in the future, this may need to be updated.
 ///
 /// (But as a general code principle there shouldn't be huge jobs, they should
 /// just use IDs and references to items in the database.)
 pub const JOB_QUEUE_MAXIMUM_SIZE: Option<i32> = Some(1024);
 
+/// The Redis key storing which version of ftml pages were last mass rerendered for.
+const FTML_VERSION_KEY: &str = "ftml-version";
+
+/// The Redis key prefix for the lock held while starting a rerender for an ftml version.
+const FTML_VERSION_LOCK_PREFIX: &str = "ftml-version-lock:";
+
+/// How long, in seconds, the ftml version lock is held before another attempt is allowed.
+const FTML_VERSION_LOCK_EXPIRY_SECS: usize = 10 * 60; // 10 minutes
+
+/// The job depth used for pages queued by a mass rerender.
+///
+/// These are top-level rerenders, like a manual rerender, so the
+/// rerender-skip rules (which target cascading rerenders) do not apply.
+pub const MASS_RERENDER_DEPTH: u32 = 0;
+
+/// How long, in seconds, progress for a mass rerender is retained in Redis.
+const RERENDER_PROGRESS_EXPIRY_SECS: u64 = 7 * 24 * 60 * 60; // 1 week
+
+/// How long, in seconds, a mass rerender lock lasts past the next expected batch.
+///
+/// The lock is renewed with each batch, so this only matters if the batch
+/// jobs are lost, after which another rerender for the scope may be started.
+const RERENDER_LOCK_EXPIRY_SECS: usize = 10 * 60; // 10 minutes
+
 #[derive(Debug)]
 pub struct JobService;
 
@@ -94,4 +124,234 @@ impl JobService {
         )
         .await
     }
+
+    /// Checks if ftml has been upgraded since this was last run.
+    ///
+    /// If so, begins a mass rerender of all pages whose latest revision was
+    /// rendered with a different version. A per-version lock ensures only one
+    /// instance will start the rerender, and the stored version is only updated
+    /// once the rerender has been committed.
+    pub async fn check_ftml_version(state: &ServerState) -> Result<()> {
+        let txn = state.database.begin().await?;
+        let ctx = &ServiceContext::new(state, &txn);
+
+        let mut redis = ctx.redis_connect().await?;
+        let previous: Option<String> = redis.get(FTML_VERSION_KEY).await?;
+        if previous.as_deref() == Some(FTML_VERSION.as_str()) {
+            debug!("ftml version unchanged, no rerender needed");
+            return Ok(());
+        }
+
+        let lock_key = format!("{FTML_VERSION_LOCK_PREFIX}{}", FTML_VERSION.as_str());
+        let options = SetOptions::default()
+            .conditional_set(ExistenceCheck::NX)
+            .with_expiration(SetExpiry::EX(FTML_VERSION_LOCK_EXPIRY_SECS));
+
+        let acquired: Option<String> = redis.set_options(&lock_key, 1, options).await?;
+        if acquired.is_none() {
+            debug!("ftml version rerender already being started by another instance");
+            return Ok(());
+        }
+
+        info!(
+            "ftml version changed ({} -> {}), checking for outdated pages",
+            previous.as_deref().unwrap_or("<none>"),
+            FTML_VERSION.as_str(),
+        );
+
+        let progress = Self::start_rerender(ctx, RerenderScope::Outdated).await?;
+        txn.commit().await?;
+
+        redis
+            .set::<_, _, ()>(FTML_VERSION_KEY, FTML_VERSION.as_str())
+            .await?;
+
+        info!("Found {} pages rendered with an older ftml", progress.total);
+        Ok(())
+    }
+
+    /// Begins a mass rerender of all pages in the given scope.
+    ///
+    /// Pages are not queued immediately, instead a job is queued which
+    /// adds them to the queue in throttled batches.
+    ///
+    /// Only one mass rerender may run for a scope at a time, otherwise
+    /// each would queue its own copy of every batch.
+    pub async fn start_rerender(
+        ctx: &ServiceContext<'_>,
+        scope: RerenderScope,
+    ) -> Result<RerenderProgress> {
+        let mut redis = ctx.redis_connect().await?;
+        let options = SetOptions::default()
+            .conditional_set(ExistenceCheck::NX)
+            .with_expiration(SetExpiry::EX(Self::rerender_lock_expiry(ctx)));
+
+        let acquired: Option<String> =
+            redis.set_options(scope.lock_key(), 1, options).await?;
+        if acquired.is_none() {
+            warn!("Mass rerender for {scope:?} is already running");
+            return Err(Error::RerenderExists);
+        }
+
+        let txn = ctx.transaction();
+        let total = Self::rerender_query(scope).count(txn).await?;
+        info!("Starting mass rerender for {scope:?} ({total} pages)");
+
+        let now = now();
+        let progress = RerenderProgress {
+            scope,
+            total,
+            queued: 0,
+            last_page_id: 0,
+            finished: total == 0,
+            started_at: now,
+            updated_at: now,
+        };
+
+        Self::set_rerender_progress(ctx, &progress).await?;
+
+        if total == 0 {
+            redis.del::<_, ()>(scope.lock_key()).await?;
+        } else {
+            Self::queue_job(
+                ctx,
+                &Job::RerenderBatch {
+                    scope,
+                    after_page_id: 0,
+                },
+                None,
+            )
+            .await?;
+        }
+
+        Ok(progress)
+    }
+
+    /// Queues the next batch of pages for a mass rerender.
+    ///
+    /// Returns the last page ID queued if there are more pages remaining,
+    /// or `None` if the mass rerender has finished.
+    pub async fn rerender_batch(
+        ctx: &ServiceContext<'_>,
+        scope: RerenderScope,
+        after_page_id: i64,
+    ) -> Result<Option<i64>> {
+        let txn = ctx.transaction();
+        let batch_size = u64::from(ctx.config().job_rerender_batch_size.get());
+        let pages: Vec<(i64, i64)> = Self::rerender_query(scope)
+            .filter(page::Column::PageId.gt(after_page_id))
+            .select_only()
+            .column(page::Column::SiteId)
+            .column(page::Column::PageId)
+            .order_by_asc(page::Column::PageId)
+            .limit(batch_size)
+            .into_tuple()
+            .all(txn)
+            .await?;
+
+        for &(site_id, page_id) in &pages {
+            Self::queue_rerender_page(ctx, site_id, page_id, MASS_RERENDER_DEPTH).await?;
+        }
+
+        let finished = (pages.len() as u64) < batch_size;
+        let last_page_id = pages.last().map(|&(_, page_id)| page_id);
+
+        // Update progress
+        //
+        // If it was expired or removed, we start counting again from here.
+        let mut progress = match Self::get_rerender_progress(ctx, scope).await? {
+            Some(progress) => progress,
+            None => RerenderProgress {
+                scope,
+                total: 0,
+                queued: 0,
+                last_page_id: after_page_id,
+                finished: false,
+                started_at: now(),
+                updated_at: now(),
+            },
+        };
+
+        progress.queued += pages.len() as u64;
+        progress.last_page_id = last_page_id.unwrap_or(after_page_id);
+        progress.finished = finished;
+        progress.updated_at = now();
+
+        info!(
+            "Mass rerender for {scope:?}: queued {} / {} pages",
+            progress.queued, progress.total,
+        );
+
+        Self::set_rerender_progress(ctx, &progress).await?;
+
+        // Release the lock once done, or keep holding it until the next batch
+        let mut redis = ctx.redis_connect().await?;
+        if finished {
+            redis.del::<_, ()>(scope.lock_key()).await?;
+            Ok(None)
+        } else {
+            let expiry =
+                i64::try_from(Self::rerender_lock_expiry(ctx)).unwrap_or(i64::MAX);
+            redis.expire::<_, ()>(scope.lock_key(), expiry).await?;
+            Ok(last_page_id)
+        }
+    }
+
+    pub async fn get_rerender_progress(
+        ctx: &ServiceContext<'_>,
+        scope: RerenderScope,
+    ) -> Result<Option<RerenderProgress>> {
+        let mut redis = ctx.redis_connect().await?;
+        let data: Option<String> = redis.get(scope.progress_key()).await?;
+        match data {
+            Some(data) => Ok(Some(serde_json::from_str(&data)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn set_rerender_progress(
+        ctx: &ServiceContext<'_>,
+        progress: &RerenderProgress,
+    ) -> Result<()> {
+        let mut redis = ctx.redis_connect().await?;
+        let data = serde_json::to_string(progress)?;
+        redis
+            .set_ex::<_, _, ()>(
+                progress.scope.progress_key(),
+                data,
+                RERENDER_PROGRESS_EXPIRY_SECS,
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// How long, in seconds, a mass rerender lock is held before the next batch must renew it.
+    fn rerender_lock_expiry(ctx: &ServiceContext<'_>) -> usize {
+        let batch_delay = ctx.config().job_rerender_batch_delay.as_secs();
+        RERENDER_LOCK_EXPIRY_SECS
+            .saturating_add(usize::try_from(batch_delay).unwrap_or(usize::MAX))
+    }
+
+    /// Builds a query for all the non-deleted pages within a rerender scope.
+    fn rerender_query(scope: RerenderScope) -> Select<Page> {
+        let condition = Condition::all().add(page::Column::DeletedAt.is_null());
+        let condition = match scope {
+            RerenderScope::Outdated => condition
+                .add(page_revision::Column::CompiledGenerator.ne(FTML_VERSION.as_str())),
+            RerenderScope::Site { site_id } => {
+                condition.add(page::Column::SiteId.eq(site_id))
+            }
+            RerenderScope::Category {
+                site_id,
+                category_id,
+            } => condition
+                .add(page::Column::SiteId.eq(site_id))
+                .add(page::Column::PageCategoryId.eq(category_id)),
+        };
+
+        Page::find()
+            .join(JoinType::InnerJoin, page::Relation::PageRevision.def())
+            .filter(condition)
+    }
 }
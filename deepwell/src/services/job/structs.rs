@@ -18,6 +18,9 @@
  * along with this program. If not, see <http://www.gnu.org/licenses/>.
  */
 
+use crate::services::scrub::ScrubPhase;
+use time::OffsetDateTime;
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "snake_case", tag = "job", content = "data")]
 pub enum Job {
@@ -26,9 +29,111 @@ pub enum Job {
         page_id: i64,
         depth: u32,
     },
+    RerenderBatch {
+        scope: RerenderScope,
+        after_page_id: i64,
+    },
     PruneSessions,
     PruneText,
-    // TODO add job for pruning incomplete uploads (pending_blob table and corresponding columns for foreign keys)
+    PruneUploads,
+    PruneUserExports,
     NameChangeRefill,
     LiftExpiredPunishments,
+    SendInvitationEmail {
+        invitation_id: i64,
+    },
+    ExportUserData {
+        user_id: i64,
+        export_id: String,
+    },
+    VerifyCustomDomain {
+        domain: String,
+    },
+    VerifyCustomDomains,
+    CollectOrphanedBlobs,
+    RecompressTextBatch {
+        after_hash: String,
+    },
+    StartStorageScrub,
+    StorageScrubBatch {
+        scrub_id: String,
+        phase: ScrubPhase,
+    },
+}
+
+/// Which pages a mass rerender applies to.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, Hash, PartialEq, Eq)]
+#[serde(rename_all = "snake_case", tag = "scope")]
+pub enum RerenderScope {
+    /// All pages whose latest revision was rendered by a different version of ftml.
+    Outdated,
+
+    /// All pages in a site.
+    Site { site_id: i64 },
+
+    /// All pages in a category.
+    Category { site_id: i64, category_id: i64 },
+}
+
+impl RerenderScope {
+    /// The Redis key used to store progress for a mass rerender with this scope.
+    pub fn progress_key(self) -> String {
+        match self {
+            RerenderScope::Outdated => str!("rerender-progress:outdated"),
+            RerenderScope::Site { site_id } => {
+                format!("rerender-progress:site:{site_id}")
+            }
+            RerenderScope::Category { category_id, .. } => {
+                format!("rerender-progress:category:{category_id}")
+            }
+        }
+    }
+
+    /// The Redis key used to lock a mass rerender with this scope while it is running.
+    pub fn lock_key(self) -> String {
+        match self {
+            RerenderScope::Outdated => str!("rerender-lock:outdated"),
+            RerenderScope::Site { site_id } => format!("rerender-lock:site:{site_id}"),
+            RerenderScope::Category { category_id, .. } => {
+                format!("rerender-lock:category:{category_id}")
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RerenderProgress {
+    pub scope: RerenderScope,
+    pub total: u64,
+    pub queued: u64,
+    pub last_page_id: i64,
+    pub finished: bool,
+
+    #[serde(with = "time::serde::rfc3339")]
+    pub started_at: OffsetDateTime,
+
+    #[serde(with = "time::serde::rfc3339")]
+    pub updated_at: OffsetDateTime,
+}
+
+/// The state of a periodic job, as tracked by the scheduler.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PeriodicJobStatus {
+    pub name: String,
+    pub schedule: String,
+
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub next_run: Option<OffsetDateTime>,
+
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub last_run: Option<OffsetDateTime>,
+    pub last_duration_ms: Option<u64>,
+    pub last_outcome: Option<PeriodicJobOutcome>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "snake_case", tag = "result")]
+pub enum PeriodicJobOutcome {
+    Success,
+    Failure { message: String },
 }
@@ -22,7 +22,11 @@
 
 use super::prelude::*;
 use crate::api::ServerState;
-use crate::services::{PageRevisionService, SessionService, TextService, UserService};
+use crate::services::render::RenderPriority;
+use crate::services::{
+    BlobService, DomainService, MembershipService, PageRevisionService, ScrubService,
+    SessionService, TextService, UserDataService, UserService, ViewCacheService,
+};
 use crate::utils::debug_pointer;
 use rsmq_async::{PooledRsmq, RsmqConnection, RsmqMessage};
 use sea_orm::TransactionTrait;
@@ -41,7 +45,7 @@ enum JobProcessStatus {
 
 /// Used to queue a follow-up job, if needed.
 #[derive(Debug)]
-enum NextJob {
+pub(super) enum NextJob {
     Next { job: Job, delay: Option<Duration> },
     Done,
 }
@@ -174,6 +178,40 @@ impl JobWorker {
         let ctx = &ServiceContext::new(&self.state, &txn);
 
         trace!("Beginning job processing");
+        let next = Self::execute(ctx, job).await?;
+
+        // Don't delete more than once
+        //
+        // NOTE: We're only at this point if the job succeeded.
+        if !no_more_retries {
+            trace!("Job execution finished, deleting message");
+            self.rsmq.delete_message(JOB_QUEUE_NAME, &data.id).await?;
+        }
+
+        // Add follow-up job to queue, if required.
+        match next {
+            NextJob::Done => debug!("Job execution finished, no follow-up job to add"),
+            NextJob::Next { job, delay } => {
+                debug!("Job execution finished, follow-up job has been produced");
+                trace!("* Job:   {job:?}");
+                trace!("* Delay: {delay:?}");
+
+                JobService::queue_job(ctx, &job, delay).await?;
+            }
+        }
+
+        trace!("Committing transaction, returning success");
+        let invalidations = ctx.take_view_cache_invalidations();
+        txn.commit().await?;
+        ViewCacheService::run_invalidations(&self.state, invalidations).await;
+        Ok(JobProcessStatus::ReceivedJob)
+    }
+
+    /// Performs the work for a job.
+    ///
+    /// This is separate from the queue handling so that the scheduler can
+    /// run periodic jobs directly.
+    pub(super) async fn execute(ctx: &ServiceContext<'_>, job: Job) -> Result<NextJob> {
         let next = match job {
             Job::RerenderPage {
                 site_id,
@@ -183,72 +221,121 @@ impl JobWorker {
                 debug!(
                     "Rerendering page ID {page_id} in site ID {site_id} (depth {depth})",
                 );
-                PageRevisionService::rerender(ctx, site_id, page_id, depth).await?;
+                PageRevisionService::rerender(
+                    ctx,
+                    site_id,
+                    page_id,
+                    depth,
+                    RenderPriority::Background,
+                )
+                .await?;
                 NextJob::Done
             }
+            Job::RerenderBatch {
+                scope,
+                after_page_id,
+            } => {
+                debug!("Queuing mass rerender batch for {scope:?} after page ID {after_page_id}");
+                match JobService::rerender_batch(ctx, scope, after_page_id).await? {
+                    None => NextJob::Done,
+                    Some(after_page_id) => NextJob::Next {
+                        job: Job::RerenderBatch {
+                            scope,
+                            after_page_id,
+                        },
+                        delay: Some(ctx.config().job_rerender_batch_delay),
+                    },
+                }
+            }
             Job::PruneSessions => {
                 debug!("Pruning all expired sesions from database");
                 SessionService::prune(ctx).await?;
-                NextJob::Next {
-                    job: Job::PruneSessions,
-                    delay: Some(self.state.config.job_prune_session),
-                }
+                NextJob::Done
             }
             Job::PruneText => {
                 debug!("Pruning all unused text items from database");
                 TextService::prune(ctx).await?;
-                NextJob::Next {
-                    job: Job::PruneText,
-                    delay: Some(self.state.config.job_prune_text),
-                }
+                NextJob::Done
+            }
+            Job::PruneUploads => {
+                debug!("Pruning all expired pending uploads");
+                BlobService::prune_uploads(ctx).await?;
+                NextJob::Done
+            }
+            Job::PruneUserExports => {
+                debug!("Pruning all expired user data exports");
+                UserDataService::prune_exports(ctx).await?;
+                NextJob::Done
             }
             Job::NameChangeRefill => {
                 debug!("Checking users for those who can get a name change token refill");
                 UserService::refresh_name_change_tokens(ctx).await?;
-                NextJob::Next {
-                    job: Job::NameChangeRefill,
-                    delay: Some(self.state.config.job_name_change_refill),
-                }
+                NextJob::Done
             }
             Job::LiftExpiredPunishments => {
                 debug!("Checking if any outstanding punishments have expired");
                 // TODO implement tempban removal
                 //
                 //      We aren't going to be able to create jobs that have a wait time of say,
-                //      2 years, so instead we will just have this job run daily and check
-                //      to see if any bans have expired
+                //      2 years, so instead this job is run periodically by the scheduler
+                //      and checks to see if any bans have expired
                 //
                 //      currently only bans are the temporary, but others can be added here
-                NextJob::Next {
-                    job: Job::LiftExpiredPunishments,
-                    delay: Some(self.state.config.job_lift_expired_punishments),
+                NextJob::Done
+            }
+            Job::SendInvitationEmail { invitation_id } => {
+                debug!("Sending email for site invitation ID {invitation_id}");
+                MembershipService::send_invitation_email(ctx, invitation_id).await?;
+                NextJob::Done
+            }
+            Job::ExportUserData { user_id, export_id } => {
+                debug!("Building data export for user ID {user_id}");
+                UserDataService::build_export(ctx, user_id, &export_id).await?;
+                NextJob::Done
+            }
+            Job::VerifyCustomDomain { domain } => {
+                debug!("Checking challenge record for custom domain '{domain}'");
+                DomainService::verify_custom(ctx, &domain).await?;
+                NextJob::Done
+            }
+            Job::VerifyCustomDomains => {
+                debug!("Checking challenge records for all custom domains");
+                DomainService::verify_all_custom(ctx).await?;
+                NextJob::Done
+            }
+            Job::CollectOrphanedBlobs => {
+                debug!("Collecting blobs which are no longer used");
+                BlobService::collect_orphans(ctx).await?;
+                NextJob::Done
+            }
+            Job::RecompressTextBatch { after_hash } => {
+                debug!("Recompressing text rows after hash '{after_hash}'");
+                match TextService::recompress_batch(ctx, &after_hash).await? {
+                    None => NextJob::Done,
+                    Some(after_hash) => NextJob::Next {
+                        job: Job::RecompressTextBatch { after_hash },
+                        delay: Some(ctx.config().job_recompress_text_batch_delay),
+                    },
                 }
             }
-        };
-
-        // Don't delete more than once
-        //
-        // NOTE: We're only at this point if the job succeeded.
-        if !no_more_retries {
-            trace!("Job execution finished, deleting message");
-            self.rsmq.delete_message(JOB_QUEUE_NAME, &data.id).await?;
-        }
-
-        // Add follow-up job to queue, if required.
-        match next {
-            NextJob::Done => debug!("Job execution finished, no follow-up job to add"),
-            NextJob::Next { job, delay } => {
-                debug!("Job execution finished, follow-up job has been produced");
-                trace!("* Job:   {job:?}");
-                trace!("* Delay: {delay:?}");
-
-                JobService::queue_job(ctx, &job, delay).await?;
+            Job::StartStorageScrub => {
+                debug!("Starting scrub to verify stored text and blobs");
+                ScrubService::start(ctx).await?;
+                NextJob::Done
             }
-        }
+            Job::StorageScrubBatch { scrub_id, phase } => {
+                debug!("Running storage scrub {scrub_id} batch for {phase:?}");
+                match ScrubService::run_batch(ctx, &scrub_id, phase).await? {
+                    None => NextJob::Done,
+                    Some(phase) => NextJob::Next {
+                        job: Job::StorageScrubBatch { scrub_id, phase },
+                        delay: Some(ctx.config().job_scrub_batch_delay),
+                    },
+                }
+            }
+        };
 
-        trace!("Committing transaction, returning success");
-        txn.commit().await?;
-        Ok(JobProcessStatus::ReceivedJob)
+        Ok(next)
     }
 }
 
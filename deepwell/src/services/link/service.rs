@@ -138,8 +138,6 @@ impl LinkService {
         page_slug: &str,
         connection_types: Option<&[ConnectionType]>,
     ) -> Result<GetLinksToMissingOutput> {
-        let txn = ctx.transaction();
-
         // Ensure the page doesn't actually exist
         if let Some(page) =
             PageService::get_optional(ctx, site_id, Reference::from(page_slug)).await?
@@ -153,7 +151,20 @@ impl LinkService {
             return Err(Error::PageExists);
         }
 
-        // Retrieve connections for this slot
+        Self::get_to_missing_unchecked(ctx, site_id, page_slug, connection_types).await
+    }
+
+    /// Like `get_to_missing()`, but without checking that the page is absent.
+    ///
+    /// This is used when a page has just been created, to find the
+    /// pages which referred to it before it existed.
+    pub async fn get_to_missing_unchecked(
+        ctx: &ServiceContext<'_>,
+        site_id: i64,
+        page_slug: &str,
+        connection_types: Option<&[ConnectionType]>,
+    ) -> Result<GetLinksToMissingOutput> {
+        let txn = ctx.transaction();
         let connections = PageConnectionMissing::find()
             .filter(
                 Condition::all()
@@ -228,18 +239,20 @@ impl LinkService {
         site_id: i64,
         page_id: i64,
         backlinks: &Backlinks<'_>,
+        included_pages: &[PageRef<'_>],
     ) -> Result<()> {
         let mut connections = HashMap::new();
         let mut connections_missing = HashMap::new();
         let mut external_links = HashMap::new();
 
-        // Get include stats
-        for include in &backlinks.included_pages {
+        // Get messy include stats
+        //
+        // These are resolved before parsing, see RenderService::resolve_includes().
+        for include in included_pages {
             count_connections(
                 ctx,
                 site_id,
                 include,
-                // TODO: update Backlinks so that it also tracks other kinds of includes and components
                 ConnectionType::IncludeMessy,
                 &mut connections,
                 &mut connections_missing,
@@ -247,6 +260,19 @@ impl LinkService {
             .await?;
         }
 
+        // Get element include stats
+        for include in &backlinks.included_pages {
+            count_connections(
+                ctx,
+                site_id,
+                include,
+                ConnectionType::IncludeElements,
+                &mut connections,
+                &mut connections_missing,
+            )
+            .await?;
+        }
+
         // Get internal page link stats
         for link in &backlinks.internal_links {
             count_connections(
@@ -481,7 +507,14 @@ async fn count_connections(
         None => site_id,
         Some(slug) => {
             let reference = Reference::Slug(cow!(slug));
-            SiteService::get(ctx, reference).await?.site_id
+            match SiteService::get_optional(ctx, reference).await? {
+                Some(site) => site.site_id,
+                None => {
+                    // Missing connections are per-site, so there is nothing to record
+                    debug!("Connection to page '{page_slug}' on nonexistent site '{slug}', skipping");
+                    return Ok(());
+                }
+            }
         }
     };
 
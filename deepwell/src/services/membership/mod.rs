@@ -0,0 +1,38 @@
+/*
+ * services/membership/mod.rs
+ *
+ * DEEPWELL - Wikijump API provider and database manager
+ * Copyright (C) 2019-2025 Wikijump Team
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Service for managing how users become site members.
+//!
+//! Depending on the site's `MembershipPolicy`, users may join directly,
+//! apply and wait for site staff to accept or reject their application,
+//! or join by invitation. Invitations can be sent to an existing user, or
+//! to an email address, in which case a token is generated to accept it.
+
+#[allow(unused_imports)]
+mod prelude {
+    pub use super::super::prelude::*;
+    pub use super::structs::*;
+}
+
+mod service;
+mod structs;
+
+pub use self::service::MembershipService;
+pub use self::structs::*;
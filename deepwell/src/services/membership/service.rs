@@ -0,0 +1,774 @@
+/*
+ * services/membership/service.rs
+ *
+ * DEEPWELL - Wikijump API provider and database manager
+ * Copyright (C) 2019-2025 Wikijump Team
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use super::prelude::*;
+use crate::models::relation::Model as RelationModel;
+use crate::models::site_invitation::{
+    self, Entity as SiteInvitation, Model as SiteInvitationModel,
+};
+use crate::models::user::Model as UserModel;
+use crate::services::email::EmailService;
+use crate::services::filter::{FilterClass, FilterType};
+use crate::services::job::{Job, JobService};
+use crate::services::relation::{
+    CreateSiteApplication, CreateSiteMember, GetSiteApplication, GetSiteBan,
+    GetSiteMember, RemoveSiteApplication, SiteApplicationData, SiteMemberAccepted,
+    SiteMemberData,
+};
+use crate::services::site::MembershipPolicy;
+use crate::services::{
+    DomainService, FilterService, RelationService, SiteService, UserService,
+};
+use crate::utils::assert_is_csprng;
+use fluent::{FluentArgs, FluentValue};
+use rand::distributions::{Alphanumeric, DistString};
+use rand::thread_rng;
+use unic_langid::LanguageIdentifier;
+
+#[derive(Debug)]
+pub struct MembershipService;
+
+impl MembershipService {
+    // Joining
+
+    /// Joins a site directly, if its membership policy is open.
+    pub async fn join(
+        ctx: &ServiceContext<'_>,
+        JoinSite { site_id, user_id }: JoinSite,
+    ) -> Result<()> {
+        info!("User ID {user_id} is joining site ID {site_id}");
+
+        Self::check_policy(ctx, site_id, MembershipPolicy::Open).await?;
+        let user = Self::check_can_join(ctx, site_id, user_id).await?;
+        Self::check_filters(ctx, site_id, &user).await?;
+
+        RelationService::create_site_member(
+            ctx,
+            CreateSiteMember {
+                site_id,
+                user_id,
+                metadata: SiteMemberData {
+                    accepted: SiteMemberAccepted::SelfJoined,
+                },
+                created_by: user_id,
+            },
+        )
+        .await?;
+
+        // In case they applied before the policy was changed
+        Self::clear_application(ctx, site_id, user_id, user_id).await
+    }
+
+    /// Directly adds a user as a site member, on behalf of site staff.
+    ///
+    /// This is equivalent to the user being invited and accepting, so it
+    /// is only permitted if the site's membership policy allows invitations.
+    /// Users cannot add themselves this way, they must use `join()` instead.
+    pub async fn add_member(
+        ctx: &ServiceContext<'_>,
+        AddSiteMember {
+            site_id,
+            user_id,
+            staff_user_id,
+        }: AddSiteMember,
+    ) -> Result<()> {
+        info!(
+            "Adding user ID {user_id} as a member of site ID {site_id} (by user ID {staff_user_id})",
+        );
+
+        if user_id == staff_user_id {
+            error!("User ID {user_id} cannot add themselves as a member of site ID {site_id}");
+            return Err(Error::SiteMembershipPolicy);
+        }
+
+        Self::check_invitations_allowed(ctx, site_id).await?;
+        let user = Self::check_can_join(ctx, site_id, user_id).await?;
+        Self::check_filters(ctx, site_id, &user).await?;
+
+        RelationService::create_site_member(
+            ctx,
+            CreateSiteMember {
+                site_id,
+                user_id,
+                metadata: SiteMemberData {
+                    accepted: SiteMemberAccepted::Accepted(staff_user_id),
+                },
+                created_by: staff_user_id,
+            },
+        )
+        .await?;
+
+        Self::clear_application(ctx, site_id, user_id, staff_user_id).await
+    }
+
+    // Applications
+
+    /// Submits an application to join a site.
+    pub async fn apply(
+        ctx: &ServiceContext<'_>,
+        ApplyToSite {
+            site_id,
+            user_id,
+            message,
+        }: ApplyToSite,
+    ) -> Result<()> {
+        info!("User ID {user_id} is applying to join site ID {site_id}");
+
+        Self::check_policy(ctx, site_id, MembershipPolicy::Apply).await?;
+        let user = Self::check_can_join(ctx, site_id, user_id).await?;
+
+        if RelationService::site_application_exists(
+            ctx,
+            GetSiteApplication { site_id, user_id },
+        )
+        .await?
+        {
+            error!("User ID {user_id} already has a pending application to site ID {site_id}");
+            return Err(Error::SiteApplicationExists);
+        }
+
+        Self::check_filters(ctx, site_id, &user).await?;
+
+        RelationService::create_site_application(
+            ctx,
+            CreateSiteApplication {
+                site_id,
+                user_id,
+                metadata: SiteApplicationData { message },
+                created_by: user_id,
+            },
+        )
+        .await
+    }
+
+    /// Gets all pending applications for a site, for site staff to review.
+    pub async fn get_applications(
+        ctx: &ServiceContext<'_>,
+        site_id: i64,
+    ) -> Result<Vec<RelationModel>> {
+        info!("Getting pending membership applications for site ID {site_id}");
+        RelationService::get_pending_site_applications(ctx, site_id).await
+    }
+
+    /// Accepts an application, making the user a site member.
+    ///
+    /// This does not check the membership policy, so that applications
+    /// which were submitted before a policy change can still be processed.
+    pub async fn accept_application(
+        ctx: &ServiceContext<'_>,
+        ProcessApplication {
+            site_id,
+            user_id,
+            staff_user_id,
+        }: ProcessApplication,
+    ) -> Result<()> {
+        info!(
+            "Accepting membership application for user ID {user_id} to site ID {site_id} (by user ID {staff_user_id})",
+        );
+
+        Self::remove_application(ctx, site_id, user_id, staff_user_id).await?;
+        let user = Self::check_can_join(ctx, site_id, user_id).await?;
+        Self::check_filters(ctx, site_id, &user).await?;
+
+        RelationService::create_site_member(
+            ctx,
+            CreateSiteMember {
+                site_id,
+                user_id,
+                metadata: SiteMemberData {
+                    accepted: SiteMemberAccepted::Accepted(staff_user_id),
+                },
+                created_by: staff_user_id,
+            },
+        )
+        .await
+    }
+
+    /// Rejects an application, removing it from the queue.
+    pub async fn reject_application(
+        ctx: &ServiceContext<'_>,
+        ProcessApplication {
+            site_id,
+            user_id,
+            staff_user_id,
+        }: ProcessApplication,
+    ) -> Result<()> {
+        info!(
+            "Rejecting membership application for user ID {user_id} to site ID {site_id} (by user ID {staff_user_id})",
+        );
+
+        Self::remove_application(ctx, site_id, user_id, staff_user_id).await
+    }
+
+    async fn remove_application(
+        ctx: &ServiceContext<'_>,
+        site_id: i64,
+        user_id: i64,
+        removed_by: i64,
+    ) -> Result<()> {
+        let get = GetSiteApplication { site_id, user_id };
+        if !RelationService::site_application_exists(ctx, get).await? {
+            error!("No pending application for user ID {user_id} to site ID {site_id}");
+            return Err(Error::SiteApplicationNotFound);
+        }
+
+        RelationService::remove_site_application(
+            ctx,
+            RemoveSiteApplication {
+                site_id,
+                user_id,
+                removed_by,
+            },
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Removes the user's pending application to the site, if they have one.
+    ///
+    /// This is for when the user becomes a member by other means,
+    /// so that the application is not left in the queue.
+    async fn clear_application(
+        ctx: &ServiceContext<'_>,
+        site_id: i64,
+        user_id: i64,
+        removed_by: i64,
+    ) -> Result<()> {
+        let get = GetSiteApplication { site_id, user_id };
+        if RelationService::site_application_exists(ctx, get).await? {
+            debug!(
+                "Removing pending application for user ID {user_id} to site ID {site_id}"
+            );
+            RelationService::remove_site_application(
+                ctx,
+                RemoveSiteApplication {
+                    site_id,
+                    user_id,
+                    removed_by,
+                },
+            )
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    // Invitations
+
+    /// Invites an existing user to join a site.
+    pub async fn invite_user(
+        ctx: &ServiceContext<'_>,
+        InviteUser {
+            site_id,
+            user_id,
+            message,
+            created_by,
+        }: InviteUser,
+    ) -> Result<CreateInvitationOutput> {
+        info!(
+            "Inviting user ID {user_id} to site ID {site_id} (by user ID {created_by})"
+        );
+
+        Self::check_invitations_allowed(ctx, site_id).await?;
+        let user = Self::check_can_join(ctx, site_id, user_id).await?;
+        Self::check_filters(ctx, site_id, &user).await?;
+
+        let model = site_invitation::ActiveModel {
+            user_id: Set(Some(user_id)),
+            ..Self::new_invitation(ctx.config(), site_id, created_by, message)
+        };
+
+        Self::insert_invitation(ctx, model).await
+    }
+
+    /// Invites an email address to join a site.
+    ///
+    /// The token is emailed to the address, which the recipient
+    /// uses to accept the invitation once they have an account.
+    pub async fn invite_email(
+        ctx: &ServiceContext<'_>,
+        InviteEmail {
+            site_id,
+            email,
+            message,
+            created_by,
+        }: InviteEmail,
+    ) -> Result<CreateInvitationOutput> {
+        info!("Inviting an email address to site ID {site_id} (by user ID {created_by})");
+
+        Self::check_invitations_allowed(ctx, site_id).await?;
+
+        let filter_matcher = FilterService::get_matcher(
+            ctx,
+            FilterClass::Site(site_id),
+            FilterType::Email,
+        )
+        .await?;
+
+        filter_matcher.verify(ctx, &email).await?;
+
+        let config = ctx.config();
+        let model = site_invitation::ActiveModel {
+            email: Set(Some(email)),
+            token: Set(Some(Self::new_token(config))),
+            ..Self::new_invitation(config, site_id, created_by, message)
+        };
+
+        Self::insert_invitation(ctx, model).await
+    }
+
+    /// Accepts an invitation, making the user a site member.
+    ///
+    /// The invitation must not be expired, accepted, or revoked.
+    /// For invitations to a specific user, only that user may accept it.
+    pub async fn accept_invitation(
+        ctx: &ServiceContext<'_>,
+        AcceptInvitation {
+            invitation: reference,
+            user_id,
+        }: AcceptInvitation,
+    ) -> Result<()> {
+        let txn = ctx.transaction();
+        let by_token = matches!(reference, InvitationReference::Token(_));
+        let invitation = Self::get_invitation(ctx, reference).await?;
+        let site_id = invitation.site_id;
+
+        // Emailed invitations can only be accepted with the token sent to that
+        // address, otherwise anyone could accept them by guessing their IDs.
+        if invitation.user_id.is_none() && !by_token {
+            error!(
+                "Invitation ID {} was emailed, but not referred to by token",
+                invitation.invitation_id,
+            );
+            return Err(Error::SiteInvitationNotFound);
+        }
+
+        info!(
+            "User ID {user_id} is accepting invitation ID {} to site ID {site_id}",
+            invitation.invitation_id,
+        );
+
+        if !Self::is_pending(&invitation) {
+            error!(
+                "Invitation ID {} is no longer valid",
+                invitation.invitation_id
+            );
+            return Err(Error::SiteInvitationExpired);
+        }
+
+        if let Some(invited_user_id) = invitation.user_id {
+            if invited_user_id != user_id {
+                error!(
+                    "Invitation ID {} is for user ID {invited_user_id}, not user ID {user_id}",
+                    invitation.invitation_id,
+                );
+                return Err(Error::SiteInvitationWrongUser);
+            }
+        }
+
+        let user = Self::check_can_join(ctx, site_id, user_id).await?;
+        Self::check_filters(ctx, site_id, &user).await?;
+
+        RelationService::create_site_member(
+            ctx,
+            CreateSiteMember {
+                site_id,
+                user_id,
+                metadata: SiteMemberData {
+                    accepted: SiteMemberAccepted::Invitation(invitation.created_by),
+                },
+                created_by: user_id,
+            },
+        )
+        .await?;
+
+        Self::clear_application(ctx, site_id, user_id, user_id).await?;
+
+        let model = site_invitation::ActiveModel {
+            invitation_id: Set(invitation.invitation_id),
+            accepted_at: Set(Some(now())),
+            accepted_by: Set(Some(user_id)),
+            ..Default::default()
+        };
+        model.update(txn).await?;
+
+        Ok(())
+    }
+
+    /// Revokes a pending invitation.
+    pub async fn revoke_invitation(
+        ctx: &ServiceContext<'_>,
+        RevokeInvitation {
+            site_id,
+            invitation_id,
+            revoked_by,
+        }: RevokeInvitation,
+    ) -> Result<SiteInvitationModel> {
+        info!("Revoking invitation ID {invitation_id} (by user ID {revoked_by})");
+
+        let txn = ctx.transaction();
+        let invitation =
+            Self::get_invitation(ctx, InvitationReference::Id(invitation_id)).await?;
+
+        if invitation.site_id != site_id {
+            warn!("Invitation's site ID and passed site ID do not match");
+            return Err(Error::SiteInvitationNotFound);
+        }
+
+        if !Self::is_pending(&invitation) {
+            error!("Invitation ID {invitation_id} is no longer valid");
+            return Err(Error::SiteInvitationExpired);
+        }
+
+        let model = site_invitation::ActiveModel {
+            invitation_id: Set(invitation_id),
+            revoked_at: Set(Some(now())),
+            revoked_by: Set(Some(revoked_by)),
+            ..Default::default()
+        };
+
+        let invitation = model.update(txn).await?;
+        Ok(invitation)
+    }
+
+    /// Revokes all pending invitations to this site for the given user.
+    ///
+    /// # Returns
+    /// The number of invitations revoked.
+    pub async fn revoke_user_invitations(
+        ctx: &ServiceContext<'_>,
+        site_id: i64,
+        user_id: i64,
+        revoked_by: i64,
+    ) -> Result<u64> {
+        info!("Revoking all invitations for user ID {user_id} to site ID {site_id}");
+
+        let txn = ctx.transaction();
+        let model = site_invitation::ActiveModel {
+            revoked_at: Set(Some(now())),
+            revoked_by: Set(Some(revoked_by)),
+            ..Default::default()
+        };
+
+        let rows_revoked = SiteInvitation::update_many()
+            .set(model)
+            .filter(
+                Self::pending_condition()
+                    .add(site_invitation::Column::SiteId.eq(site_id))
+                    .add(site_invitation::Column::UserId.eq(user_id)),
+            )
+            .exec(txn)
+            .await?
+            .rows_affected;
+
+        Ok(rows_revoked)
+    }
+
+    /// Gets all pending invitations for a site.
+    pub async fn get_invitations(
+        ctx: &ServiceContext<'_>,
+        site_id: i64,
+    ) -> Result<Vec<SiteInvitationModel>> {
+        info!("Getting pending invitations for site ID {site_id}");
+
+        let txn = ctx.transaction();
+        let invitations = SiteInvitation::find()
+            .filter(
+                Self::pending_condition()
+                    .add(site_invitation::Column::SiteId.eq(site_id)),
+            )
+            .order_by_asc(site_invitation::Column::CreatedAt)
+            .all(txn)
+            .await?;
+
+        Ok(invitations)
+    }
+
+    pub async fn get_invitation_optional(
+        ctx: &ServiceContext<'_>,
+        reference: InvitationReference,
+    ) -> Result<Option<SiteInvitationModel>> {
+        let txn = ctx.transaction();
+        let condition = match reference {
+            InvitationReference::Id(invitation_id) => {
+                site_invitation::Column::InvitationId.eq(invitation_id)
+            }
+            InvitationReference::Token(token) => site_invitation::Column::Token.eq(token),
+        };
+
+        let invitation = SiteInvitation::find().filter(condition).one(txn).await?;
+        Ok(invitation)
+    }
+
+    #[inline]
+    pub async fn get_invitation(
+        ctx: &ServiceContext<'_>,
+        reference: InvitationReference,
+    ) -> Result<SiteInvitationModel> {
+        find_or_error!(
+            Self::get_invitation_optional(ctx, reference),
+            SiteInvitation
+        )
+    }
+
+    /// Emails an invitation to its recipient.
+    ///
+    /// This is run by the job worker. Invitations which are no
+    /// longer pending by the time the job runs are skipped.
+    pub async fn send_invitation_email(
+        ctx: &ServiceContext<'_>,
+        invitation_id: i64,
+    ) -> Result<()> {
+        let invitation =
+            Self::get_invitation(ctx, InvitationReference::Id(invitation_id)).await?;
+
+        if !Self::is_pending(&invitation) {
+            warn!(
+                "Invitation ID {invitation_id} is no longer pending, not sending email"
+            );
+            return Ok(());
+        }
+
+        let (site, inviter) = try_join!(
+            SiteService::get(ctx, Reference::Id(invitation.site_id)),
+            UserService::get(ctx, Reference::Id(invitation.created_by)),
+        )?;
+
+        // Determine recipient, and which locales to write the email in
+        let mut locales = Vec::new();
+        let recipient = match (invitation.user_id, invitation.email) {
+            (Some(user_id), _) => {
+                let user = UserService::get(ctx, Reference::Id(user_id)).await?;
+                for locale in &user.locales {
+                    locales.push(LanguageIdentifier::from_bytes(locale.as_bytes())?);
+                }
+                user.email
+            }
+            (None, Some(email)) => email,
+            (None, None) => {
+                error!("Invitation ID {invitation_id} has neither a user nor an email");
+                return Err(Error::BadRequest);
+            }
+        };
+        locales.push(LanguageIdentifier::from_bytes(site.locale.as_bytes())?);
+
+        let config = ctx.config();
+        let site_url =
+            format!("https://{}/", DomainService::domain_for_site(config, &site));
+        let expires_days = (invitation.expires_at - now()).whole_days().max(1);
+
+        // Built in its own scope, since the Fluent arguments are not Send
+        let (subject, body) = {
+            let mut args = FluentArgs::new();
+            args.set("site", fluent_str!(site.name));
+            args.set("inviter", fluent_str!(inviter.name));
+            args.set("url", fluent_str!(site_url));
+            args.set("count", expires_days);
+            if let Some(token) = &invitation.token {
+                args.set("token", fluent_str!(*token));
+            }
+
+            let localization = ctx.localization();
+            let translate = |key| localization.translate(&locales, key, &args);
+
+            let subject = translate("emails-site-invitation.subject")?;
+            let mut body = String::new();
+            for key in [
+                "emails-site-invitation.greeting",
+                "emails-site-invitation.intro",
+            ] {
+                body.push_str(&translate(key)?);
+                body.push_str("\n\n");
+            }
+
+            if !invitation.message.is_empty() {
+                body.push_str(&translate("emails-site-invitation.message")?);
+                body.push_str("\n\n");
+                for line in invitation.message.lines() {
+                    body.push_str("> ");
+                    body.push_str(line);
+                    body.push('\n');
+                }
+                body.push('\n');
+            }
+
+            let action_key = match invitation.token {
+                Some(_) => "emails-site-invitation.token",
+                None => "emails-site-invitation.action",
+            };
+
+            for key in [
+                action_key,
+                "emails-site-invitation.expires",
+                "emails-site-invitation.outro",
+            ] {
+                body.push_str(&translate(key)?);
+                body.push_str("\n\n");
+            }
+
+            (subject.into_owned(), body)
+        };
+
+        info!("Sending email for invitation ID {invitation_id}");
+        EmailService::send(ctx, &recipient, &subject, body).await
+    }
+
+    // Helpers
+
+    /// Ensures the site's membership policy is the one required for this action.
+    async fn check_policy(
+        ctx: &ServiceContext<'_>,
+        site_id: i64,
+        required: MembershipPolicy,
+    ) -> Result<()> {
+        let policy = SiteService::get_membership_policy(ctx, site_id).await?;
+        if policy != required {
+            error!(
+                "Site ID {site_id} has membership policy {policy:?}, but {required:?} is required",
+            );
+            return Err(Error::SiteMembershipPolicy);
+        }
+
+        Ok(())
+    }
+
+    async fn check_invitations_allowed(
+        ctx: &ServiceContext<'_>,
+        site_id: i64,
+    ) -> Result<()> {
+        let policy = SiteService::get_membership_policy(ctx, site_id).await?;
+        if !policy.allows_invitations() {
+            error!("Site ID {site_id} has membership policy {policy:?}, cannot invite");
+            return Err(Error::SiteMembershipPolicy);
+        }
+
+        Ok(())
+    }
+
+    /// Checks that the user is able to become a member of the site.
+    ///
+    /// They must not be banned from the site, nor already a member.
+    async fn check_can_join(
+        ctx: &ServiceContext<'_>,
+        site_id: i64,
+        user_id: i64,
+    ) -> Result<UserModel> {
+        RelationService::check_site_ban(ctx, GetSiteBan { site_id, user_id }, "join")
+            .await?;
+
+        if RelationService::site_member_exists(ctx, GetSiteMember { site_id, user_id })
+            .await?
+        {
+            error!("User ID {user_id} is already a member of site ID {site_id}");
+            return Err(Error::SiteMemberExists);
+        }
+
+        UserService::get(ctx, Reference::Id(user_id)).await
+    }
+
+    /// Checks the user against the site's user and email filters.
+    ///
+    /// Platform filters are not checked here, since they
+    /// were already applied when the user was created.
+    async fn check_filters(
+        ctx: &ServiceContext<'_>,
+        site_id: i64,
+        user: &UserModel,
+    ) -> Result<()> {
+        info!("Checking user data against site filters...");
+
+        let (user_matcher, email_matcher) = try_join!(
+            FilterService::get_matcher(ctx, FilterClass::Site(site_id), FilterType::User),
+            FilterService::get_matcher(
+                ctx,
+                FilterClass::Site(site_id),
+                FilterType::Email
+            ),
+        )?;
+
+        try_join!(
+            user_matcher.verify(ctx, &user.name),
+            user_matcher.verify(ctx, &user.slug),
+            email_matcher.verify(ctx, &user.email),
+        )?;
+
+        Ok(())
+    }
+
+    fn new_invitation(
+        config: &Config,
+        site_id: i64,
+        created_by: i64,
+        message: String,
+    ) -> site_invitation::ActiveModel {
+        let created_at = now();
+        site_invitation::ActiveModel {
+            site_id: Set(site_id),
+            created_by: Set(created_by),
+            created_at: Set(created_at),
+            expires_at: Set(created_at + config.invitation_duration),
+            message: Set(message),
+            ..Default::default()
+        }
+    }
+
+    async fn insert_invitation(
+        ctx: &ServiceContext<'_>,
+        model: site_invitation::ActiveModel,
+    ) -> Result<CreateInvitationOutput> {
+        let txn = ctx.transaction();
+        let SiteInvitationModel {
+            invitation_id,
+            expires_at,
+            ..
+        } = model.insert(txn).await?;
+
+        JobService::queue_job(ctx, &Job::SendInvitationEmail { invitation_id }, None)
+            .await?;
+
+        Ok(CreateInvitationOutput {
+            invitation_id,
+            expires_at,
+        })
+    }
+
+    /// Securely generates a new invitation token.
+    fn new_token(config: &Config) -> String {
+        debug!("Generating a new invitation token");
+        let mut rng = thread_rng();
+        assert_is_csprng(&rng);
+        Alphanumeric.sample_string(&mut rng, config.invitation_token_length)
+    }
+
+    fn is_pending(invitation: &SiteInvitationModel) -> bool {
+        invitation.accepted_at.is_none()
+            && invitation.revoked_at.is_none()
+            && invitation.expires_at > now()
+    }
+
+    /// Condition for invitations which can still be accepted.
+    fn pending_condition() -> Condition {
+        Condition::all()
+            .add(site_invitation::Column::AcceptedAt.is_null())
+            .add(site_invitation::Column::RevokedAt.is_null())
+            .add(site_invitation::Column::ExpiresAt.gt(now()))
+    }
+}
@@ -0,0 +1,96 @@
+/*
+ * services/membership/structs.rs
+ *
+ * DEEPWELL - Wikijump API provider and database manager
+ * Copyright (C) 2019-2025 Wikijump Team
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use time::OffsetDateTime;
+
+#[derive(Deserialize, Debug, Copy, Clone)]
+pub struct JoinSite {
+    pub site_id: i64,
+    pub user_id: i64,
+}
+
+#[derive(Deserialize, Debug, Copy, Clone)]
+pub struct AddSiteMember {
+    pub site_id: i64,
+    pub user_id: i64,
+    pub staff_user_id: i64,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ApplyToSite {
+    pub site_id: i64,
+    pub user_id: i64,
+    pub message: String,
+}
+
+#[derive(Deserialize, Debug, Copy, Clone)]
+pub struct ProcessApplication {
+    pub site_id: i64,
+    pub user_id: i64,
+    pub staff_user_id: i64,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct InviteUser {
+    pub site_id: i64,
+    pub user_id: i64,
+    pub message: String,
+    pub created_by: i64,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct InviteEmail {
+    pub site_id: i64,
+    pub email: String,
+    pub message: String,
+    pub created_by: i64,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct CreateInvitationOutput {
+    pub invitation_id: i64,
+
+    #[serde(with = "time::serde::rfc3339")]
+    pub expires_at: OffsetDateTime,
+}
+
+/// How an invitation is being referred to when accepting it.
+///
+/// Invitations to a user are referred to by ID, but emailed
+/// invitations must be accepted with the token sent to that address.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum InvitationReference {
+    Id(i64),
+    Token(String),
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct AcceptInvitation {
+    pub invitation: InvitationReference,
+    pub user_id: i64,
+}
+
+#[derive(Deserialize, Debug, Copy, Clone)]
+pub struct RevokeInvitation {
+    pub site_id: i64,
+    pub invitation_id: i64,
+    pub revoked_by: i64,
+}
@@ -20,6 +20,9 @@
 
 use super::prelude::*;
 use crate::models::message::{self, Entity as Message, Model as MessageModel};
+use crate::models::message_attachment::{
+    self, Entity as MessageAttachment, Model as MessageAttachmentModel,
+};
 use crate::models::message_draft::{
     self, Entity as MessageDraft, Model as MessageDraftModel,
 };
@@ -28,14 +31,19 @@ use crate::models::message_record::{
     self, Entity as MessageRecord, Model as MessageRecordModel,
 };
 use crate::models::sea_orm_active_enums::{MessageRecipientType, UserType};
-use crate::services::render::{RenderOutput, RenderService};
-use crate::services::{RelationService, TextService, UserService};
+use crate::services::blob::FinalizeBlobUploadOutput;
+use crate::services::file::check_file_name;
+use crate::services::render::{RenderOutput, RenderPriority, RenderService};
+use crate::services::{
+    BlobService, RelationService, StorageService, TextService, UserService,
+};
 use crate::utils::validate_locale;
 use cuid2::cuid;
 use ftml::data::{PageInfo, ScoreValue};
 use ftml::layout::Layout;
 use ftml::settings::{WikitextMode, WikitextSettings};
 use sea_orm::DatabaseTransaction;
+use sea_query::Expr;
 
 #[derive(Debug)]
 pub struct MessageService;
@@ -55,6 +63,7 @@ impl MessageService {
             wikitext,
             reply_to,
             forwarded_from,
+            attachments,
         }: CreateMessageDraft,
     ) -> Result<MessageDraftModel> {
         info!("Creating message draft for user ID {user_id}");
@@ -93,6 +102,9 @@ impl MessageService {
         .insert(txn)
         .await?;
 
+        // Attach files
+        Self::add_attachments(ctx, &draft.external_id, user_id, attachments).await?;
+
         Ok(draft)
     }
 
@@ -106,6 +118,8 @@ impl MessageService {
             locale,
             subject,
             wikitext,
+            add_attachments,
+            remove_attachments,
         }: UpdateMessageDraft,
     ) -> Result<MessageDraftModel> {
         info!("Updating message draft {draft_id}");
@@ -138,6 +152,20 @@ impl MessageService {
         .update(txn)
         .await?;
 
+        // Update attached files
+        if !remove_attachments.is_empty() {
+            Self::delete_attachments(
+                ctx,
+                &draft.external_id,
+                draft.user_id,
+                Some(remove_attachments),
+            )
+            .await?;
+        }
+
+        Self::add_attachments(ctx, &draft.external_id, draft.user_id, add_attachments)
+            .await?;
+
         Ok(draft)
     }
 
@@ -177,14 +205,15 @@ impl MessageService {
 
         let config = ctx.config();
         let wikitext_hash = TextService::create(ctx, wikitext.clone()).await?;
+        // Direct messages do not permit page syntax, so unlike page revisions
+        // there are no page-level styles or metadata worth storing.
         let RenderOutput {
-            // TODO: use html_output
-            html_output: _,
             // TODO: use ftml errors
             errors: _,
             compiled_hash,
             compiled_at,
             compiled_generator,
+            ..
         } = Self::render(ctx, wikitext, &locale, config.message_layout).await?;
 
         Ok(message_draft::ActiveModel {
@@ -205,10 +234,126 @@ impl MessageService {
 
     pub async fn delete_draft(ctx: &ServiceContext<'_>, draft_id: String) -> Result<()> {
         let txn = ctx.transaction();
+        let draft = Self::get_draft(ctx, &draft_id).await?;
+        Self::delete_attachments(ctx, &draft_id, draft.user_id, None).await?;
         MessageDraft::delete_by_id(draft_id).exec(txn).await?;
         Ok(())
     }
 
+    /// Removes attachments from a message draft, crediting back their storage usage.
+    ///
+    /// If `attachment_ids` is `None`, then all of the draft's attachments are removed.
+    async fn delete_attachments(
+        ctx: &ServiceContext<'_>,
+        draft_id: &str,
+        user_id: i64,
+        attachment_ids: Option<Vec<i64>>,
+    ) -> Result<()> {
+        let txn = ctx.transaction();
+        let mut condition =
+            Condition::all().add(message_attachment::Column::DraftId.eq(draft_id));
+
+        if let Some(attachment_ids) = attachment_ids {
+            condition = condition.add(
+                message_attachment::Column::MessageAttachmentId.is_in(attachment_ids),
+            );
+        }
+
+        let sizes: Vec<i64> = MessageAttachment::find()
+            .select_only()
+            .column(message_attachment::Column::Size)
+            .filter(condition.clone())
+            .into_tuple()
+            .all(txn)
+            .await?;
+
+        MessageAttachment::delete_many()
+            .filter(condition)
+            .exec(txn)
+            .await?;
+
+        StorageService::credit_attachments(ctx, user_id, sizes.iter().sum()).await
+    }
+
+    /// Attaches uploaded files to a message draft.
+    async fn add_attachments(
+        ctx: &ServiceContext<'_>,
+        draft_id: &str,
+        user_id: i64,
+        attachments: Vec<NewMessageAttachment>,
+    ) -> Result<()> {
+        let config = ctx.config();
+        let txn = ctx.transaction();
+
+        for NewMessageAttachment {
+            uploaded_blob_id,
+            mut name,
+        } in attachments
+        {
+            info!("Attaching pending blob ID {uploaded_blob_id} to message draft {draft_id}");
+            check_file_name(&mut name)?;
+
+            // Check the declared size before the upload is moved into place
+            let size =
+                BlobService::get_pending_length(ctx, user_id, &uploaded_blob_id).await?;
+
+            if size > config.maximum_message_attachment_bytes {
+                error!(
+                    "Message attachment is too large (is {}, max {})",
+                    size, config.maximum_message_attachment_bytes,
+                );
+                return Err(Error::MessageAttachmentTooBig {
+                    size,
+                    maximum: config.maximum_message_attachment_bytes,
+                });
+            }
+
+            let FinalizeBlobUploadOutput {
+                s3_hash,
+                mime,
+                size,
+                ..
+            } = BlobService::finish_upload(ctx, user_id, &uploaded_blob_id).await?;
+
+            // Charge attachment to storage usage, refusing if over quota
+            StorageService::charge_attachment(ctx, user_id, &s3_hash, size).await?;
+
+            let model = message_attachment::ActiveModel {
+                draft_id: Set(Some(str!(draft_id))),
+                name: Set(name),
+                s3_hash: Set(s3_hash.to_vec()),
+                mime: Set(mime),
+                size: Set(size),
+                ..Default::default()
+            };
+            model.insert(txn).await?;
+        }
+
+        Self::check_attachment_count(ctx, draft_id).await
+    }
+
+    async fn check_attachment_count(
+        ctx: &ServiceContext<'_>,
+        draft_id: &str,
+    ) -> Result<()> {
+        let config = ctx.config();
+        let txn = ctx.transaction();
+        let count = MessageAttachment::find()
+            .filter(message_attachment::Column::DraftId.eq(draft_id))
+            .count(txn)
+            .await?;
+
+        if count > config.maximum_message_attachments as u64 {
+            error!(
+                "Too many message attachments (is {}, max {})",
+                count, config.maximum_message_attachments,
+            );
+            return Err(Error::MessageTooManyAttachments);
+        }
+
+        Ok(())
+    }
+
     // Message methods
 
     pub async fn send(
@@ -266,6 +411,8 @@ impl MessageService {
             return Err(Error::MessageTooManyRecipients);
         }
 
+        Self::check_attachment_count(ctx, draft_id).await?;
+
         let mut recipients_to_add = Vec::new();
         for recipient_user_id in recipients.iter() {
             // Ensure user is not blocked
@@ -294,6 +441,7 @@ impl MessageService {
 
         // The message sending process:
         // * Insert message_draft row to message_record
+        // * Move message_attachment rows from draft to record
         // * Delete message_draft row
         // * Insert message_recipient rows
         // * Insert inbox message rows for each recipient
@@ -319,6 +467,20 @@ impl MessageService {
         };
         let record_model = model.insert(txn).await?;
 
+        // Move attachments to message record
+        MessageAttachment::update_many()
+            .col_expr(
+                message_attachment::Column::DraftId,
+                Expr::value(Option::<String>::None),
+            )
+            .col_expr(
+                message_attachment::Column::RecordId,
+                Expr::value(record_id.clone()),
+            )
+            .filter(message_attachment::Column::DraftId.eq(&record_id))
+            .exec(txn)
+            .await?;
+
         // Delete message draft
         Self::delete_draft(ctx, record_id.clone()).await?;
 
@@ -483,6 +645,112 @@ impl MessageService {
         find_or_error!(Self::get_draft_optional(ctx, draft_id), MessageDraft)
     }
 
+    /// Lists the files attached to a message draft or record.
+    ///
+    /// Only the author of a draft, or the sender and recipients
+    /// of a message record, may see its attachments.
+    pub async fn get_attachments(
+        ctx: &ServiceContext<'_>,
+        GetMessageAttachments {
+            message_id,
+            user_id,
+        }: GetMessageAttachments,
+    ) -> Result<Vec<MessageAttachmentInfo>> {
+        match Self::get_draft_optional(ctx, &message_id).await? {
+            Some(draft) if draft.user_id != user_id => {
+                error!("User ID {user_id} is not the author of the draft");
+                return Err(Error::MessageDraftNotFound);
+            }
+            Some(_) => (),
+            None => {
+                Self::check_message_access(ctx, &message_id, user_id, "attachments")
+                    .await?;
+            }
+        }
+
+        let txn = ctx.transaction();
+        let attachments = MessageAttachment::find()
+            .filter(
+                Condition::any()
+                    .add(message_attachment::Column::DraftId.eq(&message_id))
+                    .add(message_attachment::Column::RecordId.eq(&message_id)),
+            )
+            .order_by_asc(message_attachment::Column::MessageAttachmentId)
+            .all(txn)
+            .await?
+            .into_iter()
+            .map(|model| MessageAttachmentInfo {
+                message_attachment_id: model.message_attachment_id,
+                created_at: model.created_at,
+                name: model.name,
+                mime: model.mime,
+                size: model.size,
+            })
+            .collect();
+
+        Ok(attachments)
+    }
+
+    /// Gets a download URL for a message attachment, if the user has access to it.
+    pub async fn get_attachment(
+        ctx: &ServiceContext<'_>,
+        GetMessageAttachment {
+            message_attachment_id,
+            user_id,
+        }: GetMessageAttachment,
+    ) -> Result<GetMessageAttachmentOutput> {
+        info!(
+            "Getting message attachment ID {message_attachment_id} for user ID {user_id}"
+        );
+
+        let txn = ctx.transaction();
+        let MessageAttachmentModel {
+            draft_id,
+            record_id,
+            name,
+            s3_hash,
+            mime,
+            size,
+            ..
+        } = MessageAttachment::find_by_id(message_attachment_id)
+            .one(txn)
+            .await?
+            .ok_or(Error::MessageAttachmentNotFound)?;
+
+        // To protect privacy, attachments which the user cannot access
+        // are reported as not existing.
+        let has_access = match (draft_id, record_id) {
+            (Some(draft_id), _) => {
+                Self::get_draft(ctx, &draft_id).await?.user_id == user_id
+            }
+            (None, Some(record_id)) => {
+                match Self::check_message_access(ctx, &record_id, user_id, "attachment")
+                    .await
+                {
+                    Ok(()) => true,
+                    Err(Error::MessageNotFound) => false,
+                    Err(error) => return Err(error),
+                }
+            }
+            (None, None) => false,
+        };
+
+        if !has_access {
+            error!("User ID {user_id} cannot access message attachment ID {message_attachment_id}");
+            return Err(Error::MessageAttachmentNotFound);
+        }
+
+        let url =
+            BlobService::get_download_url(ctx, &s3_hash, &mime, Some(&name)).await?;
+        Ok(GetMessageAttachmentOutput {
+            message_attachment_id,
+            name,
+            mime,
+            size,
+            url,
+        })
+    }
+
     // Helper methods
 
     /// Helper method to insert a group of `message_recipient` rows.
@@ -543,7 +811,7 @@ impl MessageService {
         // Check that the user has access to the message.
         // That is, the user is the sender or one of the recipients.
         if record.sender_id != user_id
-            && Self::any_recipient_exists(ctx, record_id, user_id).await?
+            && !Self::any_recipient_exists(ctx, record_id, user_id).await?
         {
             error!("User ID {user_id} is not a sender or recipient of the {purpose}",);
 
@@ -597,7 +865,14 @@ impl MessageService {
             language: cow!(locale),
         };
 
-        RenderService::render(ctx, wikitext, &page_info, &settings).await
+        RenderService::render(
+            ctx,
+            wikitext,
+            &page_info,
+            &settings,
+            RenderPriority::Interactive,
+        )
+        .await
     }
 }
 
@@ -18,6 +18,8 @@
  * along with this program. If not, see <http://www.gnu.org/licenses/>.
  */
 
+use time::OffsetDateTime;
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct CreateMessageDraft {
     pub user_id: i64,
@@ -29,6 +31,9 @@ pub struct CreateMessageDraft {
     pub wikitext: String,
     pub reply_to: Option<String>,
     pub forwarded_from: Option<String>,
+
+    #[serde(default)]
+    pub attachments: Vec<NewMessageAttachment>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -40,6 +45,12 @@ pub struct UpdateMessageDraft {
     pub locale: String,
     pub subject: String,
     pub wikitext: String,
+
+    #[serde(default)]
+    pub add_attachments: Vec<NewMessageAttachment>,
+
+    #[serde(default)]
+    pub remove_attachments: Vec<i64>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -49,6 +60,49 @@ pub struct SendMessageDraft {
 
 pub type DeleteMessageDraft = SendMessageDraft;
 
+/// A file to attach to a message draft.
+///
+/// The blob is uploaded the same way as for files, starting
+/// with `BlobService::start_upload()`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct NewMessageAttachment {
+    pub uploaded_blob_id: String,
+    pub name: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct GetMessageAttachments {
+    /// The ID of either a draft, or a sent message record.
+    pub message_id: String,
+    pub user_id: i64,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct MessageAttachmentInfo {
+    pub message_attachment_id: i64,
+
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+    pub name: String,
+    pub mime: String,
+    pub size: i64,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct GetMessageAttachment {
+    pub message_attachment_id: i64,
+    pub user_id: i64,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct GetMessageAttachmentOutput {
+    pub message_attachment_id: i64,
+    pub name: String,
+    pub mime: String,
+    pub size: i64,
+    pub url: String,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct DraftRecipients {
     #[serde(rename = "r")]
@@ -69,25 +69,33 @@ pub mod filter;
 pub mod import;
 pub mod job;
 pub mod link;
+pub mod membership;
 pub mod message;
 pub mod message_report;
 pub mod mfa;
+pub mod oidc;
 pub mod outdate;
 pub mod page;
 pub mod page_query;
 pub mod page_revision;
 pub mod parent;
 pub mod password;
+pub mod rate_limit;
 pub mod relation;
 pub mod render;
+pub mod scan;
 pub mod score;
+pub mod scrub;
 pub mod session;
 pub mod settings;
 pub mod site;
 pub mod special_page;
+pub mod storage;
+pub mod tag;
 pub mod text;
 pub mod user;
 pub mod user_bot_owner;
+pub mod user_data;
 pub mod view;
 pub mod vote;
 
@@ -103,9 +111,11 @@ pub use self::file_revision::FileRevisionService;
 pub use self::filter::FilterService;
 pub use self::job::JobService;
 pub use self::link::LinkService;
+pub use self::membership::MembershipService;
 pub use self::message::MessageService;
 pub use self::message_report::MessageReportService;
 pub use self::mfa::MfaService;
+pub use self::oidc::OidcService;
 pub use self::outdate::OutdateService;
 pub use self::page::PageService;
 // TODO convert page attribution to a type of relation
@@ -113,16 +123,21 @@ pub use self::page_query::PageQueryService;
 pub use self::page_revision::PageRevisionService;
 pub use self::parent::ParentService;
 pub use self::password::PasswordService;
+pub use self::rate_limit::RateLimitService;
 pub use self::relation::RelationService;
 pub use self::render::RenderService;
 pub use self::score::ScoreService;
+pub use self::scrub::ScrubService;
 pub use self::session::SessionService;
 pub use self::settings::SettingsService;
 pub use self::site::SiteService;
 pub use self::special_page::SpecialPageService;
+pub use self::storage::StorageService;
+pub use self::tag::TagService;
 pub use self::text::TextService;
 pub use self::user::UserService;
 // TODO convert user-bot to a type of relation
 pub use self::user_bot_owner::UserBotOwnerService;
-pub use self::view::ViewService;
+pub use self::user_data::UserDataService;
+pub use self::view::{ViewCacheService, ViewService};
 pub use self::vote::VoteService;
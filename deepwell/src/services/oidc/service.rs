@@ -0,0 +1,549 @@
+/*
+ * services/oidc/service.rs
+ *
+ * DEEPWELL - Wikijump API provider and database manager
+ * Copyright (C) 2019-2025 Wikijump Team
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Authentication through external OpenID Connect identity providers.
+//!
+//! This implements the relying party side of the authorization code flow, with PKCE.
+//! Framerail sends the user to the URL produced by `authorize()`, and once the provider
+//! redirects them back, passes the code and state to `callback()`. The code is then
+//! exchanged for an ID token, which is validated against the provider's published keys.
+//!
+//! Each identity (a provider and its subject identifier) is linked to one user.
+//! An identity which is not yet linked can be linked to an existing user by email,
+//! or used to create a new user, depending on the provider's configuration.
+//! After this, a session is created as it would be for a password login,
+//! including requiring MFA if the user has it set up.
+
+use super::prelude::*;
+use crate::models::sea_orm_active_enums::UserType;
+use crate::models::user::{self, Entity as User, Model as UserModel};
+use crate::models::user_oidc_identity::{
+    self, Entity as UserOidcIdentity, Model as UserOidcIdentityModel,
+};
+use crate::services::session::CreateSession;
+use crate::services::user::{CreateUser, CreateUserOutput};
+use crate::services::{SessionService, UserService};
+use crate::types::OidcProvider;
+use crate::utils::assert_is_csprng;
+use data_encoding::BASE64URL_NOPAD;
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use once_cell::sync::Lazy;
+use rand::distributions::{Alphanumeric, DistString};
+use rand::thread_rng;
+use redis::AsyncCommands;
+use reqwest::{Client, Url};
+use serde::de::DeserializeOwned;
+use sha2::{Digest, Sha256};
+use std::time::Duration as StdDuration;
+
+/// HTTP client used for all requests to identity providers.
+static HTTP_CLIENT: Lazy<Client> = Lazy::new(|| {
+    Client::builder()
+        .timeout(StdDuration::from_secs(10))
+        .build()
+        .expect("Unable to build HTTP client")
+});
+
+/// Length of the randomly-generated `state` and `nonce` parameters.
+const STATE_LENGTH: usize = 32;
+
+/// Length of the randomly-generated PKCE code verifier.
+///
+/// Must be between 43 and 128, see RFC 7636.
+const CODE_VERIFIER_LENGTH: usize = 64;
+
+/// Length of the unused password set for users created through a provider.
+const PASSWORD_LENGTH: usize = 64;
+
+#[derive(Debug)]
+pub struct OidcService;
+
+impl OidcService {
+    /// Lists the configured providers, for display on login pages.
+    pub fn providers(config: &Config) -> Vec<OidcProviderInfo> {
+        config
+            .oidc_providers
+            .iter()
+            .map(|provider| OidcProviderInfo {
+                name: provider.name.clone(),
+                display_name: provider.display_name.clone(),
+            })
+            .collect()
+    }
+
+    fn get_provider<'a>(config: &'a Config, name: &str) -> Result<&'a OidcProvider> {
+        config
+            .oidc_providers
+            .iter()
+            .find(|provider| provider.name == name)
+            .ok_or(Error::OidcProviderNotFound)
+    }
+
+    /// Begins a login through the given provider.
+    ///
+    /// # Returns
+    /// The URL to send the user to, and the `state` value which the provider will
+    /// return to us. Framerail should bind the state to the user's browser (such as
+    /// in a cookie) and verify it matches on the callback.
+    pub async fn authorize(
+        ctx: &ServiceContext<'_>,
+        OidcAuthorize { provider }: OidcAuthorize,
+    ) -> Result<OidcAuthorizeOutput> {
+        let config = ctx.config();
+        let provider = Self::get_provider(config, &provider)?;
+        info!(
+            "Starting login through identity provider '{}'",
+            provider.name
+        );
+
+        let discovery = Self::discover(provider).await?;
+
+        // Generate secrets for this login
+        let (state, nonce, code_verifier) = {
+            let mut rng = thread_rng();
+            assert_is_csprng(&rng);
+
+            (
+                Alphanumeric.sample_string(&mut rng, STATE_LENGTH),
+                Alphanumeric.sample_string(&mut rng, STATE_LENGTH),
+                Alphanumeric.sample_string(&mut rng, CODE_VERIFIER_LENGTH),
+            )
+        };
+
+        let code_challenge = BASE64URL_NOPAD.encode(&Sha256::digest(&code_verifier));
+
+        // Build authorization URL
+        let mut scopes = provider.scopes.clone();
+        if !scopes.iter().any(|scope| scope == "openid") {
+            scopes.insert(0, str!("openid"));
+        }
+
+        let mut url = Url::parse(&discovery.authorization_endpoint).map_err(|error| {
+            Error::OidcProvider(format!("Invalid authorization endpoint: {error}"))
+        })?;
+
+        url.query_pairs_mut()
+            .append_pair("response_type", "code")
+            .append_pair("client_id", &provider.client_id)
+            .append_pair("redirect_uri", &provider.redirect_uri)
+            .append_pair("scope", &scopes.join(" "))
+            .append_pair("state", &state)
+            .append_pair("nonce", &nonce)
+            .append_pair("code_challenge", &code_challenge)
+            .append_pair("code_challenge_method", "S256");
+
+        // Store login state until the callback
+        let login_state = OidcLoginState {
+            provider: provider.name.clone(),
+            nonce,
+            code_verifier,
+        };
+
+        let mut redis = ctx.redis_connect().await?;
+        redis
+            .set_ex::<_, _, ()>(
+                state_key(&state),
+                serde_json::to_string(&login_state)?,
+                config.oidc_state_expiry.as_secs(),
+            )
+            .await?;
+
+        Ok(OidcAuthorizeOutput {
+            authorization_url: url.into(),
+            state,
+        })
+    }
+
+    /// Completes a login through the given provider.
+    ///
+    /// Exchanges the authorization code, validates the resulting ID token,
+    /// and creates a session for the linked user.
+    pub async fn callback(
+        ctx: &ServiceContext<'_>,
+        OidcCallback {
+            provider,
+            state,
+            code,
+            ip_address,
+            user_agent,
+            locales,
+        }: OidcCallback,
+    ) -> Result<OidcCallbackOutput> {
+        let provider = Self::get_provider(ctx.config(), &provider)?;
+        info!(
+            "Completing login through identity provider '{}'",
+            provider.name
+        );
+
+        // Retrieve login state, each state can only be used once
+        let login_state: OidcLoginState = {
+            let mut redis = ctx.redis_connect().await?;
+            let data: Option<String> = redis.get_del(state_key(&state)).await?;
+            match data {
+                Some(data) => serde_json::from_str(&data)?,
+                None => {
+                    warn!("Login state not found, may have expired or already been used");
+                    return Err(Error::OidcStateInvalid);
+                }
+            }
+        };
+
+        if login_state.provider != provider.name {
+            warn!(
+                "Login state is for provider '{}', not '{}'",
+                login_state.provider, provider.name,
+            );
+            return Err(Error::OidcStateInvalid);
+        }
+
+        // Exchange code and validate token
+        let discovery = Self::discover(provider).await?;
+        let id_token =
+            Self::exchange_code(ctx, provider, &discovery, &code, &login_state).await?;
+        let claims = Self::validate_token(provider, &discovery, &id_token).await?;
+
+        if claims.nonce.as_deref() != Some(login_state.nonce.as_str()) {
+            warn!("ID token nonce does not match login state");
+            return Err(Error::InvalidAuthentication);
+        }
+
+        // Find or create the user, and log them in
+        let (user, user_created) =
+            Self::resolve_user(ctx, provider, &claims, locales).await?;
+
+        let needs_mfa = user.multi_factor_secret.is_some();
+        info!(
+            "Identity provider authentication for user ID {} succeeded (login complete: {})",
+            user.user_id, !needs_mfa,
+        );
+
+        let session_token = SessionService::create(
+            ctx,
+            CreateSession {
+                user_id: user.user_id,
+                ip_address,
+                user_agent,
+                restricted: needs_mfa,
+            },
+        )
+        .await?;
+
+        Ok(OidcCallbackOutput {
+            session_token,
+            needs_mfa,
+            user_id: user.user_id,
+            user_created,
+        })
+    }
+
+    /// Fetches the provider's discovery document.
+    async fn discover(provider: &OidcProvider) -> Result<OidcDiscovery> {
+        let issuer = provider.issuer.trim_end_matches('/');
+        let url = format!("{issuer}/.well-known/openid-configuration");
+        let discovery: OidcDiscovery = fetch_json(&url).await?;
+
+        // The issuer must match exactly, per OpenID Connect Discovery section 4.3
+        if discovery.issuer.trim_end_matches('/') != issuer {
+            error!(
+                "Discovery document issuer '{}' does not match configured issuer '{}'",
+                discovery.issuer, provider.issuer,
+            );
+            return Err(Error::OidcProvider(str!("Issuer mismatch")));
+        }
+
+        Ok(discovery)
+    }
+
+    /// Exchanges an authorization code for an ID token.
+    async fn exchange_code(
+        ctx: &ServiceContext<'_>,
+        provider: &OidcProvider,
+        discovery: &OidcDiscovery,
+        code: &str,
+        login_state: &OidcLoginState,
+    ) -> Result<String> {
+        debug!("Exchanging authorization code with identity provider");
+
+        let mut form = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", &provider.redirect_uri),
+            ("client_id", &provider.client_id),
+            ("code_verifier", &login_state.code_verifier),
+        ];
+
+        if let Some(client_secret) = ctx.oidc_client_secret(provider) {
+            form.push(("client_secret", client_secret));
+        }
+
+        let response = HTTP_CLIENT
+            .post(&discovery.token_endpoint)
+            .form(&form)
+            .send()
+            .await?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            error!("Token exchange failed ({status}): {body}");
+
+            // A rejected code is an authentication failure, not a provider issue
+            return Err(if status.is_client_error() {
+                Error::InvalidAuthentication
+            } else {
+                Error::OidcProvider(format!("Token endpoint returned {status}"))
+            });
+        }
+
+        let OidcTokenResponse { id_token } = response.json().await?;
+        Ok(id_token)
+    }
+
+    /// Validates an ID token against the provider's keys, and returns its claims.
+    async fn validate_token(
+        provider: &OidcProvider,
+        discovery: &OidcDiscovery,
+        id_token: &str,
+    ) -> Result<OidcClaims> {
+        debug!("Validating ID token from identity provider");
+
+        let header = jsonwebtoken::decode_header(id_token).map_err(|error| {
+            warn!("Unable to decode ID token header: {error}");
+            Error::InvalidAuthentication
+        })?;
+
+        // Symmetric algorithms would use the client secret as the key,
+        // only accept tokens signed with the provider's published keys.
+        if matches!(
+            header.alg,
+            Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512,
+        ) {
+            warn!("ID token uses unsupported algorithm {:?}", header.alg);
+            return Err(Error::InvalidAuthentication);
+        }
+
+        let jwks: JwkSet = fetch_json(&discovery.jwks_uri).await?;
+        let jwk = match header.kid {
+            Some(ref kid) => jwks.find(kid),
+            None if jwks.keys.len() == 1 => jwks.keys.first(),
+            None => None,
+        };
+
+        let key = match jwk {
+            Some(jwk) => DecodingKey::from_jwk(jwk).map_err(|error| {
+                Error::OidcProvider(format!("Invalid signing key: {error}"))
+            })?,
+            None => {
+                warn!(
+                    "No signing key found for ID token (key ID {:?})",
+                    header.kid
+                );
+                return Err(Error::InvalidAuthentication);
+            }
+        };
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[&discovery.issuer]);
+        validation.set_audience(&[&provider.client_id]);
+        validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+
+        match jsonwebtoken::decode::<OidcClaims>(id_token, &key, &validation) {
+            Ok(token) => Ok(token.claims),
+            Err(error) => {
+                warn!("ID token failed validation: {error}");
+                Err(Error::InvalidAuthentication)
+            }
+        }
+    }
+
+    /// Finds the user for this identity, linking or creating one if needed.
+    ///
+    /// # Returns
+    /// The user, and whether it was newly created.
+    async fn resolve_user(
+        ctx: &ServiceContext<'_>,
+        provider: &OidcProvider,
+        claims: &OidcClaims,
+        locales: Vec<String>,
+    ) -> Result<(UserModel, bool)> {
+        let txn = ctx.transaction();
+
+        // Existing identity
+        if let Some(identity) =
+            UserOidcIdentity::find_by_id((provider.name.clone(), claims.sub.clone()))
+                .one(txn)
+                .await?
+        {
+            debug!("Identity is linked to user ID {}", identity.user_id);
+
+            let user_id = identity.user_id;
+            let mut model = identity.into_active_model();
+            model.email = Set(claims.email.clone());
+            model.last_login_at = Set(Some(now()));
+            model.update(txn).await?;
+
+            let user = UserService::get(ctx, Reference::Id(user_id)).await?;
+            return Ok((user, false));
+        }
+
+        // Link to an existing user by verified email
+        if provider.link_by_email && claims.email_verified {
+            if let Some(ref email) = claims.email {
+                let user = User::find()
+                    .filter(
+                        Condition::all()
+                            .add(user::Column::Email.eq(email.as_str()))
+                            .add(user::Column::UserType.eq(UserType::Regular))
+                            .add(user::Column::DeletedAt.is_null()),
+                    )
+                    .one(txn)
+                    .await?;
+
+                if let Some(user) = user {
+                    info!("Linking identity to user ID {} by email", user.user_id);
+                    Self::link(ctx, provider, claims, user.user_id).await?;
+                    return Ok((user, false));
+                }
+            }
+        }
+
+        // Create a new user
+        if !provider.create_users {
+            warn!("Identity is not linked, and provider does not permit creating users");
+            return Err(Error::OidcIdentityNotLinked);
+        }
+
+        let email = claims.email.clone().unwrap_or_default();
+        let name = match (&claims.preferred_username, &claims.name) {
+            (Some(name), _) | (None, Some(name)) => name.clone(),
+            (None, None) => match email.split_once('@') {
+                Some((local, _)) => str!(local),
+                None => {
+                    warn!("Identity has no name or email, cannot create user");
+                    return Err(Error::OidcIdentityNotLinked);
+                }
+            },
+        };
+
+        // The user authenticates through the provider, so their password
+        // is random and never revealed. They may set one by resetting it.
+        let password = {
+            let mut rng = thread_rng();
+            assert_is_csprng(&rng);
+            Alphanumeric.sample_string(&mut rng, PASSWORD_LENGTH)
+        };
+
+        info!("Creating new user '{name}' for identity");
+        let CreateUserOutput { user_id, .. } = UserService::create(
+            ctx,
+            CreateUser {
+                user_type: UserType::Regular,
+                name,
+                email,
+                locales,
+                password,
+                bypass_filter: false,
+                bypass_email_verification: claims.email_verified,
+            },
+        )
+        .await?;
+
+        Self::link(ctx, provider, claims, user_id).await?;
+        let user = UserService::get(ctx, Reference::Id(user_id)).await?;
+        Ok((user, true))
+    }
+
+    async fn link(
+        ctx: &ServiceContext<'_>,
+        provider: &OidcProvider,
+        claims: &OidcClaims,
+        user_id: i64,
+    ) -> Result<()> {
+        let txn = ctx.transaction();
+        let model = user_oidc_identity::ActiveModel {
+            provider: Set(provider.name.clone()),
+            subject: Set(claims.sub.clone()),
+            user_id: Set(user_id),
+            email: Set(claims.email.clone()),
+            last_login_at: Set(Some(now())),
+            ..Default::default()
+        };
+
+        model.insert(txn).await?;
+        Ok(())
+    }
+
+    /// Gets all identities linked to a user.
+    pub async fn get_identities(
+        ctx: &ServiceContext<'_>,
+        user_id: i64,
+    ) -> Result<Vec<UserOidcIdentityModel>> {
+        let txn = ctx.transaction();
+        let identities = UserOidcIdentity::find()
+            .filter(user_oidc_identity::Column::UserId.eq(user_id))
+            .order_by_asc(user_oidc_identity::Column::Provider)
+            .all(txn)
+            .await?;
+
+        Ok(identities)
+    }
+
+    /// Removes the link between a user and their identity at a provider.
+    pub async fn unlink(
+        ctx: &ServiceContext<'_>,
+        OidcUnlink { user_id, provider }: OidcUnlink,
+    ) -> Result<()> {
+        info!("Unlinking identity provider '{provider}' from user ID {user_id}");
+
+        let txn = ctx.transaction();
+        let DeleteResult { rows_affected, .. } = UserOidcIdentity::delete_many()
+            .filter(
+                Condition::all()
+                    .add(user_oidc_identity::Column::UserId.eq(user_id))
+                    .add(user_oidc_identity::Column::Provider.eq(provider)),
+            )
+            .exec(txn)
+            .await?;
+
+        if rows_affected == 0 {
+            return Err(Error::OidcIdentityNotFound);
+        }
+
+        Ok(())
+    }
+}
+
+#[inline]
+fn state_key(state: &str) -> String {
+    format!("oidc-state:{state}")
+}
+
+async fn fetch_json<T: DeserializeOwned>(url: &str) -> Result<T> {
+    let value = HTTP_CLIENT
+        .get(url)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+
+    Ok(value)
+}
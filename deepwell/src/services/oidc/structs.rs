@@ -0,0 +1,107 @@
+/*
+ * services/oidc/structs.rs
+ *
+ * DEEPWELL - Wikijump API provider and database manager
+ * Copyright (C) 2019-2025 Wikijump Team
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use std::net::IpAddr;
+
+#[derive(Serialize, Debug, Clone)]
+pub struct OidcProviderInfo {
+    pub name: String,
+    pub display_name: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct OidcAuthorize {
+    pub provider: String,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct OidcAuthorizeOutput {
+    pub authorization_url: String,
+    pub state: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct OidcCallback {
+    pub provider: String,
+    pub state: String,
+    pub code: String,
+    pub ip_address: IpAddr,
+    pub user_agent: String,
+
+    /// Locales to use if a new user is created.
+    pub locales: Vec<String>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct OidcCallbackOutput {
+    pub session_token: String,
+    pub needs_mfa: bool,
+    pub user_id: i64,
+    pub user_created: bool,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct OidcUnlink {
+    pub user_id: i64,
+    pub provider: String,
+}
+
+/// Data kept between starting an authorization and its callback.
+///
+/// Stored in Redis, keyed by the `state` parameter.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct OidcLoginState {
+    pub provider: String,
+    pub nonce: String,
+    pub code_verifier: String,
+}
+
+/// The fields used from a provider's discovery document.
+///
+/// See <https://openid.net/specs/openid-connect-discovery-1_0.html#ProviderMetadata>.
+#[derive(Deserialize, Debug, Clone)]
+pub struct OidcDiscovery {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub jwks_uri: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct OidcTokenResponse {
+    pub id_token: String,
+}
+
+/// The claims used from a validated ID token.
+///
+/// Standard claims such as `exp`, `iss`, and `aud` are checked
+/// during validation, and are not needed after that.
+#[derive(Deserialize, Debug, Clone)]
+pub struct OidcClaims {
+    pub sub: String,
+    pub nonce: Option<String>,
+    pub email: Option<String>,
+
+    #[serde(default)]
+    pub email_verified: bool,
+
+    pub preferred_username: Option<String>,
+    pub name: Option<String>,
+}
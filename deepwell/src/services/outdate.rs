@@ -20,7 +20,7 @@
 
 use super::prelude::*;
 use crate::models::page::Model as PageModel;
-use crate::services::{JobService, LinkService, PageService};
+use crate::services::{JobService, LinkService, PageService, ViewCacheService};
 use crate::types::{ConnectionType, PageOrder};
 use crate::utils::split_category_name;
 
@@ -62,6 +62,7 @@ impl OutdateService {
         try_join!(
             Self::process_page_edit(ctx, site_id, page_id, slug, depth),
             Self::outdate_incoming_links(ctx, page_id, depth),
+            Self::outdate_missing_includes(ctx, site_id, page_id, slug, depth),
         )?;
 
         Ok(())
@@ -87,14 +88,18 @@ impl OutdateService {
     }
 
     /// Queues the given pages for re-rendering.
+    ///
+    /// Cached views of the page are also cleared, since whatever
+    /// caused this outdating may be visible on it.
     pub async fn outdate(
         ctx: &ServiceContext<'_>,
         page_id: i64,
         depth: u32,
     ) -> Result<()> {
-        let PageModel { site_id, .. } =
+        let PageModel { site_id, slug, .. } =
             PageService::get_direct(ctx, page_id, false).await?;
 
+        ViewCacheService::invalidate_page(ctx, site_id, &slug);
         JobService::queue_rerender_page(ctx, site_id, page_id, depth + 1).await
     }
 
@@ -140,6 +145,40 @@ impl OutdateService {
         Ok(())
     }
 
+    /// Outdates pages which include this slot, from when it had no page.
+    ///
+    /// When a page is created, the pages which attempted to include it
+    /// need to be rerendered to replace the missing include message.
+    pub async fn outdate_missing_includes(
+        ctx: &ServiceContext<'_>,
+        site_id: i64,
+        page_id: i64,
+        slug: &str,
+        depth: u32,
+    ) -> Result<()> {
+        const CONNECTION_TYPES: &[ConnectionType] = &[
+            ConnectionType::IncludeMessy,
+            ConnectionType::IncludeElements,
+            ConnectionType::Component,
+        ];
+
+        for id in LinkService::get_to_missing_unchecked(
+            ctx,
+            site_id,
+            slug,
+            Some(CONNECTION_TYPES),
+        )
+        .await?
+        .connections
+        .iter()
+        .map(|connection| connection.from_page_id)
+        .filter(|id| *id != page_id)
+        {
+            Self::outdate(ctx, id, depth).await?;
+        }
+        Ok(())
+    }
+
     pub async fn outdate_templates(
         ctx: &ServiceContext<'_>,
         site_id: i64,
@@ -23,6 +23,7 @@ use crate::models::page::{self, Entity as Page, Model as PageModel};
 use crate::models::page_category::Model as PageCategoryModel;
 use crate::models::page_revision::Model as PageRevisionModel;
 use crate::models::sea_orm_active_enums::PageRevisionType;
+use crate::services::category::CategoryAction;
 use crate::services::filter::{FilterClass, FilterType};
 use crate::services::page_revision::{
     CreateFirstPageRevision, CreateFirstPageRevisionOutput, CreatePageRevision,
@@ -30,7 +31,8 @@ use crate::services::page_revision::{
     CreateTombstonePageRevision,
 };
 use crate::services::{
-    CategoryService, FilterService, PageRevisionService, SiteService, TextService,
+    CategoryService, FilterService, PageRevisionService, SiteService, TagService,
+    TextService, ViewCacheService,
 };
 use crate::types::PageOrder;
 use crate::utils::{get_category_name, trim_default};
@@ -75,10 +77,20 @@ impl PageService {
         }
 
         // Create category if not already present
-        let PageCategoryModel { category_id, .. } =
+        let category =
             CategoryService::get_or_create(ctx, site_id, get_category_name(&slug))
                 .await?;
 
+        CategoryService::check_permission(
+            ctx,
+            &category,
+            user_id,
+            CategoryAction::Create,
+        )
+        .await?;
+
+        let PageCategoryModel { category_id, .. } = category;
+
         // Insert page
         let model = page::ActiveModel {
             site_id: Set(site_id),
@@ -136,17 +148,27 @@ impl PageService {
                     wikitext,
                     title,
                     alt_title,
-                    tags,
+                    mut tags,
                 },
         }: EditPage<'_>,
     ) -> Result<Option<EditPageOutput>> {
         let txn = ctx.transaction();
         let PageModel {
             page_id,
+            page_category_id,
             latest_revision_id,
             ..
         } = Self::get(ctx, site_id, reference).await?;
 
+        Self::check_category_permission(
+            ctx,
+            site_id,
+            page_category_id,
+            user_id,
+            CategoryAction::Edit,
+        )
+        .await?;
+
         // Perform filter validation
         Self::run_filter(
             ctx,
@@ -161,6 +183,12 @@ impl PageService {
         )
         .await?;
 
+        // Normalize tags, and ensure they are permitted on this site
+        if let Maybe::Set(ref mut tags) = tags {
+            TagService::normalize(tags);
+            TagService::check_whitelist(ctx, site_id, tags).await?;
+        }
+
         // Get and check latest revision
         let last_revision =
             PageRevisionService::get_latest(ctx, site_id, page_id).await?;
@@ -231,11 +259,21 @@ impl PageService {
         let txn = ctx.transaction();
         let PageModel {
             page_id,
+            page_category_id: old_category_id,
             slug: old_slug,
             latest_revision_id,
             ..
         } = Self::get(ctx, site_id, reference).await?;
 
+        Self::check_category_permission(
+            ctx,
+            site_id,
+            old_category_id,
+            user_id,
+            CategoryAction::Rename,
+        )
+        .await?;
+
         // Check last revision ID argument
         check_last_revision(None, latest_revision_id, last_revision_id)?;
 
@@ -250,10 +288,23 @@ impl PageService {
         Self::check_conflicts(ctx, site_id, &new_slug, "move").await?;
 
         // Create category if not already present
-        let PageCategoryModel { category_id, .. } =
+        let category =
             CategoryService::get_or_create(ctx, site_id, get_category_name(&new_slug))
                 .await?;
 
+        // Moving into a different category is like creating a page there
+        if category.category_id != old_category_id {
+            CategoryService::check_permission(
+                ctx,
+                &category,
+                user_id,
+                CategoryAction::Create,
+            )
+            .await?;
+        }
+
+        let PageCategoryModel { category_id, .. } = category;
+
         // Get latest revision
         let last_revision =
             PageRevisionService::get_latest(ctx, site_id, page_id).await?;
@@ -333,10 +384,20 @@ impl PageService {
         let txn = ctx.transaction();
         let PageModel {
             page_id,
+            page_category_id,
             latest_revision_id,
             ..
         } = Self::get(ctx, site_id, reference).await?;
 
+        Self::check_category_permission(
+            ctx,
+            site_id,
+            page_category_id,
+            user_id,
+            CategoryAction::Delete,
+        )
+        .await?;
+
         // Get and check latest revision
         let last_revision =
             PageRevisionService::get_latest(ctx, site_id, page_id).await?;
@@ -461,10 +522,20 @@ impl PageService {
         let txn = ctx.transaction();
         let PageModel {
             page_id,
+            page_category_id,
             latest_revision_id,
             ..
         } = Self::get(ctx, site_id, reference).await?;
 
+        Self::check_category_permission(
+            ctx,
+            site_id,
+            page_category_id,
+            user_id,
+            CategoryAction::Edit,
+        )
+        .await?;
+
         // Get target revision and latest revision
         let (target_revision, last_revision) = try_join!(
             PageRevisionService::get(ctx, site_id, page_id, revision_number),
@@ -474,6 +545,10 @@ impl PageService {
         // TODO Handle hidden fields, see https://scuttle.atlassian.net/browse/WJ-1285
         let _ = target_revision.hidden;
 
+        // Ensure the restored tags are still permitted on this site,
+        // since the whitelist may have changed since that revision.
+        TagService::check_whitelist(ctx, site_id, &target_revision.tags).await?;
+
         // Check last revision ID
         check_last_revision(Some(&last_revision), latest_revision_id, last_revision_id)?;
 
@@ -557,7 +632,8 @@ impl PageService {
             ..Default::default()
         };
 
-        model.update(txn).await?;
+        let page = model.update(txn).await?;
+        ViewCacheService::invalidate_page(ctx, site_id, &page.slug);
         Ok(())
     }
 
@@ -767,6 +843,21 @@ impl PageService {
         Ok(pages)
     }
 
+    /// Ensures that a user may perform this action on pages in the given category.
+    ///
+    /// See `CategoryService::check_permission()`.
+    pub async fn check_category_permission(
+        ctx: &ServiceContext<'_>,
+        site_id: i64,
+        category_id: i64,
+        user_id: i64,
+        action: CategoryAction,
+    ) -> Result<()> {
+        let category =
+            CategoryService::get(ctx, site_id, Reference::Id(category_id)).await?;
+        CategoryService::check_permission(ctx, &category, user_id, action).await
+    }
+
     /// Checks to see if a page already exists at the slug specified.
     ///
     /// If so, this method fails with `Error::PageExists`. Otherwise it returns nothing.
@@ -25,6 +25,7 @@ use crate::services::score::ScoreValue;
 use crate::types::PageDetails;
 use ftml::layout::Layout;
 use ftml::parsing::ParseError;
+use serde_json::Value as JsonValue;
 use time::OffsetDateTime;
 
 #[derive(Deserialize, Debug, Clone)]
@@ -115,6 +116,8 @@ pub struct GetPageOutput {
     pub revision_user_id: i64,
     pub wikitext: Option<String>,
     pub compiled_html: Option<String>,
+    pub compiled_style: Option<String>,
+    pub compiled_meta: Option<JsonValue>,
 
     #[serde(with = "time::serde::rfc3339")]
     pub compiled_at: OffsetDateTime,
@@ -24,8 +24,8 @@ use super::prelude::*;
 use crate::models::page::{self, Entity as Page};
 use crate::models::page_category::{self, Entity as PageCategory};
 use crate::models::page_connection::{self, Entity as PageConnection};
-use crate::models::page_parent::{self, Entity as PageParent};
-use crate::models::{page_revision, text};
+use crate::models::{page_revision, relation, text};
+use crate::services::relation::page_child_ids_subquery;
 use crate::services::{PageService, ParentService};
 use sea_query::{Expr, Query};
 use std::convert::Infallible;
@@ -166,10 +166,12 @@ impl PageQueryService {
         // In the places where this is used, this could be implemented
         // as a subquery, meaning:
         //
-        // SELECT child_page_id FROM page_parent
-        // WHERE parent_page_id IN (
-        //     SELECT parent_page_id FROM page_parent
-        //     WHERE child_page_id = $0
+        // SELECT from_id FROM relation
+        // WHERE relation_type = 'parent'
+        // AND dest_id IN (
+        //     SELECT dest_id FROM relation
+        //     WHERE relation_type = 'parent'
+        //     AND from_id = $0
         // )
         //
         // However looking at the query plan, this would be implemented
@@ -184,23 +186,18 @@ impl PageQueryService {
                 )
                 .await?
                 .into_iter()
-                .map(|parent| parent.parent_page_id)
+                .map(|parent| parent.dest_id)
             };
         }
 
         let page_parent_condition = match page_parent {
             // Pages with no parents.
-            // This means that there should be no rows in `page_parent`
+            // This means that there should be no active parent relations
             // where they are the child page.
             PageParentSelector::NoParent => {
                 debug!("Selecting pages with no parents");
 
-                page::Column::PageId.not_in_subquery(
-                    Query::select()
-                        .column(page_parent::Column::ChildPageId)
-                        .from(PageParent)
-                        .to_owned(),
-                )
+                page::Column::PageId.not_in_subquery(page_child_ids_subquery(None))
             }
 
             // Pages which are siblings of the current page,
@@ -208,15 +205,9 @@ impl PageQueryService {
             PageParentSelector::SameParents => {
                 debug!("Selecting pages are siblings under the given parents");
 
-                page::Column::PageId.in_subquery(
-                    Query::select()
-                        .column(page_parent::Column::ChildPageId)
-                        .from(PageParent)
-                        .and_where(
-                            page_parent::Column::ParentPageId.is_in(get_parents!()),
-                        )
-                        .to_owned(),
-                )
+                page::Column::PageId.in_subquery(page_child_ids_subquery(Some(
+                    relation::Column::DestId.is_in(get_parents!()),
+                )))
             }
 
             // Pages which are not siblings of the current page,
@@ -224,37 +215,18 @@ impl PageQueryService {
             PageParentSelector::DifferentParents => {
                 debug!("Selecting pages which are not siblings under the given parents",);
 
-                let parents = ParentService::get_parents(
-                    ctx,
-                    current_site_id,
-                    Reference::Id(current_page_id),
-                )
-                .await?
-                .into_iter()
-                .map(|parent| parent.parent_page_id);
-
-                page::Column::PageId.in_subquery(
-                    Query::select()
-                        .column(page_parent::Column::ChildPageId)
-                        .from(PageParent)
-                        .and_where(
-                            page_parent::Column::ParentPageId.is_not_in(get_parents!()),
-                        )
-                        .to_owned(),
-                )
+                page::Column::PageId.in_subquery(page_child_ids_subquery(Some(
+                    relation::Column::DestId.is_not_in(get_parents!()),
+                )))
             }
 
             // Pages which are children of the current page.
             PageParentSelector::ChildOf => {
                 debug!("Selecting pages which are children of the current page",);
 
-                page::Column::PageId.in_subquery(
-                    Query::select()
-                        .column(page_parent::Column::ChildPageId)
-                        .from(PageParent)
-                        .and_where(page_parent::Column::ParentPageId.eq(current_page_id))
-                        .to_owned(),
-                )
+                page::Column::PageId.in_subquery(page_child_ids_subquery(Some(
+                    relation::Column::DestId.eq(current_page_id),
+                )))
             }
 
             // Pages with any of the specified parents.
@@ -268,13 +240,9 @@ impl PageQueryService {
                     .into_iter()
                     .map(|page| page.page_id);
 
-                page::Column::PageId.in_subquery(
-                    Query::select()
-                        .column(page_parent::Column::ChildPageId)
-                        .from(PageParent)
-                        .and_where(page_parent::Column::ParentPageId.is_in(parent_ids))
-                        .to_owned(),
-                )
+                page::Column::PageId.in_subquery(page_child_ids_subquery(Some(
+                    relation::Column::DestId.is_in(parent_ids),
+                )))
             }
         };
         condition = condition.add(page_parent_condition);
@@ -385,9 +353,7 @@ impl PageQueryService {
                     error!("Ordering by page size, not yet implemented");
                     join_revision!();
                     join_text!();
-                    let col = Expr::col(text::Column::Contents);
-                    let expr = SimpleExpr::FunctionCall(Func::char_length(col));
-                    query = query.order_by(expr, order);
+                    query = query.order_by(text::Column::Length, order);
                 }
                 OrderProperty::Score => {
                     error!("Ordering by score, not yet implemented");
@@ -23,8 +23,8 @@
 
 use super::prelude::*;
 use crate::models::{
-    page::Model as PageModel, page_parent::Model as PageParentModel,
-    page_revision::Model as PageRevisionModel,
+    page::Model as PageModel, page_revision::Model as PageRevisionModel,
+    relation::Model as RelationModel,
 };
 use crate::services::score::ScoreValue;
 use std::borrow::Cow;
@@ -291,7 +291,7 @@ pub struct PageResult {
     metadata: PageModel,
     last_revision: PageRevisionModel,
     // last_comment: TODO,
-    page_parents: Vec<PageParentModel>,
+    page_parents: Vec<RelationModel>,
     wikitext: String,
     score: f32,
 }
@@ -23,11 +23,11 @@ use crate::models::page_revision::{
     self, Entity as PageRevision, Model as PageRevisionModel,
 };
 use crate::models::sea_orm_active_enums::PageRevisionType;
-use crate::services::render::RenderOutput;
+use crate::services::render::{RenderOutput, RenderPriority};
 use crate::services::score::ScoreValue;
 use crate::services::{
     LinkService, OutdateService, PageService, ParentService, RenderService, ScoreService,
-    SettingsService, SiteService, TextService,
+    SettingsService, SiteService, TagService, TextService, ViewCacheService,
 };
 use crate::types::FetchDirection;
 use crate::utils::{split_category, split_category_name};
@@ -129,6 +129,8 @@ impl PageRevisionService {
             mut compiled_hash,
             mut compiled_at,
             mut compiled_generator,
+            mut compiled_style_hash,
+            mut compiled_meta,
             hidden,
             mut title,
             mut alt_title,
@@ -164,10 +166,12 @@ impl PageRevisionService {
             }
         }
 
+        let mut tags_changed = false;
         if let Maybe::Set(new_tags) = body.tags {
             if tags != new_tags {
                 changes.push(str!("tags"));
                 tags = new_tags;
+                tags_changed = true;
             }
         }
 
@@ -178,7 +182,9 @@ impl PageRevisionService {
         let wikitext = match body.wikitext {
             // Insert new wikitext and update hash
             Maybe::Set(new_wikitext) => {
-                let new_hash = TextService::create(ctx, new_wikitext.clone()).await?;
+                let new_hash =
+                    TextService::create_delta(ctx, new_wikitext.clone(), &wikitext_hash)
+                        .await?;
 
                 if wikitext_hash != new_hash {
                     changes.push(str!("wikitext"));
@@ -195,7 +201,7 @@ impl PageRevisionService {
         // If nothing has changed, then don't create a new revision
         if changes.is_empty() {
             debug!("No changes in edit, only rerendering the page");
-            Self::rerender(ctx, site_id, page_id, 0).await?;
+            Self::rerender(ctx, site_id, page_id, 0, RenderPriority::Interactive).await?;
             return Ok(None);
         }
 
@@ -225,21 +231,22 @@ impl PageRevisionService {
             //
             // Since outdating depends on scope (see PageRevisionTasks),
             // we don't do that right after here.
-            //
-            // TODO: use html_output
             let render_output = Self::render_and_update_links(
                 ctx,
                 site_id,
                 page_id,
                 wikitext,
                 render_input,
+                RenderPriority::Interactive,
             )
             .await?;
 
             // Update fields
             parser_errors = Some(render_output.errors);
             replace_hash(&mut compiled_hash, &render_output.compiled_hash);
+            replace_hash(&mut compiled_style_hash, &render_output.compiled_style_hash);
             compiled_generator = render_output.compiled_generator;
+            compiled_meta = render_output.compiled_meta;
             compiled_at = now();
         }
 
@@ -308,6 +315,17 @@ impl PageRevisionService {
             }
         };
 
+        // Keep the tag index in sync with the latest revision
+        if tags_changed {
+            TagService::update_index(ctx, site_id, page_id, &tags).await?;
+        }
+
+        // Clear cached views for this page, at both slugs if it was moved
+        ViewCacheService::invalidate_page(ctx, site_id, &slug);
+        if let Some(ref old_slug) = old_slug {
+            ViewCacheService::invalidate_page(ctx, site_id, old_slug);
+        }
+
         // Insert the new revision into the table
         let model = page_revision::ActiveModel {
             revision_type: Set(revision_type),
@@ -320,6 +338,8 @@ impl PageRevisionService {
             compiled_hash: Set(compiled_hash),
             compiled_at: Set(compiled_at),
             compiled_generator: Set(compiled_generator),
+            compiled_style_hash: Set(compiled_style_hash),
+            compiled_meta: Set(compiled_meta),
             comments: Set(comments),
             hidden: Set(hidden),
             title: Set(title),
@@ -384,17 +404,26 @@ impl PageRevisionService {
         };
 
         let RenderOutput {
-            // TODO: use html_output
-            html_output: _,
             errors,
             compiled_hash,
+            compiled_style_hash,
+            compiled_meta,
             compiled_at,
             compiled_generator,
-        } = Self::render_and_update_links(ctx, site_id, page_id, wikitext, render_input)
-            .await?;
+            ..
+        } = Self::render_and_update_links(
+            ctx,
+            site_id,
+            page_id,
+            wikitext,
+            render_input,
+            RenderPriority::Interactive,
+        )
+        .await?;
 
         // Run outdater
         OutdateService::process_page_displace(ctx, site_id, page_id, &slug, 0).await?;
+        ViewCacheService::invalidate_page(ctx, site_id, &slug);
 
         // Insert the first revision into the table
         let model = page_revision::ActiveModel {
@@ -408,6 +437,8 @@ impl PageRevisionService {
             compiled_hash: Set(compiled_hash.to_vec()),
             compiled_at: Set(compiled_at),
             compiled_generator: Set(compiled_generator),
+            compiled_style_hash: Set(compiled_style_hash.to_vec()),
+            compiled_meta: Set(compiled_meta),
             comments: Set(comments),
             hidden: Set(vec![]),
             title: Set(title),
@@ -449,6 +480,8 @@ impl PageRevisionService {
             compiled_hash,
             compiled_at,
             compiled_generator,
+            compiled_style_hash,
+            compiled_meta,
             title,
             alt_title,
             slug,
@@ -458,9 +491,10 @@ impl PageRevisionService {
 
         // Run outdater
         OutdateService::process_page_displace(ctx, site_id, page_id, &slug, 0).await?;
+        ViewCacheService::invalidate_page(ctx, site_id, &slug);
 
         // Delete parent-child relationships, if any
-        ParentService::remove_all(ctx, page_id).await?;
+        ParentService::remove_all(ctx, page_id, user_id).await?;
 
         // Insert the tombstone revision into the table
         let model = page_revision::ActiveModel {
@@ -474,6 +508,8 @@ impl PageRevisionService {
             compiled_hash: Set(compiled_hash),
             compiled_at: Set(compiled_at),
             compiled_generator: Set(compiled_generator),
+            compiled_style_hash: Set(compiled_style_hash),
+            compiled_meta: Set(compiled_meta),
             comments: Set(comments),
             hidden: Set(vec![]),
             title: Set(title),
@@ -523,6 +559,7 @@ impl PageRevisionService {
         let PageRevisionModel {
             wikitext_hash,
             mut compiled_hash,
+            mut compiled_style_hash,
             hidden,
             title,
             alt_title,
@@ -555,20 +592,30 @@ impl PageRevisionService {
 
         let wikitext = TextService::get(ctx, &wikitext_hash).await?;
         let RenderOutput {
-            // TODO: use html_output
-            html_output: _,
             errors,
             compiled_hash: new_compiled_hash,
+            compiled_style_hash: new_compiled_style_hash,
+            compiled_meta,
             compiled_at,
             compiled_generator,
-        } = Self::render_and_update_links(ctx, site_id, page_id, wikitext, render_input)
-            .await?;
+            ..
+        } = Self::render_and_update_links(
+            ctx,
+            site_id,
+            page_id,
+            wikitext,
+            render_input,
+            RenderPriority::Interactive,
+        )
+        .await?;
 
         replace_hash(&mut compiled_hash, &new_compiled_hash);
+        replace_hash(&mut compiled_style_hash, &new_compiled_style_hash);
 
         // Run outdater
         OutdateService::process_page_displace(ctx, site_id, page_id, &new_slug, 0)
             .await?;
+        ViewCacheService::invalidate_page(ctx, site_id, &new_slug);
 
         // Insert the resurrection revision into the table
         let model = page_revision::ActiveModel {
@@ -582,6 +629,8 @@ impl PageRevisionService {
             compiled_hash: Set(compiled_hash),
             compiled_at: Set(compiled_at),
             compiled_generator: Set(compiled_generator),
+            compiled_style_hash: Set(compiled_style_hash),
+            compiled_meta: Set(compiled_meta),
             comments: Set(comments),
             hidden: Set(hidden),
             title: Set(title),
@@ -617,6 +666,7 @@ impl PageRevisionService {
             score,
             tags,
         }: RenderPageInfo<'_>,
+        priority: RenderPriority,
     ) -> Result<RenderOutput> {
         // Get site
         let site = SiteService::get(ctx, Reference::from(site_id)).await?;
@@ -636,10 +686,18 @@ impl PageRevisionService {
         };
 
         // Parse and render
-        let output = RenderService::render(ctx, wikitext, &page_info, &settings).await?;
+        let output =
+            RenderService::render(ctx, wikitext, &page_info, &settings, priority).await?;
 
         // Update backlinks
-        LinkService::update(ctx, site_id, page_id, &output.html_output.backlinks).await?;
+        LinkService::update(
+            ctx,
+            site_id,
+            page_id,
+            &output.html_output.backlinks,
+            &output.included_pages,
+        )
+        .await?;
 
         Ok(output)
     }
@@ -651,11 +709,15 @@ impl PageRevisionService {
     /// The `depth` parameter describes the number of layers of prior rerendering
     /// automatically leading to other updates. For a manual rerender this value
     /// should be 0.
+    ///
+    /// The `priority` parameter selects which render lane is used, rerenders
+    /// from jobs should use the background lane.
     pub async fn rerender(
         ctx: &ServiceContext<'_>,
         site_id: i64,
         page_id: i64,
         depth: u32,
+        priority: RenderPriority,
     ) -> Result<()> {
         let txn = ctx.transaction();
         let revision = Self::get_latest(ctx, site_id, page_id).await?;
@@ -706,22 +768,33 @@ impl PageRevisionService {
             tags: &revision.tags,
         };
 
-        // TODO use html_output
         let RenderOutput {
             compiled_hash,
+            compiled_style_hash,
+            compiled_meta,
             compiled_generator,
             ..
-        } = Self::render_and_update_links(ctx, site_id, page_id, wikitext, render_input)
-            .await?;
+        } = Self::render_and_update_links(
+            ctx,
+            site_id,
+            page_id,
+            wikitext,
+            render_input,
+            priority,
+        )
+        .await?;
 
         // Update descendents
         OutdateService::process_page_edit(ctx, site_id, page_id, &revision.slug, depth)
             .await?;
+        ViewCacheService::invalidate_page(ctx, site_id, &revision.slug);
 
         let model = page_revision::ActiveModel {
             updated_at: Set(Some(now())),
             revision_id: Set(revision.revision_id),
             compiled_hash: Set(compiled_hash.to_vec()),
+            compiled_style_hash: Set(compiled_style_hash.to_vec()),
+            compiled_meta: Set(compiled_meta),
             compiled_generator: Set(compiled_generator),
             ..Default::default()
         };
@@ -786,6 +859,7 @@ impl PageRevisionService {
 
         // Update and return
         model.update(txn).await?;
+        ViewCacheService::invalidate_page(ctx, site_id, &latest.slug);
         Ok(())
     }
 
@@ -23,6 +23,7 @@ use crate::models::sea_orm_active_enums::PageRevisionType;
 use crate::types::{FetchDirection, PageDetails};
 use ftml::layout::Layout;
 use ftml::parsing::ParseError;
+use serde_json::Value as JsonValue;
 use std::num::NonZeroI32;
 use time::OffsetDateTime;
 
@@ -173,6 +174,8 @@ pub struct PageRevisionModelFiltered {
     pub changes: Vec<String>,
     pub wikitext: Option<String>,
     pub compiled_html: Option<String>,
+    pub compiled_style: Option<String>,
+    pub compiled_meta: Option<JsonValue>,
 
     #[serde(with = "time::serde::rfc3339")]
     pub compiled_at: OffsetDateTime,
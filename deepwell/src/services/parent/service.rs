@@ -18,11 +18,18 @@
  * along with this program. If not, see <http://www.gnu.org/licenses/>.
  */
 
-// TODO replace ParentService with a new relation type
-
 use super::prelude::*;
-use crate::models::page_parent::{self, Entity as PageParent, Model as PageParentModel};
-use crate::services::PageService;
+use crate::models::page::{self, Entity as Page};
+use crate::models::page_revision::{self, Entity as PageRevision};
+use crate::models::relation::Model as RelationModel;
+use crate::services::relation::{
+    CreatePageParent, GetPageParent, RemovePageParent, MAXIMUM_PARENT_DEPTH,
+};
+use crate::services::{PageService, RelationService};
+use std::collections::{HashMap, HashSet, VecDeque};
+
+/// The maximum number of levels of descendants which can be fetched in a tree.
+pub const MAXIMUM_TREE_DEPTH: u32 = 16;
 
 #[derive(Debug)]
 pub struct ParentService;
@@ -31,56 +38,49 @@ impl ParentService {
     /// Adds a parental relationship with the two given pages.
     ///
     /// Both pages must be extant and on the same site.
+    /// The relationship is rejected if it would create a cycle.
     ///
     /// # Returns
     /// Returns `Some` with a model if the relationship was created,
     /// and `None` if it already existed.
     pub async fn create(
         ctx: &ServiceContext<'_>,
-        ParentDescription {
+        ModifyParent {
             site_id,
             parent: parent_reference,
             child: child_reference,
-        }: ParentDescription<'_>,
-    ) -> Result<Option<PageParentModel>> {
-        let txn = ctx.transaction();
-
+            user_id,
+        }: ModifyParent<'_>,
+    ) -> Result<Option<RelationModel>> {
         let (parent_page, child_page) = try_join!(
             PageService::get(ctx, site_id, parent_reference),
             PageService::get(ctx, site_id, child_reference),
         )?;
 
-        // Check if the two pages are the same
-        if parent_page.page_id == child_page.page_id {
-            error!(
-                "Cannot parent a page to itself (ID {})",
-                parent_page.page_id,
-            );
-            return Err(Error::PageParentExists);
-        }
+        let get = GetPageParent {
+            parent_page_id: parent_page.page_id,
+            child_page_id: child_page.page_id,
+        };
 
         // Check if this relationship already exists
-        let relationship =
-            PageParent::find_by_id((parent_page.page_id, child_page.page_id))
-                .one(txn)
-                .await?;
-
-        match relationship {
-            // Create new parent relationship
-            None => {
-                let model = page_parent::ActiveModel {
-                    parent_page_id: Set(parent_page.page_id),
-                    child_page_id: Set(child_page.page_id),
-                    ..Default::default()
-                };
+        if RelationService::page_parent_exists(ctx, get).await? {
+            return Ok(None);
+        }
 
-                let parent = model.insert(txn).await?;
-                Ok(Some(parent))
-            }
+        // Create new parent relationship
+        RelationService::create_page_parent(
+            ctx,
+            CreatePageParent {
+                parent_page_id: parent_page.page_id,
+                child_page_id: child_page.page_id,
+                metadata: (),
+                created_by: user_id,
+            },
+        )
+        .await?;
 
-            // Parent relationship already exists
-            Some(_) => Ok(None),
-        }
+        let model = RelationService::get_page_parent(ctx, get).await?;
+        Ok(Some(model))
     }
 
     /// Removes the parental relationship with the two given pages.
@@ -90,53 +90,46 @@ impl ParentService {
     /// `false` if it was already absent.
     pub async fn remove(
         ctx: &ServiceContext<'_>,
-        ParentDescription {
+        ModifyParent {
             site_id,
             parent: parent_reference,
             child: child_reference,
-        }: ParentDescription<'_>,
+            user_id,
+        }: ModifyParent<'_>,
     ) -> Result<RemoveParentOutput> {
-        let txn = ctx.transaction();
-
         let (parent_page, child_page) = try_join!(
             PageService::get(ctx, site_id, parent_reference),
             PageService::get(ctx, site_id, child_reference),
         )?;
 
-        let DeleteResult { rows_affected } =
-            PageParent::delete_by_id((parent_page.page_id, child_page.page_id))
-                .exec(txn)
-                .await?;
+        let get = GetPageParent {
+            parent_page_id: parent_page.page_id,
+            child_page_id: child_page.page_id,
+        };
+
+        if !RelationService::page_parent_exists(ctx, get).await? {
+            return Ok(RemoveParentOutput { was_deleted: false });
+        }
 
-        debug_assert!(
-            rows_affected <= 1,
-            "Rows deleted using ID was more than 1: {rows_affected}",
-        );
+        RelationService::remove_page_parent(
+            ctx,
+            RemovePageParent {
+                parent_page_id: parent_page.page_id,
+                child_page_id: child_page.page_id,
+                removed_by: user_id,
+            },
+        )
+        .await?;
 
-        let was_deleted = rows_affected == 1;
-        Ok(RemoveParentOutput { was_deleted })
+        Ok(RemoveParentOutput { was_deleted: true })
     }
 
     pub async fn get_optional(
         ctx: &ServiceContext<'_>,
-        ParentDescription {
-            site_id,
-            parent: parent_reference,
-            child: child_reference,
-        }: ParentDescription<'_>,
-    ) -> Result<Option<PageParentModel>> {
-        let txn = ctx.transaction();
-
-        let (parent_page, child_page) = try_join!(
-            PageService::get(ctx, site_id, parent_reference),
-            PageService::get(ctx, site_id, child_reference),
-        )?;
-
-        let model = PageParent::find_by_id((parent_page.page_id, child_page.page_id))
-            .one(txn)
-            .await?;
-
-        Ok(model)
+        description: ParentDescription<'_>,
+    ) -> Result<Option<RelationModel>> {
+        let get = Self::get_ids(ctx, description).await?;
+        RelationService::get_optional_page_parent(ctx, get).await
     }
 
     #[inline]
@@ -144,30 +137,39 @@ impl ParentService {
     pub async fn get(
         ctx: &ServiceContext<'_>,
         description: ParentDescription<'_>,
-    ) -> Result<PageParentModel> {
+    ) -> Result<RelationModel> {
         find_or_error!(Self::get_optional(ctx, description), PageParent)
     }
 
+    /// Gets the history of the parental relationship between the two given pages.
+    ///
+    /// This includes all past instances of the relationship, including
+    /// who created and removed them, and when.
+    pub async fn get_history(
+        ctx: &ServiceContext<'_>,
+        description: ParentDescription<'_>,
+    ) -> Result<Vec<RelationModel>> {
+        let get = Self::get_ids(ctx, description).await?;
+        RelationService::get_page_parent_history(ctx, get).await
+    }
+
     /// Gets all relationships of the given type.
     pub async fn get_relationships(
         ctx: &ServiceContext<'_>,
         site_id: i64,
         reference: Reference<'_>,
         relationship_type: ParentalRelationshipType,
-    ) -> Result<Vec<PageParentModel>> {
-        let txn = ctx.transaction();
+    ) -> Result<Vec<RelationModel>> {
         let page_id = PageService::get_id(ctx, site_id, reference).await?;
-        let column = match relationship_type {
-            ParentalRelationshipType::Parent => page_parent::Column::ChildPageId,
-            ParentalRelationshipType::Child => page_parent::Column::ParentPageId,
-        };
-
-        let models = PageParent::find()
-            .filter(column.eq(page_id))
-            .all(txn)
-            .await?;
-
-        Ok(models)
+        let page_ids = [page_id];
+        match relationship_type {
+            ParentalRelationshipType::Parent => {
+                RelationService::get_page_parent_relations(ctx, &page_ids).await
+            }
+            ParentalRelationshipType::Child => {
+                RelationService::get_page_child_relations(ctx, &page_ids).await
+            }
+        }
     }
 
     /// Gets all children of the given page.
@@ -176,7 +178,7 @@ impl ParentService {
         ctx: &ServiceContext<'_>,
         site_id: i64,
         reference: Reference<'_>,
-    ) -> Result<Vec<PageParentModel>> {
+    ) -> Result<Vec<RelationModel>> {
         Self::get_relationships(ctx, site_id, reference, ParentalRelationshipType::Child)
             .await
     }
@@ -186,31 +188,224 @@ impl ParentService {
         ctx: &ServiceContext<'_>,
         site_id: i64,
         reference: Reference<'_>,
-    ) -> Result<Vec<PageParentModel>> {
+    ) -> Result<Vec<RelationModel>> {
         Self::get_relationships(ctx, site_id, reference, ParentalRelationshipType::Parent)
             .await
     }
 
+    /// Gets the chain of ancestors for the given page, starting from the root.
+    ///
+    /// Where a page has several parents, the one which was added first is followed.
+    /// The page itself is not included in the output.
+    pub async fn get_breadcrumbs(
+        ctx: &ServiceContext<'_>,
+        site_id: i64,
+        reference: Reference<'_>,
+    ) -> Result<Vec<ParentPage>> {
+        let page_id = PageService::get_id(ctx, site_id, reference).await?;
+        let mut visited = HashSet::new();
+        let mut ancestors = Vec::new();
+        let mut current_id = page_id;
+        visited.insert(page_id);
+
+        for _ in 0..MAXIMUM_PARENT_DEPTH {
+            let relations =
+                RelationService::get_page_parent_relations(ctx, &[current_id]).await?;
+
+            match relations.first() {
+                Some(relation) if visited.insert(relation.dest_id) => {
+                    current_id = relation.dest_id;
+                    ancestors.push(current_id);
+                }
+                _ => break,
+            }
+        }
+
+        // Root-first order, as breadcrumbs are displayed
+        ancestors.reverse();
+
+        let mut pages = Self::get_parent_pages(ctx, site_id, &ancestors).await?;
+        let breadcrumbs = ancestors
+            .into_iter()
+            .filter_map(|page_id| pages.remove(&page_id))
+            .collect();
+
+        Ok(breadcrumbs)
+    }
+
+    /// Gets the tree of descendants for the given page.
+    ///
+    /// The tree is fetched breadth-first, one query per level, up to the given depth.
+    /// Since a page can have several parents, it may appear in more than one place,
+    /// but its children are only listed under its first occurrence in breadth-first
+    /// order. Otherwise shared descendants would be expanded again on every path.
+    pub async fn get_tree(
+        ctx: &ServiceContext<'_>,
+        GetParentTree {
+            site_id,
+            page: reference,
+            max_depth,
+        }: GetParentTree<'_>,
+    ) -> Result<ParentTreeNode> {
+        let max_depth = max_depth
+            .unwrap_or(MAXIMUM_TREE_DEPTH)
+            .min(MAXIMUM_TREE_DEPTH);
+
+        let page_id = PageService::get_id(ctx, site_id, reference).await?;
+        let mut children: HashMap<i64, Vec<i64>> = HashMap::new();
+        let mut visited = HashSet::new();
+        let mut current = vec![page_id];
+        visited.insert(page_id);
+
+        for _ in 0..max_depth {
+            let relations =
+                RelationService::get_page_child_relations(ctx, &current).await?;
+
+            current = Vec::new();
+            for relation in relations {
+                children
+                    .entry(relation.dest_id)
+                    .or_default()
+                    .push(relation.from_id);
+
+                if visited.insert(relation.from_id) {
+                    current.push(relation.from_id);
+                }
+            }
+
+            if current.is_empty() {
+                break;
+            }
+        }
+
+        let page_ids: Vec<i64> = visited.into_iter().collect();
+        let pages = Self::get_parent_pages(ctx, site_id, &page_ids).await?;
+
+        if !pages.contains_key(&page_id) {
+            return Err(Error::PageNotFound);
+        }
+
+        // Lay out the tree breadth-first, as (page ID, child node indices)
+        let mut nodes: Vec<(i64, Vec<usize>)> = vec![(page_id, Vec::new())];
+        let mut expanded = HashSet::new();
+        let mut queue = VecDeque::from([(0, 0)]);
+
+        while let Some((index, depth)) = queue.pop_front() {
+            let page_id = nodes[index].0;
+            if depth >= max_depth || !expanded.insert(page_id) {
+                continue;
+            }
+
+            for &child_id in children.get(&page_id).into_iter().flatten() {
+                if pages.contains_key(&child_id) {
+                    let child_index = nodes.len();
+                    nodes.push((child_id, Vec::new()));
+                    nodes[index].1.push(child_index);
+                    queue.push_back((child_index, depth + 1));
+                }
+            }
+        }
+
+        fn build(
+            index: usize,
+            nodes: &[(i64, Vec<usize>)],
+            pages: &HashMap<i64, ParentPage>,
+        ) -> ParentTreeNode {
+            let (page_id, ref child_indices) = nodes[index];
+            ParentTreeNode {
+                page: pages[&page_id].clone(),
+                children: child_indices
+                    .iter()
+                    .map(|&child_index| build(child_index, nodes, pages))
+                    .collect(),
+            }
+        }
+
+        Ok(build(0, &nodes, &pages))
+    }
+
     /// Removes all parent relationships involving this page.
     ///
     /// Whether this page is a parent or a child, this method
     /// will remove all those relationships.
     ///
     /// # Returns
-    /// Returns the number of relationships deleted.
-    pub async fn remove_all(ctx: &ServiceContext<'_>, page_id: i64) -> Result<u64> {
+    /// Returns the number of relationships removed.
+    pub async fn remove_all(
+        ctx: &ServiceContext<'_>,
+        page_id: i64,
+        user_id: i64,
+    ) -> Result<u64> {
+        RelationService::remove_all_page_parents(ctx, page_id, user_id).await
+    }
+
+    /// Resolves both page references in a parental relationship.
+    async fn get_ids(
+        ctx: &ServiceContext<'_>,
+        ParentDescription {
+            site_id,
+            parent: parent_reference,
+            child: child_reference,
+        }: ParentDescription<'_>,
+    ) -> Result<GetPageParent> {
+        let (parent_page_id, child_page_id) = try_join!(
+            PageService::get_id(ctx, site_id, parent_reference),
+            PageService::get_id(ctx, site_id, child_reference),
+        )?;
+
+        Ok(GetPageParent {
+            parent_page_id,
+            child_page_id,
+        })
+    }
+
+    /// Gets the slug and title for each of the given pages.
+    ///
+    /// Pages which are deleted or in a different site are omitted.
+    async fn get_parent_pages(
+        ctx: &ServiceContext<'_>,
+        site_id: i64,
+        page_ids: &[i64],
+    ) -> Result<HashMap<i64, ParentPage>> {
         let txn = ctx.transaction();
+        let pages = Page::find()
+            .filter(
+                Condition::all()
+                    .add(page::Column::SiteId.eq(site_id))
+                    .add(page::Column::PageId.is_in(page_ids.iter().copied()))
+                    .add(page::Column::DeletedAt.is_null()),
+            )
+            .all(txn)
+            .await?;
 
-        let rows_deleted = PageParent::delete_many()
+        let titles: HashMap<i64, String> = PageRevision::find()
+            .select_only()
+            .column(page_revision::Column::PageId)
+            .column(page_revision::Column::Title)
             .filter(
-                Condition::any()
-                    .add(page_parent::Column::ParentPageId.eq(page_id))
-                    .add(page_parent::Column::ChildPageId.eq(page_id)),
+                page_revision::Column::RevisionId
+                    .is_in(pages.iter().filter_map(|page| page.latest_revision_id)),
             )
-            .exec(txn)
+            .into_tuple()
+            .all(txn)
             .await?
-            .rows_affected;
+            .into_iter()
+            .collect();
+
+        let pages = pages
+            .into_iter()
+            .map(|page| {
+                let title = titles.get(&page.page_id).cloned().unwrap_or_default();
+                let output = ParentPage {
+                    page_id: page.page_id,
+                    slug: page.slug,
+                    title,
+                };
+
+                (page.page_id, output)
+            })
+            .collect();
 
-        Ok(rows_deleted)
+        Ok(pages)
     }
 }
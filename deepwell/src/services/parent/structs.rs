@@ -29,10 +29,19 @@ pub struct ParentDescription<'a> {
     pub child: Reference<'a>,
 }
 
+#[derive(Deserialize, Debug, Clone)]
+pub struct ModifyParent<'a> {
+    pub site_id: i64,
+    pub parent: Reference<'a>,
+    pub child: Reference<'a>,
+    pub user_id: i64,
+}
+
 #[derive(Deserialize, Debug, Clone)]
 pub struct UpdateParents<'a> {
     pub site_id: i64,
     pub child: Reference<'a>,
+    pub user_id: i64,
     pub add: Option<Vec<Reference<'a>>>,
     pub remove: Option<Vec<Reference<'a>>>,
 }
@@ -84,3 +93,29 @@ pub struct UpdateParentsOutput {
     pub added: Option<Vec<i64>>,
     pub removed: Option<Vec<bool>>,
 }
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct GetParentTree<'a> {
+    pub site_id: i64,
+    pub page: Reference<'a>,
+
+    /// How many levels of descendants to retrieve.
+    ///
+    /// If unset or greater than `MAXIMUM_TREE_DEPTH`, then that limit is used.
+    #[serde(default)]
+    pub max_depth: Option<u32>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct ParentPage {
+    pub page_id: i64,
+    pub slug: String,
+    pub title: String,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct ParentTreeNode {
+    #[serde(flatten)]
+    pub page: ParentPage,
+    pub children: Vec<ParentTreeNode>,
+}
@@ -0,0 +1,240 @@
+/*
+ * services/rate_limit/service.rs
+ *
+ * DEEPWELL - Wikijump API provider and database manager
+ * Copyright (C) 2019-2025 Wikijump Team
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use super::prelude::*;
+use crate::hash::k12_hash;
+use once_cell::sync::Lazy;
+use redis::{AsyncCommands, Script};
+
+/// Atomically takes a token from each of the given buckets.
+///
+/// If any bucket is empty, then no tokens are taken, and the number
+/// of milliseconds until the request could succeed is returned.
+///
+/// Arguments are the bucket size, the refill rate in tokens per millisecond,
+/// and the current time in milliseconds.
+static TOKEN_BUCKET_SCRIPT: Lazy<Script> = Lazy::new(|| {
+    Script::new(
+        r#"
+local burst = tonumber(ARGV[1])
+local rate = tonumber(ARGV[2])
+local now = tonumber(ARGV[3])
+local levels = {}
+local wait = 0
+
+for i, key in ipairs(KEYS) do
+    local data = redis.call('HMGET', key, 'tokens', 'updated')
+    local tokens = tonumber(data[1]) or burst
+    local updated = tonumber(data[2]) or now
+    tokens = math.min(burst, tokens + math.max(0, now - updated) * rate)
+    levels[i] = tokens
+
+    if tokens < 1 then
+        wait = math.max(wait, math.ceil((1 - tokens) / rate))
+    end
+end
+
+if wait > 0 then
+    return {0, wait}
+end
+
+for i, key in ipairs(KEYS) do
+    redis.call('HSET', key, 'tokens', tostring(levels[i] - 1), 'updated', tostring(now))
+    redis.call('PEXPIRE', key, math.ceil(burst / rate))
+end
+
+return {1, 0}
+"#,
+    )
+});
+
+#[derive(Debug)]
+pub struct RateLimitService;
+
+impl RateLimitService {
+    /// Takes a token from each of the client's buckets for this class of method.
+    ///
+    /// # Errors
+    /// Returns `Error::TooManyRequests` if any of the buckets are empty.
+    pub async fn check(
+        ctx: &ServiceContext<'_>,
+        class: RateLimitClass,
+        keys: &RateLimitKeys,
+    ) -> Result<()> {
+        if keys.is_empty() {
+            trace!("No client identifiers in request, skipping rate limit");
+            return Ok(());
+        }
+
+        let class_name = class.value();
+        let bucket = class.bucket(ctx.config());
+        let mut script = TOKEN_BUCKET_SCRIPT.prepare_invoke();
+
+        if let Some(user_id) = keys.user_id {
+            script.key(format!("rate-limit:{class_name}:user:{user_id}"));
+        }
+
+        if let Some(ref session_token) = keys.session_token {
+            let hash = hex::encode(k12_hash(session_token.as_bytes()));
+            script.key(format!("rate-limit:{class_name}:session:{hash}"));
+        }
+
+        if let Some(ref ip_address) = keys.ip_address {
+            script.key(format!("rate-limit:{class_name}:ip:{ip_address}"));
+        }
+
+        let now_ms = now().unix_timestamp_nanos() / 1_000_000;
+        let refill_per_ms = f64::from(bucket.per_minute.get()) / 60_000.0;
+
+        let mut redis = ctx.redis_connect().await?;
+        let (allowed, wait_ms): (bool, u64) = script
+            .arg(bucket.burst.get())
+            .arg(refill_per_ms)
+            .arg(i64::try_from(now_ms).unwrap_or(i64::MAX))
+            .invoke_async(&mut redis)
+            .await?;
+
+        if !allowed {
+            warn!(
+                "Rate limit exceeded for {class_name} methods ({keys:?}), retry in {wait_ms} ms",
+            );
+            return Err(Error::TooManyRequests);
+        }
+
+        Ok(())
+    }
+
+    /// Fails if logins to this account are currently locked.
+    pub async fn check_login_lockout(
+        ctx: &ServiceContext<'_>,
+        account: LoginAccount<'_>,
+    ) -> Result<()> {
+        let mut redis = ctx.redis_connect().await?;
+        let locked: bool = redis.exists(Self::lockout_key(account)).await?;
+        if locked {
+            warn!("Login attempt for locked account, refusing");
+            return Err(Error::LoginLockedOut);
+        }
+
+        Ok(())
+    }
+
+    /// Records a failed login or MFA verification, locking the account if there have been too many.
+    pub async fn record_login_failure(
+        ctx: &ServiceContext<'_>,
+        account: LoginAccount<'_>,
+    ) -> Result<()> {
+        let config = ctx.config();
+        let failures_key = Self::failures_key(account);
+        let mut redis = ctx.redis_connect().await?;
+
+        let failures: u16 = redis.incr(&failures_key, 1).await?;
+        if failures == 1 {
+            // First failure in this window, start the timer
+            let window =
+                i64::try_from(config.login_lockout_window.as_secs()).unwrap_or(i64::MAX);
+
+            redis.expire::<_, ()>(&failures_key, window).await?;
+        }
+
+        if failures >= config.login_lockout_attempts.get() {
+            warn!("Too many failed logins ({failures}), locking account");
+
+            redis
+                .set_ex::<_, _, ()>(
+                    Self::lockout_key(account),
+                    1,
+                    config.login_lockout_duration.as_secs(),
+                )
+                .await?;
+
+            redis.del::<_, ()>(&failures_key).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Clears failed login attempts after a successful login.
+    pub async fn clear_login_failures(
+        ctx: &ServiceContext<'_>,
+        account: LoginAccount<'_>,
+    ) -> Result<()> {
+        let mut redis = ctx.redis_connect().await?;
+        redis.del::<_, ()>(Self::failures_key(account)).await?;
+        Ok(())
+    }
+
+    fn failures_key(account: LoginAccount) -> String {
+        format!("login-failures:{}", Self::account_key(account))
+    }
+
+    fn lockout_key(account: LoginAccount) -> String {
+        format!("login-lockout:{}", Self::account_key(account))
+    }
+
+    /// Produces the key for an account.
+    ///
+    /// Unknown login identifiers are hashed, so that emails are not stored in Redis.
+    fn account_key(account: LoginAccount) -> String {
+        match account {
+            LoginAccount::User(user_id) => format!("user:{user_id}"),
+            LoginAccount::Unknown(name_or_email) => {
+                let normalized = name_or_email.trim().to_lowercase();
+                format!("unknown:{}", hex::encode(k12_hash(normalized.as_bytes())))
+            }
+        }
+    }
+}
+
+#[test]
+fn test_account_key() {
+    assert_eq!(
+        RateLimitService::account_key(LoginAccount::User(42)),
+        "user:42",
+    );
+
+    // Unknown identifiers are normalized and hashed
+    let key = RateLimitService::account_key(LoginAccount::Unknown("person@example.com"));
+    assert!(key.starts_with("unknown:"));
+    assert!(!key.contains("example"));
+    assert_eq!(
+        key,
+        RateLimitService::account_key(LoginAccount::Unknown(" Person@Example.COM ")),
+    );
+    assert_ne!(
+        key,
+        RateLimitService::account_key(LoginAccount::Unknown("other@example.com")),
+    );
+
+    // Users and unknown identifiers never share keys
+    assert_ne!(
+        RateLimitService::account_key(LoginAccount::User(42)),
+        RateLimitService::account_key(LoginAccount::Unknown("42")),
+    );
+
+    assert_eq!(
+        RateLimitService::failures_key(LoginAccount::User(42)),
+        "login-failures:user:42",
+    );
+    assert_eq!(
+        RateLimitService::lockout_key(LoginAccount::User(42)),
+        "login-lockout:user:42",
+    );
+}
@@ -0,0 +1,152 @@
+/*
+ * services/rate_limit/structs.rs
+ *
+ * DEEPWELL - Wikijump API provider and database manager
+ * Copyright (C) 2019-2025 Wikijump Team
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use crate::config::Config;
+use crate::types::RateLimitBucket;
+use jsonrpsee::types::params::Params;
+use serde_json::Value as JsonValue;
+
+/// Which class of API method is being rate limited.
+///
+/// Each class has its own limits and its own set of buckets.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, Hash, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum RateLimitClass {
+    Authentication,
+    Write,
+    Message,
+    Account,
+}
+
+impl RateLimitClass {
+    pub fn value(self) -> &'static str {
+        match self {
+            RateLimitClass::Authentication => "authentication",
+            RateLimitClass::Write => "write",
+            RateLimitClass::Message => "message",
+            RateLimitClass::Account => "account",
+        }
+    }
+
+    pub fn bucket(self, config: &Config) -> RateLimitBucket {
+        match self {
+            RateLimitClass::Authentication => config.rate_limit_authentication,
+            RateLimitClass::Write => config.rate_limit_write,
+            RateLimitClass::Message => config.rate_limit_message,
+            RateLimitClass::Account => config.rate_limit_account,
+        }
+    }
+}
+
+/// The fields of a request which identify the client.
+///
+/// These are taken from the method's parameters, so only those
+/// which are present for that method are used.
+#[derive(Debug, Clone, Default)]
+pub struct RateLimitKeys {
+    pub user_id: Option<i64>,
+    pub session_token: Option<String>,
+    pub ip_address: Option<String>,
+}
+
+impl RateLimitKeys {
+    pub fn from_params(params: &Params) -> Self {
+        // Methods with positional or scalar parameters have no keys
+        let value = match params.parse::<JsonValue>() {
+            Ok(JsonValue::Object(value)) => value,
+            _ => return RateLimitKeys::default(),
+        };
+
+        let string = |key| value.get(key).and_then(JsonValue::as_str).map(String::from);
+
+        RateLimitKeys {
+            user_id: value.get("user_id").and_then(JsonValue::as_i64),
+            session_token: string("session_token"),
+            ip_address: string("ip_address"),
+        }
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.user_id.is_none()
+            && self.session_token.is_none()
+            && self.ip_address.is_none()
+    }
+}
+
+/// The account a login attempt is for, used to track failed logins.
+#[derive(Debug, Copy, Clone)]
+pub enum LoginAccount<'a> {
+    /// An existing user, so that any of their names or emails count the same.
+    User(i64),
+
+    /// A name or email which does not belong to any user.
+    Unknown(&'a str),
+}
+
+#[test]
+fn test_rate_limit_keys() {
+    macro_rules! check {
+        ($params:expr, $user_id:expr, $session_token:expr, $ip_address:expr $(,)?) => {{
+            let keys = RateLimitKeys::from_params(&Params::new($params));
+            assert_eq!(keys.user_id, $user_id, "User ID doesn't match");
+            assert_eq!(
+                keys.session_token.as_deref(),
+                $session_token,
+                "Session token doesn't match",
+            );
+            assert_eq!(
+                keys.ip_address.as_deref(),
+                $ip_address,
+                "IP address doesn't match",
+            );
+        }};
+    }
+
+    check!(None, None, None, None);
+    check!(Some("[1, 2, 3]"), None, None, None);
+    check!(Some(r#""scp-173""#), None, None, None);
+    check!(Some("{}"), None, None, None);
+    check!(
+        Some(r#"{"user_id": 4, "session_token": "wj:abc", "ip_address": "::1"}"#),
+        Some(4),
+        Some("wj:abc"),
+        Some("::1"),
+    );
+    check!(
+        Some(r#"{"ip_address": "127.0.0.1", "name": "aismallard"}"#),
+        None,
+        None,
+        Some("127.0.0.1"),
+    );
+
+    // Fields of the wrong type are ignored
+    check!(
+        Some(r#"{"user_id": "4", "session_token": 10, "ip_address": null}"#),
+        None,
+        None,
+        None,
+    );
+
+    assert!(RateLimitKeys::from_params(&Params::new(Some("{}"))).is_empty());
+    assert!(
+        !RateLimitKeys::from_params(&Params::new(Some(r#"{"user_id": 1}"#))).is_empty()
+    );
+}
@@ -28,6 +28,7 @@
 //!
 //! For example:
 //! * `site` / `member` / `user` &mdash; User is a site member
+//! * `site` / `application` / `user` &mdash; User has applied to be a site member
 //! * `user` / `block` / `user` &mdash; User has blocked another user
 
 #[allow(unused_imports)]
@@ -42,8 +43,10 @@ mod prelude {
 #[macro_use]
 mod macros;
 
+mod page_parent;
 mod page_star;
 mod page_watch;
+mod site_application;
 mod site_ban;
 mod site_member;
 mod site_user;
@@ -52,8 +55,10 @@ mod user_block;
 mod user_contact;
 mod user_follow;
 
+pub use self::page_parent::*;
 pub use self::page_star::*;
 pub use self::page_watch::*;
+pub use self::site_application::*;
 pub use self::site_ban::*;
 pub use self::site_member::*;
 pub use self::site_user::*;
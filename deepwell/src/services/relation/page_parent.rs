@@ -0,0 +1,213 @@
+/*
+ * services/relation/page_parent.rs
+ *
+ * DEEPWELL - Wikijump API provider and database manager
+ * Copyright (C) 2019-2025 Wikijump Team
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Governs the relation which tracks page parents.
+//!
+//! The parent page is the `dest`, and the child page is the `from`.
+//! A page may have several parents, but the parent graph must never
+//! contain a cycle, which is checked whenever a relation is added.
+
+use super::prelude::*;
+use sea_orm::sea_query::{Query, SelectStatement, SimpleExpr};
+use std::collections::HashSet;
+
+/// The maximum number of levels the parent graph is traversed when checking for cycles.
+///
+/// Page hierarchies are expected to be shallow, this is a safety limit to avoid
+/// runaway queries rather than a practical restriction.
+pub const MAXIMUM_PARENT_DEPTH: usize = 64;
+
+impl_relation!(
+    PageParent,
+    Page,
+    parent_page_id,
+    Page,
+    child_page_id,
+    (),
+    NO_CREATE_IMPL,
+);
+
+impl RelationService {
+    pub async fn create_page_parent(
+        ctx: &ServiceContext<'_>,
+        CreatePageParent {
+            parent_page_id,
+            child_page_id,
+            metadata: (),
+            created_by,
+        }: CreatePageParent,
+    ) -> Result<()> {
+        // Cannot add this relationship if the child is already an ancestor of the parent,
+        // this includes the case where the two pages are the same.
+        if Self::page_is_ancestor(ctx, child_page_id, parent_page_id).await? {
+            error!(
+                "Cannot parent page ID {child_page_id} to page ID {parent_page_id}, it would create a cycle",
+            );
+            return Err(Error::PageParentCycle);
+        }
+
+        create_operation!(
+            ctx,
+            PageParent,
+            Page,
+            parent_page_id,
+            Page,
+            child_page_id,
+            created_by,
+            &(),
+        )
+    }
+
+    /// Determines if `ancestor_id` is the same as, or an ancestor of, `page_id`.
+    ///
+    /// This walks up the parent graph one level at a time, fetching all
+    /// the parents of the current level in one query.
+    pub async fn page_is_ancestor(
+        ctx: &ServiceContext<'_>,
+        ancestor_id: i64,
+        page_id: i64,
+    ) -> Result<bool> {
+        let mut visited = HashSet::new();
+        let mut current = vec![page_id];
+
+        for _ in 0..MAXIMUM_PARENT_DEPTH {
+            if current.contains(&ancestor_id) {
+                return Ok(true);
+            }
+
+            visited.extend(current.iter().copied());
+            current = Self::get_page_parent_relations(ctx, &current)
+                .await?
+                .into_iter()
+                .map(|relation| relation.dest_id)
+                .filter(|page_id| !visited.contains(page_id))
+                .collect();
+
+            if current.is_empty() {
+                return Ok(false);
+            }
+        }
+
+        error!("Page parent graph for page ID {page_id} exceeded maximum depth");
+        Err(Error::PageParentDepthExceeded)
+    }
+
+    /// Gets all active parent relations for which any of the given pages are the child.
+    pub async fn get_page_parent_relations(
+        ctx: &ServiceContext<'_>,
+        child_page_ids: &[i64],
+    ) -> Result<Vec<RelationModel>> {
+        Self::get_page_parent_relations_by(
+            ctx,
+            relation::Column::FromId.is_in(child_page_ids.iter().copied()),
+        )
+        .await
+    }
+
+    /// Gets all active parent relations for which any of the given pages are the parent.
+    pub async fn get_page_child_relations(
+        ctx: &ServiceContext<'_>,
+        parent_page_ids: &[i64],
+    ) -> Result<Vec<RelationModel>> {
+        Self::get_page_parent_relations_by(
+            ctx,
+            relation::Column::DestId.is_in(parent_page_ids.iter().copied()),
+        )
+        .await
+    }
+
+    async fn get_page_parent_relations_by(
+        ctx: &ServiceContext<'_>,
+        condition: SimpleExpr,
+    ) -> Result<Vec<RelationModel>> {
+        let txn = ctx.transaction();
+        let relations = Relation::find()
+            .filter(page_parent_condition().add(condition))
+            .order_by_asc(relation::Column::CreatedAt)
+            .all(txn)
+            .await?;
+
+        Ok(relations)
+    }
+
+    /// Removes all parent relations involving this page.
+    ///
+    /// Whether this page is a parent or a child, this method
+    /// will remove all those relationships.
+    ///
+    /// # Returns
+    /// Returns the number of relationships removed.
+    pub async fn remove_all_page_parents(
+        ctx: &ServiceContext<'_>,
+        page_id: i64,
+        removed_by: i64,
+    ) -> Result<u64> {
+        let txn = ctx.transaction();
+        let model = relation::ActiveModel {
+            deleted_at: Set(Some(now())),
+            deleted_by: Set(Some(removed_by)),
+            ..Default::default()
+        };
+
+        let rows_removed = Relation::update_many()
+            .set(model)
+            .filter(
+                page_parent_condition().add(
+                    Condition::any()
+                        .add(relation::Column::DestId.eq(page_id))
+                        .add(relation::Column::FromId.eq(page_id)),
+                ),
+            )
+            .exec(txn)
+            .await?
+            .rows_affected;
+
+        Ok(rows_removed)
+    }
+}
+
+/// Builds a subquery which selects the IDs of child pages.
+///
+/// If a condition is passed, only relations matching it are selected.
+/// This is intended to be combined with `relation::Column::DestId`,
+/// to only select children of particular parent pages.
+pub fn page_child_ids_subquery(condition: Option<SimpleExpr>) -> SelectStatement {
+    let mut query = Query::select();
+    query
+        .column(relation::Column::FromId)
+        .from(Relation)
+        .cond_where(page_parent_condition());
+
+    if let Some(condition) = condition {
+        query.and_where(condition);
+    }
+
+    query
+}
+
+/// Condition for all active page parent relations.
+fn page_parent_condition() -> Condition {
+    Condition::all()
+        .add(relation::Column::RelationType.eq(RelationType::PageParent.value()))
+        .add(relation::Column::DestType.eq(RelationObjectType::Page))
+        .add(relation::Column::FromType.eq(RelationObjectType::Page))
+        .add(relation::Column::OverwrittenAt.is_null())
+        .add(relation::Column::DeletedAt.is_null())
+}
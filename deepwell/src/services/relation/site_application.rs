@@ -0,0 +1,88 @@
+/*
+ * services/relation/site_application.rs
+ *
+ * DEEPWELL - Wikijump API provider and database manager
+ * Copyright (C) 2019-2025 Wikijump Team
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use super::prelude::*;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SiteApplicationData {
+    pub message: String,
+}
+
+impl_relation!(
+    SiteApplication,
+    Site,
+    site_id,
+    User,
+    user_id,
+    SiteApplicationData,
+    NO_CREATE_IMPL,
+);
+
+impl RelationService {
+    pub async fn create_site_application(
+        ctx: &ServiceContext<'_>,
+        CreateSiteApplication {
+            site_id,
+            user_id,
+            metadata,
+            created_by,
+        }: CreateSiteApplication,
+    ) -> Result<()> {
+        // Cannot apply if banned
+        Self::check_site_ban(ctx, GetSiteBan { site_id, user_id }, "apply to").await?;
+
+        create_operation!(
+            ctx,
+            SiteApplication,
+            Site,
+            site_id,
+            User,
+            user_id,
+            created_by,
+            &metadata,
+        )
+    }
+
+    /// Gets all pending applications for a site, oldest first.
+    pub async fn get_pending_site_applications(
+        ctx: &ServiceContext<'_>,
+        site_id: i64,
+    ) -> Result<Vec<RelationModel>> {
+        let txn = ctx.transaction();
+        let applications = Relation::find()
+            .filter(
+                Condition::all()
+                    .add(
+                        relation::Column::RelationType
+                            .eq(RelationType::SiteApplication.value()),
+                    )
+                    .add(relation::Column::DestType.eq(RelationObjectType::Site))
+                    .add(relation::Column::DestId.eq(site_id))
+                    .add(relation::Column::FromType.eq(RelationObjectType::User))
+                    .add(relation::Column::OverwrittenAt.is_null())
+                    .add(relation::Column::DeletedAt.is_null()),
+            )
+            .order_by_asc(relation::Column::CreatedAt)
+            .all(txn)
+            .await?;
+
+        Ok(applications)
+    }
+}
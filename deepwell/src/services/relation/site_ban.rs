@@ -19,7 +19,9 @@
  */
 
 use super::prelude::*;
-use super::site_member::RemoveSiteMember;
+use super::site_application::{GetSiteApplication, RemoveSiteApplication};
+use super::site_member::{GetSiteMember, RemoveSiteMember};
+use crate::services::MembershipService;
 use time::Date;
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
@@ -49,16 +51,34 @@ impl RelationService {
             metadata,
         }: CreateSiteBan,
     ) -> Result<()> {
-        Self::remove_site_member(
-            ctx,
-            RemoveSiteMember {
-                site_id,
-                user_id,
-                removed_by: created_by,
-            },
-        )
-        .await?;
-        // TODO: remove site member applications
+        if Self::site_member_exists(ctx, GetSiteMember { site_id, user_id }).await? {
+            Self::remove_site_member(
+                ctx,
+                RemoveSiteMember {
+                    site_id,
+                    user_id,
+                    removed_by: created_by,
+                },
+            )
+            .await?;
+        }
+
+        if Self::site_application_exists(ctx, GetSiteApplication { site_id, user_id })
+            .await?
+        {
+            Self::remove_site_application(
+                ctx,
+                RemoveSiteApplication {
+                    site_id,
+                    user_id,
+                    removed_by: created_by,
+                },
+            )
+            .await?;
+        }
+
+        MembershipService::revoke_user_invitations(ctx, site_id, user_id, created_by)
+            .await?;
         // TODO: remove site roles
 
         create_operation!(
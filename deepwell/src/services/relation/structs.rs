@@ -128,11 +128,11 @@ pub enum RelationDirection {
 pub enum RelationType {
     SiteUser,
     SiteBan,
-    #[allow(dead_code)] // TEMP
     SiteApplication,
     SiteMember,
     PageStar,
     PageWatch,
+    PageParent,
     UserFollow,
     #[allow(dead_code)] // TEMP
     UserContact,
@@ -154,6 +154,7 @@ impl RelationType {
             RelationType::SiteMember => "member",
             RelationType::PageStar => "star",
             RelationType::PageWatch => "watch",
+            RelationType::PageParent => "parent",
             RelationType::UserFollow => "follow",
             RelationType::UserContact => "contact",
             RelationType::UserContactRequest => "contact-request",
@@ -178,6 +179,7 @@ impl RelationType {
             RelationType::SiteMember => t!(Site, User),
             RelationType::PageStar => t!(Page, User),
             RelationType::PageWatch => t!(Page, User),
+            RelationType::PageParent => t!(Page, Page),
             RelationType::UserFollow => t!(User, User),
             RelationType::UserContact => t!(User, User),
             RelationType::UserContactRequest => t!(User, User),
@@ -0,0 +1,305 @@
+/*
+ * services/render/includer.rs
+ *
+ * DEEPWELL - Wikijump API provider and database manager
+ * Copyright (C) 2019-2025 Wikijump Team
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Resolution of messy includes (`[[include-messy]]`) during rendering.
+//!
+//! ftml's `Includer` trait is synchronous, so pages cannot be fetched from
+//! the database while it runs. Instead, includes are resolved in two passes:
+//! the first collects the pages referenced by the wikitext, which are then
+//! fetched (and have their own includes resolved, recursively), and the
+//! second substitutes the fetched wikitext using `PageIncluder`.
+
+use super::prelude::*;
+use super::service::RenderService;
+use crate::services::{PageRevisionService, PageService, SiteService, TextService};
+use ftml::data::PageRef;
+use ftml::includes::{FetchedPage, IncludeRef, Includer};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use wikidot_normalize::normalize;
+
+/// The maximum number of levels of nested includes which will be resolved.
+pub const MAXIMUM_INCLUDE_DEPTH: usize = 5;
+
+/// The maximum size of wikitext after includes are expanded, in bytes.
+///
+/// Since a page can include another many times, which can do the same in turn,
+/// the expanded size can grow exponentially with depth unless capped.
+pub const MAXIMUM_INCLUDE_SIZE: usize = 4 * 1024 * 1024;
+
+/// The key used to identify an included page, `(site slug, page slug)`.
+type PageKey = (String, String);
+
+/// The result of resolving all includes in a piece of wikitext.
+#[derive(Debug, Default)]
+pub struct ResolvedIncludes {
+    /// The wikitext, with all include blocks substituted.
+    pub wikitext: String,
+
+    /// All pages which were included, directly or indirectly.
+    ///
+    /// These always have the site specified, since pages included
+    /// from another site resolve relative references against that site.
+    pub included_pages: Vec<PageRef<'static>>,
+}
+
+/// State shared through the recursive resolution of includes.
+struct IncludeState<'a> {
+    settings: &'a WikitextSettings,
+
+    /// Cache of fetched wikitext, so each page is only fetched once per render.
+    /// `None` means that the page (or its site) does not exist.
+    fetched: HashMap<PageKey, Option<String>>,
+
+    /// All pages which were included, in order of first inclusion.
+    included: Vec<PageKey>,
+
+    /// The current chain of includes, used to detect cycles.
+    chain: Vec<PageKey>,
+}
+
+impl RenderService {
+    /// Resolves all messy includes in the given wikitext.
+    ///
+    /// Included pages have their own includes resolved, up to `MAXIMUM_INCLUDE_DEPTH`.
+    /// Cyclical includes, or those which would expand past `MAXIMUM_INCLUDE_SIZE`,
+    /// are replaced with an error message instead of being expanded.
+    pub async fn resolve_includes(
+        ctx: &ServiceContext<'_>,
+        site_slug: &str,
+        wikitext: String,
+        settings: &WikitextSettings,
+    ) -> Result<ResolvedIncludes> {
+        let mut state = IncludeState {
+            settings,
+            fetched: HashMap::new(),
+            included: Vec::new(),
+            chain: Vec::new(),
+        };
+
+        let wikitext = expand(ctx, site_slug, wikitext, &mut state).await?;
+        let included_pages = state
+            .included
+            .into_iter()
+            .map(|(site, page)| PageRef::page_and_site(site, page))
+            .collect();
+
+        Ok(ResolvedIncludes {
+            wikitext,
+            included_pages,
+        })
+    }
+}
+
+/// Recursively expands the includes in one piece of wikitext.
+///
+/// This is boxed since async functions cannot recurse directly.
+fn expand<'a>(
+    ctx: &'a ServiceContext<'_>,
+    site_slug: &'a str,
+    wikitext: String,
+    state: &'a mut IncludeState<'_>,
+) -> Pin<Box<dyn Future<Output = Result<String>> + Send + 'a>> {
+    Box::pin(async move {
+        // First pass, to find which pages are included
+        let page_refs: Vec<PageKey> = {
+            let empty = HashMap::new();
+            let includer = PageIncluder {
+                site_slug,
+                contents: &empty,
+            };
+
+            let (_, page_refs) =
+                ftml::include(&wikitext, state.settings, includer, || {
+                    Error::RenderIncluder
+                })?;
+
+            page_refs
+                .iter()
+                .map(|page_ref| page_key(site_slug, page_ref))
+                .collect()
+        };
+
+        if page_refs.is_empty() {
+            return Ok(wikitext);
+        }
+
+        // Fetch and expand each included page
+        let mut contents = HashMap::new();
+        for key in page_refs {
+            if contents.contains_key(&key) {
+                continue;
+            }
+
+            let content = if state.chain.contains(&key) {
+                warn!(
+                    "Include cycle found for page '{}' on site '{}'",
+                    key.1, key.0
+                );
+                Some(error_block(&format!(
+                    "Page \"{}\" cannot include itself.",
+                    key.1,
+                )))
+            } else if state.chain.len() >= MAXIMUM_INCLUDE_DEPTH {
+                warn!(
+                    "Maximum include depth reached, not including page '{}'",
+                    key.1
+                );
+                Some(error_block(&format!(
+                    "Page \"{}\" cannot be included, too many nested includes.",
+                    key.1,
+                )))
+            } else {
+                if !state.included.contains(&key) {
+                    state.included.push(key.clone());
+                }
+
+                match fetch_page(ctx, &key, state).await? {
+                    Some(content) => {
+                        state.chain.push(key.clone());
+                        let result = expand(ctx, &key.0, content, state).await;
+                        state.chain.pop();
+                        Some(result?)
+                    }
+                    None => None,
+                }
+            };
+
+            contents.insert(key, content);
+        }
+
+        // Second pass, substituting the page contents
+        let includer = PageIncluder {
+            site_slug,
+            contents: &contents,
+        };
+
+        let (output, _) = ftml::include(&wikitext, state.settings, includer, || {
+            Error::RenderIncluder
+        })?;
+
+        if output.len() > MAXIMUM_INCLUDE_SIZE {
+            warn!(
+                "Expanded includes are too large ({} bytes), not including",
+                output.len(),
+            );
+            return Ok(error_block(
+                "Pages cannot be included, the included content is too large.",
+            ));
+        }
+
+        Ok(output)
+    })
+}
+
+/// Gets the wikitext of the latest revision of the given page, if it exists.
+async fn fetch_page(
+    ctx: &ServiceContext<'_>,
+    key: &PageKey,
+    state: &mut IncludeState<'_>,
+) -> Result<Option<String>> {
+    if let Some(content) = state.fetched.get(key) {
+        return Ok(content.clone());
+    }
+
+    let (site_slug, page_slug) = key;
+    debug!("Fetching included page '{page_slug}' on site '{site_slug}'");
+
+    let content = match SiteService::get_optional(ctx, Reference::Slug(cow!(site_slug)))
+        .await?
+    {
+        None => None,
+        Some(site) => {
+            match PageService::get_optional(
+                ctx,
+                site.site_id,
+                Reference::Slug(cow!(page_slug)),
+            )
+            .await?
+            {
+                None => None,
+                Some(page) => {
+                    let revision =
+                        PageRevisionService::get_latest(ctx, site.site_id, page.page_id)
+                            .await?;
+
+                    Some(TextService::get(ctx, &revision.wikitext_hash).await?)
+                }
+            }
+        }
+    };
+
+    state.fetched.insert(key.clone(), content.clone());
+    Ok(content)
+}
+
+fn page_key(site_slug: &str, page_ref: &PageRef) -> PageKey {
+    let (site, page) = page_ref.fields_or(site_slug);
+    let mut page = str!(page);
+    normalize(&mut page);
+    (str!(site), page)
+}
+
+fn error_block(message: &str) -> String {
+    format!("[[div class=\"error-block\"]]\n{message}\n[[/div]]")
+}
+
+/// An `Includer` which substitutes already-fetched page contents.
+///
+/// A value of `None` in `contents` means that the page does not exist.
+/// Pages which are absent altogether are also treated as not existing,
+/// which is the case during the first pass, where only the references matter.
+#[derive(Debug)]
+pub struct PageIncluder<'a> {
+    site_slug: &'a str,
+    contents: &'a HashMap<PageKey, Option<String>>,
+}
+
+impl<'t> Includer<'t> for PageIncluder<'_> {
+    type Error = Error;
+
+    fn include_pages(
+        &mut self,
+        includes: &[IncludeRef<'t>],
+    ) -> Result<Vec<FetchedPage<'t>>> {
+        let pages = includes
+            .iter()
+            .map(|include| {
+                let page_ref = include.page_ref().clone();
+                let content = self
+                    .contents
+                    .get(&page_key(self.site_slug, &page_ref))
+                    .and_then(|content| content.clone())
+                    .map(Cow::Owned);
+
+                FetchedPage { page_ref, content }
+            })
+            .collect();
+
+        Ok(pages)
+    }
+
+    fn no_such_include(&mut self, page_ref: &PageRef<'t>) -> Result<Cow<'t, str>> {
+        let message = format!("Page to be included \"{page_ref}\" cannot be found!");
+        Ok(Cow::Owned(error_block(&message)))
+    }
+}
@@ -33,8 +33,14 @@ mod prelude {
     };
 }
 
+mod includer;
+mod pool;
 mod service;
 mod structs;
 
+pub use self::includer::{PageIncluder, ResolvedIncludes, MAXIMUM_INCLUDE_DEPTH};
+pub use self::pool::{
+    RenderMetricsSnapshot, RenderPool, RenderPoolMetrics, RenderPriority,
+};
 pub use self::service::RenderService;
 pub use self::structs::*;
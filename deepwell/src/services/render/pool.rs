@@ -0,0 +1,450 @@
+/*
+ * services/render/pool.rs
+ *
+ * DEEPWELL - Wikijump API provider and database manager
+ * Copyright (C) 2019-2025 Wikijump Team
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Runs ftml on a dedicated pool of render threads.
+//!
+//! Parsing and rendering are entirely synchronous, so running them within
+//! a future means they block a runtime thread, and any timeout wrapped around
+//! them cannot fire until they have already finished.
+//!
+//! Instead, render requests are sent to worker threads which are outside of
+//! the async runtime, and the result is ferried back over a oneshot channel.
+//! The caller waits on that channel, which _can_ be timed out. There are two
+//! lanes, each with its own threads and bounded queue, so that background
+//! rerender jobs cannot crowd out interactive requests.
+//!
+//! Requests which are abandoned while still queued (such as from a timeout
+//! or a disconnected client) are dropped without being rendered. However,
+//! ftml has no means of interrupting a render in progress, so a timed out
+//! render continues to occupy its worker until it finishes. Its output is
+//! then discarded.
+//!
+//! Such renders are counted against their lane's capacity until they finish.
+//! If every worker in the interactive lane is occupied by a timed out render,
+//! new interactive requests are rejected immediately rather than queued behind
+//! them.
+
+use super::prelude::*;
+use std::borrow::Cow;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration as StdDuration, Instant};
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::timeout;
+
+type ResponsePayload = (HtmlOutput, Vec<ParseError>);
+
+type RequestSender = mpsc::Sender<RenderRequest>;
+
+/// The render is queued or in progress, and the caller is waiting on it.
+const STATE_PENDING: u8 = 0;
+
+/// The render has finished, and its output was sent to the caller.
+const STATE_FINISHED: u8 = 1;
+
+/// The caller timed out while the render was in progress.
+const STATE_TIMED_OUT: u8 = 2;
+type RequestReceiver = Arc<Mutex<mpsc::Receiver<RenderRequest>>>;
+
+/// Which lane a render request should be queued in.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, Hash, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum RenderPriority {
+    /// A render a user is actively waiting on, such as an edit or page view.
+    ///
+    /// If this lane's queue is full, the request fails immediately.
+    Interactive,
+
+    /// A render performed by a job, such as a rerender after a dependency changed.
+    ///
+    /// If this lane's queue is full, the caller waits until there is room.
+    Background,
+}
+
+impl RenderPriority {
+    pub fn value(self) -> &'static str {
+        match self {
+            RenderPriority::Interactive => "interactive",
+            RenderPriority::Background => "background",
+        }
+    }
+}
+
+#[derive(Debug)]
+struct RenderRequest {
+    wikitext: String,
+    page_info: PageInfo<'static>,
+    settings: WikitextSettings,
+    started: oneshot::Sender<()>,
+    response: oneshot::Sender<ResponsePayload>,
+    state: Arc<AtomicU8>,
+}
+
+#[derive(Debug)]
+struct RenderLane {
+    sink: RequestSender,
+    metrics: Arc<RenderMetrics>,
+    workers: u64,
+}
+
+impl RenderLane {
+    fn spawn(priority: RenderPriority, workers: u16, queue_size: usize) -> Self {
+        info!(
+            "Starting {} render workers for {} lane",
+            workers,
+            priority.value(),
+        );
+
+        let (sink, source) = mpsc::channel(queue_size);
+        let source = Arc::new(Mutex::new(source));
+        let metrics = Arc::new(RenderMetrics::default());
+
+        for id in 1..=workers {
+            let source = Arc::clone(&source);
+            let metrics = Arc::clone(&metrics);
+
+            thread::Builder::new()
+                .name(format!("render-{}-{id}", priority.value()))
+                .spawn(move || RenderPool::main_loop(priority, source, metrics))
+                .expect("Unable to spawn render worker thread");
+        }
+
+        RenderLane {
+            sink,
+            metrics,
+            workers: u64::from(workers),
+        }
+    }
+}
+
+/// Render statistics for one lane.
+///
+/// Durations only cover the time spent within ftml, not time waiting in the queue.
+#[derive(Debug, Default)]
+pub struct RenderMetrics {
+    completed: AtomicU64,
+    panicked: AtomicU64,
+    timed_out: AtomicU64,
+    cancelled: AtomicU64,
+    rejected: AtomicU64,
+    abandoned: AtomicU64,
+    total_duration_us: AtomicU64,
+    max_duration_us: AtomicU64,
+}
+
+impl RenderMetrics {
+    fn record(&self, duration: StdDuration) {
+        let duration_us = u64::try_from(duration.as_micros()).unwrap_or(u64::MAX);
+
+        self.completed.fetch_add(1, Ordering::Relaxed);
+        self.total_duration_us
+            .fetch_add(duration_us, Ordering::Relaxed);
+        self.max_duration_us
+            .fetch_max(duration_us, Ordering::Relaxed);
+    }
+
+    #[inline]
+    fn increment(counter: &AtomicU64) {
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> RenderMetricsSnapshot {
+        let completed = self.completed.load(Ordering::Relaxed);
+        let total_duration_us = self.total_duration_us.load(Ordering::Relaxed);
+
+        RenderMetricsSnapshot {
+            completed,
+            panicked: self.panicked.load(Ordering::Relaxed),
+            timed_out: self.timed_out.load(Ordering::Relaxed),
+            cancelled: self.cancelled.load(Ordering::Relaxed),
+            rejected: self.rejected.load(Ordering::Relaxed),
+            abandoned: self.abandoned.load(Ordering::Relaxed),
+            total_duration_us,
+            mean_duration_us: total_duration_us.checked_div(completed).unwrap_or(0),
+            max_duration_us: self.max_duration_us.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct RenderMetricsSnapshot {
+    pub completed: u64,
+    pub panicked: u64,
+    pub timed_out: u64,
+    pub cancelled: u64,
+    pub rejected: u64,
+
+    /// Timed out renders which are still occupying a worker.
+    pub abandoned: u64,
+
+    pub total_duration_us: u64,
+    pub mean_duration_us: u64,
+    pub max_duration_us: u64,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct RenderPoolMetrics {
+    pub interactive: RenderMetricsSnapshot,
+    pub background: RenderMetricsSnapshot,
+}
+
+#[derive(Debug)]
+pub struct RenderPool {
+    interactive: RenderLane,
+    background: RenderLane,
+}
+
+impl RenderPool {
+    /// Starts the render worker threads and returns an instance of this struct.
+    ///
+    /// Like `MimeAnalyzer`, only one of these should be created, and it lives
+    /// in the server state.
+    pub fn spawn(config: &Config) -> Self {
+        let queue_size = usize::from(config.render_queue_size.get());
+
+        RenderPool {
+            interactive: RenderLane::spawn(
+                RenderPriority::Interactive,
+                config.render_workers.get(),
+                queue_size,
+            ),
+            background: RenderLane::spawn(
+                RenderPriority::Background,
+                config.render_background_workers.get(),
+                queue_size,
+            ),
+        }
+    }
+
+    #[inline]
+    fn lane(&self, priority: RenderPriority) -> &RenderLane {
+        match priority {
+            RenderPriority::Interactive => &self.interactive,
+            RenderPriority::Background => &self.background,
+        }
+    }
+
+    pub fn metrics(&self) -> RenderPoolMetrics {
+        RenderPoolMetrics {
+            interactive: self.interactive.metrics.snapshot(),
+            background: self.background.metrics.snapshot(),
+        }
+    }
+
+    /// Main loop for a render worker.
+    ///
+    /// Workers in the same lane share one receiver, whichever worker
+    /// is idle takes the next request.
+    fn main_loop(
+        priority: RenderPriority,
+        source: RequestReceiver,
+        metrics: Arc<RenderMetrics>,
+    ) {
+        loop {
+            // Only hold the lock while waiting, not while rendering
+            let request = {
+                let mut source = source.lock().expect("Render queue lock poisoned");
+                source.blocking_recv()
+            };
+
+            let RenderRequest {
+                mut wikitext,
+                page_info,
+                settings,
+                started,
+                response,
+                state,
+            } = match request {
+                Some(request) => request,
+                None => break,
+            };
+
+            // If the caller is no longer waiting, don't bother
+            if started.send(()).is_err() || response.is_closed() {
+                debug!("Render request abandoned before starting, skipping");
+                RenderMetrics::increment(&metrics.cancelled);
+                continue;
+            }
+
+            let start = Instant::now();
+            let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                ftml::preprocess(&mut wikitext);
+                let tokens = ftml::tokenize(&wikitext);
+                let result = ftml::parse(&tokens, &page_info, &settings);
+                let (tree, errors) = result.into();
+                let html_output = HtmlRender.render(&tree, &page_info, &settings);
+                (html_output, errors)
+            }));
+            let elapsed = start.elapsed();
+
+            // If the caller timed out, this render no longer occupies the lane
+            if state
+                .compare_exchange(
+                    STATE_PENDING,
+                    STATE_FINISHED,
+                    Ordering::AcqRel,
+                    Ordering::Acquire,
+                )
+                .is_err()
+            {
+                metrics.abandoned.fetch_sub(1, Ordering::AcqRel);
+            }
+
+            match result {
+                Ok(output) => {
+                    debug!(
+                        "Finished {} render in {} ms",
+                        priority.value(),
+                        elapsed.as_millis(),
+                    );
+
+                    metrics.record(elapsed);
+
+                    // Caller may have timed out, in which case the output is discarded
+                    if response.send(output).is_err() {
+                        warn!(
+                            "Render finished after caller stopped waiting ({} ms)",
+                            elapsed.as_millis(),
+                        );
+                    }
+                }
+                Err(_) => {
+                    // Dropping the response sender informs the caller
+                    error!("Render panicked after {} ms", elapsed.as_millis());
+                    RenderMetrics::increment(&metrics.panicked);
+                }
+            }
+        }
+
+        panic!("Render channel closed (this usually happens when the main application crashes)");
+    }
+
+    /// Submits a render request and waits for its output.
+    ///
+    /// The timeout begins once a worker has picked up the request,
+    /// time spent waiting in the queue is not counted against it.
+    pub async fn render(
+        &self,
+        priority: RenderPriority,
+        render_timeout: StdDuration,
+        wikitext: String,
+        page_info: &PageInfo<'_>,
+        settings: &WikitextSettings,
+    ) -> Result<ResponsePayload> {
+        let lane = self.lane(priority);
+        let (started_send, started_recv) = oneshot::channel();
+        let (resp_send, mut resp_recv) = oneshot::channel();
+        let state = Arc::new(AtomicU8::new(STATE_PENDING));
+        let request = RenderRequest {
+            wikitext,
+            page_info: owned_page_info(page_info),
+            settings: settings.clone(),
+            started: started_send,
+            response: resp_send,
+            state: Arc::clone(&state),
+        };
+
+        // Timed out renders still occupy their workers, so if all of them are
+        // taken up this way, an interactive request would only wait in the queue.
+        if priority == RenderPriority::Interactive
+            && lane.metrics.abandoned.load(Ordering::Acquire) >= lane.workers
+        {
+            warn!("All interactive render workers are occupied by timed out renders, rejecting request");
+            RenderMetrics::increment(&lane.metrics.rejected);
+            return Err(Error::RenderQueueFull);
+        }
+
+        // Send the request
+        match priority {
+            RenderPriority::Interactive => {
+                lane.sink.try_send(request).map_err(|error| match error {
+                    mpsc::error::TrySendError::Full(_) => {
+                        warn!("Interactive render queue is full, rejecting request");
+                        RenderMetrics::increment(&lane.metrics.rejected);
+                        Error::RenderQueueFull
+                    }
+                    mpsc::error::TrySendError::Closed(_) => {
+                        panic!("Render channel is closed")
+                    }
+                })?;
+            }
+            RenderPriority::Background => {
+                lane.sink
+                    .send(request)
+                    .await
+                    .expect("Render channel is closed");
+            }
+        }
+
+        // Wait for a worker to begin, then wait for the output
+        //
+        // If the response sender is dropped, the worker panicked.
+        started_recv.await.map_err(|_| Error::RenderWorker)?;
+        match timeout(render_timeout, &mut resp_recv).await {
+            Ok(Ok(output)) => Ok(output),
+            Ok(Err(_)) => Err(Error::RenderWorker),
+            Err(_) => {
+                // Count against the lane before marking, so the worker
+                // never releases the render before it has been counted.
+                lane.metrics.abandoned.fetch_add(1, Ordering::AcqRel);
+                if state
+                    .compare_exchange(
+                        STATE_PENDING,
+                        STATE_TIMED_OUT,
+                        Ordering::AcqRel,
+                        Ordering::Acquire,
+                    )
+                    .is_err()
+                {
+                    // Finished just as the timeout fired, use the output
+                    lane.metrics.abandoned.fetch_sub(1, Ordering::AcqRel);
+                    return resp_recv.await.map_err(|_| Error::RenderWorker);
+                }
+
+                warn!(
+                    "Render exceeded timeout of {} ms, abandoning",
+                    render_timeout.as_millis(),
+                );
+                RenderMetrics::increment(&lane.metrics.timed_out);
+                Err(Error::RenderTimeout)
+            }
+        }
+    }
+}
+
+/// Copies all borrowed fields so the `PageInfo` can be sent to a worker thread.
+fn owned_page_info(page_info: &PageInfo) -> PageInfo<'static> {
+    fn owned(value: &str) -> Cow<'static, str> {
+        Cow::Owned(str!(value))
+    }
+
+    PageInfo {
+        page: owned(&page_info.page),
+        category: page_info.category.as_deref().map(owned),
+        site: owned(&page_info.site),
+        title: owned(&page_info.title),
+        alt_title: page_info.alt_title.as_deref().map(owned),
+        score: page_info.score,
+        tags: page_info.tags.iter().map(|tag| owned(tag)).collect(),
+        language: owned(&page_info.language),
+    }
+}
@@ -18,9 +18,19 @@
  * along with this program. If not, see <http://www.gnu.org/licenses/>.
  */
 
+use super::includer::ResolvedIncludes;
+use super::pool::RenderPriority;
 use super::prelude::*;
 use crate::services::TextService;
-use tokio::time::timeout;
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+/// Matches the style blocks which ftml emits for page-level CSS.
+///
+/// ftml only produces bare `<style>` tags, and the CSS within is re-serialized
+/// by `parcel_css`, so it cannot contain a closing tag of its own.
+static STYLE_REGEX: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?s)<style>(.*?)</style>").unwrap());
 
 #[derive(Debug)]
 pub struct RenderService;
@@ -28,41 +38,88 @@ pub struct RenderService;
 impl RenderService {
     pub async fn render(
         ctx: &ServiceContext<'_>,
-        mut wikitext: String,
+        wikitext: String,
         page_info: &PageInfo<'_>,
         settings: &WikitextSettings,
+        priority: RenderPriority,
     ) -> Result<RenderOutput> {
         let compiled_generator = FTML_VERSION.clone();
 
-        // Isolate the actual render task.
-        // This way we can cut it off if it times out.
+        // Substitute included pages
+        let ResolvedIncludes {
+            wikitext,
+            included_pages,
+        } = Self::resolve_includes(ctx, &page_info.site, wikitext, settings).await?;
 
+        // Run ftml to parse and render.
+        // This happens on the render pool, since it is blocking.
         let config = ctx.config();
-        let (html_output, errors) = timeout(config.render_timeout, async {
-            // Run ftml to parse and render
-            // TODO include
-            ftml::preprocess(&mut wikitext);
-            let tokens = ftml::tokenize(&wikitext);
-            let result = ftml::parse(&tokens, page_info, settings);
-            let (tree, errors) = result.into();
-            let html_output = HtmlRender.render(&tree, page_info, settings);
-            (html_output, errors)
-        })
-        .await
-        // Not using Error::from() because timeouts could occur in other places,
-        // and this error variant is not specific to all timeouts.
-        .map_err(|_| Error::RenderTimeout)?;
+        let (mut html_output, errors) = ctx
+            .render_pool()
+            .render(
+                priority,
+                config.render_timeout,
+                wikitext,
+                page_info,
+                settings,
+            )
+            .await?;
+
+        // Gather page metadata
+        let compiled_style = Self::extract_styles(&mut html_output.body);
+        let compiled_meta = serde_json::to_value(&html_output.meta)?;
 
-        // Insert compiled HTML into text table
-        let compiled_hash = TextService::create(ctx, html_output.body.clone()).await?;
+        // Insert compiled HTML and CSS into text table
+        let (compiled_hash, compiled_style_hash) = try_join!(
+            TextService::create(ctx, html_output.body.clone()),
+            TextService::create(ctx, compiled_style),
+        )?;
 
         // Build and return
         Ok(RenderOutput {
             html_output,
             errors,
             compiled_hash,
+            compiled_style_hash,
+            compiled_meta,
             compiled_at: now(),
             compiled_generator,
+            included_pages,
         })
     }
+
+    /// Moves all the page-level CSS from the rendered HTML into one stylesheet.
+    ///
+    /// The style blocks are removed from the HTML body, so that consumers
+    /// place the stylesheet in the document head instead of duplicating it.
+    fn extract_styles(html: &mut String) -> String {
+        let mut styles = String::new();
+        for capture in STYLE_REGEX.captures_iter(html) {
+            if !styles.is_empty() {
+                styles.push('\n');
+            }
+
+            styles.push_str(&capture[1]);
+        }
+
+        if !styles.is_empty() {
+            *html = STYLE_REGEX.replace_all(html, "").into_owned();
+        }
+
+        styles
+    }
+}
+
+#[test]
+fn extract_styles() {
+    let mut html =
+        str!("<p>A</p><style>.a { color: red; }</style><p>B</p><style>\n.b {}\n</style>");
+    let styles = RenderService::extract_styles(&mut html);
+    assert_eq!(html, "<p>A</p><p>B</p>");
+    assert_eq!(styles, ".a { color: red; }\n\n.b {}\n");
+
+    let mut html = str!("<p>No styles</p>");
+    let styles = RenderService::extract_styles(&mut html);
+    assert_eq!(html, "<p>No styles</p>");
+    assert!(styles.is_empty());
 }
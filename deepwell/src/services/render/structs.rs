@@ -20,6 +20,8 @@
 
 use super::prelude::*;
 use crate::hash::TextHash;
+use ftml::data::PageRef;
+use serde_json::Value as JsonValue;
 use time::OffsetDateTime;
 
 #[derive(Serialize, Debug)]
@@ -27,8 +29,13 @@ pub struct RenderOutput {
     pub html_output: HtmlOutput,
     pub errors: Vec<ParseError>,
     pub compiled_hash: TextHash,
+    pub compiled_style_hash: TextHash,
+    pub compiled_meta: JsonValue,
 
     #[serde(with = "time::serde::rfc3339")]
     pub compiled_at: OffsetDateTime,
     pub compiled_generator: String,
+
+    /// All pages included via messy includes, directly or indirectly.
+    pub included_pages: Vec<PageRef<'static>>,
 }
@@ -0,0 +1,186 @@
+/*
+ * services/scan/impls/clamd.rs
+ *
+ * DEEPWELL - Wikijump API provider and database manager
+ * Copyright (C) 2019-2025 Wikijump Team
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Scanner which uses a ClamAV daemon, through its `INSTREAM` command.
+//!
+//! The daemon must have a `StreamMaxLength` at least as large as the maximum
+//! blob size, otherwise it will refuse to scan larger blobs.
+
+use super::prelude::*;
+use std::future::Future;
+use std::io;
+use std::time::Duration as StdDuration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpStream, UnixStream};
+use tokio::time;
+
+/// The largest chunk of data to send to clamd at once.
+const MAXIMUM_CHUNK_SIZE: usize = 64 * 1024;
+
+/// The longest reply to read from clamd.
+const MAXIMUM_REPLY_LENGTH: u64 = 4096;
+
+trait Connection: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Connection for T {}
+
+#[derive(Debug)]
+pub struct ClamdScanner {
+    address: String,
+    timeout: StdDuration,
+}
+
+impl ClamdScanner {
+    /// Creates a new clamd scanner.
+    ///
+    /// The address is either a path to a Unix socket, or a TCP `host:port`.
+    pub fn new(address: &str, timeout: StdDuration) -> Self {
+        ClamdScanner {
+            address: str!(address),
+            timeout,
+        }
+    }
+
+    async fn connect(&self) -> io::Result<Box<dyn Connection>> {
+        if self.address.starts_with('/') {
+            let stream = UnixStream::connect(&self.address).await?;
+            Ok(Box::new(stream))
+        } else {
+            let stream = TcpStream::connect(&self.address).await?;
+            Ok(Box::new(stream))
+        }
+    }
+}
+
+#[async_trait]
+impl Scanner for ClamdScanner {
+    #[inline]
+    fn name(&self) -> &'static str {
+        "clamd"
+    }
+
+    async fn begin(&self) -> Result<Box<dyn ScanSession>> {
+        debug!("Connecting to clamd at '{}'", self.address);
+
+        let stream = with_timeout(self.timeout, async {
+            let mut stream = self.connect().await?;
+            stream.write_all(b"zINSTREAM\0").await?;
+            Ok(stream)
+        })
+        .await?;
+
+        Ok(Box::new(ClamdSession {
+            stream,
+            timeout: self.timeout,
+        }))
+    }
+}
+
+struct ClamdSession {
+    stream: Box<dyn Connection>,
+    timeout: StdDuration,
+}
+
+#[async_trait]
+impl ScanSession for ClamdSession {
+    async fn update(&mut self, data: &[u8]) -> Result<()> {
+        // Each chunk is prefixed by its length as a big-endian 32-bit integer
+        for chunk in data.chunks(MAXIMUM_CHUNK_SIZE) {
+            let length = u32::try_from(chunk.len()).expect("Chunk size exceeds u32");
+
+            with_timeout(self.timeout, async {
+                self.stream.write_u32(length).await?;
+                self.stream.write_all(chunk).await
+            })
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn finish(mut self: Box<Self>) -> Result<ScanResult> {
+        // A zero-length chunk marks the end of the stream
+        let mut reply = Vec::new();
+        with_timeout(self.timeout, async {
+            self.stream.write_u32(0).await?;
+            self.stream.flush().await?;
+            (&mut self.stream)
+                .take(MAXIMUM_REPLY_LENGTH)
+                .read_to_end(&mut reply)
+                .await
+        })
+        .await?;
+
+        parse_reply(&reply)
+    }
+}
+
+async fn with_timeout<T, F>(timeout: StdDuration, future: F) -> Result<T>
+where
+    F: Future<Output = io::Result<T>>,
+{
+    match time::timeout(timeout, future).await {
+        Ok(Ok(value)) => Ok(value),
+        Ok(Err(error)) => {
+            error!("Error communicating with clamd: {error}");
+            Err(Error::BlobScanner(error.to_string()))
+        }
+        Err(_) => {
+            error!("Timed out communicating with clamd");
+            Err(Error::BlobScanner(str!("timed out")))
+        }
+    }
+}
+
+/// Parses the reply to an `INSTREAM` command.
+///
+/// This is either `stream: OK`, `stream: <signature> FOUND`,
+/// or an error message ending with `ERROR`.
+fn parse_reply(reply: &[u8]) -> Result<ScanResult> {
+    let reply = String::from_utf8_lossy(reply);
+    let reply = reply.trim_end_matches(['\0', '\n']);
+    let result = reply.strip_prefix("stream: ").unwrap_or(reply);
+
+    if result == "OK" {
+        return Ok(ScanResult::Clean);
+    }
+
+    if let Some(signature) = result.strip_suffix(" FOUND") {
+        return Ok(ScanResult::Infected {
+            signature: str!(signature),
+        });
+    }
+
+    error!("Unexpected reply from clamd: {reply}");
+    Err(Error::BlobScanner(str!(reply)))
+}
+
+#[test]
+fn test_parse_reply() {
+    assert_eq!(parse_reply(b"stream: OK\0").unwrap(), ScanResult::Clean);
+    assert_eq!(
+        parse_reply(b"stream: Eicar-Test-Signature FOUND\0").unwrap(),
+        ScanResult::Infected {
+            signature: str!("Eicar-Test-Signature"),
+        },
+    );
+    assert!(parse_reply(b"INSTREAM size limit exceeded. ERROR\0").is_err());
+    assert!(parse_reply(b"").is_err());
+}
@@ -0,0 +1,40 @@
+/*
+ * services/scan/mod.rs
+ *
+ * DEEPWELL - Wikijump API provider and database manager
+ * Copyright (C) 2019-2025 Wikijump Team
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+//! Malware scanning for uploaded blobs.
+//!
+//! Scanners are pluggable, implementing the `Scanner` trait. Blob data is fed to
+//! the scanner as it is streamed from S3 during upload finalization, so the blob
+//! is never held in memory in full. See `BlobService::finish_upload()`.
+
+#[allow(unused_imports)]
+mod prelude {
+    pub use super::super::prelude::*;
+    pub use super::scanner::{ScanSession, Scanner};
+    pub use super::structs::*;
+    pub use async_trait::async_trait;
+}
+
+mod impls;
+mod scanner;
+mod structs;
+
+pub use self::impls::*;
+pub use self::scanner::{build_scanner, ScanSession, Scanner};
+pub use self::structs::*;
@@ -0,0 +1,69 @@
+/*
+ * services/scan/scanner.rs
+ *
+ * DEEPWELL - Wikijump API provider and database manager
+ * Copyright (C) 2019-2025 Wikijump Team
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use super::prelude::*;
+use super::ClamdScanner;
+use crate::types::ScannerType;
+use std::fmt::Debug;
+
+#[async_trait]
+pub trait Scanner: Debug + Send + Sync {
+    /// A short name for this scanner, which is recorded with each scan.
+    fn name(&self) -> &'static str;
+
+    /// Begins scanning a new blob.
+    ///
+    /// The data is then passed in chunks to the returned session as it is read.
+    async fn begin(&self) -> Result<Box<dyn ScanSession>>;
+}
+
+#[async_trait]
+pub trait ScanSession: Send {
+    /// Passes the next chunk of the blob to the scanner.
+    async fn update(&mut self, data: &[u8]) -> Result<()>;
+
+    /// Finishes scanning the blob, returning the result.
+    ///
+    /// If the scanner cannot determine whether the blob is clean,
+    /// this should yield an error rather than `ScanResult::Clean`.
+    async fn finish(self: Box<Self>) -> Result<ScanResult>;
+}
+
+/// Creates the scanner to use, based on the configuration.
+///
+/// Returns `None` if scanning is disabled.
+pub fn build_scanner(config: &Config) -> Option<Box<dyn Scanner>> {
+    match config.scanner_type {
+        ScannerType::None => {
+            warn!("No malware scanner is configured, uploaded blobs will not be scanned");
+            None
+        }
+        ScannerType::Clamd => {
+            info!(
+                "Using clamd at '{}' to scan uploaded blobs",
+                config.clamd_address
+            );
+            Some(Box::new(ClamdScanner::new(
+                &config.clamd_address,
+                config.scanner_timeout,
+            )))
+        }
+    }
+}
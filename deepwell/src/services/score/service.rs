@@ -20,6 +20,7 @@
 
 use super::impls::*;
 use super::prelude::*;
+use crate::services::SettingsService;
 
 #[derive(Debug)]
 pub struct ScoreService;
@@ -35,13 +36,34 @@ impl ScoreService {
 
     /// Gets the correct `Scorer` implementation for this page.
     ///
-    /// Currently stubbed, will be implemented when relevant settings are added.
+    /// This is based on the score type configured for the page,
+    /// see `SettingsService::get_score_type()`.
     pub async fn get_scorer(
-        _ctx: &ServiceContext<'_>,
-        _page_id: i64,
-    ) -> Result<&'static impl Scorer> {
-        // TODO
-        Ok(&TestScorer)
+        ctx: &ServiceContext<'_>,
+        page_id: i64,
+    ) -> Result<&'static (dyn Scorer + Send + Sync)> {
+        let score_type = SettingsService::get_score_type(ctx, page_id).await?;
+        match Self::scorer_for(score_type) {
+            Some(scorer) => Ok(scorer),
+            None => {
+                error!("No scorer implementation for score type {score_type:?}");
+                Err(Error::BadRequest)
+            }
+        }
+    }
+
+    /// Gets the `Scorer` implementation for the given score type, if there is one.
+    pub fn scorer_for(
+        score_type: ScoreType,
+    ) -> Option<&'static (dyn Scorer + Send + Sync)> {
+        match score_type {
+            ScoreType::Null => Some(&NullScorer),
+            ScoreType::Test => Some(&TestScorer),
+            ScoreType::Sum => Some(&SumScorer),
+            ScoreType::Mean => Some(&MeanScorer),
+            ScoreType::Percent => Some(&PercentScorer),
+            ScoreType::Median => None, // TODO
+        }
     }
 
     /// Helper method for retrieving a `VoteMap` for a page.
@@ -18,7 +18,9 @@
  * along with this program. If not, see <http://www.gnu.org/licenses/>.
  */
 
+use crate::services::Error as ServiceError;
 use std::collections::BTreeMap;
+use std::str::FromStr;
 
 pub use crate::services::vote::VoteValue;
 
@@ -39,6 +41,36 @@ pub enum ScoreType {
     Percent,
 }
 
+impl ScoreType {
+    /// Get the constant string value used to represent this score type in the database.
+    pub fn value(self) -> &'static str {
+        match self {
+            ScoreType::Null => "null",
+            ScoreType::Test => "test",
+            ScoreType::Sum => "sum",
+            ScoreType::Mean => "mean",
+            ScoreType::Median => "median",
+            ScoreType::Percent => "percent",
+        }
+    }
+}
+
+impl FromStr for ScoreType {
+    type Err = ServiceError;
+
+    fn from_str(value: &str) -> Result<ScoreType, ServiceError> {
+        match value {
+            "null" => Ok(ScoreType::Null),
+            "test" => Ok(ScoreType::Test),
+            "sum" => Ok(ScoreType::Sum),
+            "mean" => Ok(ScoreType::Mean),
+            "median" => Ok(ScoreType::Median),
+            "percent" => Ok(ScoreType::Percent),
+            _ => Err(ServiceError::InvalidEnumValue),
+        }
+    }
+}
+
 #[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub struct VoteMap {
     inner: BTreeMap<VoteValue, u64>,
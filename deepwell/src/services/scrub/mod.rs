@@ -0,0 +1,42 @@
+/*
+ * services/scrub/mod.rs
+ *
+ * DEEPWELL - Wikijump API provider and database manager
+ * Copyright (C) 2019-2025 Wikijump Team
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Service for verifying the integrity of stored text and blobs.
+//!
+//! Text rows and blobs are addressed by the hash of their contents, but nothing
+//! otherwise checks that the stored bytes still match. A scrub walks the text table,
+//! every blob in S3, and every blob referenced in the database, recording any which
+//! are missing, corrupted (their contents do not match their hash), or mismatched
+//! (their length differs from what file revisions recorded).
+//!
+//! Like mass rerenders, scrubs are run in throttled batches through the job queue,
+//! with progress kept in Redis. Only the issues from the latest scrub are retained.
+
+#[allow(unused_imports)]
+mod prelude {
+    pub use super::super::prelude::*;
+    pub use super::structs::*;
+}
+
+mod service;
+mod structs;
+
+pub use self::service::ScrubService;
+pub use self::structs::*;
@@ -0,0 +1,371 @@
+/*
+ * services/scrub/service.rs
+ *
+ * DEEPWELL - Wikijump API provider and database manager
+ * Copyright (C) 2019-2025 Wikijump Team
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use super::prelude::*;
+use crate::hash::{blob_hash_to_hex, k12_hash};
+use crate::models::scrub_issue::{self, Entity as ScrubIssue};
+use crate::models::text::{self, Entity as Text};
+use crate::services::blob::BlobService;
+use crate::services::job::{Job, JobService};
+use crate::services::TextService;
+use cuid2::cuid;
+use redis::AsyncCommands;
+
+/// How long, in seconds, progress for a storage scrub is retained in Redis.
+const SCRUB_PROGRESS_EXPIRY_SECS: u64 = 30 * 24 * 60 * 60; // 30 days
+
+/// The maximum number of scrub issues which can be fetched at once.
+const MAX_ISSUE_LIMIT: u64 = 100;
+
+#[derive(Debug)]
+pub struct ScrubService;
+
+impl ScrubService {
+    /// Begins a new storage scrub.
+    ///
+    /// This clears the issues found by the previous scrub, and supersedes
+    /// it if it is still running.
+    pub async fn start(ctx: &ServiceContext<'_>) -> Result<ScrubProgress> {
+        let txn = ctx.transaction();
+        let scrub_id = cuid();
+        info!("Starting storage scrub {scrub_id}");
+
+        ScrubIssue::delete_many().exec(txn).await?;
+
+        let now = now();
+        let phase = ScrubPhase::Text { after: str!() };
+        let progress = ScrubProgress {
+            scrub_id: scrub_id.clone(),
+            phase: phase.clone(),
+            finished: false,
+            texts_checked: 0,
+            blobs_checked: 0,
+            references_checked: 0,
+            issues_found: 0,
+            started_at: now,
+            updated_at: now,
+        };
+
+        Self::set_progress(ctx, &progress).await?;
+        JobService::queue_job(ctx, &Job::StorageScrubBatch { scrub_id, phase }, None)
+            .await?;
+
+        Ok(progress)
+    }
+
+    /// Checks the next batch of items for a storage scrub.
+    ///
+    /// Returns the phase to continue from if there are more items remaining,
+    /// or `None` if the scrub has finished or was superseded by a newer one.
+    pub async fn run_batch(
+        ctx: &ServiceContext<'_>,
+        scrub_id: &str,
+        phase: ScrubPhase,
+    ) -> Result<Option<ScrubPhase>> {
+        let mut progress = match Self::get_progress(ctx).await? {
+            Some(progress) if progress.scrub_id == scrub_id => progress,
+            _ => {
+                warn!("Storage scrub {scrub_id} is no longer current, stopping");
+                return Ok(None);
+            }
+        };
+
+        let batch_size = u64::from(ctx.config().job_scrub_batch_size.get());
+        let next = match phase {
+            ScrubPhase::Text { after } => {
+                Self::check_texts(ctx, &mut progress, &after, batch_size).await?
+            }
+            ScrubPhase::Blobs { after } => {
+                Self::check_blobs(ctx, &mut progress, after, batch_size).await?
+            }
+            ScrubPhase::References { after } => {
+                Self::check_references(ctx, &mut progress, &after, batch_size).await?
+            }
+        };
+
+        if let Some(ref phase) = next {
+            progress.phase = phase.clone();
+        }
+
+        progress.finished = next.is_none();
+        progress.updated_at = now();
+
+        info!(
+            "Storage scrub {scrub_id}: checked {} texts, {} blobs, {} references, found {} issues",
+            progress.texts_checked,
+            progress.blobs_checked,
+            progress.references_checked,
+            progress.issues_found,
+        );
+
+        Self::set_progress(ctx, &progress).await?;
+        Ok(next)
+    }
+
+    /// Rehashes a batch of text rows, flagging any whose contents have changed
+    /// or can no longer be decoded.
+    async fn check_texts(
+        ctx: &ServiceContext<'_>,
+        progress: &mut ScrubProgress,
+        after: &str,
+        batch_size: u64,
+    ) -> Result<Option<ScrubPhase>> {
+        let txn = ctx.transaction();
+        let texts = Text::find()
+            .filter(text::Column::Hash.gt(decode_cursor(after)?))
+            .order_by_asc(text::Column::Hash)
+            .limit(batch_size)
+            .all(txn)
+            .await?;
+
+        let count = texts.len() as u64;
+        let last_hash = texts.last().map(|text| hex::encode(&text.hash));
+
+        for text in texts {
+            let hash = text.hash.clone();
+            let length = text.length;
+            let (issue, details) = match TextService::decode(ctx, text).await {
+                Ok(contents) => {
+                    let actual = k12_hash(contents.as_bytes());
+                    let actual_length = contents.chars().count();
+                    if actual != hash.as_slice() {
+                        let details = format!("Contents hash to {}", hex::encode(actual));
+                        (ScrubIssueType::Corrupted, details)
+                    } else if actual_length != length as usize {
+                        let details = format!(
+                            "Contents are {actual_length} characters, recorded as {length}",
+                        );
+                        (ScrubIssueType::Mismatched, details)
+                    } else {
+                        continue;
+                    }
+                }
+                Err(Error::TextCodec(message)) => (ScrubIssueType::Corrupted, message),
+                Err(error) => return Err(error),
+            };
+
+            Self::add_issue(
+                ctx,
+                progress,
+                ScrubTarget::Text,
+                issue,
+                &hash,
+                Some(details),
+            )
+            .await?;
+        }
+
+        progress.texts_checked += count;
+
+        let next = match last_hash {
+            Some(after) if count == batch_size => ScrubPhase::Text { after },
+            _ => ScrubPhase::Blobs { after: str!() },
+        };
+
+        Ok(Some(next))
+    }
+
+    /// Rehashes a batch of blobs stored in S3, flagging any whose contents
+    /// have changed or whose length differs from what was recorded.
+    async fn check_blobs(
+        ctx: &ServiceContext<'_>,
+        progress: &mut ScrubProgress,
+        after: String,
+        batch_size: u64,
+    ) -> Result<Option<ScrubPhase>> {
+        let start_after = if after.is_empty() { None } else { Some(after) };
+        let (hashes, next_key) =
+            BlobService::list_stored_page(ctx, start_after, batch_size as usize).await?;
+
+        for hash in &hashes {
+            // The blob may have been deleted since it was listed
+            let (actual, length) = match BlobService::hash_stored(ctx, hash).await? {
+                Some(result) => result,
+                None => continue,
+            };
+
+            if &actual != hash {
+                let details = format!("Contents hash to {}", blob_hash_to_hex(&actual));
+                Self::add_issue(
+                    ctx,
+                    progress,
+                    ScrubTarget::Blob,
+                    ScrubIssueType::Corrupted,
+                    hash,
+                    Some(details),
+                )
+                .await?;
+                continue;
+            }
+
+            let sizes = BlobService::get_recorded_sizes(ctx, hash).await?;
+            if sizes.iter().any(|&size| size as u64 != length) {
+                let details =
+                    format!("Stored length is {length} bytes, recorded as {sizes:?}");
+                Self::add_issue(
+                    ctx,
+                    progress,
+                    ScrubTarget::Blob,
+                    ScrubIssueType::Mismatched,
+                    hash,
+                    Some(details),
+                )
+                .await?;
+            }
+        }
+
+        progress.blobs_checked += hashes.len() as u64;
+
+        let next = match next_key {
+            Some(after) => ScrubPhase::Blobs { after },
+            None => ScrubPhase::References { after: str!() },
+        };
+
+        Ok(Some(next))
+    }
+
+    /// Checks that a batch of blobs referenced in the database exist in S3.
+    async fn check_references(
+        ctx: &ServiceContext<'_>,
+        progress: &mut ScrubProgress,
+        after: &str,
+        batch_size: u64,
+    ) -> Result<Option<ScrubPhase>> {
+        let hashes =
+            BlobService::list_referenced_page(ctx, &decode_cursor(after)?, batch_size)
+                .await?;
+
+        for hash in &hashes {
+            if !BlobService::exists(ctx, hash).await? {
+                Self::add_issue(
+                    ctx,
+                    progress,
+                    ScrubTarget::Blob,
+                    ScrubIssueType::Missing,
+                    hash,
+                    None,
+                )
+                .await?;
+            }
+        }
+
+        progress.references_checked += hashes.len() as u64;
+
+        let next = match hashes.last() {
+            Some(hash) if hashes.len() as u64 == batch_size => {
+                Some(ScrubPhase::References {
+                    after: hex::encode(hash),
+                })
+            }
+            _ => None,
+        };
+
+        Ok(next)
+    }
+
+    async fn add_issue(
+        ctx: &ServiceContext<'_>,
+        progress: &mut ScrubProgress,
+        target: ScrubTarget,
+        issue: ScrubIssueType,
+        hash: &[u8],
+        details: Option<String>,
+    ) -> Result<()> {
+        warn!(
+            "Storage scrub found {} {} with hash {}",
+            issue.value(),
+            target.value(),
+            hex::encode(hash),
+        );
+
+        let txn = ctx.transaction();
+        let model = scrub_issue::ActiveModel {
+            target: Set(str!(target.value())),
+            issue: Set(str!(issue.value())),
+            hash: Set(hash.to_vec()),
+            details: Set(details),
+            ..Default::default()
+        };
+        model.insert(txn).await?;
+
+        progress.issues_found += 1;
+        Ok(())
+    }
+
+    pub async fn get_progress(ctx: &ServiceContext<'_>) -> Result<Option<ScrubProgress>> {
+        let mut redis = ctx.redis_connect().await?;
+        let data: Option<String> = redis.get(SCRUB_PROGRESS_KEY).await?;
+        match data {
+            Some(data) => Ok(Some(serde_json::from_str(&data)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn set_progress(
+        ctx: &ServiceContext<'_>,
+        progress: &ScrubProgress,
+    ) -> Result<()> {
+        let mut redis = ctx.redis_connect().await?;
+        let data = serde_json::to_string(progress)?;
+        redis
+            .set_ex::<_, _, ()>(SCRUB_PROGRESS_KEY, data, SCRUB_PROGRESS_EXPIRY_SECS)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Gets the issues found by the latest storage scrub, in the order they were found.
+    pub async fn get_issues(
+        ctx: &ServiceContext<'_>,
+        GetScrubIssues {
+            after_issue_id,
+            limit,
+        }: GetScrubIssues,
+    ) -> Result<Vec<ScrubIssueOutput>> {
+        let txn = ctx.transaction();
+        let limit = limit.unwrap_or(MAX_ISSUE_LIMIT).min(MAX_ISSUE_LIMIT);
+        let issues = ScrubIssue::find()
+            .filter(scrub_issue::Column::ScrubIssueId.gt(after_issue_id))
+            .order_by_asc(scrub_issue::Column::ScrubIssueId)
+            .limit(limit)
+            .all(txn)
+            .await?
+            .into_iter()
+            .map(|model| ScrubIssueOutput {
+                scrub_issue_id: model.scrub_issue_id,
+                found_at: model.found_at,
+                target: model.target,
+                issue: model.issue,
+                hash: hex::encode(model.hash),
+                details: model.details,
+            })
+            .collect();
+
+        Ok(issues)
+    }
+}
+
+/// Decodes a hex hash cursor from a scrub phase.
+fn decode_cursor(after: &str) -> Result<Vec<u8>> {
+    hex::decode(after).map_err(|error| {
+        error!("Invalid storage scrub cursor '{after}': {error}");
+        Error::BadRequest
+    })
+}
@@ -0,0 +1,118 @@
+/*
+ * services/scrub/structs.rs
+ *
+ * DEEPWELL - Wikijump API provider and database manager
+ * Copyright (C) 2019-2025 Wikijump Team
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use super::prelude::*;
+use time::OffsetDateTime;
+
+/// The Redis key storing progress for the current or last storage scrub.
+pub const SCRUB_PROGRESS_KEY: &str = "scrub-progress";
+
+/// What kind of stored item a scrub issue refers to.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, Hash, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ScrubTarget {
+    Text,
+    Blob,
+}
+
+impl ScrubTarget {
+    pub fn value(self) -> &'static str {
+        match self {
+            ScrubTarget::Text => "text",
+            ScrubTarget::Blob => "blob",
+        }
+    }
+}
+
+/// What is wrong with a stored item.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, Hash, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ScrubIssueType {
+    /// The item is referenced but is not stored.
+    Missing,
+
+    /// The item's contents do not match its hash.
+    Corrupted,
+
+    /// The item's length does not match what was recorded for it.
+    Mismatched,
+}
+
+impl ScrubIssueType {
+    pub fn value(self) -> &'static str {
+        match self {
+            ScrubIssueType::Missing => "missing",
+            ScrubIssueType::Corrupted => "corrupted",
+            ScrubIssueType::Mismatched => "mismatched",
+        }
+    }
+}
+
+/// Which part of storage a scrub is checking, and where it is up to.
+///
+/// Cursors are hex-encoded hashes, with an empty string meaning the start.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case", tag = "phase")]
+pub enum ScrubPhase {
+    /// Rehashing the contents of each text row.
+    Text { after: String },
+
+    /// Rehashing each blob stored in S3.
+    Blobs { after: String },
+
+    /// Checking each blob referenced in the database exists.
+    References { after: String },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ScrubProgress {
+    pub scrub_id: String,
+    pub phase: ScrubPhase,
+    pub finished: bool,
+    pub texts_checked: u64,
+    pub blobs_checked: u64,
+    pub references_checked: u64,
+    pub issues_found: u64,
+
+    #[serde(with = "time::serde::rfc3339")]
+    pub started_at: OffsetDateTime,
+
+    #[serde(with = "time::serde::rfc3339")]
+    pub updated_at: OffsetDateTime,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct GetScrubIssues {
+    #[serde(default)]
+    pub after_issue_id: i64,
+    pub limit: Option<u64>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ScrubIssueOutput {
+    pub scrub_issue_id: i64,
+
+    #[serde(with = "time::serde::rfc3339")]
+    pub found_at: OffsetDateTime,
+    pub target: String,
+    pub issue: String,
+    pub hash: String,
+    pub details: Option<String>,
+}
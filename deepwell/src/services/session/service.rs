@@ -29,6 +29,11 @@
 //! which represents the current session. It has a somewhat short
 //! expiry (30 minutes) which needs to be renewed by the client
 //! periodically.
+//!
+//! Separately, a session which has not been used recently is considered
+//! idle, and is no longer valid even if it has not yet expired. Sessions
+//! may also be bound to the client which created them, in which case
+//! use by a different user agent or from a different subnet ends the session.
 
 use super::prelude::*;
 use crate::models::session::{self, Entity as Session, Model as SessionModel};
@@ -36,6 +41,19 @@ use crate::models::user::{self, Entity as User, Model as UserModel};
 use crate::utils::assert_is_csprng;
 use rand::distributions::{Alphanumeric, DistString};
 use rand::thread_rng;
+use sea_orm::sea_query::Expr;
+use sea_orm::{TransactionTrait, UpdateResult};
+use std::net::IpAddr;
+use std::sync::Arc;
+use time::Duration as TimeDuration;
+use woothee::parser::Parser as UserAgentParser;
+use woothee::woothee::VALUE_UNKNOWN;
+
+/// How often a session's last use time is updated.
+///
+/// This avoids writing to the session on every request,
+/// at the cost of a slight imprecision in the idle timeout.
+const SESSION_TOUCH_INTERVAL: TimeDuration = TimeDuration::minutes(1);
 
 #[derive(Debug)]
 pub struct SessionService;
@@ -71,9 +89,11 @@ impl SessionService {
             user_id: Set(user_id),
             created_at: Set(now),
             expires_at: Set(expiry),
-            ip_address: Set(str!(ip_address)), // TODO inet type?
+            last_used_at: Set(now),
+            ip_address: Set(ip_address.to_string()),
             user_agent: Set(user_agent),
             restricted: Set(restricted),
+            ..Default::default()
         };
 
         let SessionModel { session_token, .. } = model.insert(txn).await?;
@@ -95,45 +115,158 @@ impl SessionService {
         token
     }
 
-    /// Gets a session model from its token.
-    /// Yields an error if the given session token does not exist or is expired.
-    pub async fn get(
+    pub async fn get_optional(
+        ctx: &ServiceContext<'_>,
+        session_token: &str,
+    ) -> Result<Option<SessionModel>> {
+        let txn = ctx.transaction();
+        let session = Session::find()
+            .filter(
+                Condition::all()
+                    .add(session::Column::SessionToken.eq(session_token))
+                    .add(Self::active_condition(ctx.config())),
+            )
+            .one(txn)
+            .await?;
+
+        Ok(session)
+    }
+
+    /// Gets a session model from its token, on behalf of the given client.
+    ///
+    /// The session binding is checked, see `check_binding()`.
+    /// The session is also marked as having been used.
+    ///
+    /// All lookups of a session on behalf of a client should go through
+    /// here, so that a stolen session token cannot be used elsewhere.
+    pub async fn get_for_client(
         ctx: &ServiceContext<'_>,
         session_token: &str,
+        client: SessionClient<'_>,
     ) -> Result<SessionModel> {
-        info!("Looking up session with token {session_token}");
-        Self::get_optional(ctx, session_token)
+        Self::get_optional_for_client(ctx, session_token, client)
             .await?
             .ok_or(Error::InvalidSessionToken)
     }
 
-    pub async fn get_optional(
+    /// Like `get_for_client()`, but yields `None` if the session does not exist or is expired.
+    pub async fn get_optional_for_client(
         ctx: &ServiceContext<'_>,
         session_token: &str,
+        client: SessionClient<'_>,
     ) -> Result<Option<SessionModel>> {
+        let session = match Self::get_optional(ctx, session_token).await? {
+            Some(session) => session,
+            None => return Ok(None),
+        };
+
+        Self::check_binding(ctx, &session, client).await?;
+        Self::touch(ctx, session_token).await?;
+        Ok(Some(session))
+    }
+
+    /// Ensures that a session is being used by the same client which created it.
+    ///
+    /// Which properties are checked depend on the configuration.
+    /// If there is a mismatch, the session is invalidated and the user
+    /// will need to authenticate again.
+    ///
+    /// NOTE: Because a mismatch returns an error, which rolls back the
+    ///       request's transaction, the session is invalidated in a
+    ///       separate transaction so that the revocation persists.
+    pub async fn check_binding(
+        ctx: &ServiceContext<'_>,
+        session: &SessionModel,
+        client: SessionClient<'_>,
+    ) -> Result<()> {
+        let config = ctx.config();
+
+        if config.session_bind_user_agent && session.user_agent != client.user_agent {
+            warn!(
+                "User agent for session ID {} has changed, invalidating",
+                session.session_id,
+            );
+
+            Self::invalidate_separately(ctx, &session.session_token).await?;
+            return Err(Error::SessionBindingMismatch);
+        }
+
+        if config.session_bind_ip_subnet {
+            let same_subnet = match parse_ip_address(&session.ip_address) {
+                Some(session_ip) => ip_subnet_matches(
+                    session_ip,
+                    client.ip_address,
+                    config.session_ipv4_subnet_prefix,
+                    config.session_ipv6_subnet_prefix,
+                ),
+                None => false,
+            };
+
+            if !same_subnet {
+                warn!(
+                    "IP address for session ID {} has changed subnets, invalidating",
+                    session.session_id,
+                );
+
+                Self::invalidate_separately(ctx, &session.session_token).await?;
+                return Err(Error::SessionBindingMismatch);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Invalidates a session in its own transaction, see `check_binding()`.
+    async fn invalidate_separately(
+        ctx: &ServiceContext<'_>,
+        session_token: &str,
+    ) -> Result<()> {
+        let state = ctx.state();
+        let db_state = Arc::clone(&state);
+
+        // Produce temporary context in a new transaction
+        let txn = db_state.database.begin().await?;
+        let inner_ctx = ServiceContext::new(&state, &txn);
+        Self::invalidate(&inner_ctx, str!(session_token)).await?;
+        txn.commit().await?;
+        Ok(())
+    }
+
+    /// Marks a session as having just been used, resetting its idle timeout.
+    ///
+    /// To avoid a write on every request, the session is only updated if
+    /// it was last marked as used more than `SESSION_TOUCH_INTERVAL` ago.
+    pub async fn touch(ctx: &ServiceContext<'_>, session_token: &str) -> Result<()> {
         let txn = ctx.transaction();
-        let session = Session::find()
+        let now = now();
+        let UpdateResult { rows_affected } = Session::update_many()
+            .col_expr(session::Column::LastUsedAt, Expr::value(now))
             .filter(
                 Condition::all()
                     .add(session::Column::SessionToken.eq(session_token))
-                    .add(session::Column::ExpiresAt.gt(now())),
+                    .add(session::Column::LastUsedAt.lt(now - SESSION_TOUCH_INTERVAL)),
             )
-            .one(txn)
+            .exec(txn)
             .await?;
 
-        Ok(session)
+        if rows_affected > 0 {
+            debug!("Updated last use time for session");
+        }
+
+        Ok(())
     }
 
-    /// Gets the associated `UserModel` from an active session.
+    /// Gets the associated `UserModel` from an active session, on behalf of the given client.
     ///
-    /// Performs a join rather than two separate fetches.
-    /// Yields an error if the given session token does not exist or is expired.
+    /// Yields an error if the given session token does not exist or is expired,
+    /// or if the client does not match the session binding.
     ///
     /// The `restricted` status must match the argument passed.
     pub async fn get_user(
         ctx: &ServiceContext<'_>,
         session_token: &str,
         restricted: bool,
+        client: SessionClient<'_>,
     ) -> Result<UserModel> {
         info!("Looking up user for session token");
 
@@ -143,13 +276,14 @@ impl SessionService {
             .filter(
                 Condition::all()
                     .add(session::Column::SessionToken.eq(session_token))
-                    .add(session::Column::ExpiresAt.gt(now()))
+                    .add(Self::active_condition(ctx.config()))
                     .add(session::Column::Restricted.eq(restricted)),
             )
             .one(txn)
             .await?
             .ok_or(Error::UserNotFound)?;
 
+        Self::get_for_client(ctx, session_token, client).await?;
         Ok(user)
     }
 
@@ -166,14 +300,61 @@ impl SessionService {
             .filter(
                 Condition::all()
                     .add(session::Column::UserId.eq(user_id))
-                    .add(session::Column::ExpiresAt.gt(now())),
+                    .add(Self::active_condition(ctx.config())),
             )
+            .order_by_desc(session::Column::LastUsedAt)
             .all(txn)
             .await?;
 
         Ok(sessions)
     }
 
+    /// Lists all active sessions for a user, with parsed device information.
+    ///
+    /// The session token must belong to the given user, and is marked as the current session.
+    pub async fn list(
+        ctx: &ServiceContext<'_>,
+        session_token: &str,
+        user_id: i64,
+        client: SessionClient<'_>,
+    ) -> Result<Vec<SessionInfo>> {
+        info!("Listing all sessions for user ID {user_id}");
+
+        let session = Self::get_for_client(ctx, session_token, client).await?;
+        Self::check_user_id(&session, user_id)?;
+
+        let parser = UserAgentParser::new();
+        let sessions = Self::get_all(ctx, user_id)
+            .await?
+            .into_iter()
+            .map(|session| {
+                let current = session.session_token == session_token;
+                Self::describe(&parser, session, current)
+            })
+            .collect();
+
+        Ok(sessions)
+    }
+
+    /// Converts a session into its user-facing description, without the session token.
+    pub(crate) fn describe(
+        parser: &UserAgentParser,
+        session: SessionModel,
+        current: bool,
+    ) -> SessionInfo {
+        SessionInfo {
+            session_id: session.session_id,
+            current,
+            created_at: session.created_at,
+            expires_at: session.expires_at,
+            last_used_at: session.last_used_at,
+            ip_address: parse_ip_address(&session.ip_address),
+            device: parse_device(parser, &session.user_agent),
+            user_agent: session.user_agent,
+            restricted: session.restricted,
+        }
+    }
+
     /// Renews a session, invalidating the old one and creating a new one.
     ///
     /// # Returns
@@ -191,7 +372,11 @@ impl SessionService {
         info!("Renewing session ID {old_session_token}");
 
         // Get existing session to ensure the token matches the passed user ID.
-        let old_session = Self::get(ctx, &old_session_token).await?;
+        let client = SessionClient {
+            ip_address,
+            user_agent: &user_agent,
+        };
+        let old_session = Self::get_for_client(ctx, &old_session_token, client).await?;
         if old_session.user_id != user_id {
             error!(
                 "Requested session renewal, user IDs do not match! (current: {}, request: {})",
@@ -241,6 +426,48 @@ impl SessionService {
         Ok(())
     }
 
+    /// Invalidates a single session belonging to the user, by its ID.
+    ///
+    /// The session token must belong to the same user, and is used to
+    /// authorize the request. This permits revoking a particular session,
+    /// such as for a lost device, without logging out all other sessions.
+    pub async fn invalidate_by_id(
+        ctx: &ServiceContext<'_>,
+        InvalidateSession {
+            session_token,
+            user_id,
+            session_id,
+            ip_address,
+            user_agent,
+        }: InvalidateSession,
+    ) -> Result<()> {
+        info!("Invalidating session ID {session_id} for user ID {user_id}");
+
+        let txn = ctx.transaction();
+        let client = SessionClient {
+            ip_address,
+            user_agent: &user_agent,
+        };
+        let session = Self::get_for_client(ctx, &session_token, client).await?;
+        Self::check_user_id(&session, user_id)?;
+
+        let DeleteResult { rows_affected } = Session::delete_many()
+            .filter(
+                Condition::all()
+                    .add(session::Column::SessionId.eq(session_id))
+                    .add(session::Column::UserId.eq(user_id)),
+            )
+            .exec(txn)
+            .await?;
+
+        if rows_affected != 1 {
+            error!("This session was already deleted or does not exist");
+            return Err(Error::SessionNotFound);
+        }
+
+        Ok(())
+    }
+
     /// Invalidates all others sessions _except_ the one listed.
     /// This enables a user to "log out all other sessions",
     /// a useful security feature. See [WJ-364].
@@ -253,23 +480,13 @@ impl SessionService {
         ctx: &ServiceContext<'_>,
         session_token: &str,
         user_id: i64,
+        client: SessionClient<'_>,
     ) -> Result<u64> {
         info!("Invalidation all other session IDs for user ID {user_id}");
 
         let txn = ctx.transaction();
-        let session = Self::get(ctx, session_token).await?;
-        if session.user_id != user_id {
-            error!(
-                "Requested invalidation of other sessions, user IDs do not match! (current: {}, request: {})",
-                session.user_id,
-                user_id,
-            );
-
-            return Err(Error::SessionUserId {
-                active_user_id: user_id,
-                session_user_id: session.user_id,
-            });
-        }
+        let session = Self::get_for_client(ctx, session_token, client).await?;
+        Self::check_user_id(&session, user_id)?;
 
         // Delete all sessions from user_id, except if it's this session_token
         let DeleteResult { rows_affected } = Session::delete_many()
@@ -285,7 +502,7 @@ impl SessionService {
         Ok(rows_affected)
     }
 
-    /// Prunes all expired sessions from the database.
+    /// Prunes all expired or idle sessions from the database.
     ///
     /// # Returns
     /// The number of pruned sessions.
@@ -294,11 +511,115 @@ impl SessionService {
 
         let txn = ctx.transaction();
         let DeleteResult { rows_affected } = Session::delete_many()
-            .filter(session::Column::ExpiresAt.lte(now()))
+            .filter(Self::active_condition(ctx.config()).not())
             .exec(txn)
             .await?;
 
         debug!("{rows_affected} expired sessions were pruned");
         Ok(rows_affected)
     }
+
+    /// Builds the condition for a session to be active.
+    ///
+    /// That is, it has not expired, and has not been idle for too long.
+    fn active_condition(config: &Config) -> Condition {
+        let now = now();
+        let mut condition = Condition::all().add(session::Column::ExpiresAt.gt(now));
+
+        if let Some(idle_timeout) = config.session_idle_timeout {
+            condition = condition.add(session::Column::LastUsedAt.gt(now - idle_timeout));
+        }
+
+        condition
+    }
+
+    fn check_user_id(session: &SessionModel, user_id: i64) -> Result<()> {
+        if session.user_id != user_id {
+            error!(
+                "Requested session operation, user IDs do not match! (current: {}, request: {})",
+                session.user_id,
+                user_id,
+            );
+
+            return Err(Error::SessionUserId {
+                active_user_id: user_id,
+                session_user_id: session.user_id,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// Parses an IP address as stored in the database.
+///
+/// Postgres' text representation of `INET` includes the prefix length,
+/// which is stripped here since sessions always store single hosts.
+fn parse_ip_address(value: &str) -> Option<IpAddr> {
+    let address = match value.split_once('/') {
+        Some((address, _)) => address,
+        None => value,
+    };
+
+    address.parse().ok()
+}
+
+/// Determines if both IP addresses are in the same subnet.
+///
+/// Addresses of different families are never in the same subnet.
+fn ip_subnet_matches(
+    first: IpAddr,
+    second: IpAddr,
+    ipv4_prefix: u8,
+    ipv6_prefix: u8,
+) -> bool {
+    match (first, second) {
+        (IpAddr::V4(first), IpAddr::V4(second)) => {
+            let mask = u32::MAX
+                .checked_shl(32 - u32::from(ipv4_prefix))
+                .unwrap_or(0);
+
+            u32::from(first) & mask == u32::from(second) & mask
+        }
+        (IpAddr::V6(first), IpAddr::V6(second)) => {
+            let mask = u128::MAX
+                .checked_shl(128 - u32::from(ipv6_prefix))
+                .unwrap_or(0);
+
+            u128::from(first) & mask == u128::from(second) & mask
+        }
+        _ => false,
+    }
+}
+
+/// Extracts browser and operating system information from a user agent.
+fn parse_device(parser: &UserAgentParser, user_agent: &str) -> DeviceInfo {
+    fn known(value: &str) -> Option<String> {
+        if value.is_empty() || value == VALUE_UNKNOWN {
+            None
+        } else {
+            Some(str!(value))
+        }
+    }
+
+    let result = match parser.parse(user_agent) {
+        Some(result) => result,
+        None => return DeviceInfo::default(),
+    };
+
+    let device_type = match result.category {
+        "pc" => DeviceType::Desktop,
+        "smartphone" | "mobilephone" => DeviceType::Mobile,
+        "appliance" => DeviceType::Appliance,
+        "crawler" => DeviceType::Bot,
+        _ => DeviceType::Unknown,
+    };
+
+    DeviceInfo {
+        device_type,
+        browser: known(result.name),
+        browser_version: known(result.version),
+        os: known(result.os),
+        os_version: known(&result.os_version),
+    }
 }
@@ -20,6 +20,7 @@
 
 use crate::models::session::Model as SessionModel;
 use std::net::IpAddr;
+use time::OffsetDateTime;
 
 #[derive(Deserialize, Debug, Clone)]
 pub struct CreateSession {
@@ -37,6 +38,13 @@ pub struct RenewSession {
     pub user_agent: String,
 }
 
+#[derive(Deserialize, Debug, Clone)]
+pub struct GetSession {
+    pub session_token: String,
+    pub ip_address: IpAddr,
+    pub user_agent: String,
+}
+
 pub type GetOtherSessions = InvalidateOtherSessions;
 
 #[derive(Serialize, Debug, Clone)]
@@ -49,4 +57,70 @@ pub struct GetOtherSessionsOutput {
 pub struct InvalidateOtherSessions {
     pub session_token: String,
     pub user_id: i64,
+    pub ip_address: IpAddr,
+    pub user_agent: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct InvalidateSession {
+    pub session_token: String,
+    pub user_id: i64,
+    pub session_id: i64,
+    pub ip_address: IpAddr,
+    pub user_agent: String,
+}
+
+/// The client which is currently making use of a session.
+///
+/// Used for session binding, see `SessionService::check_binding()`.
+#[derive(Debug, Copy, Clone)]
+pub struct SessionClient<'a> {
+    pub ip_address: IpAddr,
+    pub user_agent: &'a str,
+}
+
+/// A user-facing description of a session.
+///
+/// Unlike `SessionModel`, this does not contain the session token,
+/// so it is safe to display sessions other than the current one.
+#[derive(Serialize, Debug, Clone)]
+pub struct SessionInfo {
+    pub session_id: i64,
+    pub current: bool,
+
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+
+    #[serde(with = "time::serde::rfc3339")]
+    pub expires_at: OffsetDateTime,
+
+    #[serde(with = "time::serde::rfc3339")]
+    pub last_used_at: OffsetDateTime,
+
+    pub ip_address: Option<IpAddr>,
+    pub user_agent: String,
+    pub device: DeviceInfo,
+    pub restricted: bool,
+}
+
+/// Information about a client, as parsed from its user agent.
+#[derive(Serialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct DeviceInfo {
+    pub device_type: DeviceType,
+    pub browser: Option<String>,
+    pub browser_version: Option<String>,
+    pub os: Option<String>,
+    pub os_version: Option<String>,
+}
+
+#[derive(Serialize, Debug, Copy, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DeviceType {
+    Desktop,
+    Mobile,
+    Appliance,
+    Bot,
+
+    #[default]
+    Unknown,
 }
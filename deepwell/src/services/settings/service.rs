@@ -19,6 +19,7 @@
  */
 
 use super::prelude::*;
+use crate::services::score::ScoreType;
 use crate::services::{CategoryService, PageService, SiteService};
 use ftml::layout::Layout;
 
@@ -72,4 +73,29 @@ impl SettingsService {
         debug!("Using platform-level layout");
         Ok(ctx.config().default_page_layout)
     }
+
+    /// Get the score type associated with this page.
+    ///
+    /// If the page's category has a score type override,
+    /// then that is returned. Otherwise the platform-level
+    /// score type is used.
+    pub async fn get_score_type(
+        ctx: &ServiceContext<'_>,
+        page_id: i64,
+    ) -> Result<ScoreType> {
+        debug!("Getting score type for page ID {page_id}");
+        let page = PageService::get_direct(ctx, page_id, true).await?;
+        let category =
+            CategoryService::get(ctx, page.site_id, Reference::Id(page.page_category_id))
+                .await?;
+
+        if let Some(score_type) = category.score_type {
+            debug!("Found category-level score type override: {score_type}");
+            return score_type.parse();
+        }
+
+        // TODO: add site-level score type setting
+        debug!("Using platform-level score type");
+        Ok(ScoreType::Test)
+    }
 }
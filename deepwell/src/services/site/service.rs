@@ -22,12 +22,18 @@ use wikidot_normalize::normalize;
 
 use super::prelude::*;
 use crate::constants::SYSTEM_USER_ID;
+use crate::models::file::{self, Entity as File};
+use crate::models::page::{self, Entity as Page};
 use crate::models::sea_orm_active_enums::{AliasType, UserType};
 use crate::models::site::{self, Entity as Site, Model as SiteModel};
+use crate::models::user::{self, Entity as User};
 use crate::services::alias::CreateAlias;
+use crate::services::file::License;
 use crate::services::relation::CreateSiteUser;
 use crate::services::user::{CreateUser, UpdateUserBody};
-use crate::services::{AliasService, Error, RelationService, UserService};
+use crate::services::{
+    AliasService, Error, RelationService, TagService, UserService, ViewCacheService,
+};
 use crate::utils::validate_locale;
 use ftml::layout::Layout;
 use ref_map::*;
@@ -146,10 +152,14 @@ impl SiteService {
             model.name = Set(name);
         }
 
-        if let Maybe::Set(new_slug) = input.slug {
-            Self::update_slug(ctx, &site, &new_slug, updating_user_id).await?;
-            site_user_body.name = Maybe::Set(format!("site:{new_slug}"));
-            model.slug = Set(new_slug);
+        if let Maybe::Set(mut new_slug) = input.slug {
+            normalize(&mut new_slug);
+
+            if new_slug != site.slug {
+                Self::update_slug(ctx, &site, &new_slug, updating_user_id).await?;
+                site_user_body.name = Maybe::Set(format!("site:{new_slug}"));
+                model.slug = Set(new_slug);
+            }
         }
 
         if let Maybe::Set(tagline) = input.tagline {
@@ -169,6 +179,28 @@ impl SiteService {
 
         if let Maybe::Set(layout) = input.layout {
             model.layout = Set(layout.map(|l| str!(l.value())));
+            ViewCacheService::invalidate_site(ctx, site.site_id);
+        }
+
+        if let Maybe::Set(policy) = input.membership_policy {
+            model.membership_policy = Set(str!(policy.value()));
+        }
+
+        if let Maybe::Set(mut tag_whitelist) = input.tag_whitelist {
+            if let Some(ref mut tags) = tag_whitelist {
+                TagService::normalize(tags);
+            }
+
+            model.tag_whitelist = Set(tag_whitelist);
+        }
+
+        if let Maybe::Set(default_license) = input.default_license {
+            let default_license = match default_license {
+                Some(id) => Some(License::get_known(&id)?.id.clone()),
+                None => None,
+            };
+
+            model.default_license = Set(default_license);
         }
 
         // Update site
@@ -203,8 +235,20 @@ impl SiteService {
     ) -> Result<()> {
         info!("Updating slug for site {}, adding alias", site.site_id);
 
+        Self::check_conflicts(ctx, new_slug, "update slug").await?;
+
         let old_slug = &site.slug;
         match AliasService::get_optional(ctx, AliasType::Site, new_slug).await? {
+            // Alias belongs to another site, cannot take it
+            Some(alias) if alias.target_id != site.site_id => {
+                error!(
+                    "Site slug '{}' is an alias for another site (ID {}), cannot update slug",
+                    new_slug, alias.target_id,
+                );
+
+                return Err(Error::SiteExists);
+            }
+
             // Swap alias with site's current slug
             //
             // Don't return a future, nothing to do after
@@ -240,6 +284,200 @@ impl SiteService {
         Ok(())
     }
 
+    /// Deletes a site, hiding it and all of its contents.
+    ///
+    /// The site's pages, files, and site user are marked as deleted with the
+    /// same timestamp as the site itself, so that restoring the site brings back
+    /// exactly the items which were removed alongside it, and not any which were
+    /// deleted separately beforehand.
+    ///
+    /// Site aliases and custom domains are retained, so that they are available
+    /// again when the site is restored, but do not resolve while it is deleted.
+    pub async fn delete(
+        ctx: &ServiceContext<'_>,
+        reference: Reference<'_>,
+        user_id: i64,
+    ) -> Result<SiteModel> {
+        let txn = ctx.transaction();
+        let site = Self::get(ctx, reference).await?;
+        let site_id = site.site_id;
+        let deleted_at = now();
+        info!("Deleting site with ID {site_id} (by user ID {user_id})");
+
+        // Hide pages and files
+        let pages_deleted = Page::update_many()
+            .set(page::ActiveModel {
+                deleted_at: Set(Some(deleted_at)),
+                ..Default::default()
+            })
+            .filter(
+                Condition::all()
+                    .add(page::Column::SiteId.eq(site_id))
+                    .add(page::Column::DeletedAt.is_null()),
+            )
+            .exec(txn)
+            .await?
+            .rows_affected;
+
+        let files_deleted = File::update_many()
+            .set(file::ActiveModel {
+                deleted_at: Set(Some(deleted_at)),
+                ..Default::default()
+            })
+            .filter(
+                Condition::all()
+                    .add(file::Column::SiteId.eq(site_id))
+                    .add(file::Column::DeletedAt.is_null()),
+            )
+            .exec(txn)
+            .await?
+            .rows_affected;
+
+        debug!("Deleted {pages_deleted} pages and {files_deleted} files with site");
+
+        // Hide site user
+        let site_user_id =
+            RelationService::get_site_user_id_for_site(ctx, site_id).await?;
+        let model = user::ActiveModel {
+            user_id: Set(site_user_id),
+            deleted_at: Set(Some(deleted_at)),
+            ..Default::default()
+        };
+        model.update(txn).await?;
+
+        // Set deletion flag
+        let model = site::ActiveModel {
+            site_id: Set(site_id),
+            deleted_at: Set(Some(deleted_at)),
+            ..Default::default()
+        };
+        let site = model.update(txn).await?;
+        Ok(site)
+    }
+
+    /// Restores a deleted site, along with everything deleted with it.
+    ///
+    /// If `slug` is set, the site is restored under that slug instead, which is
+    /// needed if another site has taken its slug in the meantime.
+    pub async fn restore(
+        ctx: &ServiceContext<'_>,
+        RestoreSite {
+            site_id,
+            user_id,
+            slug,
+        }: RestoreSite,
+    ) -> Result<SiteModel> {
+        let txn = ctx.transaction();
+        let site = Self::get_direct(ctx, site_id, true).await?;
+        info!("Restoring site with ID {site_id} (by user ID {user_id})");
+
+        let deleted_at = match site.deleted_at {
+            Some(deleted_at) => deleted_at,
+            None => {
+                warn!("Site requested to be restored is not currently deleted");
+                return Err(Error::SiteNotDeleted);
+            }
+        };
+
+        let slug = match slug {
+            Some(mut slug) => {
+                normalize(&mut slug);
+                slug
+            }
+            None => site.slug.clone(),
+        };
+
+        Self::check_conflicts(ctx, &slug, "restore").await?;
+        match AliasService::get_optional(ctx, AliasType::Site, &slug).await? {
+            // Alias belongs to another site, cannot take it
+            Some(alias) if alias.target_id != site_id => {
+                error!("Site slug '{slug}' is an alias for another site, cannot restore");
+                return Err(Error::SiteExists);
+            }
+
+            // Restoring under one of the site's own aliases,
+            // swap it with the site's current slug
+            Some(alias) => {
+                debug!("Swapping slug between restored site and alias");
+                AliasService::swap(ctx, alias.alias_id, &site.slug).await?;
+            }
+
+            None => (),
+        }
+
+        // Restore pages and files deleted alongside the site
+        let pages_restored = Page::update_many()
+            .set(page::ActiveModel {
+                deleted_at: Set(None),
+                ..Default::default()
+            })
+            .filter(
+                Condition::all()
+                    .add(page::Column::SiteId.eq(site_id))
+                    .add(page::Column::DeletedAt.eq(deleted_at)),
+            )
+            .exec(txn)
+            .await?
+            .rows_affected;
+
+        let files_restored = File::update_many()
+            .set(file::ActiveModel {
+                deleted_at: Set(None),
+                ..Default::default()
+            })
+            .filter(
+                Condition::all()
+                    .add(file::Column::SiteId.eq(site_id))
+                    .add(file::Column::DeletedAt.eq(deleted_at)),
+            )
+            .exec(txn)
+            .await?
+            .rows_affected;
+
+        debug!("Restored {pages_restored} pages and {files_restored} files with site");
+
+        // Restore site user
+        let site_user_id =
+            RelationService::get_site_user_id_for_site(ctx, site_id).await?;
+        User::update_many()
+            .set(user::ActiveModel {
+                deleted_at: Set(None),
+                ..Default::default()
+            })
+            .filter(
+                Condition::all()
+                    .add(user::Column::UserId.eq(site_user_id))
+                    .add(user::Column::DeletedAt.eq(deleted_at)),
+            )
+            .exec(txn)
+            .await?;
+
+        // Unset deletion flag
+        let model = site::ActiveModel {
+            site_id: Set(site_id),
+            slug: Set(slug.clone()),
+            deleted_at: Set(None),
+            updated_at: Set(Some(now())),
+            ..Default::default()
+        };
+        let new_site = model.update(txn).await?;
+
+        // Keep the site user's name in sync, if the slug changed
+        if site.slug != new_site.slug {
+            UserService::update(
+                ctx,
+                Reference::Id(site_user_id),
+                UpdateUserBody {
+                    name: Maybe::Set(format!("site:{slug}")),
+                    ..Default::default()
+                },
+            )
+            .await?;
+        }
+
+        Ok(new_site)
+    }
+
     #[inline]
     pub async fn exists(
         ctx: &ServiceContext<'_>,
@@ -250,9 +488,22 @@ impl SiteService {
             .map(|site| site.is_some())
     }
 
+    #[inline]
     pub async fn get_optional(
+        ctx: &ServiceContext<'_>,
+        reference: Reference<'_>,
+    ) -> Result<Option<SiteModel>> {
+        Self::get_optional_allow_deleted(ctx, reference, false).await
+    }
+
+    /// Gets a site, optionally including deleted sites.
+    ///
+    /// If deleted sites are allowed and several have the same slug,
+    /// then an active site is preferred, otherwise the most recently deleted.
+    pub async fn get_optional_allow_deleted(
         ctx: &ServiceContext<'_>,
         mut reference: Reference<'_>,
+        allow_deleted: bool,
     ) -> Result<Option<SiteModel>> {
         let txn = ctx.transaction();
 
@@ -267,23 +518,28 @@ impl SiteService {
                 // If present, this is the actual site. Proceed with SELECT by id.
                 // Rewrite reference so in the "real" site search
                 // we locate directly via site ID.
+                //
+                // Aliases of deleted sites are kept, so the site is
+                // still checked for deletion below.
                 reference = Reference::Id(alias.target_id);
             }
         }
 
-        let site = match reference {
-            Reference::Id(id) => Site::find_by_id(id).one(txn).await?,
-            Reference::Slug(slug) => {
-                Site::find()
-                    .filter(
-                        Condition::all()
-                            .add(site::Column::Slug.eq(slug))
-                            .add(site::Column::DeletedAt.is_null()),
-                    )
-                    .one(txn)
-                    .await?
-            }
-        };
+        let mut condition = Condition::all().add(match reference {
+            Reference::Id(id) => site::Column::SiteId.eq(id),
+            Reference::Slug(slug) => site::Column::Slug.eq(slug),
+        });
+
+        if !allow_deleted {
+            condition = condition.add(site::Column::DeletedAt.is_null());
+        }
+
+        // In descending order, Postgres sorts nulls (active sites) first
+        let site = Site::find()
+            .filter(condition)
+            .order_by_desc(site::Column::DeletedAt)
+            .one(txn)
+            .await?;
 
         Ok(site)
     }
@@ -296,6 +552,43 @@ impl SiteService {
         find_or_error!(Self::get_optional(ctx, reference), Site)
     }
 
+    /// Gets the membership policy for the given site.
+    pub async fn get_membership_policy(
+        ctx: &ServiceContext<'_>,
+        site_id: i64,
+    ) -> Result<MembershipPolicy> {
+        let site = Self::get(ctx, Reference::Id(site_id)).await?;
+        site.membership_policy.parse()
+    }
+
+    /// Gets a site by ID, optionally including deleted sites.
+    pub async fn get_direct_optional(
+        ctx: &ServiceContext<'_>,
+        site_id: i64,
+        allow_deleted: bool,
+    ) -> Result<Option<SiteModel>> {
+        let txn = ctx.transaction();
+        let site = Site::find_by_id(site_id).one(txn).await?;
+        if let Some(ref site) = site {
+            if !allow_deleted && site.deleted_at.is_some() {
+                // If we're not looking for deleted sites, then
+                // return nothing if the site whose ID match is.
+                return Ok(None);
+            }
+        }
+
+        Ok(site)
+    }
+
+    #[inline]
+    pub async fn get_direct(
+        ctx: &ServiceContext<'_>,
+        site_id: i64,
+        allow_deleted: bool,
+    ) -> Result<SiteModel> {
+        find_or_error!(Self::get_direct_optional(ctx, site_id, allow_deleted), Site)
+    }
+
     /// Gets the site ID from a reference, looking up if necessary.
     ///
     /// Convenience method since this is much more common than the optional
@@ -21,8 +21,10 @@
 use crate::models::alias::Model as AliasModel;
 use crate::models::site::Model as SiteModel;
 use crate::models::site_domain::Model as SiteDomainModel;
+use crate::services::Error;
 use crate::types::{Maybe, Reference};
 use ftml::layout::Layout;
+use std::str::FromStr;
 
 #[derive(Deserialize, Debug, Clone)]
 pub struct CreateSite {
@@ -47,6 +49,18 @@ pub struct GetSite<'a> {
     pub site: Reference<'a>,
 }
 
+/// Input for fetching a site along with its aliases and domains.
+///
+/// Deleted sites are only returned if `allow_deleted` is set,
+/// for instance so that they can be found to be restored.
+#[derive(Deserialize, Debug, Clone)]
+pub struct GetSiteDetails<'a> {
+    pub site: Reference<'a>,
+
+    #[serde(default)]
+    pub allow_deleted: bool,
+}
+
 #[derive(Serialize, Debug, Clone)]
 pub struct GetSiteOutput {
     #[serde(flatten)]
@@ -64,6 +78,19 @@ pub struct UpdateSite<'a> {
     pub body: UpdateSiteBody,
 }
 
+#[derive(Deserialize, Debug, Clone)]
+pub struct DeleteSite<'a> {
+    pub site: Reference<'a>,
+    pub user_id: i64,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct RestoreSite {
+    pub site_id: i64,
+    pub user_id: i64,
+    pub slug: Option<String>,
+}
+
 #[derive(Deserialize, Debug, Clone, Default)]
 #[serde(default)]
 pub struct UpdateSiteBody {
@@ -73,4 +100,55 @@ pub struct UpdateSiteBody {
     pub description: Maybe<String>,
     pub locale: Maybe<String>,
     pub layout: Maybe<Option<Layout>>,
+    pub membership_policy: Maybe<MembershipPolicy>,
+    pub tag_whitelist: Maybe<Option<Vec<String>>>,
+    pub default_license: Maybe<Option<String>>,
+}
+
+/// How users are able to become members of a site.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, Hash, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum MembershipPolicy {
+    /// Any user may join the site directly.
+    Open,
+
+    /// Users must apply, and be accepted by site staff.
+    Apply,
+
+    /// Users may only join if they are invited.
+    InviteOnly,
+
+    /// No new members may join the site.
+    Closed,
+}
+
+impl MembershipPolicy {
+    pub fn value(self) -> &'static str {
+        match self {
+            MembershipPolicy::Open => "open",
+            MembershipPolicy::Apply => "apply",
+            MembershipPolicy::InviteOnly => "invite-only",
+            MembershipPolicy::Closed => "closed",
+        }
+    }
+
+    /// Whether site staff may send invitations under this policy.
+    #[inline]
+    pub fn allows_invitations(self) -> bool {
+        !matches!(self, MembershipPolicy::Closed)
+    }
+}
+
+impl FromStr for MembershipPolicy {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<MembershipPolicy, Error> {
+        match value {
+            "open" => Ok(MembershipPolicy::Open),
+            "apply" => Ok(MembershipPolicy::Apply),
+            "invite-only" => Ok(MembershipPolicy::InviteOnly),
+            "closed" => Ok(MembershipPolicy::Closed),
+            _ => Err(Error::InvalidEnumValue),
+        }
+    }
 }
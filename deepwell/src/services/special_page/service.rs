@@ -20,7 +20,10 @@
 
 use super::prelude::*;
 use crate::models::site::Model as SiteModel;
-use crate::services::{PageRevisionService, PageService, RenderService, TextService};
+use crate::services::render::RenderPriority;
+use crate::services::{
+    CategoryService, PageRevisionService, PageService, RenderService, TextService,
+};
 use crate::types::Reference;
 use crate::utils::split_category;
 use fluent::{FluentArgs, FluentValue};
@@ -55,10 +58,31 @@ impl SpecialPageService {
         // Produces a list of slugs to use as a page template, the first one that
         // exists is the one that's used.
         let config = ctx.config();
+        let category_template = match sp_page_type {
+            SpecialPageType::Template => {
+                Self::get_category_template(
+                    ctx,
+                    site.site_id,
+                    page_info.category.ref_map(|s| s.as_ref()),
+                )
+                .await?
+            }
+            _ => None,
+        };
+
         let (slugs, translate_key) = match sp_page_type {
             // TODO: Figure out exact template ordering (e.g. _template vs cat:_template)
             //       See https://scuttle.atlassian.net/browse/WJ-1201
-            SpecialPageType::Template => (vec![cow!(config.special_page_template)], ""),
+            SpecialPageType::Template => {
+                let mut slugs = vec![cow!(config.special_page_template)];
+
+                // If the category has a template page configured, check it first.
+                if let Some(template) = category_template {
+                    slugs.insert(0, Cow::Owned(template));
+                }
+
+                (slugs, "")
+            }
             SpecialPageType::Missing => {
                 let slugs = Self::slugs_with_category(
                     &config.special_page_missing,
@@ -91,8 +115,14 @@ impl SpecialPageService {
         // The "page" here is what would've been there in this case,
         // passed in by the caller.
         let settings = WikitextSettings::from_mode(WikitextMode::Page, layout);
-        let render_output =
-            RenderService::render(ctx, wikitext.clone(), &page_info, &settings).await?;
+        let render_output = RenderService::render(
+            ctx,
+            wikitext.clone(),
+            &page_info,
+            &settings,
+            RenderPriority::Interactive,
+        )
+        .await?;
 
         Ok(GetSpecialPageOutput {
             wikitext,
@@ -100,6 +130,21 @@ impl SpecialPageService {
         })
     }
 
+    /// Gets the template page slug configured for this category, if any.
+    async fn get_category_template(
+        ctx: &ServiceContext<'_>,
+        site_id: i64,
+        category: Option<&str>,
+    ) -> Result<Option<String>> {
+        let category = category.unwrap_or("_default");
+        let template =
+            CategoryService::get_optional(ctx, site_id, Reference::from(category))
+                .await?
+                .and_then(|category| category.template);
+
+        Ok(template)
+    }
+
     fn slugs_with_category<'a>(
         base_slug: &'a str,
         page_category: Option<&'a str>,
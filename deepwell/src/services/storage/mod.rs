@@ -0,0 +1,39 @@
+/*
+ * services/storage/mod.rs
+ *
+ * DEEPWELL - Wikijump API provider and database manager
+ * Copyright (C) 2019-2025 Wikijump Team
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+//! Service for accounting the storage used by sites and users.
+//!
+//! Because blobs are deduplicated, each distinct blob is only charged once to a site,
+//! no matter how many files or revisions there use it. Similarly, users are charged
+//! once for each distinct blob they have uploaded, across all sites.
+//!
+//! Usage is tracked incrementally as file revisions are created, and can be
+//! recalculated from the file revision table if it needs to be corrected.
+
+#[allow(unused_imports)]
+mod prelude {
+    pub use super::super::prelude::*;
+    pub use super::structs::*;
+}
+
+mod service;
+mod structs;
+
+pub use self::service::StorageService;
+pub use self::structs::*;
@@ -0,0 +1,471 @@
+/*
+ * services/storage/service.rs
+ *
+ * DEEPWELL - Wikijump API provider and database manager
+ * Copyright (C) 2019-2025 Wikijump Team
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use super::prelude::*;
+use crate::models::file_revision::{self, Entity as FileRevision};
+use crate::models::storage_usage::{
+    self, Entity as StorageUsageEntity, Model as StorageUsageModel,
+};
+use crate::services::blob::EMPTY_BLOB_HASH;
+use sea_orm::{prelude::*, DatabaseBackend, FromQueryResult, Statement};
+use sea_query::OnConflict;
+
+#[derive(Debug)]
+pub struct StorageService;
+
+impl StorageService {
+    /// Gets the current storage usage and quota for a site or user.
+    pub async fn get_usage(
+        ctx: &ServiceContext<'_>,
+        owner: StorageOwner,
+    ) -> Result<StorageUsage> {
+        info!("Getting storage usage for {owner:?}");
+        let model = Self::get_optional(ctx, owner).await?;
+        Ok(Self::build_usage(ctx.config(), owner, model))
+    }
+
+    /// Checks that adding this many bytes would not exceed the quota.
+    ///
+    /// Yields `Error::StorageQuotaExceeded` if it would.
+    pub async fn check_quota(
+        ctx: &ServiceContext<'_>,
+        owner: StorageOwner,
+        size: i64,
+    ) -> Result<()> {
+        let StorageUsage {
+            used_bytes,
+            quota_bytes,
+            ..
+        } = Self::get_usage(ctx, owner).await?;
+
+        match quota_bytes {
+            Some(quota_bytes) if used_bytes.saturating_add(size) > quota_bytes => {
+                error!(
+                    "Adding {size} bytes would exceed storage quota for {owner:?} ({used_bytes} / {quota_bytes} bytes used)",
+                );
+
+                Err(Error::StorageQuotaExceeded {
+                    owner,
+                    used: used_bytes,
+                    quota: quota_bytes,
+                    size,
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Charges a blob being added as a file revision to its site and uploader.
+    ///
+    /// Only those who do not already have the blob are charged, and neither is
+    /// charged if either would exceed their quota. This must be called before
+    /// the file revision is inserted.
+    pub async fn charge_blob(
+        ctx: &ServiceContext<'_>,
+        site_id: i64,
+        user_id: i64,
+        s3_hash: &[u8],
+        size: i64,
+    ) -> Result<()> {
+        if size == 0 || s3_hash == EMPTY_BLOB_HASH {
+            debug!("Empty blob is not charged to storage usage");
+            return Ok(());
+        }
+
+        let mut charges = Vec::with_capacity(2);
+        if !Self::site_has_blob(ctx, site_id, s3_hash).await? {
+            charges.push(StorageOwner::Site { site_id });
+        }
+        if !Self::user_has_blob(ctx, user_id, s3_hash).await? {
+            charges.push(StorageOwner::User { user_id });
+        }
+
+        for &owner in &charges {
+            Self::check_quota(ctx, owner, size).await?;
+        }
+
+        for owner in charges {
+            debug!("Charging {size} bytes to storage usage for {owner:?}");
+            Self::add_usage(ctx, owner, size).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Charges a message attachment to the user attaching it.
+    ///
+    /// Unlike file revisions, each attachment is charged separately, since
+    /// it is removed along with its message. This must be called before the
+    /// attachment is inserted.
+    pub async fn charge_attachment(
+        ctx: &ServiceContext<'_>,
+        user_id: i64,
+        s3_hash: &[u8],
+        size: i64,
+    ) -> Result<()> {
+        if size == 0 || s3_hash == EMPTY_BLOB_HASH {
+            debug!("Empty blob is not charged to storage usage");
+            return Ok(());
+        }
+
+        let owner = StorageOwner::User { user_id };
+        Self::check_quota(ctx, owner, size).await?;
+
+        debug!("Charging {size} bytes to storage usage for {owner:?}");
+        Self::add_usage(ctx, owner, size).await
+    }
+
+    /// Credits storage usage back to a user after message attachments were removed.
+    pub async fn credit_attachments(
+        ctx: &ServiceContext<'_>,
+        user_id: i64,
+        size: i64,
+    ) -> Result<()> {
+        if size == 0 {
+            return Ok(());
+        }
+
+        let owner = StorageOwner::User { user_id };
+        debug!("Crediting {size} bytes to storage usage for {owner:?}");
+        Self::add_usage(ctx, owner, -size).await
+    }
+
+    /// Gets the IDs of all users who are charged for a message attachment with this blob.
+    pub async fn attachment_owners(
+        ctx: &ServiceContext<'_>,
+        s3_hash: &[u8],
+    ) -> Result<Vec<i64>> {
+        #[derive(Debug, FromQueryResult)]
+        struct AttachmentOwner {
+            user_id: i64,
+        }
+
+        let txn = ctx.transaction();
+        let owners = AttachmentOwner::find_by_statement(Statement::from_sql_and_values(
+            DatabaseBackend::Postgres,
+            "
+            SELECT DISTINCT COALESCE(message_draft.user_id, message_record.sender_id) AS user_id
+            FROM message_attachment
+            LEFT JOIN message_draft
+                ON message_attachment.draft_id = message_draft.external_id
+            LEFT JOIN message_record
+                ON message_attachment.record_id = message_record.external_id
+            WHERE message_attachment.s3_hash = $1
+            ",
+            [s3_hash.to_vec().into()],
+        ))
+        .all(txn)
+        .await?
+        .into_iter()
+        .map(|owner| owner.user_id)
+        .collect();
+
+        Ok(owners)
+    }
+
+    /// Overrides the storage quota for a site or user.
+    pub async fn set_quota(
+        ctx: &ServiceContext<'_>,
+        SetStorageQuota {
+            owner,
+            quota_bytes,
+            user_id,
+        }: SetStorageQuota,
+    ) -> Result<StorageUsage> {
+        info!("Setting storage quota for {owner:?} to {quota_bytes:?} (by user ID {user_id})");
+
+        if let Some(quota_bytes) = quota_bytes {
+            if quota_bytes < 0 {
+                error!("Storage quota cannot be negative");
+                return Err(Error::BadRequest);
+            }
+        }
+
+        // TODO add to audit log
+        let txn = ctx.transaction();
+        let model = match Self::get_optional(ctx, owner).await? {
+            Some(model) => {
+                let model = storage_usage::ActiveModel {
+                    storage_usage_id: Set(model.storage_usage_id),
+                    quota_bytes: Set(quota_bytes),
+                    quota_updated_by: Set(Some(user_id)),
+                    quota_updated_at: Set(Some(now())),
+                    ..Default::default()
+                };
+                model.update(txn).await?
+            }
+            None => {
+                let mut model = Self::new_model(owner);
+                model.quota_bytes = Set(quota_bytes);
+                model.quota_updated_by = Set(Some(user_id));
+                model.quota_updated_at = Set(Some(now()));
+                model.insert(txn).await?
+            }
+        };
+
+        Ok(Self::build_usage(ctx.config(), owner, Some(model)))
+    }
+
+    /// Recalculates the storage used by a site or user from its file revisions.
+    ///
+    /// Users are also charged for the message attachments they have added.
+    /// Usage is normally tracked incrementally, this is for when blobs are
+    /// removed (such as by hard deletion) or the count needs to be corrected.
+    pub async fn recalculate(
+        ctx: &ServiceContext<'_>,
+        owner: StorageOwner,
+    ) -> Result<StorageUsage> {
+        info!("Recalculating storage usage for {owner:?}");
+
+        #[derive(Debug, FromQueryResult)]
+        struct UsedBytes {
+            used_bytes: i64,
+        }
+
+        // Column names here are static strings, not user input.
+        // Users are only charged for revisions where they uploaded the blob.
+        let (column, owner_id, extra_condition, attachment_query) = match owner {
+            StorageOwner::Site { site_id } => ("site_id", site_id, "", "0"),
+            StorageOwner::User { user_id } => (
+                "user_id",
+                user_id,
+                "AND 'blob' = ANY(changes)",
+                "
+                SELECT COALESCE(SUM(message_attachment.size), 0)
+                FROM message_attachment
+                LEFT JOIN message_draft
+                    ON message_attachment.draft_id = message_draft.external_id
+                LEFT JOIN message_record
+                    ON message_attachment.record_id = message_record.external_id
+                WHERE COALESCE(message_draft.user_id, message_record.sender_id) = $1
+                AND message_attachment.s3_hash != $2
+                ",
+            ),
+        };
+
+        let txn = ctx.transaction();
+        let UsedBytes { used_bytes } =
+            UsedBytes::find_by_statement(Statement::from_sql_and_values(
+                DatabaseBackend::Postgres,
+                format!(
+                    "
+                    SELECT (
+                        (
+                            SELECT COALESCE(SUM(size), 0)
+                            FROM (
+                                SELECT DISTINCT ON (s3_hash) size
+                                FROM file_revision
+                                WHERE {column} = $1
+                                AND s3_hash != $2
+                                {extra_condition}
+                            ) AS blobs
+                        ) + ({attachment_query})
+                    )::BIGINT AS used_bytes
+                    ",
+                ),
+                [owner_id.into(), EMPTY_BLOB_HASH.to_vec().into()],
+            ))
+            .one(txn)
+            .await?
+            .expect("No results from SUM aggregate query");
+
+        debug!("Storage usage for {owner:?} is {used_bytes} bytes");
+
+        let model = match Self::get_optional(ctx, owner).await? {
+            Some(model) => {
+                let model = storage_usage::ActiveModel {
+                    storage_usage_id: Set(model.storage_usage_id),
+                    used_bytes: Set(used_bytes),
+                    ..Default::default()
+                };
+                model.update(txn).await?
+            }
+            None => {
+                let mut model = Self::new_model(owner);
+                model.used_bytes = Set(used_bytes);
+                model.insert(txn).await?
+            }
+        };
+
+        Ok(Self::build_usage(ctx.config(), owner, Some(model)))
+    }
+
+    /// Adds to the storage used by a site or user, creating its row if needed.
+    ///
+    /// This is a single upsert, so that concurrent first uploads for the
+    /// same owner do not both attempt to insert a row.
+    async fn add_usage(
+        ctx: &ServiceContext<'_>,
+        owner: StorageOwner,
+        bytes: i64,
+    ) -> Result<()> {
+        // Credits only apply to owners which have already been charged.
+        //
+        // These cannot be upserted, since the inserted row would fail
+        // the check that usage is not negative before the conflict is seen.
+        if bytes < 0 {
+            let txn = ctx.transaction();
+            StorageUsageEntity::update_many()
+                .col_expr(
+                    storage_usage::Column::UsedBytes,
+                    Expr::col(storage_usage::Column::UsedBytes).add(bytes),
+                )
+                .filter(Self::owner_condition(owner))
+                .exec(txn)
+                .await?;
+
+            return Ok(());
+        }
+
+        let owner_column = match owner {
+            StorageOwner::Site { .. } => storage_usage::Column::SiteId,
+            StorageOwner::User { .. } => storage_usage::Column::UserId,
+        };
+
+        let txn = ctx.transaction();
+        let mut model = Self::new_model(owner);
+        model.used_bytes = Set(bytes);
+        StorageUsageEntity::insert(model)
+            .on_conflict(
+                OnConflict::column(owner_column)
+                    .value(
+                        storage_usage::Column::UsedBytes,
+                        Expr::cust("storage_usage.used_bytes + EXCLUDED.used_bytes"),
+                    )
+                    .to_owned(),
+            )
+            .exec_without_returning(txn)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn site_has_blob(
+        ctx: &ServiceContext<'_>,
+        site_id: i64,
+        s3_hash: &[u8],
+    ) -> Result<bool> {
+        let txn = ctx.transaction();
+        let revision_id: Option<i64> = FileRevision::find()
+            .select_only()
+            .column(file_revision::Column::RevisionId)
+            .filter(
+                Condition::all()
+                    .add(file_revision::Column::SiteId.eq(site_id))
+                    .add(file_revision::Column::S3Hash.eq(s3_hash)),
+            )
+            .limit(1)
+            .into_tuple()
+            .one(txn)
+            .await?;
+
+        Ok(revision_id.is_some())
+    }
+
+    async fn user_has_blob(
+        ctx: &ServiceContext<'_>,
+        user_id: i64,
+        s3_hash: &[u8],
+    ) -> Result<bool> {
+        let txn = ctx.transaction();
+        let revision_id: Option<i64> = FileRevision::find()
+            .select_only()
+            .column(file_revision::Column::RevisionId)
+            .filter(
+                Condition::all()
+                    .add(file_revision::Column::UserId.eq(user_id))
+                    .add(file_revision::Column::S3Hash.eq(s3_hash))
+                    .add(Expr::cust("'blob' = ANY(changes)")),
+            )
+            .limit(1)
+            .into_tuple()
+            .one(txn)
+            .await?;
+
+        Ok(revision_id.is_some())
+    }
+
+    async fn get_optional(
+        ctx: &ServiceContext<'_>,
+        owner: StorageOwner,
+    ) -> Result<Option<StorageUsageModel>> {
+        let txn = ctx.transaction();
+        let model = StorageUsageEntity::find()
+            .filter(Self::owner_condition(owner))
+            .one(txn)
+            .await?;
+
+        Ok(model)
+    }
+
+    fn owner_condition(owner: StorageOwner) -> Condition {
+        match owner {
+            StorageOwner::Site { site_id } => {
+                Condition::all().add(storage_usage::Column::SiteId.eq(site_id))
+            }
+            StorageOwner::User { user_id } => {
+                Condition::all().add(storage_usage::Column::UserId.eq(user_id))
+            }
+        }
+    }
+
+    fn new_model(owner: StorageOwner) -> storage_usage::ActiveModel {
+        let (site_id, user_id) = match owner {
+            StorageOwner::Site { site_id } => (Some(site_id), None),
+            StorageOwner::User { user_id } => (None, Some(user_id)),
+        };
+
+        storage_usage::ActiveModel {
+            site_id: Set(site_id),
+            user_id: Set(user_id),
+            ..Default::default()
+        }
+    }
+
+    fn build_usage(
+        config: &Config,
+        owner: StorageOwner,
+        model: Option<StorageUsageModel>,
+    ) -> StorageUsage {
+        let default_quota = match owner {
+            StorageOwner::Site { .. } => config.site_storage_quota,
+            StorageOwner::User { .. } => config.user_storage_quota,
+        };
+
+        match model {
+            None => StorageUsage {
+                owner,
+                used_bytes: 0,
+                quota_bytes: default_quota,
+                quota_overridden: false,
+                quota_updated_by: None,
+                quota_updated_at: None,
+            },
+            Some(model) => StorageUsage {
+                owner,
+                used_bytes: model.used_bytes,
+                quota_bytes: model.quota_bytes.or(default_quota),
+                quota_overridden: model.quota_bytes.is_some(),
+                quota_updated_by: model.quota_updated_by,
+                quota_updated_at: model.quota_updated_at,
+            },
+        }
+    }
+}
@@ -0,0 +1,57 @@
+/*
+ * services/storage/structs.rs
+ *
+ * DEEPWELL - Wikijump API provider and database manager
+ * Copyright (C) 2019-2025 Wikijump Team
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use time::OffsetDateTime;
+
+/// Which site or user storage is being accounted for.
+#[derive(
+    Serialize, Deserialize, Debug, Copy, Clone, Hash, PartialEq, Eq, PartialOrd, Ord,
+)]
+#[serde(rename_all = "snake_case", tag = "type")]
+pub enum StorageOwner {
+    Site { site_id: i64 },
+    User { user_id: i64 },
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct StorageUsage {
+    pub owner: StorageOwner,
+    pub used_bytes: i64,
+
+    /// The quota currently in effect, or `None` if unlimited.
+    pub quota_bytes: Option<i64>,
+
+    /// Whether the quota was set specifically for this owner,
+    /// rather than being the configured default.
+    pub quota_overridden: bool,
+    pub quota_updated_by: Option<i64>,
+
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub quota_updated_at: Option<OffsetDateTime>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct SetStorageQuota {
+    pub owner: StorageOwner,
+
+    /// The new quota, or `None` to revert to the configured default.
+    pub quota_bytes: Option<i64>,
+    pub user_id: i64,
+}
@@ -0,0 +1,322 @@
+/*
+ * services/tag/service.rs
+ *
+ * DEEPWELL - Wikijump API provider and database manager
+ * Copyright (C) 2019-2025 Wikijump Team
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use super::prelude::*;
+use crate::models::page::{self, Entity as Page, Model as PageModel};
+use crate::models::page_tag::{self, Entity as PageTag};
+use crate::services::page::{EditPage, EditPageBody};
+use crate::services::{PageRevisionService, PageService, SiteService};
+use sea_orm::sea_query::Query;
+use sea_orm::ItemsAndPagesNumber;
+
+/// The maximum number of pages which can be requested at once
+/// when listing the pages with a tag.
+const MAXIMUM_PAGES_PER_REQUEST: u64 = 250;
+
+#[derive(Debug)]
+pub struct TagService;
+
+impl TagService {
+    /// Normalizes a list of tags in-place.
+    ///
+    /// Tags are trimmed of whitespace, with empty tags removed.
+    /// The list is then sorted and deduplicated, which is the
+    /// form they are stored in on page revisions.
+    pub fn normalize(tags: &mut Vec<String>) {
+        for tag in tags.iter_mut() {
+            let trimmed = tag.trim();
+            if trimmed.len() != tag.len() {
+                *tag = str!(trimmed);
+            }
+        }
+
+        tags.retain(|tag| !tag.is_empty());
+        tags.sort();
+        tags.dedup();
+    }
+
+    /// Ensures that all of the given tags are permitted on this site.
+    ///
+    /// If the site has no tag whitelist, then any tag is allowed.
+    pub async fn check_whitelist(
+        ctx: &ServiceContext<'_>,
+        site_id: i64,
+        tags: &[String],
+    ) -> Result<()> {
+        let site = SiteService::get(ctx, Reference::Id(site_id)).await?;
+        let whitelist = match site.tag_whitelist {
+            Some(whitelist) => whitelist,
+            None => return Ok(()),
+        };
+
+        for tag in tags {
+            if !whitelist.contains(tag) {
+                error!("Tag '{tag}' is not in the whitelist for site ID {site_id}");
+                return Err(Error::TagNotAllowed);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Replaces the indexed tags for a page.
+    ///
+    /// This should be called whenever a revision changing the tags is created.
+    pub async fn update_index(
+        ctx: &ServiceContext<'_>,
+        site_id: i64,
+        page_id: i64,
+        tags: &[String],
+    ) -> Result<()> {
+        debug!("Updating tag index for page ID {page_id} in site ID {site_id}");
+
+        let txn = ctx.transaction();
+        PageTag::delete_many()
+            .filter(page_tag::Column::PageId.eq(page_id))
+            .exec(txn)
+            .await?;
+
+        if tags.is_empty() {
+            return Ok(());
+        }
+
+        let models = tags.iter().map(|tag| page_tag::ActiveModel {
+            page_id: Set(page_id),
+            site_id: Set(site_id),
+            tag: Set(tag.clone()),
+        });
+
+        PageTag::insert_many(models).exec(txn).await?;
+        Ok(())
+    }
+
+    /// Gets all tags used in a site, along with the number of pages using each.
+    ///
+    /// Tags on deleted pages are not counted.
+    pub async fn get_cloud(
+        ctx: &ServiceContext<'_>,
+        site_id: i64,
+    ) -> Result<Vec<TagCount>> {
+        info!("Getting tag cloud for site ID {site_id}");
+
+        let txn = ctx.transaction();
+        let tags = PageTag::find()
+            .select_only()
+            .column(page_tag::Column::Tag)
+            .column_as(page_tag::Column::PageId.count(), "count")
+            .join(JoinType::InnerJoin, page_tag::Relation::Page.def())
+            .filter(
+                Condition::all()
+                    .add(page_tag::Column::SiteId.eq(site_id))
+                    .add(page::Column::DeletedAt.is_null()),
+            )
+            .group_by(page_tag::Column::Tag)
+            .order_by_asc(page_tag::Column::Tag)
+            .into_model::<TagCount>()
+            .all(txn)
+            .await?;
+
+        Ok(tags)
+    }
+
+    /// Gets a paginated list of the extant pages with a tag.
+    pub async fn get_pages(
+        ctx: &ServiceContext<'_>,
+        site_id: i64,
+        GetTagPages {
+            tag,
+            page,
+            per_page,
+            order,
+            descending,
+            ..
+        }: GetTagPages<'_>,
+    ) -> Result<GetTagPagesOutput> {
+        if per_page == 0 || per_page > MAXIMUM_PAGES_PER_REQUEST {
+            error!(
+                "Invalid number of pages per request: {per_page} (maximum {MAXIMUM_PAGES_PER_REQUEST})",
+            );
+            return Err(Error::BadRequest);
+        }
+
+        let txn = ctx.transaction();
+        let direction = if descending {
+            sea_orm::Order::Desc
+        } else {
+            sea_orm::Order::Asc
+        };
+
+        let paginator = Page::find()
+            .filter(
+                Condition::all()
+                    .add(page::Column::SiteId.eq(site_id))
+                    .add(page::Column::DeletedAt.is_null())
+                    .add(
+                        page::Column::PageId.in_subquery(
+                            Query::select()
+                                .column(page_tag::Column::PageId)
+                                .from(PageTag)
+                                .and_where(page_tag::Column::SiteId.eq(site_id))
+                                .and_where(page_tag::Column::Tag.eq(tag.trim()))
+                                .to_owned(),
+                        ),
+                    ),
+            )
+            .order_by(order.into_column(), direction)
+            .order_by_asc(page::Column::PageId)
+            .paginate(txn, per_page);
+
+        let ItemsAndPagesNumber {
+            number_of_items: total_items,
+            number_of_pages: total_pages,
+        } = paginator.num_items_and_pages().await?;
+
+        let pages = paginator.fetch_page(page).await?;
+
+        Ok(GetTagPagesOutput {
+            pages,
+            total_pages,
+            total_items,
+        })
+    }
+
+    /// Renames a tag across all pages in a site.
+    ///
+    /// If the new tag is already in use, this is equivalent to merging the two.
+    pub async fn rename(
+        ctx: &ServiceContext<'_>,
+        site_id: i64,
+        RenameTag {
+            site,
+            tag,
+            new_tag,
+            user_id,
+            revision_comments,
+        }: RenameTag<'_>,
+    ) -> Result<MergeTagsOutput> {
+        Self::merge(
+            ctx,
+            site_id,
+            MergeTags {
+                site,
+                tags: vec![tag],
+                target: new_tag,
+                user_id,
+                revision_comments,
+            },
+        )
+        .await
+    }
+
+    /// Replaces several tags with a single target tag across all pages in a site.
+    ///
+    /// A new revision is created for each page which has any of the source tags.
+    /// As these are regular edits, the target tag must be permitted by the
+    /// site's tag whitelist, if it has one.
+    pub async fn merge(
+        ctx: &ServiceContext<'_>,
+        site_id: i64,
+        MergeTags {
+            mut tags,
+            target,
+            user_id,
+            revision_comments,
+            ..
+        }: MergeTags<'_>,
+    ) -> Result<MergeTagsOutput> {
+        let target = str!(target.trim());
+        if target.is_empty() {
+            error!("Cannot merge tags into an empty tag");
+            return Err(Error::BadRequest);
+        }
+
+        Self::normalize(&mut tags);
+        tags.retain(|tag| tag != &target);
+
+        info!(
+            "Merging tags {tags:?} into '{target}' in site ID {site_id} (by user ID {user_id})",
+        );
+
+        if tags.is_empty() {
+            debug!("No tags to merge, nothing to do");
+            return Ok(MergeTagsOutput { page_ids: vec![] });
+        }
+
+        let txn = ctx.transaction();
+        let pages = Page::find()
+            .filter(
+                Condition::all()
+                    .add(page::Column::SiteId.eq(site_id))
+                    .add(page::Column::DeletedAt.is_null())
+                    .add(
+                        page::Column::PageId.in_subquery(
+                            Query::select()
+                                .column(page_tag::Column::PageId)
+                                .from(PageTag)
+                                .and_where(page_tag::Column::SiteId.eq(site_id))
+                                .and_where(page_tag::Column::Tag.is_in(tags.iter()))
+                                .to_owned(),
+                        ),
+                    ),
+            )
+            .order_by_asc(page::Column::PageId)
+            .all(txn)
+            .await?;
+
+        let mut page_ids = Vec::with_capacity(pages.len());
+        for PageModel {
+            page_id,
+            latest_revision_id,
+            ..
+        } in pages
+        {
+            let revision = PageRevisionService::get_latest(ctx, site_id, page_id).await?;
+            let mut new_tags: Vec<String> = revision
+                .tags
+                .into_iter()
+                .filter(|tag| !tags.contains(tag))
+                .collect();
+
+            new_tags.push(target.clone());
+
+            let output = PageService::edit(
+                ctx,
+                EditPage {
+                    site_id,
+                    page: Reference::Id(page_id),
+                    last_revision_id: latest_revision_id.unwrap_or(revision.revision_id),
+                    revision_comments: revision_comments.clone(),
+                    user_id,
+                    body: EditPageBody {
+                        tags: Maybe::Set(new_tags),
+                        ..Default::default()
+                    },
+                },
+            )
+            .await?;
+
+            if output.is_some() {
+                page_ids.push(page_id);
+            }
+        }
+
+        Ok(MergeTagsOutput { page_ids })
+    }
+}
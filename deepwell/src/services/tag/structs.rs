@@ -0,0 +1,78 @@
+/*
+ * services/tag/structs.rs
+ *
+ * DEEPWELL - Wikijump API provider and database manager
+ * Copyright (C) 2019-2025 Wikijump Team
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use crate::models::page::Model as PageModel;
+use crate::types::{PageOrderColumn, Reference};
+use sea_orm::FromQueryResult;
+
+#[derive(Serialize, Debug, Clone, FromQueryResult)]
+pub struct TagCount {
+    pub tag: String,
+    pub count: i64,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct GetTagPages<'a> {
+    pub site: Reference<'a>,
+    pub tag: String,
+    pub page: u64,
+    pub per_page: u64,
+
+    #[serde(default)]
+    pub order: PageOrderColumn,
+
+    #[serde(default)]
+    pub descending: bool,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct GetTagPagesOutput {
+    pub pages: Vec<PageModel>,
+    pub total_pages: u64,
+    pub total_items: u64,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct RenameTag<'a> {
+    pub site: Reference<'a>,
+    pub tag: String,
+    pub new_tag: String,
+    pub user_id: i64,
+
+    #[serde(default)]
+    pub revision_comments: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct MergeTags<'a> {
+    pub site: Reference<'a>,
+    pub tags: Vec<String>,
+    pub target: String,
+    pub user_id: i64,
+
+    #[serde(default)]
+    pub revision_comments: String,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct MergeTagsOutput {
+    /// The IDs of all pages which had a new revision created.
+    pub page_ids: Vec<i64>,
+}
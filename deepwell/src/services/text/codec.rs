@@ -0,0 +1,152 @@
+/*
+ * services/text/codec.rs
+ *
+ * DEEPWELL - Wikijump API provider and database manager
+ * Copyright (C) 2019-2025 Wikijump Team
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Low-level encoding of stored text, independent of the database.
+
+use std::io::{self, Read};
+use zstd::stream::read::Decoder;
+
+/// The length of the header at the start of each delta.
+///
+/// This contains the lengths of the prefix and suffix shared with the base text,
+/// each as a little-endian 32-bit integer.
+const DELTA_HEADER_LENGTH: usize = 8;
+
+/// Compresses data with zstd, optionally using a trained dictionary.
+pub fn compress(
+    data: &[u8],
+    level: i32,
+    dictionary: Option<&[u8]>,
+) -> io::Result<Vec<u8>> {
+    match dictionary {
+        Some(dictionary) => {
+            zstd::bulk::Compressor::with_dictionary(level, dictionary)?.compress(data)
+        }
+        None => zstd::bulk::compress(data, level),
+    }
+}
+
+/// Decompresses zstd data, which must use the same dictionary it was compressed with.
+pub fn decompress(data: &[u8], dictionary: Option<&[u8]>) -> io::Result<Vec<u8>> {
+    let mut output = Vec::new();
+    match dictionary {
+        Some(dictionary) => {
+            Decoder::with_dictionary(data, dictionary)?.read_to_end(&mut output)?
+        }
+        None => Decoder::new(data)?.read_to_end(&mut output)?,
+    };
+
+    Ok(output)
+}
+
+/// Produces a delta which transforms `base` into `target`.
+///
+/// A delta is the length of the prefix and suffix that both share,
+/// followed by the bytes in between which differ. Most edits only
+/// change one area of a page, so this is simple but effective.
+pub fn encode_delta(base: &[u8], target: &[u8]) -> Vec<u8> {
+    let prefix = base
+        .iter()
+        .zip(target)
+        .take_while(|(base, target)| base == target)
+        .count();
+
+    let suffix = base[prefix..]
+        .iter()
+        .rev()
+        .zip(target[prefix..].iter().rev())
+        .take_while(|(base, target)| base == target)
+        .count();
+
+    let middle = &target[prefix..target.len() - suffix];
+    let mut delta = Vec::with_capacity(DELTA_HEADER_LENGTH + middle.len());
+    delta.extend_from_slice(&(prefix as u32).to_le_bytes());
+    delta.extend_from_slice(&(suffix as u32).to_le_bytes());
+    delta.extend_from_slice(middle);
+    delta
+}
+
+/// Applies a delta produced by `encode_delta()` to its base.
+///
+/// Returns `None` if the delta is malformed or does not fit the base.
+pub fn apply_delta(base: &[u8], delta: &[u8]) -> Option<Vec<u8>> {
+    if delta.len() < DELTA_HEADER_LENGTH {
+        return None;
+    }
+
+    let (header, middle) = delta.split_at(DELTA_HEADER_LENGTH);
+    let prefix = u32::from_le_bytes(header[..4].try_into().ok()?) as usize;
+    let suffix = u32::from_le_bytes(header[4..].try_into().ok()?) as usize;
+    if prefix.checked_add(suffix)? > base.len() {
+        return None;
+    }
+
+    let mut output = Vec::with_capacity(prefix + middle.len() + suffix);
+    output.extend_from_slice(&base[..prefix]);
+    output.extend_from_slice(middle);
+    output.extend_from_slice(&base[base.len() - suffix..]);
+    Some(output)
+}
+
+#[test]
+fn delta() {
+    macro_rules! check {
+        ($base:expr, $target:expr $(,)?) => {{
+            let base: &[u8] = $base;
+            let target: &[u8] = $target;
+            let delta = encode_delta(base, target);
+            assert_eq!(
+                apply_delta(base, &delta).as_deref(),
+                Some(target),
+                "Delta did not reproduce target",
+            );
+            delta
+        }};
+    }
+
+    check!(b"", b"");
+    check!(b"", b"apple");
+    check!(b"apple", b"");
+    check!(b"apple", b"apple");
+    check!(b"aaaa", b"aa");
+    check!(b"aa", b"aaaa");
+    check!(b"the quick brown fox", b"the quick red fox");
+    check!(b"banana", b"bandana");
+
+    let delta = check!(
+        b"[[div]]\nsome text\n[[/div]]",
+        b"[[div]]\nsome more text\n[[/div]]"
+    );
+    assert_eq!(delta.len(), DELTA_HEADER_LENGTH + 5);
+
+    assert_eq!(apply_delta(b"short", b"bad"), None);
+    assert_eq!(
+        apply_delta(b"short", &encode_delta(b"a longer base", b"a longer base!")),
+        None
+    );
+}
+
+#[test]
+fn compression() {
+    let data = "wikitext ".repeat(100);
+    let compressed = compress(data.as_bytes(), 3, None).unwrap();
+    assert!(compressed.len() < data.len());
+    assert_eq!(decompress(&compressed, None).unwrap(), data.as_bytes());
+}
@@ -0,0 +1,43 @@
+/*
+ * services/text/mod.rs
+ *
+ * DEEPWELL - Wikijump API provider and database manager
+ * Copyright (C) 2019-2025 Wikijump Team
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! The text service, for storing large strings in the database.
+//! For instance, page wikitext sources and compiled HTML outputs.
+//!
+//! It uses content-addressable storage, meaning that data is uniquely
+//! identified by its hash.
+//!
+//! Text may be stored as-is, compressed with zstd (possibly using a dictionary
+//! trained from existing text), or as a compressed delta against another text row.
+//! The hash is always of the original contents, and all of this is hidden behind
+//! `TextService::get()`, so callers only ever see the original string.
+
+#[allow(unused_imports)]
+mod prelude {
+    pub use super::super::prelude::*;
+    pub use super::structs::*;
+}
+
+mod codec;
+mod service;
+mod structs;
+
+pub use self::service::TextService;
+pub use self::structs::*;
@@ -0,0 +1,659 @@
+/*
+ * services/text/service.rs
+ *
+ * DEEPWELL - Wikijump API provider and database manager
+ * Copyright (C) 2019-2025 Wikijump Team
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use super::codec::{apply_delta, compress, decompress, encode_delta};
+use super::prelude::*;
+use crate::hash::{k12_hash, TextHash, TEXT_HASH_LENGTH};
+use crate::models::message_draft::{self, Entity as MessageDraft};
+use crate::models::message_record::{self, Entity as MessageRecord};
+use crate::models::page_revision::{self, Entity as PageRevision};
+use crate::models::text::{self, Entity as Text, Model as TextModel};
+use crate::models::text_dictionary::{self, Entity as TextDictionary};
+use crate::services::job::{Job, JobService};
+use once_cell::sync::Lazy;
+use redis::AsyncCommands;
+use sea_orm::Order;
+use sea_query::{Expr, Func, Query, SimpleExpr};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+/// How long, in seconds, progress for a text recompression is retained in Redis.
+const RECOMPRESS_PROGRESS_EXPIRY_SECS: u64 = 7 * 24 * 60 * 60; // 1 week
+
+/// Cache of zstd dictionaries, by ID.
+///
+/// Dictionaries are never modified once created, so they can be
+/// cached indefinitely rather than fetched for every text row.
+static DICTIONARIES: Lazy<RwLock<HashMap<i32, Arc<[u8]>>>> =
+    Lazy::new(|| RwLock::new(HashMap::new()));
+
+/// An encoded form of some text, ready to be stored in a row.
+#[derive(Debug)]
+struct EncodedText {
+    encoding: TextEncoding,
+    contents: Option<String>,
+    data: Option<Vec<u8>>,
+    text_dictionary_id: Option<i32>,
+    base_hash: Option<Vec<u8>>,
+    delta_depth: i16,
+}
+
+#[derive(Debug)]
+pub struct TextService;
+
+impl TextService {
+    pub async fn get_optional(
+        ctx: &ServiceContext<'_>,
+        hash: &[u8],
+    ) -> Result<Option<String>> {
+        Self::check_hash_length(hash)?;
+
+        let txn = ctx.transaction();
+        match Text::find_by_id(hash).one(txn).await? {
+            Some(model) => Ok(Some(Self::decode(ctx, model).await?)),
+            None => Ok(None),
+        }
+    }
+
+    #[inline]
+    pub async fn get(ctx: &ServiceContext<'_>, hash: &[u8]) -> Result<String> {
+        find_or_error!(Self::get_optional(ctx, hash), Text)
+    }
+
+    pub async fn exists(ctx: &ServiceContext<'_>, hash: &[u8]) -> Result<bool> {
+        Self::check_hash_length(hash)?;
+
+        let txn = ctx.transaction();
+        let count = Text::find_by_id(hash).count(txn).await?;
+        Ok(count > 0)
+    }
+
+    /// Possibly retrieve text, if a flag is set.
+    ///
+    /// This utility conditionally retrieves the
+    /// text given by the specified hash only
+    /// if the flag `should_fetch` is true.
+    /// Otherwise, it does no action, returning `None`.
+    pub async fn get_maybe(
+        ctx: &ServiceContext<'_>,
+        should_fetch: bool,
+        hash: &[u8],
+    ) -> Result<Option<String>> {
+        if should_fetch {
+            let text = Self::get(ctx, hash).await?;
+            Ok(Some(text))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Creates a text entry with this data, if it does not already exist.
+    #[inline]
+    pub async fn create(ctx: &ServiceContext<'_>, contents: String) -> Result<TextHash> {
+        Self::create_inner(ctx, contents, None).await
+    }
+
+    /// Creates a text entry with this data, if it does not already exist.
+    ///
+    /// If enabled, it may be stored as a delta against the given text,
+    /// which should be a previous version of it, such as the wikitext
+    /// of the prior page revision.
+    #[inline]
+    pub async fn create_delta(
+        ctx: &ServiceContext<'_>,
+        contents: String,
+        base_hash: &[u8],
+    ) -> Result<TextHash> {
+        Self::create_inner(ctx, contents, Some(base_hash)).await
+    }
+
+    async fn create_inner(
+        ctx: &ServiceContext<'_>,
+        contents: String,
+        base_hash: Option<&[u8]>,
+    ) -> Result<TextHash> {
+        let txn = ctx.transaction();
+        let hash = k12_hash(contents.as_bytes());
+
+        if !Self::exists(ctx, &hash).await? {
+            let length = contents.chars().count() as i32;
+            let EncodedText {
+                encoding,
+                contents,
+                data,
+                text_dictionary_id,
+                base_hash,
+                delta_depth,
+            } = Self::encode(ctx, contents, base_hash).await?;
+
+            let model = text::ActiveModel {
+                hash: Set(hash.to_vec()),
+                encoding: Set(str!(encoding.value())),
+                length: Set(length),
+                contents: Set(contents),
+                data: Set(data),
+                text_dictionary_id: Set(text_dictionary_id),
+                base_hash: Set(base_hash),
+                delta_depth: Set(delta_depth),
+            };
+
+            Text::insert(model).exec(txn).await?;
+        }
+
+        Ok(hash)
+    }
+
+    /// Encodes text for storage, choosing whichever allowed encoding is smallest.
+    async fn encode(
+        ctx: &ServiceContext<'_>,
+        contents: String,
+        base_hash: Option<&[u8]>,
+    ) -> Result<EncodedText> {
+        let config = ctx.config();
+        let mut encoded = EncodedText {
+            encoding: TextEncoding::Plain,
+            contents: None,
+            data: None,
+            text_dictionary_id: None,
+            base_hash: None,
+            delta_depth: 0,
+        };
+
+        if contents.len() < config.text_compression_min_bytes {
+            encoded.contents = Some(contents);
+            return Ok(encoded);
+        }
+
+        let mut size = contents.len();
+
+        if config.text_compression {
+            let dictionary = Self::get_latest_dictionary(ctx).await?;
+            let data = compress(
+                contents.as_bytes(),
+                config.text_compression_level,
+                dictionary.as_ref().map(|(_, data)| data.as_ref()),
+            )
+            .map_err(codec_error)?;
+
+            if data.len() < size {
+                size = data.len();
+                encoded.encoding = TextEncoding::Zstd;
+                encoded.data = Some(data);
+                encoded.text_dictionary_id = dictionary.map(|(id, _)| id);
+            }
+        }
+
+        if let Some(base_hash) = base_hash.filter(|_| config.text_delta) {
+            let txn = ctx.transaction();
+            let max_depth = config.text_max_delta_depth.get();
+
+            if let Some(base) = Text::find_by_id(base_hash).one(txn).await? {
+                if (base.delta_depth as u16) < max_depth {
+                    let delta_depth = base.delta_depth + 1;
+                    let base = Self::decode(ctx, base).await?;
+                    let delta = encode_delta(base.as_bytes(), contents.as_bytes());
+                    let data = compress(&delta, config.text_compression_level, None)
+                        .map_err(codec_error)?;
+
+                    if data.len() < size {
+                        encoded.encoding = TextEncoding::Delta;
+                        encoded.data = Some(data);
+                        encoded.text_dictionary_id = None;
+                        encoded.base_hash = Some(base_hash.to_vec());
+                        encoded.delta_depth = delta_depth;
+                    }
+                }
+            }
+        }
+
+        if encoded.encoding == TextEncoding::Plain {
+            encoded.contents = Some(contents);
+        }
+
+        Ok(encoded)
+    }
+
+    /// Retrieves the original contents of a text row.
+    ///
+    /// For delta rows, this walks the chain back to the nearest row stored in full,
+    /// then applies each delta in turn.
+    pub(crate) async fn decode(
+        ctx: &ServiceContext<'_>,
+        model: TextModel,
+    ) -> Result<String> {
+        let txn = ctx.transaction();
+        let hash = model.hash.clone();
+        let mut model = model;
+        let mut deltas = Vec::new();
+
+        let mut contents = loop {
+            let encoding =
+                TextEncoding::from_value(&model.encoding).ok_or_else(|| {
+                    Error::TextCodec(format!(
+                        "Unknown text encoding '{}'",
+                        model.encoding
+                    ))
+                })?;
+
+            match encoding {
+                TextEncoding::Plain => match model.contents {
+                    Some(contents) => break contents.into_bytes(),
+                    None => {
+                        return Err(Error::TextCodec(str!(
+                            "Plain text row has no contents"
+                        )))
+                    }
+                },
+                TextEncoding::Zstd => break Self::decompress(ctx, &model).await?,
+                TextEncoding::Delta => {
+                    deltas.push(Self::decompress(ctx, &model).await?);
+
+                    let base_hash = model.base_hash.unwrap_or_default();
+                    model = match Text::find_by_id(base_hash).one(txn).await? {
+                        Some(model) => model,
+                        None => {
+                            return Err(Error::TextCodec(str!(
+                                "Base of text delta does not exist",
+                            )))
+                        }
+                    };
+                }
+            }
+        };
+
+        for delta in deltas.iter().rev() {
+            contents = apply_delta(&contents, delta).ok_or_else(|| {
+                Error::TextCodec(format!(
+                    "Text delta for {} does not fit its base",
+                    hex::encode(&hash),
+                ))
+            })?;
+        }
+
+        String::from_utf8(contents).map_err(|_| {
+            Error::TextCodec(format!("Text {} is not valid UTF-8", hex::encode(&hash)))
+        })
+    }
+
+    async fn decompress(ctx: &ServiceContext<'_>, model: &TextModel) -> Result<Vec<u8>> {
+        let dictionary = match model.text_dictionary_id {
+            Some(text_dictionary_id) => {
+                Some(Self::get_dictionary(ctx, text_dictionary_id).await?)
+            }
+            None => None,
+        };
+
+        let data = model.data.as_deref().unwrap_or_default();
+        decompress(data, dictionary.as_deref()).map_err(codec_error)
+    }
+
+    // Dictionaries
+
+    async fn get_dictionary(
+        ctx: &ServiceContext<'_>,
+        text_dictionary_id: i32,
+    ) -> Result<Arc<[u8]>> {
+        if let Some(data) = DICTIONARIES
+            .read()
+            .expect("Dictionary cache lock is poisoned")
+            .get(&text_dictionary_id)
+        {
+            return Ok(Arc::clone(data));
+        }
+
+        let txn = ctx.transaction();
+        let data: Arc<[u8]> = match TextDictionary::find_by_id(text_dictionary_id)
+            .one(txn)
+            .await?
+        {
+            Some(model) => Arc::from(model.data),
+            None => {
+                return Err(Error::TextCodec(format!(
+                    "Text dictionary {text_dictionary_id} does not exist",
+                )))
+            }
+        };
+
+        DICTIONARIES
+            .write()
+            .expect("Dictionary cache lock is poisoned")
+            .insert(text_dictionary_id, Arc::clone(&data));
+
+        Ok(data)
+    }
+
+    /// Gets the most recently trained dictionary, if any, to compress new text with.
+    async fn get_latest_dictionary(
+        ctx: &ServiceContext<'_>,
+    ) -> Result<Option<(i32, Arc<[u8]>)>> {
+        if ctx.config().text_dictionary_size == 0 {
+            return Ok(None);
+        }
+
+        let txn = ctx.transaction();
+        let text_dictionary_id: Option<i32> = TextDictionary::find()
+            .select_only()
+            .column(text_dictionary::Column::TextDictionaryId)
+            .order_by_desc(text_dictionary::Column::TextDictionaryId)
+            .into_tuple()
+            .one(txn)
+            .await?;
+
+        match text_dictionary_id {
+            Some(id) => Ok(Some((id, Self::get_dictionary(ctx, id).await?))),
+            None => Ok(None),
+        }
+    }
+
+    /// Trains a new zstd dictionary from a random sample of existing text.
+    ///
+    /// Returns the ID of the new dictionary, or `None` if dictionaries are
+    /// disabled or there was not enough text to train one.
+    pub async fn train_dictionary(ctx: &ServiceContext<'_>) -> Result<Option<i32>> {
+        let config = ctx.config();
+        if config.text_dictionary_size == 0 {
+            debug!("Text dictionaries are disabled, not training one");
+            return Ok(None);
+        }
+
+        let txn = ctx.transaction();
+        let models = Text::find()
+            .filter(text::Column::Encoding.ne(TextEncoding::Delta.value()))
+            .order_by(SimpleExpr::FunctionCall(Func::random()), Order::Asc)
+            .limit(config.text_dictionary_samples)
+            .all(txn)
+            .await?;
+
+        let mut samples = Vec::with_capacity(models.len());
+        for model in models {
+            samples.push(Self::decode(ctx, model).await?.into_bytes());
+        }
+
+        let sample_count = samples.len();
+        let dictionary_size = config.text_dictionary_size;
+        info!("Training text dictionary from {sample_count} samples");
+
+        let result = tokio::task::spawn_blocking(move || {
+            zstd::dict::from_samples(&samples, dictionary_size)
+        })
+        .await
+        .map_err(|error| Error::TextCodec(error.to_string()))?;
+
+        let data = match result {
+            Ok(data) => data,
+            Err(error) => {
+                warn!("Unable to train text dictionary: {error}");
+                return Ok(None);
+            }
+        };
+
+        let model = text_dictionary::ActiveModel {
+            sample_count: Set(sample_count as i32),
+            data: Set(data),
+            ..Default::default()
+        };
+        let text_dictionary_id = model.insert(txn).await?.text_dictionary_id;
+        info!("Created text dictionary ID {text_dictionary_id}");
+        Ok(Some(text_dictionary_id))
+    }
+
+    // Recompression
+
+    /// Begins recompressing all text rows stored in full.
+    ///
+    /// This trains a new dictionary (if enabled) and then re-encodes rows
+    /// with the current settings, in throttled batches through the job queue.
+    /// Delta rows are left as-is.
+    pub async fn start_recompress(
+        ctx: &ServiceContext<'_>,
+    ) -> Result<RecompressTextProgress> {
+        let text_dictionary_id = Self::train_dictionary(ctx).await?;
+
+        let txn = ctx.transaction();
+        let total = Text::find()
+            .filter(text::Column::Encoding.ne(TextEncoding::Delta.value()))
+            .count(txn)
+            .await?;
+
+        info!("Starting text recompression ({total} rows)");
+
+        let now = now();
+        let progress = RecompressTextProgress {
+            text_dictionary_id,
+            total,
+            checked: 0,
+            recompressed: 0,
+            last_hash: str!(),
+            finished: total == 0,
+            started_at: now,
+            updated_at: now,
+        };
+
+        Self::set_recompress_progress(ctx, &progress).await?;
+
+        if total > 0 {
+            JobService::queue_job(
+                ctx,
+                &Job::RecompressTextBatch { after_hash: str!() },
+                None,
+            )
+            .await?;
+        }
+
+        Ok(progress)
+    }
+
+    /// Re-encodes the next batch of text rows for a recompression.
+    ///
+    /// Returns the last hash checked (hex-encoded) if there are more rows
+    /// remaining, or `None` if the recompression has finished.
+    pub async fn recompress_batch(
+        ctx: &ServiceContext<'_>,
+        after_hash: &str,
+    ) -> Result<Option<String>> {
+        let txn = ctx.transaction();
+        let after = hex::decode(after_hash).map_err(|_| Error::BadRequest)?;
+        let batch_size = u64::from(ctx.config().job_recompress_text_batch_size.get());
+        let models = Text::find()
+            .filter(text::Column::Hash.gt(after))
+            .filter(text::Column::Encoding.ne(TextEncoding::Delta.value()))
+            .order_by_asc(text::Column::Hash)
+            .limit(batch_size)
+            .all(txn)
+            .await?;
+
+        let checked = models.len() as u64;
+        let last_hash = models.last().map(|model| hex::encode(&model.hash));
+        let mut recompressed = 0;
+
+        for model in models {
+            let hash = model.hash.clone();
+            let old_encoding = model.encoding.clone();
+            let old_dictionary_id = model.text_dictionary_id;
+            let contents = Self::decode(ctx, model).await?;
+            let encoded = Self::encode(ctx, contents, None).await?;
+
+            if encoded.encoding.value() == old_encoding
+                && encoded.text_dictionary_id == old_dictionary_id
+            {
+                continue;
+            }
+
+            let model = text::ActiveModel {
+                hash: Set(hash),
+                encoding: Set(str!(encoded.encoding.value())),
+                contents: Set(encoded.contents),
+                data: Set(encoded.data),
+                text_dictionary_id: Set(encoded.text_dictionary_id),
+                ..Default::default()
+            };
+            model.update(txn).await?;
+            recompressed += 1;
+        }
+
+        let finished = checked < batch_size;
+
+        // Update progress
+        //
+        // If it was expired or removed, we start counting again from here.
+        let mut progress = match Self::get_recompress_progress(ctx).await? {
+            Some(progress) => progress,
+            None => RecompressTextProgress {
+                text_dictionary_id: None,
+                total: 0,
+                checked: 0,
+                recompressed: 0,
+                last_hash: str!(after_hash),
+                finished: false,
+                started_at: now(),
+                updated_at: now(),
+            },
+        };
+
+        progress.checked += checked;
+        progress.recompressed += recompressed;
+        progress.last_hash = last_hash.clone().unwrap_or_else(|| str!(after_hash));
+        progress.finished = finished;
+        progress.updated_at = now();
+
+        info!(
+            "Text recompression: checked {} / {} rows, recompressed {}",
+            progress.checked, progress.total, progress.recompressed,
+        );
+
+        Self::set_recompress_progress(ctx, &progress).await?;
+
+        if finished {
+            Ok(None)
+        } else {
+            Ok(last_hash)
+        }
+    }
+
+    pub async fn get_recompress_progress(
+        ctx: &ServiceContext<'_>,
+    ) -> Result<Option<RecompressTextProgress>> {
+        let mut redis = ctx.redis_connect().await?;
+        let data: Option<String> = redis.get(RECOMPRESS_PROGRESS_KEY).await?;
+        match data {
+            Some(data) => Ok(Some(serde_json::from_str(&data)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn set_recompress_progress(
+        ctx: &ServiceContext<'_>,
+        progress: &RecompressTextProgress,
+    ) -> Result<()> {
+        let mut redis = ctx.redis_connect().await?;
+        let data = serde_json::to_string(progress)?;
+        redis
+            .set_ex::<_, _, ()>(
+                RECOMPRESS_PROGRESS_KEY,
+                data,
+                RECOMPRESS_PROGRESS_EXPIRY_SECS,
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// Searches for any text rows which are unused.
+    ///
+    /// This is rare, but can happen when text is invalidated,
+    /// such as rerendering pages.
+    ///
+    /// Rows which are the base of a delta are kept, so a chain of
+    /// unused deltas is pruned one row at a time over several runs.
+    pub async fn prune(ctx: &ServiceContext<'_>) -> Result<()> {
+        macro_rules! not_in_column {
+            ($table:expr, $column:expr $(,)?) => {
+                text::Column::Hash.not_in_subquery(
+                    Query::select()
+                        .column($column)
+                        .from($table)
+                        .and_where(Expr::col($column).is_not_null())
+                        .to_owned(),
+                )
+            };
+        }
+
+        // All foreign keys of text.hash should have conditions here.
+        // These foreign key constraints prevent us from deleting anything
+        // actually used.
+        let txn = ctx.transaction();
+        let DeleteResult { rows_affected, .. } = Text::delete_many()
+            .filter(
+                Condition::all()
+                    .add(not_in_column!(
+                        PageRevision,
+                        page_revision::Column::WikitextHash,
+                    ))
+                    .add(not_in_column!(
+                        PageRevision,
+                        page_revision::Column::CompiledHash,
+                    ))
+                    .add(not_in_column!(
+                        PageRevision,
+                        page_revision::Column::CompiledStyleHash,
+                    ))
+                    .add(not_in_column!(
+                        MessageDraft,
+                        message_draft::Column::WikitextHash,
+                    ))
+                    .add(not_in_column!(
+                        MessageDraft,
+                        message_draft::Column::CompiledHash,
+                    ))
+                    .add(not_in_column!(
+                        MessageRecord,
+                        message_record::Column::WikitextHash,
+                    ))
+                    .add(not_in_column!(
+                        MessageRecord,
+                        message_record::Column::CompiledHash,
+                    ))
+                    .add(not_in_column!(Text, text::Column::BaseHash)),
+                // TODO add forum_post_revision
+            )
+            .exec(txn)
+            .await?;
+
+        debug!("Pruned {rows_affected} unused text rows");
+        Ok(())
+    }
+
+    fn check_hash_length(hash: &[u8]) -> Result<()> {
+        if hash.len() != TEXT_HASH_LENGTH {
+            error!(
+                "Text hash length does not match, should be {}, is {}",
+                TEXT_HASH_LENGTH,
+                hash.len(),
+            );
+            return Err(Error::BadRequest);
+        }
+
+        Ok(())
+    }
+}
+
+fn codec_error(error: std::io::Error) -> Error {
+    Error::TextCodec(error.to_string())
+}
@@ -0,0 +1,74 @@
+/*
+ * services/text/structs.rs
+ *
+ * DEEPWELL - Wikijump API provider and database manager
+ * Copyright (C) 2019-2025 Wikijump Team
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use super::prelude::*;
+use time::OffsetDateTime;
+
+/// The Redis key storing progress for the current or last text recompression.
+pub const RECOMPRESS_PROGRESS_KEY: &str = "text-recompress-progress";
+
+/// How the contents of a text row are stored.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, Hash, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TextEncoding {
+    /// The contents are stored as-is.
+    Plain,
+
+    /// The contents are compressed with zstd, possibly with a dictionary.
+    Zstd,
+
+    /// A delta against the contents of another row, compressed with zstd.
+    Delta,
+}
+
+impl TextEncoding {
+    pub fn value(self) -> &'static str {
+        match self {
+            TextEncoding::Plain => "plain",
+            TextEncoding::Zstd => "zstd",
+            TextEncoding::Delta => "delta",
+        }
+    }
+
+    pub fn from_value(value: &str) -> Option<Self> {
+        match value {
+            "plain" => Some(TextEncoding::Plain),
+            "zstd" => Some(TextEncoding::Zstd),
+            "delta" => Some(TextEncoding::Delta),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RecompressTextProgress {
+    pub text_dictionary_id: Option<i32>,
+    pub total: u64,
+    pub checked: u64,
+    pub recompressed: u64,
+    pub last_hash: String,
+    pub finished: bool,
+
+    #[serde(with = "time::serde::rfc3339")]
+    pub started_at: OffsetDateTime,
+
+    #[serde(with = "time::serde::rfc3339")]
+    pub updated_at: OffsetDateTime,
+}
@@ -0,0 +1,653 @@
+/*
+ * services/user_data/service.rs
+ *
+ * DEEPWELL - Wikijump API provider and database manager
+ * Copyright (C) 2019-2025 Wikijump Team
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! Handles requests regarding the personal data we hold about a user.
+//!
+//! An export gathers everything associated with a user (their profile,
+//! authored revisions and uploads, votes, messages, relations, and sessions)
+//! into a ZIP archive. As this can be large, it is built by a job, and the
+//! result is uploaded to S3, where it can be downloaded through a presigned URL
+//! until it expires.
+//!
+//! An erasure removes personal data, but keeps the user row itself as a tombstone,
+//! so that revisions and other contributions remain attributed to a (now anonymous)
+//! account. Sent messages also remain in the mailboxes of their recipients, as they
+//! are those users' data too.
+
+use super::prelude::*;
+use crate::models::file_revision::{self, Entity as FileRevision};
+use crate::models::message::{self, Entity as Message};
+use crate::models::message_draft::{self, Entity as MessageDraft};
+use crate::models::message_record::{self, Entity as MessageRecord};
+use crate::models::message_report::{self, Entity as MessageReport};
+use crate::models::page_revision::{self, Entity as PageRevision};
+use crate::models::page_vote::{self, Entity as PageVote};
+use crate::models::relation::{self, Entity as Relation};
+use crate::models::sea_orm_active_enums::{AliasType, RelationObjectType, UserType};
+use crate::models::session::{self, Entity as Session};
+use crate::models::user::{self, Entity as User, Model as UserModel};
+use crate::models::user_oidc_identity::{self, Entity as UserOidcIdentity};
+use crate::services::job::{Job, JobService};
+use crate::services::session::SessionInfo;
+use crate::services::{
+    AliasService, BlobService, SessionService, TextService, UserService,
+};
+use crate::utils::now;
+use cuid2::cuid;
+use hex::encode as hex_encode;
+use redis::AsyncCommands;
+use sea_orm::sea_query::Query;
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+use tokio::fs::File as TokioFile;
+use woothee::parser::Parser as UserAgentParser;
+use zip::result::ZipError;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+/// Fields of the user model which are never included in exports.
+const EXPORT_EXCLUDED_FIELDS: [&str; 3] = [
+    "password",
+    "multi_factor_secret",
+    "multi_factor_recovery_codes",
+];
+
+#[derive(Debug)]
+pub struct UserDataService;
+
+impl UserDataService {
+    // Export
+
+    /// Requests a new data export for the given user.
+    ///
+    /// If an export is already in progress, it is returned instead.
+    pub async fn request_export(
+        ctx: &ServiceContext<'_>,
+        user_id: i64,
+    ) -> Result<UserExport> {
+        // Ensure the user exists
+        UserService::get(ctx, Reference::Id(user_id)).await?;
+
+        if let Some(export) = Self::get_export_status(ctx, user_id).await? {
+            if export.status == UserExportStatus::Pending {
+                info!("Data export for user ID {user_id} is already in progress");
+                return Ok(export);
+            }
+
+            Self::delete_export_archive(ctx, &export).await?;
+        }
+
+        let export = UserExport {
+            export_id: cuid(),
+            user_id,
+            status: UserExportStatus::Pending,
+            requested_at: now(),
+            completed_at: None,
+            s3_path: None,
+            size: None,
+            error: None,
+        };
+
+        info!(
+            "Requesting data export for user ID {user_id} (export ID {})",
+            export.export_id,
+        );
+
+        Self::set_export_status(ctx, &export).await?;
+        JobService::queue_job(
+            ctx,
+            &Job::ExportUserData {
+                user_id,
+                export_id: export.export_id.clone(),
+            },
+            None,
+        )
+        .await?;
+
+        Ok(export)
+    }
+
+    /// Gets the state of the user's most recent data export.
+    ///
+    /// If it is complete, then a download URL is also produced.
+    pub async fn get_export(
+        ctx: &ServiceContext<'_>,
+        user_id: i64,
+    ) -> Result<Option<UserExportOutput>> {
+        let export = match Self::get_export_status(ctx, user_id).await? {
+            Some(export) => export,
+            None => return Ok(None),
+        };
+
+        let download_url = match export.s3_path {
+            Some(ref s3_path) if export.status == UserExportStatus::Complete => {
+                let url = ctx
+                    .s3_bucket()
+                    .presign_get(s3_path, ctx.config().presigned_expiry_secs, None)
+                    .await?;
+
+                Some(url)
+            }
+            _ => None,
+        };
+
+        Ok(Some(UserExportOutput {
+            export,
+            download_url,
+        }))
+    }
+
+    /// Builds the data export archive and uploads it.
+    ///
+    /// This is run by the job worker.
+    pub async fn build_export(
+        ctx: &ServiceContext<'_>,
+        user_id: i64,
+        export_id: &str,
+    ) -> Result<()> {
+        let mut export = match Self::get_export_status(ctx, user_id).await? {
+            Some(export) if export.export_id == export_id => export,
+            _ => {
+                warn!("Data export {export_id} for user ID {user_id} is no longer current, skipping");
+                return Ok(());
+            }
+        };
+
+        info!("Building data export for user ID {user_id} (export ID {export_id})");
+        let archive_path =
+            env::temp_dir().join(format!("deepwell-export-{export_id}.zip"));
+        let result = Self::build_and_upload(ctx, user_id, &export, &archive_path).await;
+        if let Err(error) = fs::remove_file(&archive_path) {
+            if error.kind() != io::ErrorKind::NotFound {
+                warn!("Unable to remove temporary data export archive: {error}");
+            }
+        }
+
+        match result {
+            Ok((s3_path, size)) => {
+                info!("Uploaded data export for user ID {user_id} ({size} bytes)");
+                export.status = UserExportStatus::Complete;
+                export.completed_at = Some(now());
+                export.s3_path = Some(s3_path);
+                export.size = Some(u64::try_from(size).unwrap_or(u64::MAX));
+                export.error = None;
+                Self::set_export_status(ctx, &export).await?;
+                Ok(())
+            }
+            Err(error) => {
+                // Record the failure, but return the error so the job is retried
+                error!("Unable to build data export for user ID {user_id}: {error}");
+                export.status = UserExportStatus::Failed;
+                export.error = Some(error.to_string());
+                Self::set_export_status(ctx, &export).await?;
+                Err(error)
+            }
+        }
+    }
+
+    /// Writes the export archive to a temporary file, then streams it to S3.
+    ///
+    /// This way neither the archive nor the blobs within it need to be held
+    /// in memory all at once.
+    ///
+    /// # Returns
+    /// The S3 path and size of the uploaded archive.
+    async fn build_and_upload(
+        ctx: &ServiceContext<'_>,
+        user_id: i64,
+        export: &UserExport,
+        archive_path: &Path,
+    ) -> Result<(String, usize)> {
+        let file = File::create(archive_path).map_err(ZipError::from)?;
+        Self::build_archive(ctx, user_id, export, file).await?;
+
+        let s3_path = format!("export/{}.zip", export.export_id);
+        let mut file = TokioFile::open(archive_path)
+            .await
+            .map_err(ZipError::from)?;
+
+        let response = ctx
+            .s3_bucket()
+            .put_object_stream_with_content_type(&mut file, &s3_path, "application/zip")
+            .await?;
+
+        if response.status_code() != 200 {
+            error!(
+                "Unable to upload data export (HTTP {})",
+                response.status_code(),
+            );
+            return Err(Error::S3Response);
+        }
+
+        Ok((s3_path, response.uploaded_bytes()))
+    }
+
+    async fn build_archive(
+        ctx: &ServiceContext<'_>,
+        user_id: i64,
+        export: &UserExport,
+        file: File,
+    ) -> Result<()> {
+        let txn = ctx.transaction();
+        let user = UserService::get(ctx, Reference::Id(user_id)).await?;
+        let mut archive = ExportArchive::new(file);
+
+        archive.add_json("export.json", export)?;
+
+        // Profile
+        {
+            let mut profile = serde_json::to_value(&user)?;
+            if let Some(fields) = profile.as_object_mut() {
+                for field in EXPORT_EXCLUDED_FIELDS {
+                    fields.remove(field);
+                }
+            }
+
+            archive.add_json("profile.json", &profile)?;
+        }
+
+        if let Some(ref hash) = user.avatar_s3_hash {
+            if let Some(data) = BlobService::get_optional(ctx, hash).await? {
+                archive.add_file("avatar", &data)?;
+            }
+        }
+
+        let aliases = AliasService::get_all(ctx, AliasType::User, user_id).await?;
+        archive.add_json("aliases.json", &aliases)?;
+
+        // Authentication, omitting session tokens
+        let parser = UserAgentParser::new();
+        let sessions: Vec<SessionInfo> = Session::find()
+            .filter(session::Column::UserId.eq(user_id))
+            .order_by_asc(session::Column::CreatedAt)
+            .all(txn)
+            .await?
+            .into_iter()
+            .map(|session| SessionService::describe(&parser, session, false))
+            .collect();
+        archive.add_json("sessions.json", &sessions)?;
+
+        let identities = UserOidcIdentity::find()
+            .filter(user_oidc_identity::Column::UserId.eq(user_id))
+            .all(txn)
+            .await?;
+        archive.add_json("identities.json", &identities)?;
+
+        // Page revisions, with their wikitext
+        let page_revisions = PageRevision::find()
+            .filter(page_revision::Column::UserId.eq(user_id))
+            .order_by_asc(page_revision::Column::RevisionId)
+            .all(txn)
+            .await?;
+
+        for revision in &page_revisions {
+            let wikitext = TextService::get(ctx, &revision.wikitext_hash).await?;
+            archive.add_file(
+                &format!("page-revisions/{}.ftml", revision.revision_id),
+                wikitext.as_bytes(),
+            )?;
+        }
+        archive.add_json("page-revisions.json", &page_revisions)?;
+
+        // File revisions, with each distinct uploaded blob
+        let file_revisions = FileRevision::find()
+            .filter(file_revision::Column::UserId.eq(user_id))
+            .order_by_asc(file_revision::Column::RevisionId)
+            .all(txn)
+            .await?;
+
+        let hashes: BTreeSet<&[u8]> = file_revisions
+            .iter()
+            .map(|revision| revision.s3_hash.as_slice())
+            .collect();
+
+        for hash in hashes {
+            if let Some(data) = BlobService::get_optional(ctx, hash).await? {
+                archive.add_file(&format!("files/{}", hex_encode(hash)), &data)?;
+            }
+        }
+        archive.add_json("file-revisions.json", &file_revisions)?;
+
+        // Votes
+        let votes = PageVote::find()
+            .filter(page_vote::Column::UserId.eq(user_id))
+            .order_by_asc(page_vote::Column::PageVoteId)
+            .all(txn)
+            .await?;
+        archive.add_json("votes.json", &votes)?;
+
+        // Messages, including the records for every message in their mailbox
+        let messages = Message::find()
+            .filter(message::Column::UserId.eq(user_id))
+            .order_by_asc(message::Column::InternalId)
+            .all(txn)
+            .await?;
+
+        let records = MessageRecord::find()
+            .filter(
+                message_record::Column::ExternalId
+                    .is_in(messages.iter().map(|message| message.record_id.as_str())),
+            )
+            .order_by_asc(message_record::Column::CreatedAt)
+            .all(txn)
+            .await?;
+
+        for record in &records {
+            let wikitext = TextService::get(ctx, &record.wikitext_hash).await?;
+            archive.add_file(
+                &format!("messages/{}.ftml", record.external_id),
+                wikitext.as_bytes(),
+            )?;
+        }
+        archive.add_json("messages/mailbox.json", &messages)?;
+        archive.add_json("messages/records.json", &records)?;
+
+        let drafts = MessageDraft::find()
+            .filter(message_draft::Column::UserId.eq(user_id))
+            .order_by_asc(message_draft::Column::CreatedAt)
+            .all(txn)
+            .await?;
+
+        for draft in &drafts {
+            let wikitext = TextService::get(ctx, &draft.wikitext_hash).await?;
+            archive.add_file(
+                &format!("messages/drafts/{}.ftml", draft.external_id),
+                wikitext.as_bytes(),
+            )?;
+        }
+        archive.add_json("messages/drafts.json", &drafts)?;
+
+        // Relations involving the user
+        let relations = Relation::find()
+            .filter(
+                Condition::any()
+                    .add(
+                        Condition::all()
+                            .add(relation::Column::FromType.eq(RelationObjectType::User))
+                            .add(relation::Column::FromId.eq(user_id)),
+                    )
+                    .add(
+                        Condition::all()
+                            .add(relation::Column::DestType.eq(RelationObjectType::User))
+                            .add(relation::Column::DestId.eq(user_id)),
+                    )
+                    .add(relation::Column::CreatedBy.eq(user_id)),
+            )
+            .order_by_asc(relation::Column::RelationId)
+            .all(txn)
+            .await?;
+        archive.add_json("relations.json", &relations)?;
+
+        archive.finish()
+    }
+
+    async fn get_export_status(
+        ctx: &ServiceContext<'_>,
+        user_id: i64,
+    ) -> Result<Option<UserExport>> {
+        let mut redis = ctx.redis_connect().await?;
+        let data: Option<String> = redis.get(export_key(user_id)).await?;
+        match data {
+            Some(data) => Ok(Some(serde_json::from_str(&data)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn set_export_status(
+        ctx: &ServiceContext<'_>,
+        export: &UserExport,
+    ) -> Result<()> {
+        let data = serde_json::to_string(export)?;
+        let expiry = ctx.config().user_data_export_retention.as_secs();
+        let mut redis = ctx.redis_connect().await?;
+        redis
+            .set_ex::<_, _, ()>(export_key(export.user_id), data, expiry)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn delete_export_archive(
+        ctx: &ServiceContext<'_>,
+        export: &UserExport,
+    ) -> Result<()> {
+        if let Some(ref s3_path) = export.s3_path {
+            debug!("Deleting previous data export archive at {s3_path}");
+            let response = ctx.s3_bucket().delete_object(s3_path).await?;
+            if !matches!(response.status_code(), 204 | 404) {
+                error!(
+                    "Unable to delete data export archive (HTTP {})",
+                    response.status_code(),
+                );
+                return Err(Error::S3Response);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Deletes data export archives which are past their retention period.
+    ///
+    /// The status of an export expires from Redis on its own,
+    /// but the archive in S3 must be deleted separately.
+    ///
+    /// # Returns
+    /// The number of deleted archives.
+    pub async fn prune_exports(ctx: &ServiceContext<'_>) -> Result<u64> {
+        info!("Pruning all expired data export archives");
+
+        let bucket = ctx.s3_bucket();
+        let cutoff = now() - ctx.config().user_data_export_retention;
+        let results = bucket.list(str!("export/"), None).await?;
+        let mut pruned = 0;
+
+        for result in results {
+            for object in result.contents {
+                match OffsetDateTime::parse(&object.last_modified, &Rfc3339) {
+                    Ok(last_modified) if last_modified < cutoff => (),
+                    Ok(_) => continue,
+                    Err(error) => {
+                        warn!(
+                            "Unable to parse modification time of '{}': {error}",
+                            object.key,
+                        );
+                        continue;
+                    }
+                }
+
+                debug!("Deleting expired data export archive at {}", object.key);
+                let response = bucket.delete_object(&object.key).await?;
+                if !matches!(response.status_code(), 204 | 404) {
+                    error!(
+                        "Unable to delete data export archive (HTTP {})",
+                        response.status_code(),
+                    );
+                    return Err(Error::S3Response);
+                }
+
+                pruned += 1;
+            }
+        }
+
+        debug!("Pruned {pruned} expired data export archives");
+        Ok(pruned)
+    }
+
+    // Erasure
+
+    /// Erases a user's personal data, leaving a tombstone user in its place.
+    ///
+    /// This removes:
+    /// * Profile fields, email, password, and MFA secrets
+    /// * Aliases, sessions, and linked identities
+    /// * Votes
+    /// * Message drafts and mailbox copies (except those which have been reported)
+    /// * The avatar (its blob is deleted later, if not used elsewhere)
+    /// * Any data export
+    ///
+    /// Revisions, uploads, and sent messages remain, attributed to the tombstone.
+    pub async fn erase(
+        ctx: &ServiceContext<'_>,
+        reference: Reference<'_>,
+    ) -> Result<UserEraseOutput> {
+        let txn = ctx.transaction();
+        let user = UserService::get(ctx, reference).await?;
+        let user_id = user.user_id;
+        info!("Erasing personal data for user ID {user_id}");
+
+        if user.user_type != UserType::Regular {
+            error!("Cannot erase a non-regular user ({:?})", user.user_type);
+            return Err(Error::BadRequest);
+        }
+
+        let mut output = UserEraseOutput::default();
+
+        // Authentication
+        AliasService::remove_all(ctx, AliasType::User, user_id).await?;
+
+        let DeleteResult { rows_affected, .. } = Session::delete_many()
+            .filter(session::Column::UserId.eq(user_id))
+            .exec(txn)
+            .await?;
+        output.sessions = rows_affected;
+
+        let DeleteResult { rows_affected, .. } = UserOidcIdentity::delete_many()
+            .filter(user_oidc_identity::Column::UserId.eq(user_id))
+            .exec(txn)
+            .await?;
+        output.identities = rows_affected;
+
+        // Votes
+        let DeleteResult { rows_affected, .. } = PageVote::delete_many()
+            .filter(page_vote::Column::UserId.eq(user_id))
+            .exec(txn)
+            .await?;
+        output.votes = rows_affected;
+
+        // Messages
+        let DeleteResult { rows_affected, .. } = MessageDraft::delete_many()
+            .filter(message_draft::Column::UserId.eq(user_id))
+            .exec(txn)
+            .await?;
+        output.drafts = rows_affected;
+
+        // Reported messages are retained for moderation
+        let DeleteResult { rows_affected, .. } = Message::delete_many()
+            .filter(
+                Condition::all()
+                    .add(message::Column::UserId.eq(user_id))
+                    .add(
+                        message::Column::InternalId.not_in_subquery(
+                            Query::select()
+                                .column(message_report::Column::MessageId)
+                                .from(MessageReport)
+                                .to_owned(),
+                        ),
+                    ),
+            )
+            .exec(txn)
+            .await?;
+        output.messages = rows_affected;
+
+        // Data export
+        if let Some(export) = Self::get_export_status(ctx, user_id).await? {
+            Self::delete_export_archive(ctx, &export).await?;
+
+            let mut redis = ctx.redis_connect().await?;
+            redis.del::<_, ()>(export_key(user_id)).await?;
+        }
+
+        // Profile
+        let tombstone_name = format!("Deleted User {user_id}");
+        let tombstone_slug = format!("deleted-user-{user_id}");
+        let model = user::ActiveModel {
+            user_id: Set(user_id),
+            updated_at: Set(Some(now())),
+            deleted_at: Set(Some(user.deleted_at.unwrap_or_else(now))),
+            name: Set(tombstone_name),
+            slug: Set(tombstone_slug),
+            email: Set(String::new()),
+            email_is_alias: Set(None),
+            email_verified_at: Set(None),
+            password: Set(str!("!")), // Disabled password
+            multi_factor_secret: Set(None),
+            multi_factor_recovery_codes: Set(None),
+            avatar_s3_hash: Set(None),
+            real_name: Set(None),
+            gender: Set(None),
+            birthday: Set(None),
+            location: Set(None),
+            biography: Set(None),
+            user_page: Set(None),
+            ..Default::default()
+        };
+        model.update(txn).await?;
+
+        // The avatar blob itself is left for orphaned blob collection,
+        // since it may be shared, and S3 deletions cannot be rolled back.
+        output.avatar_removed = user.avatar_s3_hash.is_some();
+
+        Ok(output)
+    }
+}
+
+#[inline]
+fn export_key(user_id: i64) -> String {
+    format!("user-export:{user_id}")
+}
+
+/// Helper to write entries into a ZIP archive file.
+struct ExportArchive {
+    writer: ZipWriter<File>,
+    options: SimpleFileOptions,
+}
+
+impl ExportArchive {
+    fn new(file: File) -> Self {
+        ExportArchive {
+            writer: ZipWriter::new(file),
+            options: SimpleFileOptions::default()
+                .compression_method(CompressionMethod::Deflated),
+        }
+    }
+
+    fn add_file(&mut self, path: &str, data: &[u8]) -> Result<()> {
+        self.writer.start_file(path, self.options)?;
+        self.writer.write_all(data).map_err(ZipError::from)?;
+        Ok(())
+    }
+
+    fn add_json<T: Serialize>(&mut self, path: &str, value: &T) -> Result<()> {
+        let data = serde_json::to_vec_pretty(value)?;
+        self.add_file(path, &data)
+    }
+
+    fn finish(self) -> Result<()> {
+        let mut file = self.writer.finish()?;
+        file.flush().map_err(ZipError::from)?;
+        Ok(())
+    }
+}
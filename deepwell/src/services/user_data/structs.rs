@@ -0,0 +1,68 @@
+/*
+ * services/user_data/structs.rs
+ *
+ * DEEPWELL - Wikijump API provider and database manager
+ * Copyright (C) 2019-2025 Wikijump Team
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use time::OffsetDateTime;
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum UserExportStatus {
+    Pending,
+    Complete,
+    Failed,
+}
+
+/// The state of a user's most recent data export.
+///
+/// Stored in Redis, and expires along with the archive.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UserExport {
+    pub export_id: String,
+    pub user_id: i64,
+    pub status: UserExportStatus,
+
+    #[serde(with = "time::serde::rfc3339")]
+    pub requested_at: OffsetDateTime,
+
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub completed_at: Option<OffsetDateTime>,
+
+    pub s3_path: Option<String>,
+    pub size: Option<u64>,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct UserExportOutput {
+    #[serde(flatten)]
+    pub export: UserExport,
+
+    /// Presigned URL to download the archive, if it is complete.
+    pub download_url: Option<String>,
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct UserEraseOutput {
+    pub sessions: u64,
+    pub identities: u64,
+    pub votes: u64,
+    pub messages: u64,
+    pub drafts: u64,
+    pub avatar_removed: bool,
+}
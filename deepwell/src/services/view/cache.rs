@@ -0,0 +1,270 @@
+/*
+ * services/view/cache.rs
+ *
+ * DEEPWELL - Wikijump API provider and database manager
+ * Copyright (C) 2019-2025 Wikijump Team
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! A read-through cache of page view data, stored in Redis.
+//!
+//! Assembling a page view requires fetching the page, its latest revision,
+//! and several text objects. For popular pages this is done over and over,
+//! so the result is cached, keyed by site, page slug, and requested locales.
+//!
+//! All the entries for a page are stored in one Redis hash, so that they can
+//! be invalidated together, whatever locales they were requested with. This
+//! happens whenever the page's data changes, such as from new revisions, rerenders,
+//! layout changes, or being outdated by other pages changing.
+//!
+//! Invalidations are recorded in the `ServiceContext` and only performed once
+//! the transaction making the change has committed. Otherwise a concurrent
+//! request could repopulate the cache with the previous data before the commit.
+//! Entries also expire after a short time, in case anything else is missed.
+//!
+//! Access checks are not cached, they are performed for each request.
+
+use super::prelude::*;
+use crate::api::ServerState;
+use crate::utils::split_category;
+use redis::AsyncCommands;
+use std::sync::atomic::{AtomicU64, Ordering};
+use unic_langid::LanguageIdentifier;
+
+static HITS: AtomicU64 = AtomicU64::new(0);
+static MISSES: AtomicU64 = AtomicU64::new(0);
+static INVALIDATIONS: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug)]
+pub struct ViewCacheService;
+
+impl ViewCacheService {
+    /// Gets the cached view data for this page, if present.
+    ///
+    /// If Redis cannot be reached, the error is logged and treated as a
+    /// cache miss, so that page views are still served.
+    pub async fn get(
+        ctx: &ServiceContext<'_>,
+        site_id: i64,
+        slug: &str,
+        locales: &[LanguageIdentifier],
+    ) -> Result<Option<CachedPageView<'static>>> {
+        if !ctx.config().view_cache_enable {
+            return Ok(None);
+        }
+
+        let data = match Self::get_raw(ctx, site_id, slug, locales).await {
+            Ok(data) => data,
+            Err(error) => {
+                warn!("Unable to read from page view cache: {error}");
+                None
+            }
+        };
+
+        match data {
+            Some(data) => {
+                debug!("Page view cache hit for site ID {site_id} slug '{slug}'");
+                HITS.fetch_add(1, Ordering::Relaxed);
+                Ok(Some(serde_json::from_str(&data)?))
+            }
+            None => {
+                debug!("Page view cache miss for site ID {site_id} slug '{slug}'");
+                MISSES.fetch_add(1, Ordering::Relaxed);
+                Ok(None)
+            }
+        }
+    }
+
+    async fn get_raw(
+        ctx: &ServiceContext<'_>,
+        site_id: i64,
+        slug: &str,
+        locales: &[LanguageIdentifier],
+    ) -> Result<Option<String>> {
+        let mut redis = ctx.redis_connect().await?;
+        let data = redis
+            .hget(page_key(site_id, slug), locales_field(locales))
+            .await?;
+
+        Ok(data)
+    }
+
+    /// Stores view data for this page.
+    ///
+    /// Failing to write to the cache is not an error, it is only logged.
+    pub async fn set(
+        ctx: &ServiceContext<'_>,
+        site_id: i64,
+        slug: &str,
+        locales: &[LanguageIdentifier],
+        view: &CachedPageView<'_>,
+    ) -> Result<()> {
+        let config = ctx.config();
+        if !config.view_cache_enable {
+            return Ok(());
+        }
+
+        let key = page_key(site_id, slug);
+        let ttl = i64::try_from(config.view_cache_ttl.as_secs()).unwrap_or(i64::MAX);
+        let data = serde_json::to_string(view)?;
+
+        if let Err(error) = Self::set_raw(ctx, &key, locales, data, ttl).await {
+            warn!("Unable to write to page view cache: {error}");
+        }
+
+        Ok(())
+    }
+
+    async fn set_raw(
+        ctx: &ServiceContext<'_>,
+        key: &str,
+        locales: &[LanguageIdentifier],
+        data: String,
+        ttl: i64,
+    ) -> Result<()> {
+        let mut redis = ctx.redis_connect().await?;
+        redis::pipe()
+            .hset(key, locales_field(locales), data)
+            .ignore()
+            .expire(key, ttl)
+            .ignore()
+            .query_async::<_, ()>(&mut redis)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Removes all cached view data for a page, once the transaction commits.
+    ///
+    /// If this is a special page (such as `_404`), then its contents may
+    /// be present in the view data for other pages, so the whole site
+    /// is invalidated instead.
+    pub fn invalidate_page(ctx: &ServiceContext<'_>, site_id: i64, slug: &str) {
+        let config = ctx.config();
+        if !config.view_cache_enable {
+            return;
+        }
+
+        let (_, page_slug) = split_category(slug);
+        if page_slug.starts_with(&config.special_page_prefix) {
+            return Self::invalidate_site(ctx, site_id);
+        }
+
+        ctx.add_view_cache_invalidation(ViewCacheInvalidation::Page {
+            site_id,
+            slug: str!(slug),
+        });
+    }
+
+    /// Removes all cached view data for every page in a site, once the transaction commits.
+    ///
+    /// This is used for changes which affect all pages, such as the site's layout.
+    pub fn invalidate_site(ctx: &ServiceContext<'_>, site_id: i64) {
+        if !ctx.config().view_cache_enable {
+            return;
+        }
+
+        ctx.add_view_cache_invalidation(ViewCacheInvalidation::Site { site_id });
+    }
+
+    /// Performs the invalidations recorded during a transaction.
+    ///
+    /// This must be called after the transaction has committed, otherwise
+    /// a concurrent request could cache the previous data again.
+    /// At this point the change cannot be undone, so failures are only logged.
+    pub async fn run_invalidations(
+        state: &ServerState,
+        invalidations: Vec<ViewCacheInvalidation>,
+    ) {
+        if invalidations.is_empty() {
+            return;
+        }
+
+        if let Err(error) = Self::run_invalidations_raw(state, invalidations).await {
+            warn!("Unable to invalidate page view cache: {error}");
+        }
+    }
+
+    async fn run_invalidations_raw(
+        state: &ServerState,
+        invalidations: Vec<ViewCacheInvalidation>,
+    ) -> Result<()> {
+        let mut redis = state.redis.get_multiplexed_tokio_connection().await?;
+        for invalidation in invalidations {
+            INVALIDATIONS.fetch_add(1, Ordering::Relaxed);
+
+            let keys = match invalidation {
+                ViewCacheInvalidation::Page { site_id, slug } => {
+                    debug!("Invalidating page view cache for site ID {site_id} slug '{slug}'");
+                    vec![page_key(site_id, &slug)]
+                }
+                ViewCacheInvalidation::Site { site_id } => {
+                    debug!(
+                        "Invalidating page view cache for all pages in site ID {site_id}"
+                    );
+                    let mut iter = redis
+                        .scan_match::<_, String>(format!("page-view:{site_id}:*"))
+                        .await?;
+
+                    let mut keys = Vec::new();
+                    while let Some(key) = iter.next_item().await {
+                        keys.push(key);
+                    }
+                    keys
+                }
+            };
+
+            if !keys.is_empty() {
+                redis.del::<_, ()>(keys).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn metrics() -> ViewCacheMetrics {
+        let hits = HITS.load(Ordering::Relaxed);
+        let misses = MISSES.load(Ordering::Relaxed);
+        let total = hits + misses;
+
+        ViewCacheMetrics {
+            hits,
+            misses,
+            invalidations: INVALIDATIONS.load(Ordering::Relaxed),
+            hit_rate: if total == 0 {
+                0.0
+            } else {
+                hits as f64 / total as f64
+            },
+        }
+    }
+}
+
+#[inline]
+fn page_key(site_id: i64, slug: &str) -> String {
+    format!("page-view:{site_id}:{slug}")
+}
+
+fn locales_field(locales: &[LanguageIdentifier]) -> String {
+    let mut field = String::new();
+    for locale in locales {
+        if !field.is_empty() {
+            field.push(',');
+        }
+
+        str_write!(field, "{locale}");
+    }
+    field
+}
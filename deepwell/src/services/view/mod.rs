@@ -25,9 +25,11 @@ mod prelude {
     pub use super::structs::*;
 }
 
+mod cache;
 mod options;
 mod service;
 mod structs;
 
+pub use self::cache::ViewCacheService;
 pub use self::service::ViewService;
 pub use self::structs::*;
@@ -33,20 +33,23 @@ use super::prelude::*;
 use crate::models::page::Model as PageModel;
 use crate::models::page_revision::Model as PageRevisionModel;
 use crate::models::site::Model as SiteModel;
+use crate::services::category::CategoryAction;
 use crate::services::domain::SiteDomainResult;
-use crate::services::render::RenderOutput;
+use crate::services::render::{RenderOutput, RenderPriority};
+use crate::services::session::SessionClient;
 use crate::services::special_page::{GetSpecialPageOutput, SpecialPageType};
 use crate::services::{
-    DomainService, PageRevisionService, PageService, SessionService, SpecialPageService,
-    TextService, UserService,
+    CategoryService, DomainService, PageRevisionService, PageService, SessionService,
+    SpecialPageService, TextService, UserService, ViewCacheService,
 };
-use crate::utils::split_category;
+use crate::utils::{get_category_name, split_category};
 use fluent::{FluentArgs, FluentValue};
 use ftml::prelude::*;
 use ftml::render::html::HtmlOutput;
 use ref_map::*;
 use std::borrow::Cow;
 use std::mem;
+use std::net::IpAddr;
 use unic_langid::LanguageIdentifier;
 use wikidot_normalize::normalize;
 
@@ -61,6 +64,8 @@ impl ViewService {
             locales: locales_str,
             route,
             session_token,
+            ip_address,
+            user_agent,
         }: GetPageView,
     ) -> Result<GetPageViewOutput> {
         info!(
@@ -83,6 +88,10 @@ impl ViewService {
             &mut locales,
             &domain,
             session_token.ref_map(|s| s.as_str()),
+            SessionClient {
+                ip_address,
+                user_agent: &user_agent,
+            },
         )
         .await?
         {
@@ -132,86 +141,184 @@ impl ViewService {
             Found {
                 page: PageModel,
                 page_revision: PageRevisionModel,
+                compiled_style: String,
             },
             Missing,
             Private,
             Banned,
         }
 
-        // Get wikitext and HTML to return for this page.
-        let (status, wikitext, compiled_html) = match PageService::get_optional(
-            ctx,
-            site.site_id,
-            Reference::Slug(cow!(page_full_slug)),
-        )
-        .await?
-        {
-            // This page exists, return its data directly.
-            Some(page) => {
-                // Get associated revision
-                let page_revision =
-                    PageRevisionService::get_latest(ctx, site.site_id, page.page_id)
-                        .await?;
+        // Check user access to page
+        let user_permissions = match user_session {
+            Some(ref session) => session.user_permissions,
+            None => {
+                debug!("No user for session, getting guest permission scheme");
 
-                // Check user access to page
-                let user_permissions = match user_session {
-                    Some(ref session) => session.user_permissions,
-                    None => {
-                        debug!("No user for session, getting guest permission scheme");
+                // TODO get permissions from service
+                UserPermissions
+            }
+        };
 
-                        // TODO get permissions from service
-                        UserPermissions
-                    }
-                };
-
-                // Determine whether to return the actual page contents,
-                // or the "private page" data (_public).
-                //
-                // This returns false if the user is banned *and* the site
-                // disallows banned viewing.
-                if Self::can_access_page(ctx, user_permissions).await? {
-                    debug!("User has page access, return text data");
-
-                    if options.rerender
-                        && Self::can_edit_page(ctx, user_permissions).await?
-                    {
-                        info!(
-                            "Re-rendering revision: site ID {} page ID {} revision ID {} (depth {})",
-                            page.site_id, page.page_id, page_revision.revision_id, 0,
-                        );
-                        PageRevisionService::rerender(ctx, page.site_id, page.page_id, 0)
+        // Determine whether to return the actual page contents,
+        // or the "private page" data (_public).
+        //
+        // This is false if the user is banned *and* the site
+        // disallows banned viewing.
+        let can_access = Self::can_access_page(ctx, user_permissions).await?
+            && Self::can_view_category(
+                ctx,
+                site.site_id,
+                page_full_slug,
+                user_session.ref_map(|session| session.user.user_id),
+            )
+            .await?;
+
+        // Check the view cache before fetching page data.
+        //
+        // Re-renders and redirects always go through the full path.
+        let use_cache = !options.rerender && redirect_page.is_none();
+        let cached_view = if use_cache {
+            ViewCacheService::get(ctx, site.site_id, page_full_slug, &locales).await?
+        } else {
+            None
+        };
+
+        // Get wikitext and HTML to return for this page.
+        let (status, wikitext, compiled_html) = match cached_view {
+            Some(CachedPageView::Found {
+                page,
+                page_revision,
+                wikitext,
+                compiled_html,
+                compiled_style,
+            }) if can_access => (
+                PageStatus::Found {
+                    page: page.into_owned(),
+                    page_revision: page_revision.into_owned(),
+                    compiled_style: compiled_style.into_owned(),
+                },
+                wikitext.into_owned(),
+                compiled_html.into_owned(),
+            ),
+            Some(CachedPageView::Missing {
+                wikitext,
+                compiled_html,
+            }) => (
+                PageStatus::Missing,
+                wikitext.into_owned(),
+                compiled_html.into_owned(),
+            ),
+            _ => match PageService::get_optional(
+                ctx,
+                site.site_id,
+                Reference::Slug(cow!(page_full_slug)),
+            )
+            .await?
+            {
+                // This page exists, return its data directly.
+                Some(page) => {
+                    // Get associated revision
+                    let page_revision =
+                        PageRevisionService::get_latest(ctx, site.site_id, page.page_id)
                             .await?;
-                    };
-
-                    let (wikitext, compiled_html) = try_join!(
-                        TextService::get(ctx, &page_revision.wikitext_hash),
-                        TextService::get(ctx, &page_revision.compiled_hash),
-                    )?;
-
-                    (
-                        PageStatus::Found {
-                            page,
-                            page_revision,
-                        },
-                        wikitext,
-                        compiled_html,
-                    )
-                } else {
-                    warn!("User doesn't have page access, returning permission page");
 
-                    let (page_status, page_type) = if user_permissions.is_banned() {
-                        (PageStatus::Banned, SpecialPageType::Banned)
+                    if can_access {
+                        debug!("User has page access, return text data");
+
+                        if options.rerender
+                            && Self::can_edit_page(ctx, user_permissions).await?
+                        {
+                            info!(
+                                "Re-rendering revision: site ID {} page ID {} revision ID {} (depth {})",
+                                page.site_id, page.page_id, page_revision.revision_id, 0,
+                            );
+                            PageRevisionService::rerender(
+                                ctx,
+                                page.site_id,
+                                page.page_id,
+                                0,
+                                RenderPriority::Interactive,
+                            )
+                            .await?;
+                        };
+
+                        let (wikitext, compiled_html, compiled_style) = try_join!(
+                            TextService::get(ctx, &page_revision.wikitext_hash),
+                            TextService::get(ctx, &page_revision.compiled_hash),
+                            TextService::get(ctx, &page_revision.compiled_style_hash),
+                        )?;
+
+                        if use_cache {
+                            let view = CachedPageView::Found {
+                                page: Cow::Borrowed(&page),
+                                page_revision: Cow::Borrowed(&page_revision),
+                                wikitext: Cow::Borrowed(&wikitext),
+                                compiled_html: Cow::Borrowed(&compiled_html),
+                                compiled_style: Cow::Borrowed(&compiled_style),
+                            };
+
+                            ViewCacheService::set(
+                                ctx,
+                                site.site_id,
+                                page_full_slug,
+                                &locales,
+                                &view,
+                            )
+                            .await?;
+                        }
+
+                        (
+                            PageStatus::Found {
+                                page,
+                                page_revision,
+                                compiled_style,
+                            },
+                            wikitext,
+                            compiled_html,
+                        )
                     } else {
-                        (PageStatus::Private, SpecialPageType::Private)
-                    };
+                        warn!("User doesn't have page access, returning permission page");
+
+                        let (page_status, page_type) = if user_permissions.is_banned() {
+                            (PageStatus::Banned, SpecialPageType::Banned)
+                        } else {
+                            (PageStatus::Private, SpecialPageType::Private)
+                        };
+
+                        let GetSpecialPageOutput {
+                            wikitext,
+                            render_output,
+                        } = SpecialPageService::get(
+                            ctx,
+                            &site,
+                            page_type,
+                            &locales,
+                            config.default_page_layout,
+                            page_info,
+                        )
+                        .await?;
+
+                        let RenderOutput {
+                            html_output:
+                                HtmlOutput {
+                                    body: compiled_html,
+                                    ..
+                                },
+                            ..
+                        } = render_output;
 
+                        (page_status, wikitext, compiled_html)
+                    }
+                }
+                // The page is missing, fetch the "missing page" data (_404).
+                None => {
                     let GetSpecialPageOutput {
                         wikitext,
                         render_output,
                     } = SpecialPageService::get(
                         ctx,
                         &site,
-                        page_type,
+                        SpecialPageType::Missing,
                         &locales,
                         config.default_page_layout,
                         page_info,
@@ -227,39 +334,27 @@ impl ViewService {
                         ..
                     } = render_output;
 
-                    (page_status, wikitext, compiled_html)
-                }
-            }
-            // The page is missing, fetch the "missing page" data (_404).
-            None => {
-                let GetSpecialPageOutput {
-                    wikitext,
-                    render_output,
-                } = SpecialPageService::get(
-                    ctx,
-                    &site,
-                    SpecialPageType::Missing,
-                    &locales,
-                    config.default_page_layout,
-                    page_info,
-                )
-                .await?;
-
-                let RenderOutput {
-                    html_output:
-                        HtmlOutput {
-                            body: compiled_html,
-                            ..
-                        },
-                    ..
-                } = render_output;
+                    if use_cache {
+                        let view = CachedPageView::Missing {
+                            wikitext: Cow::Borrowed(&wikitext),
+                            compiled_html: Cow::Borrowed(&compiled_html),
+                        };
+
+                        ViewCacheService::set(
+                            ctx,
+                            site.site_id,
+                            page_full_slug,
+                            &locales,
+                            &view,
+                        )
+                        .await?;
+                    }
 
-                (PageStatus::Missing, wikitext, compiled_html)
-            }
+                    (PageStatus::Missing, wikitext, compiled_html)
+                }
+            },
         };
 
-        // TODO Check if user-agent and IP match?
-
         let viewer = Viewer {
             site,
             redirect_site,
@@ -270,6 +365,7 @@ impl ViewService {
             PageStatus::Found {
                 page,
                 page_revision,
+                compiled_style,
             } => GetPageViewOutput::PageFound {
                 viewer,
                 options,
@@ -278,6 +374,7 @@ impl ViewService {
                 redirect_page,
                 wikitext,
                 compiled_html,
+                compiled_style,
             },
             PageStatus::Missing => GetPageViewOutput::PageMissing {
                 viewer,
@@ -312,6 +409,8 @@ impl ViewService {
             locales: locales_str,
             user: user_ref,
             session_token,
+            ip_address,
+            user_agent,
         }: GetUserView<'_>,
     ) -> Result<GetUserViewOutput> {
         info!(
@@ -329,6 +428,10 @@ impl ViewService {
             &mut locales,
             &domain,
             session_token.ref_map(|s| s.as_str()),
+            SessionClient {
+                ip_address,
+                user_agent: &user_agent,
+            },
         )
         .await?
         {
@@ -338,8 +441,6 @@ impl ViewService {
             }
         };
 
-        // TODO Check if user-agent and IP match?
-
         // Get data to return for this user.
         let user = match user_ref {
             Some(user_ref) => UserService::get_optional(ctx, user_ref).await?,
@@ -364,6 +465,8 @@ impl ViewService {
             domain,
             locales: locales_str,
             session_token,
+            ip_address,
+            user_agent,
         }: GetAdminView,
     ) -> Result<GetAdminViewOutput> {
         info!(
@@ -382,6 +485,10 @@ impl ViewService {
             &mut locales,
             &domain,
             session_token.ref_map(|s| s.as_str()),
+            SessionClient {
+                ip_address,
+                user_agent: &user_agent,
+            },
         )
         .await?
         {
@@ -472,6 +579,7 @@ impl ViewService {
         locales: &mut Vec<LanguageIdentifier>,
         domain: &str,
         session_token: Option<&str>,
+        client: SessionClient<'_>,
     ) -> Result<ViewerResult> {
         info!("Getting viewer data from domain '{domain}' and session token");
 
@@ -480,7 +588,7 @@ impl ViewService {
             None => None,
             Some("") => None,
             Some(token) => {
-                let session = SessionService::get(ctx, token).await?;
+                let session = SessionService::get_for_client(ctx, token, client).await?;
                 let user = UserService::get(ctx, Reference::Id(session.user_id)).await?;
 
                 // Prefer what the user has set over what the browser is requesting
@@ -599,6 +707,34 @@ impl ViewService {
         Ok(true)
     }
 
+    /// Checks if the category a page is in permits this user to view it.
+    async fn can_view_category(
+        ctx: &ServiceContext<'_>,
+        site_id: i64,
+        page_slug: &str,
+        user_id: Option<i64>,
+    ) -> Result<bool> {
+        let category_slug = get_category_name(page_slug);
+        match CategoryService::get_optional(
+            ctx,
+            site_id,
+            Reference::Slug(cow!(category_slug)),
+        )
+        .await?
+        {
+            Some(category) => {
+                CategoryService::has_permission(
+                    ctx,
+                    &category,
+                    user_id,
+                    CategoryAction::View,
+                )
+                .await
+            }
+            None => Ok(true),
+        }
+    }
+
     async fn can_edit_page(
         _ctx: &ServiceContext<'_>,
         permissions: UserPermissions,
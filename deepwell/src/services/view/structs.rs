@@ -25,6 +25,8 @@ use crate::models::page_revision::Model as PageRevisionModel;
 use crate::models::session::Model as SessionModel;
 use crate::models::site::Model as SiteModel;
 use crate::models::user::Model as UserModel;
+use std::borrow::Cow;
+use std::net::IpAddr;
 
 // TODO replace with actual user permissions type
 #[derive(Serialize, Deserialize, Debug, Copy, Clone)]
@@ -41,6 +43,8 @@ impl UserPermissions {
 pub struct GetPageView {
     pub domain: String,
     pub session_token: Option<String>,
+    pub ip_address: IpAddr,
+    pub user_agent: String,
     pub route: Option<PageRoute>,
     pub locales: Vec<String>,
 }
@@ -63,6 +67,7 @@ pub enum GetPageViewOutput {
         redirect_page: Option<String>,
         wikitext: String,
         compiled_html: String,
+        compiled_style: String,
     },
 
     PageMissing {
@@ -88,10 +93,47 @@ pub enum GetPageViewOutput {
     },
 }
 
+/// The parts of a page view which are stored in the view cache.
+///
+/// This only covers cases where the result does not depend on the viewer.
+/// Permission pages are never cached.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "snake_case", tag = "type")]
+pub enum CachedPageView<'a> {
+    Found {
+        page: Cow<'a, PageModel>,
+        page_revision: Cow<'a, PageRevisionModel>,
+        wikitext: Cow<'a, str>,
+        compiled_html: Cow<'a, str>,
+        compiled_style: Cow<'a, str>,
+    },
+    Missing {
+        wikitext: Cow<'a, str>,
+        compiled_html: Cow<'a, str>,
+    },
+}
+
+/// A view cache invalidation, waiting for its transaction to commit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ViewCacheInvalidation {
+    Page { site_id: i64, slug: String },
+    Site { site_id: i64 },
+}
+
+#[derive(Serialize, Debug, Copy, Clone)]
+pub struct ViewCacheMetrics {
+    pub hits: u64,
+    pub misses: u64,
+    pub invalidations: u64,
+    pub hit_rate: f64,
+}
+
 #[derive(Deserialize, Debug, Clone)]
 pub struct GetUserView<'a> {
     pub domain: String,
     pub session_token: Option<String>,
+    pub ip_address: IpAddr,
+    pub user_agent: String,
     pub user: Option<Reference<'a>>,
     pub locales: Vec<String>,
 }
@@ -119,6 +161,8 @@ pub enum GetUserViewOutput {
 pub struct GetAdminView {
     pub domain: String,
     pub session_token: Option<String>,
+    pub ip_address: IpAddr,
+    pub user_agent: String,
     pub locales: Vec<String>,
 }
 
@@ -22,5 +22,8 @@
 #[serde(default)]
 pub struct FileDetails {
     /// Include the contents of the file.
+    ///
+    /// This is only permitted for small files, otherwise
+    /// the download URL should be used instead.
     pub data: bool,
 }
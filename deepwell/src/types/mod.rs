@@ -26,9 +26,13 @@ mod fetch_direction;
 mod file_details;
 mod file_order;
 mod maybe;
+mod oidc_provider;
 mod page_details;
 mod page_order;
+mod rate_limit;
 mod reference;
+mod scanner_type;
+mod schedule;
 
 pub use self::bytes::Bytes;
 pub use self::connection_type::ConnectionType;
@@ -36,6 +40,10 @@ pub use self::fetch_direction::FetchDirection;
 pub use self::file_details::FileDetails;
 pub use self::file_order::{FileOrder, FileOrderColumn};
 pub use self::maybe::Maybe;
+pub use self::oidc_provider::OidcProvider;
 pub use self::page_details::PageDetails;
 pub use self::page_order::{PageOrder, PageOrderColumn};
+pub use self::rate_limit::RateLimitBucket;
 pub use self::reference::Reference;
+pub use self::scanner_type::ScannerType;
+pub use self::schedule::Schedule;
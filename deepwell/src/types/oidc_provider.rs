@@ -0,0 +1,62 @@
+/*
+ * types/oidc_provider.rs
+ *
+ * DEEPWELL - Wikijump API provider and database manager
+ * Copyright (C) 2019-2025 Wikijump Team
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+/// Configuration for an external OpenID Connect identity provider.
+///
+/// The client secret is not stored here, it is read from the environment
+/// variable `OIDC_CLIENT_SECRET_{NAME}`, where `{NAME}` is the provider's
+/// name in uppercase with hyphens replaced by underscores.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub struct OidcProvider {
+    /// The identifier for this provider, used in requests and stored with linked identities.
+    pub name: String,
+
+    /// The human-readable name of this provider, shown on login pages.
+    pub display_name: String,
+
+    /// The issuer URL, from which the provider's discovery document is fetched.
+    pub issuer: String,
+
+    /// The client ID registered with the provider.
+    pub client_id: String,
+
+    /// Where the provider should redirect the user after authorization.
+    pub redirect_uri: String,
+
+    /// Which scopes to request. Must include `openid`.
+    pub scopes: Vec<String>,
+
+    /// Whether a new identity may be linked to an existing user with the same email.
+    ///
+    /// This only happens if the provider reports the email as verified.
+    pub link_by_email: bool,
+
+    /// Whether a new user should be created for identities which are not linked to anyone.
+    pub create_users: bool,
+}
+
+impl OidcProvider {
+    /// The name of the environment variable containing this provider's client secret.
+    pub fn client_secret_env(&self) -> String {
+        let name = self.name.to_ascii_uppercase().replace('-', "_");
+        format!("OIDC_CLIENT_SECRET_{name}")
+    }
+}
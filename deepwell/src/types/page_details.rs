@@ -25,6 +25,8 @@ pub struct PageDetails {
     pub wikitext: bool,
 
     /// Include the compiled HTML in the page output.
+    ///
+    /// This also includes the page's compiled styles and HTML metadata.
     #[serde(alias = "compiled")]
     pub compiled_html: bool,
 }
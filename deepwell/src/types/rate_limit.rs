@@ -0,0 +1,33 @@
+/*
+ * types/rate_limit.rs
+ *
+ * DEEPWELL - Wikijump API provider and database manager
+ * Copyright (C) 2019-2025 Wikijump Team
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use std::num::NonZeroU32;
+
+/// The parameters of a token bucket used for rate limiting.
+///
+/// The bucket starts full, with `burst` tokens. Each request takes one
+/// token, and tokens are restored at a rate of `per_minute`, up to the
+/// maximum of `burst`. If there are no tokens, the request is rejected.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub struct RateLimitBucket {
+    pub burst: NonZeroU32,
+    pub per_minute: NonZeroU32,
+}
@@ -0,0 +1,169 @@
+/*
+ * types/schedule.rs
+ *
+ * DEEPWELL - Wikijump API provider and database manager
+ * Copyright (C) 2019-2025 Wikijump Team
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use chrono::{DateTime, Utc};
+use cron::Schedule as CronSchedule;
+use serde::{Serialize, Serializer};
+use std::fmt::{self, Display};
+use std::str::FromStr;
+use std::time::Duration as StdDuration;
+use time::OffsetDateTime;
+
+/// How often a periodic task should run.
+///
+/// In configuration, this is either an integer number of seconds,
+/// or a string containing a cron expression. Cron expressions are
+/// evaluated in UTC and begin with a seconds field, for instance
+/// `"0 30 4 * * *"` is daily at 04:30.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(try_from = "ScheduleValue")]
+pub enum Schedule {
+    Interval(StdDuration),
+    Cron(Box<CronSchedule>),
+}
+
+impl Schedule {
+    /// Determines when the task should next run, after the given time.
+    pub fn next_after(&self, after: OffsetDateTime) -> Option<OffsetDateTime> {
+        match self {
+            Schedule::Interval(duration) => {
+                after.checked_add((*duration).try_into().ok()?)
+            }
+            Schedule::Cron(schedule) => {
+                let after = DateTime::<Utc>::from_timestamp(
+                    after.unix_timestamp(),
+                    after.nanosecond(),
+                )?;
+
+                let next = schedule.after(&after).next()?;
+                let timestamp = next.timestamp_nanos_opt()?;
+                OffsetDateTime::from_unix_timestamp_nanos(i128::from(timestamp)).ok()
+            }
+        }
+    }
+}
+
+impl Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Schedule::Interval(duration) => {
+                write!(f, "every {} seconds", duration.as_secs())
+            }
+            Schedule::Cron(schedule) => write!(f, "cron {}", schedule.source()),
+        }
+    }
+}
+
+impl Serialize for Schedule {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Schedule::Interval(duration) => serializer.serialize_u64(duration.as_secs()),
+            Schedule::Cron(schedule) => serializer.serialize_str(schedule.source()),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+enum ScheduleValue {
+    Seconds(u64),
+    Cron(String),
+}
+
+impl TryFrom<ScheduleValue> for Schedule {
+    type Error = String;
+
+    fn try_from(value: ScheduleValue) -> Result<Schedule, String> {
+        match value {
+            ScheduleValue::Seconds(0) => Err(str!("Schedule interval must be nonzero")),
+            ScheduleValue::Seconds(secs) => {
+                Ok(Schedule::Interval(StdDuration::from_secs(secs)))
+            }
+            ScheduleValue::Cron(expression) => {
+                match CronSchedule::from_str(&expression) {
+                    Ok(schedule) => Ok(Schedule::Cron(Box::new(schedule))),
+                    Err(error) => {
+                        Err(format!("Invalid cron expression '{expression}': {error}"))
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_schedule_parse() {
+    macro_rules! check {
+        ($input:expr, $expected:expr $(,)?) => {{
+            let schedule: Schedule =
+                serde_json::from_str($input).expect("Unable to parse schedule");
+
+            assert_eq!(
+                schedule.to_string(),
+                $expected,
+                "Schedule {} was not parsed as expected",
+                $input,
+            );
+        }};
+    }
+
+    macro_rules! check_err {
+        ($input:expr $(,)?) => {{
+            let result = serde_json::from_str::<Schedule>($input);
+            assert!(result.is_err(), "Schedule {} was accepted", $input);
+        }};
+    }
+
+    check!("60", "every 60 seconds");
+    check!("86400", "every 86400 seconds");
+    check!(r#""0 30 4 * * *""#, "cron 0 30 4 * * *");
+    check!(r#""0 0 * * * Mon""#, "cron 0 0 * * * Mon");
+
+    check_err!("0");
+    check_err!("-5");
+    check_err!("1.5");
+    check_err!(r#""""#);
+    check_err!(r#""not a cron expression""#);
+    check_err!(r#""0 61 * * * *""#);
+}
+
+#[test]
+fn test_schedule_next_after() {
+    let after =
+        OffsetDateTime::from_unix_timestamp(1_700_000_000).expect("Invalid timestamp"); // 2023-11-14 22:13:20 UTC
+
+    let schedule = Schedule::Interval(StdDuration::from_secs(90));
+    let next = schedule
+        .next_after(after)
+        .expect("No next time for interval");
+    assert_eq!(next.unix_timestamp(), 1_700_000_090);
+
+    // Daily at 04:30 UTC, so the next day
+    let schedule: Schedule =
+        serde_json::from_str(r#""0 30 4 * * *""#).expect("Unable to parse schedule");
+    let next = schedule.next_after(after).expect("No next time for cron");
+    assert_eq!(next.unix_timestamp(), 1_700_022_600); // 2023-11-15 04:30:00 UTC
+
+    // Every hour on the hour
+    let schedule: Schedule =
+        serde_json::from_str(r#""0 0 * * * *""#).expect("Unable to parse schedule");
+    let next = schedule.next_after(after).expect("No next time for cron");
+    assert_eq!(next.unix_timestamp(), 1_700_002_800); // 2023-11-14 23:00:00 UTC
+}
@@ -30,6 +30,8 @@ declare namespace App {
       revision_user_id: number
       wikitext: string | null
       compiled_html: string | null
+      compiled_style: string | null
+      compiled_meta: { tag_type: string; name: string; value: string }[] | null
       compiled_at: string
       compiled_generator: string
       revision_comments: string
@@ -65,6 +67,8 @@ declare namespace App {
     internationalization?: Locales
     /** Compiled HTML */
     compiled_html: string
+    /** Compiled page-level CSS, only present if the page was found */
+    compiled_style?: string
     /** Page revision */
     page_revision: {
       revision_id: number
@@ -81,6 +85,7 @@ declare namespace App {
       compiled_html: string | null
       compiled_at: string | null
       compiled_generator: string
+      compiled_meta: { tag_type: string; name: string; value: string }[]
       comments: string
       hidden: string[]
       title: string | null
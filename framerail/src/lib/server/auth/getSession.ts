@@ -1,5 +1,13 @@
 import { client } from "$lib/server/deepwell"
 
-export async function authGetSession(sessionToken: string | undefined): Promise<object> {
-  return client.request("session_get", [sessionToken ?? ""])
+export async function authGetSession(
+  sessionToken: string | undefined,
+  ipAddress: string,
+  userAgent: string
+): Promise<object> {
+  return client.request("session_get", {
+    session_token: sessionToken ?? "",
+    ip_address: ipAddress,
+    user_agent: userAgent
+  })
 }
@@ -26,3 +26,22 @@ export async function siteUpdate(
         : undefined
   })
 }
+
+export async function siteDelete(siteId: number, userId: number): Promise<object> {
+  return client.request("site_delete", {
+    site: siteId,
+    user_id: userId
+  })
+}
+
+export async function siteRestore(
+  siteId: number,
+  userId: number,
+  slug: Optional<String>
+): Promise<object> {
+  return client.request("site_restore", {
+    site_id: siteId,
+    user_id: userId,
+    slug
+  })
+}
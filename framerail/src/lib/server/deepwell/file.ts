@@ -4,17 +4,19 @@ export async function getFileByHash(
   /** Either a Uint8Array or a hex string */
   fileHash: Uint8Array | string
 ): Promise<Blob> {
-  let res = await client.request(
-    "blob_get",
-    typeof fileHash === "string" ? fileHash : Buffer.from(fileHash).toString("hex")
-  )
+  let res = await client.request("blob_get", {
+    hash:
+      typeof fileHash === "string" ? fileHash : Buffer.from(fileHash).toString("hex"),
+    data: true
+  })
 
   return new Blob([new Uint8Array(res.data)], { type: res.mime })
 }
 
-export async function startBlobUpload(userId: number, blobSize: number) {
+export async function startBlobUpload(userId: number, blobSize: number, siteId?: number) {
   return await client.request("blob_upload", {
     user_id: userId,
+    site_id: siteId,
     blob_size: blobSize
   })
 }
@@ -208,6 +208,30 @@ export async function pageParentGet(
   })
 }
 
+export async function pageParentBreadcrumbs(
+  siteId: number,
+  pageId: Optional<number>,
+  slug: string
+): Promise<object[]> {
+  return client.request("parent_breadcrumbs", {
+    site_id: siteId,
+    page: pageId ?? slug
+  })
+}
+
+export async function pageParentTree(
+  siteId: number,
+  pageId: Optional<number>,
+  slug: string,
+  maxDepth?: number
+): Promise<object> {
+  return client.request("parent_tree", {
+    site_id: siteId,
+    page: pageId ?? slug,
+    max_depth: maxDepth ?? null
+  })
+}
+
 export async function pageDeletedGet(siteId: number, slug: string): Promise<object> {
   return client.request("page_get_deleted", {
     site_id: siteId,
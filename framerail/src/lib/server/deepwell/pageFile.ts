@@ -3,6 +3,15 @@ import { client } from "$lib/server/deepwell"
 import { startBlobUpload, uploadToPresignUrl } from "$lib/server/deepwell/file"
 import type { Optional } from "$lib/types"
 
+export interface FileLicensing {
+  /** SPDX identifier, such as "CC-BY-SA-4.0" */
+  license?: string
+  author?: string
+  source_url?: string
+  own_work?: boolean
+  fair_use?: boolean
+}
+
 export async function pageFileList(
   siteId: number,
   pageId: number,
@@ -38,10 +47,10 @@ export async function pageFileCreate(
   userId: number,
   name: Optional<string>,
   file: File,
-  licensing: any,
+  licensing: Optional<FileLicensing>,
   revisionComments: Optional<string>
 ) {
-  let presign = await startBlobUpload(userId, file.size)
+  let presign = await startBlobUpload(userId, file.size, siteId)
   await uploadToPresignUrl(presign.presign_url, file)
 
   return await client.request("file_create", {
@@ -80,13 +89,13 @@ export async function pageFileEdit(
   fileId: string,
   name: string,
   file: Optional<File>,
-  licensing: Optional<any>,
+  licensing: Optional<FileLicensing>,
   lastRevisionId: number,
   revisionComments: Optional<string>
 ) {
   let presignId = undefined
   if (file && file instanceof File) {
-    let presign = await startBlobUpload(userId, file.size)
+    let presign = await startBlobUpload(userId, file.size, siteId)
     await uploadToPresignUrl(presign.presign_url, file)
     presignId = presign.pending_blob_id
   }
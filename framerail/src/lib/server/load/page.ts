@@ -11,13 +11,15 @@ export async function loadPage(
   slug: Optional<string>,
   extra: Optional<string>,
   request,
-  cookies
+  cookies,
+  ipAddress: string
 ) {
   // Set up parameters
   const url = new URL(request.url)
   const domain = url.hostname
   const route = slug || extra ? { slug, extra } : null
   const sessionToken = cookies.get("wikijump_token")
+  const userAgent = request.headers.get("User-Agent") ?? ""
   let locales = parseAcceptLangHeader(request)
 
   // TODO insert user preference at the beginning of the list
@@ -28,7 +30,9 @@ export async function loadPage(
     domain,
     [...locales, defaults.fallbackLocale],
     route,
-    sessionToken
+    sessionToken,
+    ipAddress,
+    userAgent
   )
 
   if (response.data?.site?.locale && !locales.includes(response.data.site.locale)) {
@@ -6,15 +6,28 @@ import { userView } from "$lib/server/deepwell/user"
 import type { TranslateKeys } from "$lib/types"
 import { error, redirect } from "@sveltejs/kit"
 
-export async function loadUser(username?: string, request, cookies) {
+export async function loadUser(
+  username?: string,
+  request,
+  cookies,
+  ipAddress: string
+) {
   const url = new URL(request.url)
   const domain = url.hostname
   const sessionToken = cookies.get("wikijump_token")
+  const userAgent = request.headers.get("User-Agent") ?? ""
   let locales = parseAcceptLangHeader(request)
 
   if (!locales.includes(defaults.fallbackLocale)) locales.push(defaults.fallbackLocale)
 
-  const response = await userView(domain, locales, sessionToken, username)
+  const response = await userView(
+    domain,
+    locales,
+    sessionToken,
+    ipAddress,
+    userAgent,
+    username
+  )
 
   let translateKeys: TranslateKeys = {
     ...defaults.translateKeys
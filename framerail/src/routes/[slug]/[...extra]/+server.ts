@@ -10,9 +10,9 @@ export async function POST(event) {
 
   let sessionToken = event.cookies.get("wikijump_token")
   let ipAddr = event.getClientAddress()
-  let userAgent = event.cookies.get("User-Agent")
+  let userAgent = event.request.headers.get("User-Agent") ?? ""
 
-  let session = await authGetSession(sessionToken)
+  let session = await authGetSession(sessionToken, ipAddr, userAgent)
 
   let extra = event.params.extra
     ?.toLowerCase()
@@ -163,7 +163,7 @@ export async function POST(event) {
         session?.user_id,
         name,
         file,
-        null,
+        undefined,
         comments
       )
     } else if (extra.includes("file-delete")) {
@@ -198,7 +198,7 @@ export async function POST(event) {
         fileId,
         name,
         file,
-        null,
+        undefined,
         lastRevId,
         comments
       )
@@ -289,9 +289,9 @@ export async function DELETE(event) {
 
   let sessionToken = event.cookies.get("wikijump_token")
   let ipAddr = event.getClientAddress()
-  let userAgent = event.cookies.get("User-Agent")
+  let userAgent = event.request.headers.get("User-Agent") ?? ""
 
-  let session = await authGetSession(sessionToken)
+  let session = await authGetSession(sessionToken, ipAddr, userAgent)
 
   let pageIdVal = data.get("page-id")?.toString()
   let pageId = pageIdVal ? parseInt(pageIdVal) : null
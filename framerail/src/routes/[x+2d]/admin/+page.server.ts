@@ -1,5 +1,5 @@
 import { loadAdminPage } from "$lib/server/load/admin"
 
-export async function load({ request, cookies }) {
-  return loadAdminPage(request, cookies)
+export async function load({ request, cookies, getClientAddress }) {
+  return loadAdminPage(request, cookies, getClientAddress())
 }
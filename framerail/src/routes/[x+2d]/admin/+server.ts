@@ -8,9 +8,9 @@ export async function POST(event) {
 
   let sessionToken = event.cookies.get("wikijump_token")
   let ipAddr = event.getClientAddress()
-  let userAgent = event.cookies.get("User-Agent")
+  let userAgent = event.request.headers.get("User-Agent") ?? ""
 
-  let session = await authGetSession(sessionToken)
+  let session = await authGetSession(sessionToken, ipAddr, userAgent)
 
   let action = data.get("action")?.toString().toLowerCase()
 
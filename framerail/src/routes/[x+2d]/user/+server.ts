@@ -4,9 +4,11 @@ import { userEdit } from "$lib/server/deepwell/user"
 export async function POST(event) {
   let data = await event.request.formData()
   let sessionToken = event.cookies.get("wikijump_token")
+  let ipAddr = event.getClientAddress()
+  let userAgent = event.request.headers.get("User-Agent") ?? ""
 
   try {
-    let session = await authGetSession(sessionToken)
+    let session = await authGetSession(sessionToken, ipAddr, userAgent)
 
     let name = data.get("name")?.toString().trim()
     let email = data.get("email")?.toString().trim()
@@ -1,5 +1,5 @@
 import { loadUser } from "$lib/server/load/user"
 
-export async function load({ params, request, cookies }) {
-  return loadUser(params.slug, request, cookies)
+export async function load({ params, request, cookies, getClientAddress }) {
+  return loadUser(params.slug, request, cookies, getClientAddress())
 }
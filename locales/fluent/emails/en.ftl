@@ -24,3 +24,16 @@ emails-reset-password =
     *[other] { $count } minutes.
   }
   .outro = If you did not request a password reset, no further action is required.
+
+emails-site-invitation =
+  .subject = Invitation to join { $site }
+  .greeting = You have been invited to { $site }
+  .intro = { $inviter } has invited you to become a member of { $site }, located at { $url }
+  .message = They included the following message:
+  .action = Visit the site while signed in to accept the invitation.
+  .token = To accept, sign in or create an account, then enter this invitation code: { $token }
+  .expires = This invitation will expire in { $count ->
+    [1] 1 day.
+    *[other] { $count } days.
+  }
+  .outro = If you were not expecting this invitation, no further action is required.